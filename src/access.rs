@@ -0,0 +1,146 @@
+use std::net::IpAddr;
+
+use crate::{config::Config, geoip::GeoInfo};
+
+/// Country and network (ASN) allow/deny rules applied to `/stream`
+#[derive(Debug, Clone, Default)]
+pub struct AccessRules {
+    allow_countries: Vec<String>,
+    deny_countries: Vec<String>,
+    allow_asns: Vec<u32>,
+    deny_asns: Vec<u32>,
+    message: Option<String>,
+}
+
+impl AccessRules {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            allow_countries: config.stream_allow_countries.clone(),
+            deny_countries: config.stream_deny_countries.clone(),
+            allow_asns: config.stream_allow_asns.clone(),
+            deny_asns: config.stream_deny_asns.clone(),
+            message: config.stream_block_message.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allow_countries.is_empty()
+            && self.deny_countries.is_empty()
+            && self.allow_asns.is_empty()
+            && self.deny_asns.is_empty()
+    }
+
+    /// Returns the explanation to send to the listener when access is refused.
+    /// Local and private addresses are never restricted; with an allow list in
+    /// place, addresses that cannot be located are refused.
+    pub fn check(&self, ip: IpAddr, geo: &GeoInfo) -> Result<(), String> {
+        if self.is_empty() || is_local(ip) {
+            return Ok(());
+        }
+
+        let country = geo.country.as_deref();
+        let network = match (geo.asn, &geo.as_org) {
+            (Some(asn), Some(org)) => format!("AS{} ({})", asn, org),
+            (Some(asn), None) => format!("AS{}", asn),
+            _ => "unknown network".to_string(),
+        };
+
+        let reason = if geo.asn.is_some_and(|asn| self.deny_asns.contains(&asn)) {
+            Some(format!("Streaming is not available from your network, {}.", network))
+        } else if country.is_some_and(|c| self.deny_countries.iter().any(|d| d == c)) {
+            Some(format!("Streaming is not available in your country ({}) due to licensing restrictions.", country.unwrap_or_default()))
+        } else if !self.allow_countries.is_empty() && !country.is_some_and(|c| self.allow_countries.iter().any(|a| a == c)) {
+            Some(format!(
+                "Streaming is only licensed for listeners in {}; your location ({}) is not included.",
+                self.allow_countries.join(", "),
+                country.unwrap_or("unknown"),
+            ))
+        } else if !self.allow_asns.is_empty() && !geo.asn.is_some_and(|asn| self.allow_asns.contains(&asn)) {
+            Some(format!("Streaming is only available from approved networks; {} is not one of them.", network))
+        } else {
+            None
+        };
+
+        match (reason, &self.message) {
+            (None, _) => Ok(()),
+            (Some(_), Some(message)) => Err(message.clone()),
+            (Some(reason), None) => Err(reason),
+        }
+    }
+}
+
+fn is_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_private() || v4.is_link_local(),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_local(IpAddr::V4(v4)),
+            // fc00::/7 unique local, fe80::/10 link local
+            None => v6.is_loopback() || (v6.segments()[0] & 0xfe00) == 0xfc00 || (v6.segments()[0] & 0xffc0) == 0xfe80,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geo(country: Option<&str>, asn: Option<u32>) -> GeoInfo {
+        GeoInfo {
+            country: country.map(str::to_string),
+            region: None,
+            asn,
+            as_org: None,
+        }
+    }
+
+    fn public_ip() -> IpAddr {
+        "81.2.69.160".parse().unwrap()
+    }
+
+    #[test]
+    fn test_no_rules_allows_everyone() {
+        let rules = AccessRules::default();
+        assert!(rules.check(public_ip(), &GeoInfo::default()).is_ok());
+    }
+
+    #[test]
+    fn test_country_allow_list() {
+        let rules = AccessRules { allow_countries: vec!["GB".to_string()], ..Default::default() };
+
+        assert!(rules.check(public_ip(), &geo(Some("GB"), None)).is_ok());
+        let reason = rules.check(public_ip(), &geo(Some("US"), None)).unwrap_err();
+        assert!(reason.contains("GB") && reason.contains("US"));
+        assert!(rules.check(public_ip(), &geo(None, None)).is_err());
+    }
+
+    #[test]
+    fn test_deny_lists() {
+        let rules = AccessRules {
+            deny_countries: vec!["US".to_string()],
+            deny_asns: vec![64_512],
+            ..Default::default()
+        };
+
+        assert!(rules.check(public_ip(), &geo(Some("GB"), Some(1))).is_ok());
+        assert!(rules.check(public_ip(), &geo(Some("US"), None)).is_err());
+        assert!(rules.check(public_ip(), &geo(Some("GB"), Some(64_512))).unwrap_err().contains("AS64512"));
+    }
+
+    #[test]
+    fn test_local_addresses_are_exempt() {
+        let rules = AccessRules { allow_countries: vec!["GB".to_string()], ..Default::default() };
+        for ip in ["127.0.0.1", "192.168.1.20", "::1", "::ffff:10.0.0.1", "fd00::1"] {
+            assert!(rules.check(ip.parse().unwrap(), &GeoInfo::default()).is_ok(), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_custom_message() {
+        let rules = AccessRules {
+            allow_asns: vec![1],
+            message: Some("Members only".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(rules.check(public_ip(), &geo(None, Some(2))).unwrap_err(), "Members only");
+    }
+}
@@ -0,0 +1,726 @@
+// Built-in ACME client (RFC 8555) behind the `acme` feature: obtains the TLS_CERT /
+// TLS_KEY certificate for ACME_DOMAINS from Let's Encrypt (or another ACME CA) and
+// renews it in place. HTTP-01 challenges are answered on ACME_HTTP_PORT; renewed
+// files are picked up by the TLS reload loop like any external ACME client's.
+
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::StatusCode,
+    routing::get,
+    Router,
+};
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine};
+use dashmap::DashMap;
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::config::Config;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: usize = 30;
+// How often the certificate's age is checked; also the retry delay after a failed renewal
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+const BAD_NONCE: &str = "urn:ietf:params:acme:error:badNonce";
+
+pub type AcmeResult<T> = std::result::Result<T, AcmeError>;
+
+#[derive(Error, Debug)]
+pub enum AcmeError {
+    #[error("ACME request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Key error: {0}")]
+    Key(String),
+
+    /// An RFC 7807 problem document from the CA
+    #[error("ACME server returned {status} ({kind}): {detail}")]
+    Problem { status: u16, kind: String, detail: String },
+
+    #[error("ACME protocol error: {0}")]
+    Protocol(String),
+}
+
+/// Obtains and renews the certificate for the configured domains and answers the
+/// CA's HTTP-01 challenges
+pub struct CertificateManager {
+    domains: Vec<String>,
+    email: Option<String>,
+    directory_url: String,
+    account_key_path: PathBuf,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    renew_after: Duration,
+    challenges: Arc<DashMap<String, String>>, // Token -> key authorization
+}
+
+impl CertificateManager {
+    /// `None` when ACME_DOMAINS is unset. The certificate is written to TLS_CERT and
+    /// TLS_KEY, so both must be set.
+    pub fn from_config(config: &Config) -> AcmeResult<Option<Arc<Self>>> {
+        if config.acme_domains.is_empty() {
+            return Ok(None);
+        }
+        let (Some(cert_path), Some(key_path)) = (&config.tls_cert_path, &config.tls_key_path) else {
+            return Err(AcmeError::Protocol("ACME_DOMAINS needs TLS_CERT and TLS_KEY to write the certificate to".into()));
+        };
+        Ok(Some(Arc::new(Self {
+            domains: config.acme_domains.clone(),
+            email: config.acme_email.clone(),
+            directory_url: config.acme_directory.clone(),
+            account_key_path: config.acme_account_key_path.clone()
+                .unwrap_or_else(|| key_path.with_file_name("acme-account.key")),
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+            renew_after: Duration::from_secs(config.acme_renew_days.saturating_mul(86400)),
+            challenges: Arc::new(DashMap::new()),
+        })))
+    }
+
+    /// Serves `/.well-known/acme-challenge/{token}` for pending challenges
+    pub fn challenge_router(&self) -> Router {
+        Router::new()
+            .route("/.well-known/acme-challenge/:token", get(challenge_response))
+            .with_state(Arc::clone(&self.challenges))
+    }
+
+    /// Start the plain HTTP listener the CA validates challenges against. It stays up
+    /// for renewals; returns the bound address.
+    pub async fn serve_challenges(&self, port: u16) -> AcmeResult<SocketAddr> {
+        let listener = match crate::netif::bind_dual_stack(port) {
+            Ok(listener) => tokio::net::TcpListener::from_std(listener)?,
+            Err(_) => tokio::net::TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port))).await?,
+        };
+        let addr = listener.local_addr()?;
+        let app = self.challenge_router();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!("ACME challenge listener stopped: {}", e);
+            }
+        });
+        info!("Answering ACME HTTP-01 challenges on port {}", addr.port());
+        Ok(addr)
+    }
+
+    /// The certificate file is missing or older than ACME_RENEW_DAYS
+    pub fn needs_renewal(&self) -> bool {
+        let age = std::fs::metadata(&self.cert_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        match age {
+            Some(age) => age >= self.renew_after,
+            None => !self.cert_path.exists(),
+        }
+    }
+
+    /// Renew whenever the certificate gets old enough; a failed renewal is retried at
+    /// the next check while the current certificate stays in service
+    pub fn spawn_renewal(self: &Arc<Self>) {
+        let manager = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(RENEWAL_CHECK_INTERVAL).await;
+                if !manager.needs_renewal() {
+                    continue;
+                }
+                if let Err(e) = manager.issue().await {
+                    warn!("ACME renewal failed, keeping the current certificate: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Order a certificate for all domains and write it with a fresh private key
+    pub async fn issue(&self) -> AcmeResult<()> {
+        info!("Requesting a certificate for {} from {}", self.domains.join(", "), self.directory_url);
+        let account_key = load_or_create_account_key(&self.account_key_path)?;
+        let mut client = AcmeClient::connect(&self.directory_url, account_key).await?;
+        client.register(self.email.as_deref()).await?;
+
+        let mut tokens = Vec::new();
+        let result = self.order(&mut client, &mut tokens).await;
+        for token in tokens {
+            self.challenges.remove(&token);
+        }
+        let (key, chain) = result?;
+
+        // Key first: a reload between the two writes is corrected once the chain lands
+        write_atomic(&self.key_path, pem("PRIVATE KEY", &key).as_bytes(), true)?;
+        write_atomic(&self.cert_path, chain.as_bytes(), false)?;
+        info!("Wrote the ACME certificate to {}", self.cert_path.display());
+        Ok(())
+    }
+
+    // Returns the certificate's PKCS#8 key and PEM chain
+    async fn order(&self, client: &mut AcmeClient, tokens: &mut Vec<String>) -> AcmeResult<(Vec<u8>, String)> {
+        let identifiers: Vec<Value> = self.domains.iter().map(|d| json!({ "type": "dns", "value": d })).collect();
+        let response = client.post(&client.directory.new_order.clone(), Some(&json!({ "identifiers": identifiers }))).await?;
+        let order_url = location(&response)?;
+        let order: Order = response.json().await?;
+
+        for authorization_url in &order.authorizations {
+            let authorization: Authorization = client.post(authorization_url, None).await?.json().await?;
+            if authorization.status == "valid" {
+                continue;
+            }
+            let challenge = authorization.challenges.iter()
+                .find(|c| c.kind == "http-01")
+                .ok_or_else(|| AcmeError::Protocol(format!("no http-01 challenge for {}", authorization.identifier.value)))?;
+            self.challenges.insert(challenge.token.clone(), client.key_authorization(&challenge.token));
+            tokens.push(challenge.token.clone());
+            client.post(&challenge.url, Some(&json!({}))).await?;
+
+            let authorization = client.poll(authorization_url, |a: &Authorization| a.status != "pending").await?;
+            if authorization.status != "valid" {
+                let detail = authorization.challenges.iter()
+                    .find_map(|c| c.error.as_ref()?.get("detail")?.as_str().map(str::to_string))
+                    .unwrap_or(authorization.status);
+                return Err(AcmeError::Protocol(format!("validation of {} failed: {}", authorization.identifier.value, detail)));
+            }
+        }
+
+        let rng = SystemRandom::new();
+        let key = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng)
+            .map_err(|e| AcmeError::Key(e.to_string()))?;
+        let csr = certificate_request(&self.domains, key.as_ref())?;
+        client.post(&order.finalize, Some(&json!({ "csr": URL_SAFE_NO_PAD.encode(csr) }))).await?;
+
+        let order = client.poll(&order_url, |o: &Order| o.status != "pending" && o.status != "ready" && o.status != "processing").await?;
+        let certificate_url = match (order.status.as_str(), order.certificate) {
+            ("valid", Some(url)) => url,
+            (status, _) => return Err(AcmeError::Protocol(format!("order ended as {}", status))),
+        };
+        let chain = client.post(&certificate_url, None).await?.text().await?;
+        Ok((key.as_ref().to_vec(), chain))
+    }
+}
+
+async fn challenge_response(
+    State(challenges): State<Arc<DashMap<String, String>>>,
+    UrlPath(token): UrlPath<String>,
+) -> Result<String, StatusCode> {
+    challenges.get(&token).map(|k| k.clone()).ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Order {
+    status: String,
+    #[serde(default)]
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    #[serde(default)]
+    token: String,
+    error: Option<Value>,
+}
+
+// One session with the CA: signs requests with the account key and tracks nonces
+struct AcmeClient {
+    http: reqwest::Client,
+    directory: Directory,
+    account_key: EcdsaKeyPair,
+    kid: Option<String>, // Account URL once registered
+    nonce: Option<String>,
+    rng: SystemRandom,
+}
+
+impl AcmeClient {
+    async fn connect(directory_url: &str, account_key: EcdsaKeyPair) -> AcmeResult<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        let directory = http.get(directory_url).send().await?.error_for_status()?.json().await?;
+        Ok(Self { http, directory, account_key, kid: None, nonce: None, rng: SystemRandom::new() })
+    }
+
+    /// Find or create the account for the key; requests are signed with its URL from then on
+    async fn register(&mut self, email: Option<&str>) -> AcmeResult<()> {
+        let mut payload = json!({ "termsOfServiceAgreed": true });
+        if let Some(email) = email {
+            payload["contact"] = json!([format!("mailto:{}", email)]);
+        }
+        let response = self.post(&self.directory.new_account.clone(), Some(&payload)).await?;
+        self.kid = Some(location(&response)?);
+        Ok(())
+    }
+
+    fn key_authorization(&self, token: &str) -> String {
+        format!("{}.{}", token, jwk_thumbprint(self.account_key.public_key().as_ref()))
+    }
+
+    /// Signed POST; `None` sends a POST-as-GET. A rejected nonce is retried once.
+    async fn post(&mut self, url: &str, payload: Option<&Value>) -> AcmeResult<reqwest::Response> {
+        let mut retried = false;
+        loop {
+            let nonce = match self.nonce.take() {
+                Some(nonce) => nonce,
+                None => self.new_nonce().await?,
+            };
+            let body = self.sign(url, &nonce, payload)?;
+            let response = self.http.post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await?;
+            self.nonce = replay_nonce(&response);
+            if response.status().is_success() {
+                return Ok(response);
+            }
+
+            let status = response.status().as_u16();
+            let problem: Value = response.json().await.unwrap_or_default();
+            let kind = problem["type"].as_str().unwrap_or_default();
+            if kind == BAD_NONCE && !retried {
+                retried = true;
+                continue;
+            }
+            return Err(AcmeError::Problem {
+                status,
+                kind: kind.to_string(),
+                detail: problem["detail"].as_str().unwrap_or_default().to_string(),
+            });
+        }
+    }
+
+    /// POST-as-GET `url` until `done` holds for the resource
+    async fn poll<T: serde::de::DeserializeOwned>(&mut self, url: &str, done: impl Fn(&T) -> bool) -> AcmeResult<T> {
+        for _ in 0..POLL_ATTEMPTS {
+            let resource: T = self.post(url, None).await?.json().await?;
+            if done(&resource) {
+                return Ok(resource);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        Err(AcmeError::Protocol(format!("timed out waiting for {}", url)))
+    }
+
+    async fn new_nonce(&self) -> AcmeResult<String> {
+        let response = self.http.head(&self.directory.new_nonce).send().await?.error_for_status()?;
+        replay_nonce(&response).ok_or_else(|| AcmeError::Protocol("no Replay-Nonce from newNonce".into()))
+    }
+
+    // Flattened JWS (RFC 7515) with ES256; the account is named by `kid` once registered
+    fn sign(&self, url: &str, nonce: &str, payload: Option<&Value>) -> AcmeResult<Value> {
+        let mut protected = json!({ "alg": "ES256", "nonce": nonce, "url": url });
+        match &self.kid {
+            Some(kid) => protected["kid"] = json!(kid),
+            None => protected["jwk"] = jwk(self.account_key.public_key().as_ref()),
+        }
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        let payload = payload.map(|p| URL_SAFE_NO_PAD.encode(p.to_string())).unwrap_or_default();
+        let signature = self.account_key
+            .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+            .map_err(|e| AcmeError::Key(e.to_string()))?;
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        }))
+    }
+}
+
+fn replay_nonce(response: &reqwest::Response) -> Option<String> {
+    response.headers().get("replay-nonce")?.to_str().ok().map(str::to_string)
+}
+
+fn location(response: &reqwest::Response) -> AcmeResult<String> {
+    response.headers().get(reqwest::header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| AcmeError::Protocol(format!("no Location in the response from {}", response.url())))
+}
+
+/// Public JWK of a P-256 key (uncompressed point), members in RFC 7638 thumbprint order
+fn jwk(public_key: &[u8]) -> Value {
+    json!({
+        "crv": "P-256",
+        "kty": "EC",
+        "x": URL_SAFE_NO_PAD.encode(&public_key[1..33]),
+        "y": URL_SAFE_NO_PAD.encode(&public_key[33..65]),
+    })
+}
+
+/// RFC 7638 thumbprint, the account part of every key authorization
+fn jwk_thumbprint(public_key: &[u8]) -> String {
+    // `jwk` lists the required members in lexicographic order without whitespace, as hashed here
+    let canonical = jwk(public_key).to_string();
+    URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, canonical.as_bytes()))
+}
+
+fn load_or_create_account_key(path: &Path) -> AcmeResult<EcdsaKeyPair> {
+    let rng = SystemRandom::new();
+    let pkcs8 = match std::fs::File::open(path) {
+        Ok(file) => rustls_pemfile::pkcs8_private_keys(&mut std::io::BufReader::new(file))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AcmeError::Key(format!("no PKCS#8 key in {}", path.display())))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+                .map_err(|e| AcmeError::Key(e.to_string()))?;
+            write_atomic(path, pem("PRIVATE KEY", pkcs8.as_ref()).as_bytes(), true)?;
+            info!("Created ACME account key {}", path.display());
+            pkcs8.as_ref().to_vec()
+        }
+        Err(e) => return Err(e.into()),
+    };
+    EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8, &rng)
+        .map_err(|e| AcmeError::Key(format!("{}: {}", path.display(), e)))
+}
+
+// Written beside the target and renamed over it, so TLS never loads a half-written file
+fn write_atomic(path: &Path, contents: &[u8], private: bool) -> std::io::Result<()> {
+    let temp = PathBuf::from(format!("{}.acme", path.display()));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    std::io::Write::write_all(&mut options.open(&temp)?, contents)?;
+    std::fs::rename(&temp, path)
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut out = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        out.push_str(std::str::from_utf8(line).unwrap_or_default());
+        out.push('\n');
+    }
+    out.push_str(&format!("-----END {}-----\n", label));
+    out
+}
+
+// DER object identifiers used in the certificate request
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_EXTENSION_REQUEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+/// PKCS#10 request for `domains` (subject CN is the first, all go in subjectAltName),
+/// signed with the P-256 PKCS#8 key
+fn certificate_request(domains: &[String], pkcs8: &[u8]) -> AcmeResult<Vec<u8>> {
+    let rng = SystemRandom::new();
+    let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8, &rng)
+        .map_err(|e| AcmeError::Key(e.to_string()))?;
+
+    // A CN is limited to 64 characters; a longer name is only in subjectAltName
+    let subject = match domains.first().filter(|d| d.len() <= 64) {
+        Some(domain) => der(0x30, &der(0x31, &der(0x30, &[der(0x06, OID_COMMON_NAME), der(0x0c, domain.as_bytes())].concat()))),
+        None => der(0x30, &[]),
+    };
+    let public_key_info = der(0x30, &[
+        der(0x30, &[der(0x06, OID_EC_PUBLIC_KEY), der(0x06, OID_PRIME256V1)].concat()),
+        bit_string(key.public_key().as_ref()),
+    ].concat());
+    let names: Vec<u8> = domains.iter().flat_map(|d| der(0x82, d.as_bytes())).collect();
+    let extension = der(0x30, &[der(0x06, OID_SUBJECT_ALT_NAME), der(0x04, &der(0x30, &names))].concat());
+    let attributes = der(0xa0, &der(0x30, &[
+        der(0x06, OID_EXTENSION_REQUEST),
+        der(0x31, &der(0x30, &extension)),
+    ].concat()));
+    let info = der(0x30, &[der(0x02, &[0]), subject, public_key_info, attributes].concat());
+
+    let signature = key.sign(&rng, &info).map_err(|e| AcmeError::Key(e.to_string()))?;
+    Ok(der(0x30, &[
+        info,
+        der(0x30, &der(0x06, OID_ECDSA_WITH_SHA256)),
+        bit_string(signature.as_ref()),
+    ].concat()))
+}
+
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    der(0x03, &[&[0], bytes].concat()) // No unused bits
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let len = content.len();
+    let mut out = vec![tag];
+    match len {
+        0..=0x7f => out.push(len as u8),
+        0x80..=0xff => out.extend([0x81, len as u8]),
+        _ => out.extend([0x82, (len >> 8) as u8, len as u8]),
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_FIXED};
+    use std::sync::Mutex;
+
+    // Split a DER value into (tag, content, rest)
+    fn parse(bytes: &[u8]) -> (u8, &[u8], &[u8]) {
+        let (len, header) = match bytes[1] {
+            0x81 => (bytes[2] as usize, 3),
+            0x82 => (((bytes[2] as usize) << 8) | bytes[3] as usize, 4),
+            len => (len as usize, 2),
+        };
+        (bytes[0], &bytes[header..header + len], &bytes[header + len..])
+    }
+
+    fn key_pair() -> (Vec<u8>, EcdsaKeyPair) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+        (pkcs8.as_ref().to_vec(), key)
+    }
+
+    #[test]
+    fn test_certificate_request_is_signed_and_names_every_domain() {
+        let (pkcs8, key) = key_pair();
+        let domains = vec!["radio.example.com".to_string(), "www.radio.example.com".to_string()];
+        let csr = certificate_request(&domains, &pkcs8).unwrap();
+
+        let (tag, request, rest) = parse(&csr);
+        assert_eq!(tag, 0x30);
+        assert!(rest.is_empty());
+        let info_len = request.len() - parse(request).2.len();
+        let (info, algorithm_and_signature) = request.split_at(info_len);
+        let (_, algorithm, signature) = parse(algorithm_and_signature);
+        assert_eq!(algorithm, der(0x06, OID_ECDSA_WITH_SHA256));
+        let (tag, signature, _) = parse(signature);
+        assert_eq!(tag, 0x03);
+
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, key.public_key().as_ref())
+            .verify(info, &signature[1..])
+            .expect("CSR signature verifies with the certificate key");
+        for domain in &domains {
+            let name = der(0x82, domain.as_bytes());
+            assert!(info.windows(name.len()).any(|w| w == name), "{} in subjectAltName", domain);
+        }
+    }
+
+    #[test]
+    fn test_jwk_thumbprint() {
+        let (_, key) = key_pair();
+        let public_key = key.public_key().as_ref();
+        let jwk = jwk(public_key);
+        let canonical = format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            jwk["x"].as_str().unwrap(),
+            jwk["y"].as_str().unwrap(),
+        );
+        let expected = URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, canonical.as_bytes()));
+        assert_eq!(jwk_thumbprint(public_key), expected);
+        assert_eq!(URL_SAFE_NO_PAD.decode(expected).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn test_der_long_lengths() {
+        assert_eq!(der(0x04, &[0; 3])[..2], [0x04, 3]);
+        assert_eq!(der(0x04, &[0; 200])[..3], [0x04, 0x81, 200]);
+        assert_eq!(der(0x04, &[0; 300])[..4], [0x04, 0x82, 0x01, 0x2c]);
+    }
+
+    // A minimal CA: checks JWS signatures, fetches the key authorization from the
+    // challenge listener and hands out the test certificate
+    #[derive(Default)]
+    struct MockCa {
+        base: String,
+        challenge_addr: Option<SocketAddr>,
+        account_jwk: Option<Value>,
+        validated: bool,
+        csr: Option<Vec<u8>>,
+    }
+
+    type Ca = Arc<Mutex<MockCa>>;
+
+    fn verify_jws(ca: &Ca, body: &str) -> (Value, Value) {
+        let jws: Value = serde_json::from_str(body).unwrap();
+        let decode = |field: &str| URL_SAFE_NO_PAD.decode(jws[field].as_str().unwrap()).unwrap();
+        let protected: Value = serde_json::from_slice(&decode("protected")).unwrap();
+        let jwk = match &protected["jwk"] {
+            Value::Null => ca.lock().unwrap().account_jwk.clone().expect("account registered before kid requests"),
+            jwk => jwk.clone(),
+        };
+        let point = [vec![4], URL_SAFE_NO_PAD.decode(jwk["x"].as_str().unwrap()).unwrap(), URL_SAFE_NO_PAD.decode(jwk["y"].as_str().unwrap()).unwrap()].concat();
+        let message = format!("{}.{}", jws["protected"].as_str().unwrap(), jws["payload"].as_str().unwrap());
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, point)
+            .verify(message.as_bytes(), &decode("signature"))
+            .expect("JWS signature verifies");
+        assert!(protected["nonce"].is_string());
+        let payload = decode("payload");
+        let payload = if payload.is_empty() { Value::Null } else { serde_json::from_slice(&payload).unwrap() };
+        (protected, payload)
+    }
+
+    fn nonce_headers() -> [(&'static str, String); 1] {
+        [("replay-nonce", uuid::Uuid::new_v4().simple().to_string())]
+    }
+
+    async fn spawn_mock_ca() -> Ca {
+        use axum::{response::IntoResponse, routing::post};
+
+        let ca: Ca = Arc::default();
+        let app = Router::new()
+            .route("/directory", get(|State(ca): State<Ca>| async move {
+                let base = ca.lock().unwrap().base.clone();
+                axum::Json(json!({
+                    "newNonce": format!("{}/nonce", base),
+                    "newAccount": format!("{}/account", base),
+                    "newOrder": format!("{}/order", base),
+                }))
+            }))
+            .route("/nonce", get(|| async { (nonce_headers(), "") }))
+            .route("/account", post(|State(ca): State<Ca>, body: String| async move {
+                let (protected, payload) = verify_jws(&ca, &body);
+                assert_eq!(payload["contact"], json!(["mailto:ops@example.com"]));
+                let mut state = ca.lock().unwrap();
+                state.account_jwk = Some(protected["jwk"].clone());
+                (StatusCode::CREATED, nonce_headers(), [("location", format!("{}/account/1", state.base))], "{}")
+            }))
+            .route("/order", post(|State(ca): State<Ca>, body: String| async move {
+                let (protected, payload) = verify_jws(&ca, &body);
+                let base = ca.lock().unwrap().base.clone();
+                assert_eq!(protected["kid"], json!(format!("{}/account/1", base)));
+                assert_eq!(payload["identifiers"][0]["value"], "radio.example.com");
+                let order = json!({
+                    "status": "pending",
+                    "authorizations": [format!("{}/authz/1", base)],
+                    "finalize": format!("{}/finalize/1", base),
+                });
+                (StatusCode::CREATED, nonce_headers(), [("location", format!("{}/order/1", base))], axum::Json(order))
+            }))
+            .route("/authz/1", post(|State(ca): State<Ca>, body: String| async move {
+                verify_jws(&ca, &body);
+                let state = ca.lock().unwrap();
+                let status = if state.validated { "valid" } else { "pending" };
+                (nonce_headers(), axum::Json(json!({
+                    "status": status,
+                    "identifier": { "type": "dns", "value": "radio.example.com" },
+                    "challenges": [
+                        { "type": "dns-01", "url": format!("{}/challenge/2", state.base), "token": "dns-token" },
+                        { "type": "http-01", "url": format!("{}/challenge/1", state.base), "token": "http-token" },
+                    ],
+                })))
+            }))
+            .route("/challenge/1", post(|State(ca): State<Ca>, body: String| async move {
+                verify_jws(&ca, &body);
+                let (addr, jwk) = {
+                    let state = ca.lock().unwrap();
+                    (state.challenge_addr.unwrap(), state.account_jwk.clone().unwrap())
+                };
+                let url = format!("http://127.0.0.1:{}/.well-known/acme-challenge/http-token", addr.port());
+                let served = reqwest::get(url).await.unwrap().text().await.unwrap();
+                let thumbprint = URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, jwk.to_string().as_bytes()));
+                assert_eq!(served, format!("http-token.{}", thumbprint));
+                ca.lock().unwrap().validated = true;
+                (nonce_headers(), axum::Json(json!({ "status": "processing" })))
+            }))
+            .route("/finalize/1", post(|State(ca): State<Ca>, body: String| async move {
+                let (_, payload) = verify_jws(&ca, &body);
+                let csr = URL_SAFE_NO_PAD.decode(payload["csr"].as_str().unwrap()).unwrap();
+                ca.lock().unwrap().csr = Some(csr);
+                (nonce_headers(), axum::Json(json!({ "status": "processing" })))
+            }))
+            .route("/order/1", post(|State(ca): State<Ca>, body: String| async move {
+                verify_jws(&ca, &body);
+                let base = ca.lock().unwrap().base.clone();
+                (nonce_headers(), axum::Json(json!({
+                    "status": "valid",
+                    "finalize": format!("{}/finalize/1", base),
+                    "certificate": format!("{}/cert/1", base),
+                })))
+            }))
+            .route("/cert/1", post(|State(ca): State<Ca>, body: String| async move {
+                verify_jws(&ca, &body);
+                (nonce_headers(), std::fs::read_to_string("tests/fixtures/localhost.crt").unwrap()).into_response()
+            }))
+            .with_state(Arc::clone(&ca));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        ca.lock().unwrap().base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        ca
+    }
+
+    #[tokio::test]
+    async fn test_issue_writes_certificate_and_key() {
+        let ca = spawn_mock_ca().await;
+        let dir = std::env::temp_dir().join(format!("webradio-acme-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut config = Config::from_env();
+        config.tls_cert_path = Some(dir.join("fullchain.pem"));
+        config.tls_key_path = Some(dir.join("privkey.pem"));
+        config.acme_domains = vec!["radio.example.com".to_string()];
+        config.acme_email = Some("ops@example.com".to_string());
+        config.acme_directory = format!("{}/directory", ca.lock().unwrap().base);
+        let manager = CertificateManager::from_config(&config).unwrap().unwrap();
+        ca.lock().unwrap().challenge_addr = Some(manager.serve_challenges(0).await.unwrap());
+
+        assert!(manager.needs_renewal());
+        manager.issue().await.unwrap();
+        assert!(!manager.needs_renewal());
+
+        let chain = std::fs::read_to_string(dir.join("fullchain.pem")).unwrap();
+        assert_eq!(chain, std::fs::read_to_string("tests/fixtures/localhost.crt").unwrap());
+        let mut key = std::io::BufReader::new(std::fs::File::open(dir.join("privkey.pem")).unwrap());
+        let key = rustls_pemfile::pkcs8_private_keys(&mut key).unwrap().remove(0);
+        assert!(EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &key, &SystemRandom::new()).is_ok());
+        assert!(dir.join("acme-account.key").exists());
+        assert!(ca.lock().unwrap().csr.is_some());
+        // Challenge answers are withdrawn once the order is done
+        assert!(manager.challenges.is_empty());
+
+        // A second run reuses the account key
+        let account_key = std::fs::read(dir.join("acme-account.key")).unwrap();
+        ca.lock().unwrap().validated = false;
+        manager.issue().await.unwrap();
+        assert_eq!(std::fs::read(dir.join("acme-account.key")).unwrap(), account_key);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
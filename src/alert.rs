@@ -0,0 +1,98 @@
+// Operator alerts: pushed to SSE clients as `alert` events and POSTed as JSON to
+// ALERT_WEBHOOK_URL (Slack/Discord-compatible relays, PagerDuty bridges, ...)
+
+use std::time::Duration;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    HoldStarted, // Nothing playable: fallback audio is on air
+    HoldEnded,   // Normal playback (or a relay) resumed
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub message: String,
+    pub station: String,
+    pub timestamp: u64, // Unix seconds
+}
+
+pub struct Alerts {
+    tx: broadcast::Sender<Alert>,
+    station: String,
+    webhook_url: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Alerts {
+    pub fn from_config(config: &Config) -> Self {
+        let (tx, _) = broadcast::channel(16);
+        let http = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            tx,
+            station: config.station_name.clone(),
+            webhook_url: config.alert_webhook_url.clone(),
+            http,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Alert> {
+        self.tx.subscribe()
+    }
+
+    /// Log the alert, send it to SSE subscribers and fire the webhook in the background
+    pub fn raise(&self, kind: AlertKind, message: impl Into<String>) -> Alert {
+        let alert = Alert {
+            kind,
+            message: message.into(),
+            station: self.station.clone(),
+            timestamp: chrono::Utc::now().timestamp() as u64,
+        };
+        match kind {
+            AlertKind::HoldStarted => warn!("Alert: {}", alert.message),
+            AlertKind::HoldEnded => info!("Alert: {}", alert.message),
+        }
+        let _ = self.tx.send(alert.clone()); // No SSE clients is fine
+
+        if let Some(url) = &self.webhook_url {
+            let request = self.http.post(url).json(&alert);
+            let url = url.clone();
+            tokio::spawn(async move {
+                match request.send().await.and_then(|r| r.error_for_status()) {
+                    Ok(_) => debug!("Alert webhook delivered to {}", url),
+                    Err(e) => warn!("Alert webhook {} failed: {}", url, e),
+                }
+            });
+        }
+        alert
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_raise_reaches_subscribers() {
+        let mut config = Config::from_env();
+        config.alert_webhook_url = None;
+        let alerts = Alerts::from_config(&config);
+        let mut receiver = alerts.subscribe();
+
+        alerts.raise(AlertKind::HoldStarted, "Playlist is empty");
+        let alert = receiver.recv().await.unwrap();
+        assert_eq!(alert.kind, AlertKind::HoldStarted);
+        assert_eq!(serde_json::to_value(&alert).unwrap()["kind"], "hold_started");
+    }
+}
@@ -0,0 +1,455 @@
+// Offline library analysis (`webradio analyze`): decodes every track to measure
+// integrated loudness (ITU-R BS.1770 / EBU R128), sample peak, duration and leading
+// and trailing silence, takes its fingerprint and hashes the file, storing the
+// results in playlist.json before first broadcast
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use futures::StreamExt;
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::DecoderOptions,
+    errors::Error as SymphoniaError,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+use tracing::{info, warn};
+
+use crate::{fingerprint::{self, Fingerprinter}, mp3, playlist::Playlist, types::TrackAnalysis};
+
+const REFERENCE_LUFS: f64 = -18.0; // ReplayGain 2.0
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+#[derive(Debug, Default)]
+pub struct AnalyzeSummary {
+    pub analyzed: usize,
+    pub unchanged: usize, // Skipped: file hash matches the stored analysis
+    pub failed: usize,
+    pub benched_duplicates: usize, // Lower-bitrate copies taken out of rotation
+}
+
+/// Analyze the library in `music_dir` with up to `jobs` tracks in flight and save
+/// the results to its playlist.json. Tracks whose file hasn't changed since their
+/// last analysis are skipped unless `force` is set. Silence is audio below
+/// `silence_threshold_db` (dBFS). With `bench_duplicates`, all but the
+/// highest-bitrate copy of each recording found twice are benched.
+pub async fn analyze_library(
+    music_dir: &Path,
+    jobs: usize,
+    force: bool,
+    bench_duplicates: bool,
+    silence_threshold_db: f64,
+) -> crate::Result<AnalyzeSummary> {
+    let mut playlist = Playlist::load_or_scan(music_dir).await?;
+    let total = playlist.tracks.len();
+    info!("Analyzing {} tracks with {} jobs", total, jobs);
+
+    let work: Vec<(usize, PathBuf, Option<String>)> = playlist.tracks.iter().enumerate()
+        .map(|(index, track)| {
+            // Analyses from before fingerprints are redone once to add one, and silence
+            // is measured again when the threshold changes
+            let needs_fingerprint = |a: &TrackAnalysis| a.fingerprint.is_empty() && a.duration_ms >= 1000;
+            let needs_silence = |a: &TrackAnalysis| a.silence_threshold_db != Some(silence_threshold_db);
+            let known_hash = track.analysis.as_ref()
+                .filter(|a| !force && !needs_fingerprint(a) && !needs_silence(a))
+                .map(|a| a.sha256.clone());
+            (index, music_dir.join(&track.path), known_hash)
+        })
+        .collect();
+    let mut results = futures::stream::iter(work)
+        .map(|(index, path, known_hash)| async move {
+            let result = tokio::task::spawn_blocking(move || analyze_if_changed(&path, known_hash.as_deref(), silence_threshold_db))
+                .await
+                .unwrap_or_else(|e| Err(io::Error::other(e)));
+            (index, result)
+        })
+        .buffer_unordered(jobs.max(1));
+
+    let mut summary = AnalyzeSummary::default();
+    let mut done = 0;
+    while let Some((index, result)) = results.next().await {
+        done += 1;
+        let track = &mut playlist.tracks[index];
+        match result {
+            Ok(Some(analysis)) => {
+                info!("[{}/{}] {}: {} LUFS, peak {:.1} dBFS, {:.1}s",
+                    done, total, track.path.display(),
+                    analysis.loudness_lufs.map_or("-inf".to_string(), |l| format!("{:.1}", l)),
+                    analysis.peak_dbfs, analysis.duration_ms as f64 / 1000.0);
+                if track.duration.is_none() {
+                    track.duration = Some(analysis.duration_ms / 1000);
+                }
+                track.analysis = Some(analysis);
+                summary.analyzed += 1;
+            }
+            Ok(None) => summary.unchanged += 1,
+            Err(e) => {
+                warn!("[{}/{}] {}: analysis failed: {}", done, total, track.path.display(), e);
+                summary.failed += 1;
+            }
+        }
+    }
+
+    if bench_duplicates {
+        for group in fingerprint::find_duplicates(&playlist.tracks) {
+            for &id in &group.tracks[1..] {
+                let Some(track) = playlist.tracks.iter_mut().find(|track| track.id == id && track.enabled) else {
+                    continue;
+                };
+                info!("Benching {}: duplicate of a higher-bitrate copy", track.path.display());
+                track.enabled = false;
+                track.disabled_reason = Some("duplicate".to_string());
+                summary.benched_duplicates += 1;
+            }
+        }
+    }
+
+    if summary.analyzed > 0 || summary.benched_duplicates > 0 {
+        playlist.save_to(music_dir).await?;
+    }
+    Ok(summary)
+}
+
+// Hash the file and, unless it matches `known_hash`, decode and measure it
+fn analyze_if_changed(path: &Path, known_hash: Option<&str>, silence_threshold_db: f64) -> io::Result<Option<TrackAnalysis>> {
+    let data = std::fs::read(path)?;
+    let sha256 = hex(ring::digest::digest(&ring::digest::SHA256, &data).as_ref());
+    if known_hash == Some(sha256.as_str()) {
+        return Ok(None);
+    }
+    let mut analysis = analyze_audio(data, silence_threshold_db)?;
+    analysis.sha256 = sha256;
+    Ok(Some(analysis))
+}
+
+/// Decode `data` (an MP3, FLAC, Ogg or AAC file) and measure it, counting audio below
+/// `silence_threshold_db` as silence; `sha256` is left empty
+pub fn analyze_audio(mut data: Vec<u8>, silence_threshold_db: f64) -> io::Result<TrackAnalysis> {
+    // Trailing ID3v1/APE tags aren't audio; left in, they decode as noise
+    let tags = mp3::trailing_tags_len(&data[data.len().saturating_sub(mp3::TRAILING_TAGS_PROBE)..]);
+    data.truncate(data.len().saturating_sub(tags));
+    let source = MediaSourceStream::new(Box::new(io::Cursor::new(data)), Default::default());
+    let probed = symphonia::default::get_probe()
+        .format(&Hint::new(), source, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(decode_error)?;
+    let mut format = probed.format;
+    let track = format.default_track().ok_or_else(|| io::Error::other("no audio track"))?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(decode_error)?;
+
+    let mut meter: Option<LoudnessMeter> = None;
+    let mut fingerprinter: Option<Fingerprinter> = None;
+    let mut silence: Option<SilenceDetector> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(decode_error(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue, // Skip a corrupt frame
+            Err(e) => return Err(decode_error(e)),
+        };
+        let spec = *decoded.spec();
+        let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        samples.copy_interleaved_ref(decoded);
+        meter
+            .get_or_insert_with(|| LoudnessMeter::new(spec.rate, spec.channels.count()))
+            .push(samples.samples());
+        fingerprinter
+            .get_or_insert_with(|| Fingerprinter::new(spec.rate, spec.channels.count()))
+            .push(samples.samples());
+        silence
+            .get_or_insert_with(|| SilenceDetector::new(silence_threshold_db, spec.channels.count()))
+            .push(samples.samples());
+    }
+
+    let meter = meter.ok_or_else(|| io::Error::other("no audio decoded"))?;
+    let loudness_lufs = meter.integrated_lufs();
+    let (leading_silence_ms, trailing_silence_ms) = silence.map_or((0, 0), |s| s.silence_ms(meter.sample_rate));
+    Ok(TrackAnalysis {
+        loudness_lufs,
+        gain_db: loudness_lufs.map(|lufs| REFERENCE_LUFS - lufs),
+        peak: meter.peak,
+        peak_dbfs: 20.0 * meter.peak.max(1e-10).log10(),
+        duration_ms: meter.frames * 1000 / meter.sample_rate as u64,
+        sha256: String::new(),
+        analyzed_at: chrono::Utc::now().timestamp() as u64,
+        fingerprint: fingerprinter.map(|f| fingerprint::encode(&f.finish())).unwrap_or_default(),
+        silence_threshold_db: Some(silence_threshold_db),
+        leading_silence_ms,
+        trailing_silence_ms,
+    })
+}
+
+/// Where the audio first and last rises above a level, for the silence before
+/// and after it
+#[derive(Debug)]
+struct SilenceDetector {
+    threshold: f32, // Linear sample level
+    channels: usize,
+    frames: u64,
+    first: Option<u64>, // First and last frame above the threshold
+    last: u64,
+}
+
+impl SilenceDetector {
+    fn new(threshold_db: f64, channels: usize) -> Self {
+        Self { threshold: 10f64.powf(threshold_db / 20.0) as f32, channels, frames: 0, first: None, last: 0 }
+    }
+
+    /// Add interleaved samples
+    fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            if frame.iter().any(|sample| sample.abs() > self.threshold) {
+                self.first.get_or_insert(self.frames);
+                self.last = self.frames;
+            }
+            self.frames += 1;
+        }
+    }
+
+    /// Leading and trailing silence in ms. A track that is silent throughout has
+    /// nothing to trim: skipping all of it is for the operator to decide.
+    fn silence_ms(&self, sample_rate: u32) -> (u64, u64) {
+        let Some(first) = self.first else {
+            return (0, 0);
+        };
+        let ms = |frames: u64| frames * 1000 / sample_rate as u64;
+        (ms(first), ms(self.frames - self.last - 1))
+    }
+}
+
+fn decode_error(e: SymphoniaError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// BS.1770 integrated loudness: K-weighted mean square over 400ms blocks with 75%
+/// overlap, gated at -70 LUFS and then 10 LU below the mean of what remains
+#[derive(Debug)]
+pub struct LoudnessMeter {
+    sample_rate: u32,
+    channels: usize,
+    filters: Vec<[Biquad; 2]>, // Per channel: high shelf, then high pass
+    step_len: usize,           // 100ms in frames
+    step_frames: usize,
+    step_energy: f64,          // Sum over channels of squared filtered samples in the current step
+    steps: Vec<f64>,           // Energy of each completed 100ms step
+    pub peak: f64,
+    pub frames: u64,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let rate = sample_rate as f64;
+        Self {
+            sample_rate,
+            channels,
+            filters: (0..channels).map(|_| [Biquad::high_shelf(rate), Biquad::high_pass(rate)]).collect(),
+            step_len: (sample_rate / 10) as usize,
+            step_frames: 0,
+            step_energy: 0.0,
+            steps: Vec::new(),
+            peak: 0.0,
+            frames: 0,
+        }
+    }
+
+    /// Add interleaved samples
+    pub fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (sample, [shelf, high_pass]) in frame.iter().zip(self.filters.iter_mut()) {
+                let x = *sample as f64;
+                self.peak = self.peak.max(x.abs());
+                let y = high_pass.process(shelf.process(x));
+                self.step_energy += y * y; // Channel weight 1.0 (no surround channels)
+            }
+            self.frames += 1;
+            self.step_frames += 1;
+            if self.step_frames == self.step_len {
+                self.steps.push(self.step_energy);
+                self.step_energy = 0.0;
+                self.step_frames = 0;
+            }
+        }
+    }
+
+    /// None when nothing is above the absolute gate (silence, or under 400ms)
+    pub fn integrated_lufs(&self) -> Option<f64> {
+        let block_len = (self.step_len * 4) as f64;
+        let blocks: Vec<f64> = self.steps.windows(4)
+            .map(|steps| steps.iter().sum::<f64>() / block_len)
+            .collect();
+        let loudness = |z: f64| -0.691 + 10.0 * z.log10();
+        let gated_mean = |threshold: f64| {
+            let kept: Vec<f64> = blocks.iter().copied().filter(|&z| z > 0.0 && loudness(z) > threshold).collect();
+            (!kept.is_empty()).then(|| kept.iter().sum::<f64>() / kept.len() as f64)
+        };
+
+        let ungated = gated_mean(ABSOLUTE_GATE_LUFS)?;
+        let relative_gate = loudness(ungated) + RELATIVE_GATE_LU;
+        gated_mean(relative_gate.max(ABSOLUTE_GATE_LUFS)).map(loudness)
+    }
+}
+
+// Direct form I biquad; coefficients normalized by a0
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a0: f64, a1: f64, a2: f64) -> Self {
+        Self { b: [b[0] / a0, b[1] / a0, b[2] / a0], a: [a1 / a0, a2 / a0], x: [0.0; 2], y: [0.0; 2] }
+    }
+
+    // K-weighting stage 1: +4 dB shelf above ~1.5kHz (head diffraction)
+    fn high_shelf(rate: f64) -> Self {
+        let (gain_db, q, fc) = (4.0_f64, std::f64::consts::FRAC_1_SQRT_2, 1500.0);
+        let a = 10f64.powf(gain_db / 40.0);
+        let w0 = 2.0 * std::f64::consts::PI * fc / rate;
+        let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+        Self::new(
+            [
+                a * ((a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha),
+            ],
+            (a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha,
+        )
+    }
+
+    // K-weighting stage 2: RLB high pass at ~38Hz
+    fn high_pass(rate: f64) -> Self {
+        let (q, fc) = (0.5, 38.0);
+        let w0 = 2.0 * std::f64::consts::PI * fc / rate;
+        let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+        Self::new(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(rate: u32, channels: usize, amplitude: f32, seconds: u32) -> Vec<f32> {
+        (0..rate * seconds)
+            .flat_map(|n| {
+                let v = amplitude * (2.0 * std::f32::consts::PI * 997.0 * n as f32 / rate as f32).sin();
+                std::iter::repeat_n(v, channels)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_full_scale_sine_reads_minus_3_lufs() {
+        // BS.1770: a 0 dBFS 997Hz sine in one channel measures -3.01 LKFS
+        for rate in [44_100, 48_000] {
+            let mut meter = LoudnessMeter::new(rate, 1);
+            meter.push(&sine(rate, 1, 1.0, 5));
+            let lufs = meter.integrated_lufs().unwrap();
+            assert!((lufs + 3.01).abs() < 0.1, "{}Hz: {}", rate, lufs);
+            assert!((meter.peak - 1.0).abs() < 1e-3);
+        }
+
+        // Both stereo channels: 3 dB louder; -20 dBFS: 20 dB quieter
+        let mut meter = LoudnessMeter::new(48_000, 2);
+        meter.push(&sine(48_000, 2, 0.1, 5));
+        let lufs = meter.integrated_lufs().unwrap();
+        assert!((lufs + 20.0).abs() < 0.1, "{}", lufs);
+    }
+
+    #[test]
+    fn test_silence_has_no_loudness() {
+        let mut meter = LoudnessMeter::new(48_000, 2);
+        meter.push(&vec![0.0; 48_000 * 2]);
+        assert_eq!(meter.integrated_lufs(), None);
+        assert_eq!(meter.frames, 48_000);
+    }
+
+    #[test]
+    fn test_analyze_tone_mp3() {
+        let mp3 = crate::tone::sine_mp3(1000, 2, 128).unwrap();
+        let analysis = analyze_audio(mp3.to_vec(), -50.0).unwrap();
+        // The tone is a -6 dBFS sine in both channels
+        let lufs = analysis.loudness_lufs.unwrap();
+        assert!((lufs + 6.0).abs() < 1.0, "{}", lufs);
+        assert!((analysis.gain_db.unwrap() - (-18.0 - lufs)).abs() < 1e-9);
+        assert!((analysis.peak_dbfs + 6.0).abs() < 0.5, "{}", analysis.peak_dbfs);
+        assert!((1900..2200).contains(&analysis.duration_ms), "{}", analysis.duration_ms);
+        assert_eq!(analysis.silence_threshold_db, Some(-50.0));
+        assert!(analysis.leading_silence_ms < 100, "{}", analysis.leading_silence_ms);
+    }
+
+    #[test]
+    fn test_analyze_vorbis_and_aac() {
+        // libvorbis: a full-scale 440Hz sine in both channels
+        let analysis = analyze_audio(std::fs::read("tests/fixtures/sine-440hz-stereo.ogg").unwrap(), -50.0).unwrap();
+        let lufs = analysis.loudness_lufs.unwrap();
+        assert!((-2.0..0.5).contains(&lufs), "{}", lufs);
+        assert!((950..1100).contains(&analysis.duration_ms), "{}", analysis.duration_ms);
+
+        // AAC-LC, -6 dBFS 440Hz mono, after a frame of encoder delay
+        for path in ["tests/fixtures/tone-8k-mono.m4a", "tests/fixtures/tone-8k-mono.aac"] {
+            let analysis = analyze_audio(std::fs::read(path).unwrap(), -50.0).unwrap();
+            assert!((analysis.peak_dbfs + 6.0).abs() < 0.5, "{}: {}", path, analysis.peak_dbfs);
+            assert!((1100..1200).contains(&analysis.duration_ms), "{}: {}", path, analysis.duration_ms);
+            assert_eq!(analysis.leading_silence_ms, 128, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_measures_leading_and_trailing_silence() {
+        let mp3 = std::fs::read("music/Dhiyana.mp3").unwrap();
+        let frames = crate::mp3::split_frames(&mp3);
+        let (silence, silence_ms) = crate::mp3::silence(&frames[0].0, 2000.0);
+        let audio: Vec<u8> = frames[100..300].iter().flat_map(|(_, frame)| frame.to_vec()).collect();
+
+        let analysis = analyze_audio([silence.clone(), audio, silence].concat(), -50.0).unwrap();
+        for measured in [analysis.leading_silence_ms, analysis.trailing_silence_ms] {
+            // Give or take the frames' overlap at the edges of the audio
+            assert!(measured.abs_diff(silence_ms as u64) < 100, "{} of {}", measured, silence_ms);
+        }
+
+        let mut detector = SilenceDetector::new(-50.0, 2);
+        detector.push(&[0.0, 0.001, 0.5, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(detector.silence_ms(1000), (1, 2));
+        let mut silent = SilenceDetector::new(-50.0, 1);
+        silent.push(&[0.001; 100]);
+        assert_eq!(silent.silence_ms(1000), (0, 0));
+    }
+}
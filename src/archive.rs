@@ -0,0 +1,408 @@
+use std::{io::SeekFrom, path::{Path, PathBuf}};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::{AsyncReadExt, AsyncSeekExt}};
+use tracing::warn;
+
+use crate::{error::Result, history::PlayRecord, mp3::{self, FrameHeader}};
+
+/// A recorded show available for on-demand listening
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveEntry {
+    pub id: String,
+    pub show: String,
+    pub title: Option<String>,
+    pub started_at: u64,        // Unix timestamp (seconds) when the recording started
+    pub date: String,           // YYYY-MM-DD (UTC) of started_at
+    pub duration: Option<u64>,  // Seconds, estimated from the first frame's bitrate
+    pub size: u64,
+    pub recording: bool,        // Still being written by the archiver
+    #[serde(skip)]
+    pub path: PathBuf,
+    #[serde(skip)]
+    pub chapters: Option<Vec<Chapter>>, // From the sidecar, if chapters were written
+}
+
+/// One track within a recorded show
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start_ms: u64, // Offset into the recording
+    pub end_ms: u64,
+    pub title: String,
+    pub artist: String,
+    #[serde(default)]
+    pub album: String,
+}
+
+/// Optional `<recording>.json` sidecar written next to a recording
+#[derive(Debug, Default, Deserialize)]
+struct Sidecar {
+    show: Option<String>,
+    title: Option<String>,
+    started_at: Option<u64>,
+    duration: Option<u64>,
+    chapters: Option<Vec<Chapter>>,
+    #[serde(default)]
+    recording: bool,
+}
+
+/// Filters for `/api/archive`; all given filters must match
+#[derive(Debug, Default)]
+pub struct ArchiveQuery {
+    pub show: Option<String>,    // Case-insensitive substring of the show name
+    pub date: Option<NaiveDate>, // Recorded on this day (UTC)
+    pub from: Option<NaiveDate>, // Recorded on or after this day
+    pub to: Option<NaiveDate>,   // Recorded on or before this day
+    pub q: Option<String>,       // Case-insensitive substring of show or title
+}
+
+impl ArchiveQuery {
+    pub fn matches(&self, entry: &ArchiveEntry) -> bool {
+        let contains = |haystack: &str, needle: &str| haystack.to_lowercase().contains(&needle.to_lowercase());
+        let day = NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d").ok();
+
+        self.show.as_deref().is_none_or(|show| contains(&entry.show, show))
+            && self.q.as_deref().is_none_or(|q| {
+                contains(&entry.show, q) || entry.title.as_deref().is_some_and(|title| contains(title, q))
+            })
+            && self.date.is_none_or(|date| day == Some(date))
+            && self.from.is_none_or(|from| day.is_some_and(|day| day >= from))
+            && self.to.is_none_or(|to| day.is_some_and(|day| day <= to))
+    }
+}
+
+/// Directory of recorded shows (MP3 files, optionally with JSON sidecars).
+/// Show and start time come from the sidecar, then from the file name
+/// (`<show>_<YYYY-MM-DD>[_<HHMM>|_<HHMMSS>].mp3`), then from the file itself.
+pub struct Archive {
+    dir: PathBuf,
+}
+
+impl Archive {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// All recordings, newest first; a missing directory is an empty archive
+    pub async fn list(&self) -> Result<Vec<ArchiveEntry>> {
+        let mut dir = match fs::read_dir(&self.dir).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        while let Some(item) = dir.next_entry().await? {
+            let path = item.path();
+            if !path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mp3")) {
+                continue;
+            }
+            match Self::load_entry(&path).await {
+                Ok(Some(entry)) => entries.push(entry),
+                Ok(None) => {}
+                Err(e) => warn!("Skipping archive file {}: {}", path.display(), e),
+            }
+        }
+
+        entries.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(entries)
+    }
+
+    pub async fn search(&self, query: &ArchiveQuery) -> Result<Vec<ArchiveEntry>> {
+        Ok(self.list().await?.into_iter().filter(|entry| query.matches(entry)).collect())
+    }
+
+    /// Look up a recording by id; ids are file stems, so anything that could
+    /// escape the archive directory is rejected
+    pub async fn find(&self, id: &str) -> Result<Option<ArchiveEntry>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+        let path = self.dir.join(format!("{}.mp3", id));
+        if !fs::try_exists(&path).await? {
+            return Ok(None);
+        }
+        Self::load_entry(&path).await
+    }
+
+    async fn load_entry(path: &Path) -> Result<Option<ArchiveEntry>> {
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()).filter(|id| is_valid_id(id)) else {
+            return Ok(None);
+        };
+
+        let metadata = fs::metadata(path).await?;
+        let sidecar = match fs::read(path.with_extension("json")).await {
+            Ok(data) => serde_json::from_slice::<Sidecar>(&data).unwrap_or_else(|e| {
+                warn!("Ignoring invalid archive sidecar for {}: {}", id, e);
+                Sidecar::default()
+            }),
+            Err(_) => Sidecar::default(),
+        };
+
+        let (name_show, name_started_at) = parse_file_name(id);
+        let modified_at = metadata.modified().ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let started_at = sidecar.started_at.or(name_started_at).unwrap_or(modified_at);
+        let date = DateTime::from_timestamp(started_at as i64, 0)
+            .map(|dt| dt.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+
+        let duration = match sidecar.duration {
+            Some(duration) => Some(duration),
+            None => estimate_duration(path, metadata.len()).await?,
+        };
+
+        Ok(Some(ArchiveEntry {
+            id: id.to_string(),
+            show: sidecar.show.unwrap_or(name_show),
+            title: sidecar.title,
+            started_at,
+            date,
+            duration,
+            size: metadata.len(),
+            recording: sidecar.recording,
+            path: path.to_path_buf(),
+            chapters: sidecar.chapters,
+        }))
+    }
+
+    /// Store chapters in the recording's sidecar, keeping any other fields in it
+    pub async fn write_chapters(&self, entry: &ArchiveEntry, chapters: &[Chapter]) -> Result<()> {
+        let chapters = serde_json::to_value(chapters)?;
+        update_sidecar(&entry.path, |sidecar| sidecar["chapters"] = chapters).await
+    }
+
+    /// Delete a recording and its sidecar
+    pub async fn remove(&self, entry: &ArchiveEntry) -> Result<()> {
+        fs::remove_file(&entry.path).await?;
+        match fs::remove_file(entry.path.with_extension("json")).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Edit the JSON sidecar of the recording at `recording_path` (created if missing)
+pub async fn update_sidecar(recording_path: &Path, edit: impl FnOnce(&mut serde_json::Value)) -> Result<()> {
+    let sidecar_path = recording_path.with_extension("json");
+    let mut sidecar = match fs::read(&sidecar_path).await {
+        Ok(data) => serde_json::from_slice::<serde_json::Value>(&data)
+            .ok()
+            .filter(|value| value.is_object())
+            .unwrap_or_else(|| serde_json::json!({})),
+        Err(_) => serde_json::json!({}),
+    };
+    edit(&mut sidecar);
+    fs::write(&sidecar_path, serde_json::to_vec_pretty(&sidecar)?).await?;
+    Ok(())
+}
+
+/// Chapters at track boundaries, from the plays that overlap the recording.
+/// A track already playing when the recording started becomes the first chapter at 0.
+pub fn chapters_from_history(entry: &ArchiveEntry, records: &[PlayRecord]) -> Vec<Chapter> {
+    let start = entry.started_at;
+    let end = entry.duration.map(|duration| start + duration);
+
+    let mut plays: Vec<&PlayRecord> = records.iter()
+        .filter(|record| record.played_seconds > 0 && record.started_at + record.played_seconds > start)
+        .filter(|record| end.is_none_or(|end| record.started_at < end))
+        .collect();
+    plays.sort_by_key(|record| record.started_at);
+
+    plays.iter()
+        .map(|record| {
+            let track_end = record.started_at + record.played_seconds;
+            let track_end = end.map_or(track_end, |end| track_end.min(end));
+            Chapter {
+                start_ms: record.started_at.saturating_sub(start) * 1000,
+                end_ms: (track_end - start) * 1000,
+                title: record.title.clone(),
+                artist: record.artist.clone(),
+                album: record.album.clone(),
+            }
+        })
+        .collect()
+}
+
+/// CUE sheet for the recording; INDEX positions are mm:ss:ff with 75 frames per second
+pub fn render_cue(entry: &ArchiveEntry, chapters: &[Chapter]) -> String {
+    let quote = |value: &str| value.replace('"', "'");
+    let mut cue = format!("TITLE \"{}\"\nFILE \"{}.mp3\" MP3\n", quote(&entry.show), entry.id);
+    for (i, chapter) in chapters.iter().enumerate() {
+        let frames = chapter.start_ms * 75 / 1000;
+        cue.push_str(&format!(
+            "  TRACK {:02} AUDIO\n    TITLE \"{}\"\n    PERFORMER \"{}\"\n    INDEX 01 {:02}:{:02}:{:02}\n",
+            i + 1,
+            quote(&chapter.title),
+            quote(&chapter.artist),
+            frames / (75 * 60),
+            frames / 75 % 60,
+            frames % 75,
+        ));
+    }
+    cue
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && !id.starts_with('.')
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Split `<show>_<YYYY-MM-DD>[_<HHMM>|_<HHMMSS>]` into a display name and start time
+fn parse_file_name(stem: &str) -> (String, Option<u64>) {
+    let parts: Vec<&str> = stem.split('_').collect();
+    let time = parts.last().and_then(|part| match part.len() {
+        4 => NaiveTime::parse_from_str(part, "%H%M").ok(),
+        6 => NaiveTime::parse_from_str(part, "%H%M%S").ok(),
+        _ => None,
+    });
+    let date_index = parts.len().saturating_sub(if time.is_some() { 2 } else { 1 });
+
+    let date = parts.get(date_index).and_then(|part| NaiveDate::parse_from_str(part, "%Y-%m-%d").ok());
+    match date {
+        Some(date) if date_index > 0 => {
+            let started_at = NaiveDateTime::new(date, time.unwrap_or_default()).and_utc().timestamp();
+            (parts[..date_index].join(" "), u64::try_from(started_at).ok())
+        }
+        _ => (stem.replace('_', " "), None),
+    }
+}
+
+/// Duration from the size of the audio between the tags and the bitrate of the
+/// first MP3 frame (exact for CBR)
+async fn estimate_duration(path: &Path, size: u64) -> Result<Option<u64>> {
+    let mut file = fs::File::open(path).await?;
+    let mut head = Vec::with_capacity(16 * 1024);
+    (&mut file).take(16 * 1024).read_to_end(&mut head).await?;
+    let mut tail = vec![0; size.min(mp3::TRAILING_TAGS_PROBE as u64) as usize];
+    file.seek(SeekFrom::End(-(tail.len() as i64))).await?;
+    file.read_exact(&mut tail).await?;
+    let size = size.saturating_sub(mp3::trailing_tags_len(&tail) as u64);
+
+    let offset = mp3::id3v2_len(&head);
+    if offset >= head.len() {
+        return Ok(None);
+    }
+
+    let header = (offset..head.len().saturating_sub(3)).find_map(|i| FrameHeader::parse(&head[i..]).map(|h| (i, h)));
+    Ok(header.map(|(start, header)| {
+        let audio_bytes = size.saturating_sub(start as u64);
+        audio_bytes * 8 / (header.bitrate_kbps as u64 * 1000)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_name() {
+        let (show, started_at) = parse_file_name("morning_show_2024-03-05_0730");
+        assert_eq!(show, "morning show");
+        assert_eq!(started_at, Some(1_709_623_800));
+
+        let (show, started_at) = parse_file_name("aircheck_2024-03-05_073015");
+        assert_eq!(show, "aircheck");
+        assert_eq!(started_at, Some(1_709_623_815));
+
+        let (show, started_at) = parse_file_name("jazz-hour_2024-03-05");
+        assert_eq!(show, "jazz-hour");
+        assert_eq!(started_at, Some(1_709_596_800));
+
+        assert_eq!(parse_file_name("random_recording"), ("random recording".to_string(), None));
+        assert_eq!(parse_file_name("2024-03-05").1, None);
+    }
+
+    #[test]
+    fn test_rejects_unsafe_ids() {
+        assert!(is_valid_id("morning_show_2024-03-05"));
+        assert!(!is_valid_id("../secret"));
+        assert!(!is_valid_id("a/b"));
+        assert!(!is_valid_id(".hidden"));
+        assert!(!is_valid_id(""));
+    }
+
+    #[test]
+    fn test_query_matches() {
+        let entry = ArchiveEntry {
+            id: "jazz_2024-03-05".to_string(),
+            show: "Late Jazz".to_string(),
+            title: Some("Blue Note special".to_string()),
+            started_at: 1_709_596_800,
+            date: "2024-03-05".to_string(),
+            duration: Some(3600),
+            size: 0,
+            recording: false,
+            path: PathBuf::new(),
+            chapters: None,
+        };
+        let day = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok();
+
+        assert!(ArchiveQuery::default().matches(&entry));
+        assert!(ArchiveQuery { show: Some("jazz".to_string()), ..Default::default() }.matches(&entry));
+        assert!(ArchiveQuery { q: Some("blue note".to_string()), ..Default::default() }.matches(&entry));
+        assert!(ArchiveQuery { date: day("2024-03-05"), ..Default::default() }.matches(&entry));
+        assert!(!ArchiveQuery { date: day("2024-03-06"), ..Default::default() }.matches(&entry));
+        assert!(ArchiveQuery { from: day("2024-03-01"), to: day("2024-03-05"), ..Default::default() }.matches(&entry));
+        assert!(!ArchiveQuery { from: day("2024-03-06"), ..Default::default() }.matches(&entry));
+    }
+
+    fn play(started_at: u64, played_seconds: u64, title: &str) -> PlayRecord {
+        PlayRecord {
+            started_at,
+            path: PathBuf::from(format!("{}.mp3", title)),
+            title: title.to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            isrc: None,
+            composer: None,
+            label: None,
+            played_seconds,
+            peak_listeners: 1,
+            listener_seconds: played_seconds,
+        }
+    }
+
+    #[test]
+    fn test_chapters_from_history() {
+        let entry = ArchiveEntry {
+            id: "show_2024-03-05_0730".to_string(),
+            show: "show".to_string(),
+            title: None,
+            started_at: 1000,
+            date: "1970-01-01".to_string(),
+            duration: Some(600),
+            size: 0,
+            recording: false,
+            path: PathBuf::new(),
+            chapters: None,
+        };
+        let records = vec![
+            play(700, 200, "Before"),     // Ended before the recording
+            play(1400, 100, "Third"),
+            play(900, 200, "Opening"),    // Already playing at the start
+            play(1100, 300, "Second"),
+            play(1500, 300, "Cut off"),   // Runs past the end of the recording
+            play(1500, 0, "Skipped"),     // Never heard
+            play(1600, 100, "After"),
+        ];
+
+        let chapters = chapters_from_history(&entry, &records);
+        let titles: Vec<&str> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["Opening", "Second", "Third", "Cut off"]);
+        assert_eq!((chapters[0].start_ms, chapters[0].end_ms), (0, 100_000));
+        assert_eq!((chapters[1].start_ms, chapters[1].end_ms), (100_000, 400_000));
+        assert_eq!((chapters[3].start_ms, chapters[3].end_ms), (500_000, 600_000));
+
+        let cue = render_cue(&entry, &chapters);
+        assert!(cue.starts_with("TITLE \"show\"\nFILE \"show_2024-03-05_0730.mp3\" MP3\n"));
+        assert!(cue.contains("  TRACK 02 AUDIO\n    TITLE \"Second\"\n    PERFORMER \"Artist\"\n    INDEX 01 01:40:00\n"));
+    }
+}
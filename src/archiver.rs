@@ -0,0 +1,258 @@
+use std::{path::PathBuf, sync::Arc, time::Instant};
+use chrono::{DateTime, Utc};
+use tokio::{
+    fs,
+    io::{AsyncWriteExt, BufWriter},
+    sync::broadcast,
+};
+use tracing::{info, warn};
+
+use crate::{
+    archive::{self, Archive},
+    config::{ArchiveRotation, Config},
+    error::Result,
+    monitor::Subsystem,
+    radio::{AudioChunk, RadioStation},
+};
+
+/// Records the broadcast output into the archive directory as rotating MP3
+/// files (aircheck recordings), so they show up in `/api/archive`
+pub struct Archiver {
+    archive: Archive,
+    show: String,
+    prefix: String, // File name prefix; only files with it are subject to retention
+    rotation: ArchiveRotation,
+    retention_secs: Option<u64>,
+}
+
+/// The file currently being written
+struct Recording {
+    path: PathBuf,
+    file: BufWriter<fs::File>,
+    hour: i64,     // Hours since the epoch when the recording started
+    track: String, // Track that was playing when the recording started
+    duration_ms: f64,
+}
+
+impl Archiver {
+    /// Returns `None` unless ARCHIVE_RECORD is enabled
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if !config.archive_record {
+            return None;
+        }
+        Some(Self {
+            archive: Archive::new(&config.archive_dir),
+            show: config.archive_show.clone(),
+            prefix: file_prefix(&config.archive_show),
+            rotation: config.archive_rotation,
+            retention_secs: (config.archive_retention_days > 0).then(|| config.archive_retention_days * 86_400),
+        })
+    }
+
+    pub fn spawn(self, station: Arc<RadioStation>) {
+        tokio::spawn(async move {
+            let receiver = station.subscribe().await;
+            self.run(&station, receiver).await;
+        });
+    }
+
+    async fn run(&self, station: &RadioStation, mut receiver: broadcast::Receiver<AudioChunk>) {
+        if let Err(e) = fs::create_dir_all(self.archive.dir()).await {
+            warn!("Archiver cannot create {}: {}", self.archive.dir().display(), e);
+            return;
+        }
+        self.finalize_interrupted().await;
+        info!("Recording broadcast to {} ({:?} rotation)", self.archive.dir().display(), self.rotation);
+
+        let mut current: Option<Recording> = None;
+        loop {
+            let chunk = match receiver.recv().await {
+                Ok(chunk) => chunk,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Archiver lagged by {} chunks; the recording has a gap", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            let now = Utc::now();
+            let track = track_name(station);
+            let rotate = match &current {
+                None => true,
+                Some(recording) => match self.rotation {
+                    ArchiveRotation::Hourly => now.timestamp().div_euclid(3600) != recording.hour,
+                    ArchiveRotation::PerTrack => track != recording.track,
+                },
+            };
+            if rotate {
+                if let Some(recording) = current.take() {
+                    self.finish(recording).await;
+                }
+                match self.start(now, track).await {
+                    Ok(recording) => current = Some(recording),
+                    Err(e) => {
+                        warn!("Failed to start archive recording: {}", e);
+                        continue;
+                    }
+                }
+                self.apply_retention(now).await;
+            }
+
+            if let Some(recording) = current.as_mut() {
+                let started = Instant::now();
+                let written = recording.file.write_all(&chunk.data).await;
+                station.monitor().record(Subsystem::Archiver, started.elapsed());
+                if let Err(e) = written {
+                    warn!("Failed to write archive recording {}: {}", recording.path.display(), e);
+                    // Start a fresh file with the next chunk
+                    if let Some(recording) = current.take() {
+                        self.finish(recording).await;
+                    }
+                    continue;
+                }
+                recording.duration_ms += chunk.duration_ms;
+            }
+        }
+
+        if let Some(recording) = current.take() {
+            self.finish(recording).await;
+        }
+        info!("Archiver stopped");
+    }
+
+    async fn start(&self, now: DateTime<Utc>, track: String) -> Result<Recording> {
+        let id = format!("{}_{}", self.prefix, now.format("%Y-%m-%d_%H%M%S"));
+        let path = self.archive.dir().join(format!("{}.mp3", id));
+        let file = fs::File::create(&path).await?;
+
+        let title = match self.rotation {
+            ArchiveRotation::PerTrack if !track.is_empty() => Some(track.clone()),
+            _ => None,
+        };
+        let show = self.show.clone();
+        archive::update_sidecar(&path, |sidecar| {
+            sidecar["show"] = show.into();
+            sidecar["title"] = title.into();
+            sidecar["started_at"] = (now.timestamp() as u64).into();
+            sidecar["recording"] = true.into();
+        }).await?;
+
+        info!("Archiving to {}", path.display());
+        Ok(Recording {
+            path,
+            file: BufWriter::new(file),
+            hour: now.timestamp().div_euclid(3600),
+            track,
+            duration_ms: 0.0,
+        })
+    }
+
+    async fn finish(&self, mut recording: Recording) {
+        if let Err(e) = recording.file.flush().await {
+            warn!("Failed to flush archive recording {}: {}", recording.path.display(), e);
+        }
+        let duration = (recording.duration_ms / 1000.0).round() as u64;
+        if let Err(e) = archive::update_sidecar(&recording.path, |sidecar| {
+            sidecar["duration"] = duration.into();
+            sidecar["recording"] = false.into();
+        }).await {
+            warn!("Failed to finalize archive sidecar for {}: {}", recording.path.display(), e);
+        }
+    }
+
+    /// Recordings left marked in progress by a crash or restart are complete now
+    async fn finalize_interrupted(&self) {
+        let Ok(entries) = self.archive.list().await else {
+            return;
+        };
+        for entry in entries.iter().filter(|entry| entry.recording && self.owns(&entry.id)) {
+            let duration = entry.duration;
+            let result = archive::update_sidecar(&entry.path, |sidecar| {
+                sidecar["recording"] = false.into();
+                if sidecar.get("duration").is_none_or(|d| d.is_null()) {
+                    sidecar["duration"] = duration.into();
+                }
+            }).await;
+            if let Err(e) = result {
+                warn!("Failed to finalize interrupted recording {}: {}", entry.id, e);
+            }
+        }
+    }
+
+    /// Delete this archiver's recordings that are past the retention period;
+    /// other files in the archive directory are left alone
+    async fn apply_retention(&self, now: DateTime<Utc>) {
+        let Some(retention_secs) = self.retention_secs else {
+            return;
+        };
+        let cutoff = (now.timestamp() as u64).saturating_sub(retention_secs);
+        let entries = match self.archive.list().await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Archive retention check failed: {}", e);
+                return;
+            }
+        };
+        for entry in entries.iter().filter(|entry| self.owns(&entry.id) && !entry.recording && entry.started_at < cutoff) {
+            match self.archive.remove(entry).await {
+                Ok(()) => info!("Deleted archive recording {} (older than retention)", entry.id),
+                Err(e) => warn!("Failed to delete archive recording {}: {}", entry.id, e),
+            }
+        }
+    }
+
+    fn owns(&self, id: &str) -> bool {
+        id.strip_prefix(&self.prefix).is_some_and(|rest| rest.starts_with('_'))
+    }
+}
+
+/// Show name as a file name prefix: archive ids allow only `[A-Za-z0-9._-]`
+fn file_prefix(show: &str) -> String {
+    show.trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+fn track_name(station: &RadioStation) -> String {
+    match station.current_track() {
+        Some(track) if track.artist.is_empty() => track.title,
+        Some(track) => format!("{} - {}", track.artist, track.title),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_prefix() {
+        assert_eq!(file_prefix("aircheck"), "aircheck");
+        assert_eq!(file_prefix("Morning Show"), "morning-show");
+        assert_eq!(file_prefix("Rock/Pop_2"), "rock-pop-2");
+    }
+
+    #[tokio::test]
+    async fn test_retention_only_removes_own_old_recordings() {
+        let dir = std::env::temp_dir().join(format!("webradio_archiver_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["aircheck_2020-01-01_000000", "aircheck_2099-01-01_000000", "jazz_2020-01-01"] {
+            std::fs::write(dir.join(format!("{}.mp3", name)), b"").unwrap();
+        }
+
+        let mut config = Config::from_env();
+        config.archive_record = true;
+        config.archive_dir = dir.clone();
+        config.archive_retention_days = 7;
+        let archiver = Archiver::from_config(&config).unwrap();
+        archiver.apply_retention(Utc::now()).await;
+
+        let mut remaining: Vec<String> = std::fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        remaining.sort();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(remaining, vec!["aircheck_2099-01-01_000000.mp3", "jazz_2020-01-01.mp3"]);
+    }
+}
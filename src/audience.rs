@@ -0,0 +1,236 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+use tracing::warn;
+
+use crate::{error::Result, types::GeoBreakdown};
+
+pub const MINUTE_SECS: u64 = 60;
+
+/// Audience over one period of the time series: a logged minute, or a bucket
+/// of minutes in `/api/stats/timeseries?step=`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudiencePoint {
+    pub timestamp: u64,        // Unix seconds at the start of the period
+    pub listeners: f64,        // Mean concurrent listeners
+    pub peak_listeners: usize, // Highest concurrent listener count
+    pub bytes_sent: u64,       // Audio delivered to listeners
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geo: Option<GeoBreakdown>, // MP3 listeners by location at the end of the period (STATS_GEOIP)
+}
+
+/// Accumulates the current minute; the station observes the listener count with
+/// every broadcast chunk and adds every chunk it delivers
+#[derive(Debug, Default)]
+pub struct AudienceMeter {
+    peak: AtomicU64,
+    listener_sum: AtomicU64,
+    samples: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl AudienceMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&self, listeners: usize) {
+        self.peak.fetch_max(listeners as u64, Ordering::Relaxed);
+        self.listener_sum.fetch_add(listeners as u64, Ordering::Relaxed);
+        self.samples.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_sent(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Close the minute starting at `minute` and start the next one. `listeners`
+    /// stands in for the mean when nothing was observed (no broadcast chunks,
+    /// e.g. while paused).
+    pub fn take(&self, minute: u64, listeners: usize, geo: Option<GeoBreakdown>) -> AudiencePoint {
+        let peak = self.peak.swap(0, Ordering::Relaxed);
+        let sum = self.listener_sum.swap(0, Ordering::Relaxed);
+        let samples = self.samples.swap(0, Ordering::Relaxed);
+        let bytes = self.bytes.swap(0, Ordering::Relaxed);
+        let mean = match samples {
+            0 => listeners as f64,
+            n => sum as f64 / n as f64,
+        };
+        AudiencePoint {
+            timestamp: minute,
+            listeners: (mean * 100.0).round() / 100.0,
+            peak_listeners: peak.max(listeners as u64) as usize,
+            bytes_sent: bytes,
+            geo,
+        }
+    }
+}
+
+/// Append-only per-minute audience log stored as JSON lines
+pub struct AudienceLog {
+    path: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl AudienceLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn append(&self, point: &AudiencePoint) -> Result<()> {
+        let mut line = serde_json::to_string(point)?;
+        line.push('\n');
+
+        let _guard = self.write_lock.lock().await;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    /// Minutes starting within `[from, to)`, oldest first
+    pub async fn load_range(&self, from: u64, to: u64) -> Result<Vec<AudiencePoint>> {
+        let data = match fs::read_to_string(&self.path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut points = Vec::new();
+        for (line_no, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<AudiencePoint>(line) {
+                Ok(point) if point.timestamp >= from && point.timestamp < to => points.push(point),
+                Ok(_) => {}
+                Err(e) => warn!("Skipping malformed audience log line {}: {}", line_no + 1, e),
+            }
+        }
+        points.sort_by_key(|point| point.timestamp);
+        Ok(points)
+    }
+}
+
+/// Merge minutes into `step`-second buckets aligned to `step`: mean listeners
+/// over the logged minutes, highest peak, total bytes and the highest count per
+/// location. Empty buckets are left out.
+pub fn bucket(points: &[AudiencePoint], step: u64) -> Vec<AudiencePoint> {
+    if step <= MINUTE_SECS {
+        return points.to_vec();
+    }
+    let mut buckets: Vec<(AudiencePoint, usize)> = Vec::new();
+    for point in points {
+        let start = point.timestamp - point.timestamp % step;
+        match buckets.last_mut() {
+            Some((bucket, minutes)) if bucket.timestamp == start => {
+                bucket.listeners += point.listeners;
+                bucket.peak_listeners = bucket.peak_listeners.max(point.peak_listeners);
+                bucket.bytes_sent += point.bytes_sent;
+                if let Some(geo) = &point.geo {
+                    merge_max(bucket.geo.get_or_insert_with(GeoBreakdown::default), geo);
+                }
+                *minutes += 1;
+            }
+            _ => buckets.push((AudiencePoint { timestamp: start, ..point.clone() }, 1)),
+        }
+    }
+    buckets.into_iter()
+        .map(|(mut bucket, minutes)| {
+            bucket.listeners = (bucket.listeners / minutes as f64 * 100.0).round() / 100.0;
+            bucket
+        })
+        .collect()
+}
+
+fn merge_max(into: &mut GeoBreakdown, from: &GeoBreakdown) {
+    for (into, from) in [(&mut into.countries, &from.countries), (&mut into.regions, &from.regions)] {
+        for (key, &count) in from {
+            let entry = into.entry(key.clone()).or_default();
+            *entry = (*entry).max(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(timestamp: u64, listeners: f64, peak_listeners: usize, bytes_sent: u64) -> AudiencePoint {
+        AudiencePoint { timestamp, listeners, peak_listeners, bytes_sent, geo: None }
+    }
+
+    fn countries(counts: &[(&str, usize)]) -> Option<GeoBreakdown> {
+        Some(GeoBreakdown {
+            countries: counts.iter().map(|&(country, count)| (country.to_string(), count)).collect(),
+            regions: Default::default(),
+        })
+    }
+
+    #[test]
+    fn test_meter_takes_and_resets() {
+        let meter = AudienceMeter::new();
+        meter.observe(2);
+        meter.observe(4);
+        meter.add_sent(1000);
+        meter.add_sent(500);
+        assert_eq!(meter.take(60, 3, None), point(60, 3.0, 4, 1500));
+
+        // Nothing observed: the count at the end of the minute stands in
+        assert_eq!(meter.take(120, 1, None), point(120, 1.0, 1, 0));
+    }
+
+    #[test]
+    fn test_bucket_by_hour() {
+        let points = vec![
+            point(3600, 2.0, 3, 100),
+            point(3660, 4.0, 6, 200),
+            point(7200, 1.0, 1, 50),
+        ];
+        assert_eq!(bucket(&points, 3600), vec![point(3600, 3.0, 6, 300), point(7200, 1.0, 1, 50)]);
+        assert_eq!(bucket(&points, 60), points);
+    }
+
+    #[test]
+    fn test_bucket_keeps_highest_count_per_country() {
+        let points = vec![
+            AudiencePoint { geo: countries(&[("GB", 3), ("DE", 1)]), ..point(3600, 4.0, 4, 0) },
+            AudiencePoint { geo: countries(&[("GB", 1), ("FR", 2)]), ..point(3660, 3.0, 3, 0) },
+        ];
+        let hour = bucket(&points, 3600);
+        assert_eq!(hour[0].geo, countries(&[("DE", 1), ("FR", 2), ("GB", 3)]));
+    }
+
+    #[tokio::test]
+    async fn test_append_and_load_range() {
+        let path = std::env::temp_dir().join(format!("webradio_audience_{}.jsonl", uuid::Uuid::new_v4()));
+        let log = AudienceLog::new(&path);
+        for minute in [60, 120, 180] {
+            log.append(&point(minute, 1.0, 1, 10)).await.unwrap();
+        }
+
+        let points = log.load_range(120, 180).await.unwrap();
+        assert_eq!(points, vec![point(120, 1.0, 1, 10)]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_missing_log_is_empty() {
+        let log = AudienceLog::new("/nonexistent/webradio/audience.jsonl");
+        assert!(log.load_range(0, u64::MAX).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+use axum::http::{header, HeaderMap};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use ring::hmac;
+
+/// Credentials for admin routes, checked by the `require_admin` middleware.
+///
+/// The configured token is kept only as an HMAC tag under a random key, so a
+/// presented token is compared in constant time regardless of its length.
+pub struct AdminAuth {
+    key: hmac::Key,
+    token_tag: Option<hmac::Tag>,
+}
+
+impl AdminAuth {
+    pub fn new(token: Option<&str>) -> Self {
+        let secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let token_tag = token.map(|token| hmac::sign(&key, token.as_bytes()));
+        Self { key, token_tag }
+    }
+
+    /// Whether a token is configured; without one, admin routes only serve local requests
+    pub fn is_configured(&self) -> bool {
+        self.token_tag.is_some()
+    }
+
+    /// Check the `Authorization: Bearer` or `X-API-Key` credentials of a request,
+    /// or `Authorization: Basic` with the token as password (any user name), which
+    /// is how tools written for Icecast's admin pages authenticate
+    pub fn check(&self, headers: &HeaderMap) -> bool {
+        presented_token(headers).is_some_and(|token| self.check_token(&token))
+    }
+
+    /// Check a token presented some other way, e.g. a `?token=` query parameter
+    /// from a browser WebSocket, which can't set headers
+    pub fn check_token(&self, token: &str) -> bool {
+        match &self.token_tag {
+            Some(tag) => hmac::verify(&self.key, token.as_bytes(), tag.as_ref()).is_ok(),
+            None => false,
+        }
+    }
+}
+
+fn presented_token(headers: &HeaderMap) -> Option<String> {
+    let authorization = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok());
+    let bearer = authorization
+        .and_then(|v| v.strip_prefix("Bearer ").or_else(|| v.strip_prefix("bearer ")))
+        .map(|token| token.trim().to_string());
+    let basic = || authorization
+        .and_then(|v| v.strip_prefix("Basic ").or_else(|| v.strip_prefix("basic ")))
+        .and_then(|encoded| BASE64_STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|credentials| credentials.split_once(':').map(|(_, password)| password.to_string()));
+    bearer
+        .or_else(basic)
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()).map(|token| token.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name.parse::<header::HeaderName>().unwrap(), value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_accepts_bearer_and_api_key() {
+        let auth = AdminAuth::new(Some("s3cret"));
+        assert!(auth.is_configured());
+        assert!(auth.check(&headers("authorization", "Bearer s3cret")));
+        assert!(auth.check(&headers("x-api-key", "s3cret")));
+        assert!(auth.check_token("s3cret"));
+        // admin:s3cret
+        assert!(auth.check(&headers("authorization", "Basic YWRtaW46czNjcmV0")));
+    }
+
+    #[test]
+    fn test_rejects_wrong_or_missing_token() {
+        let auth = AdminAuth::new(Some("s3cret"));
+        assert!(!auth.check(&headers("authorization", "Bearer s3cre")));
+        assert!(!auth.check(&headers("authorization", "Basic s3cret")));
+        assert!(!auth.check(&HeaderMap::new()));
+
+        let unconfigured = AdminAuth::new(None);
+        assert!(!unconfigured.is_configured());
+        assert!(!unconfigured.check(&headers("x-api-key", "")));
+        assert!(!unconfigured.check_token(""));
+    }
+}
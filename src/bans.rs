@@ -0,0 +1,103 @@
+use std::{
+    collections::BTreeMap,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::Result;
+
+/// An address refused at stream accept time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ban {
+    pub ip: IpAddr,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub banned_at: u64, // Unix seconds
+}
+
+/// Banned listener addresses, kept in a JSON file so bans survive restarts
+pub struct BanList {
+    path: PathBuf,
+    bans: RwLock<BTreeMap<IpAddr, Ban>>,
+    save_lock: tokio::sync::Mutex<()>,
+}
+
+impl BanList {
+    /// Bans saved at `path`; a missing file is an empty list, an unreadable one is
+    /// logged and treated as empty
+    pub fn load(path: &Path) -> Self {
+        let bans = match std::fs::read(path) {
+            Ok(data) => serde_json::from_slice::<Vec<Ban>>(&data).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable ban list {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            path: path.to_path_buf(),
+            bans: RwLock::new(bans.into_iter().map(|ban| (ban.ip, ban)).collect()),
+            save_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn is_banned(&self, ip: IpAddr) -> bool {
+        self.bans.read().unwrap().contains_key(&ip.to_canonical())
+    }
+
+    pub fn list(&self) -> Vec<Ban> {
+        self.bans.read().unwrap().values().cloned().collect()
+    }
+
+    /// Ban `ip`, replacing an earlier ban of it, and save the list
+    pub async fn add(&self, ip: IpAddr, reason: Option<String>) -> Result<Ban> {
+        let ban = Ban { ip: ip.to_canonical(), reason, banned_at: chrono::Utc::now().timestamp() as u64 };
+        self.bans.write().unwrap().insert(ban.ip, ban.clone());
+        self.save().await?;
+        Ok(ban)
+    }
+
+    /// Lift the ban on `ip`; false if it wasn't banned
+    pub async fn remove(&self, ip: IpAddr) -> Result<bool> {
+        let removed = self.bans.write().unwrap().remove(&ip.to_canonical()).is_some();
+        if removed {
+            self.save().await?;
+        }
+        Ok(removed)
+    }
+
+    async fn save(&self) -> Result<()> {
+        let _guard = self.save_lock.lock().await;
+        let data = serde_json::to_string_pretty(&self.list())?;
+        tokio::fs::write(&self.path, data).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bans_persist() {
+        let path = std::env::temp_dir().join(format!("webradio_bans_{}.json", uuid::Uuid::new_v4()));
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+
+        let bans = BanList::load(&path);
+        assert!(!bans.is_banned(ip));
+        bans.add(ip, Some("abuse".to_string())).await.unwrap();
+        assert!(bans.is_banned(ip));
+        // The v4-mapped form of the same address
+        assert!(bans.is_banned("::ffff:203.0.113.7".parse().unwrap()));
+
+        let reloaded = BanList::load(&path);
+        assert_eq!(reloaded.list()[0].reason.as_deref(), Some("abuse"));
+        assert!(reloaded.remove(ip).await.unwrap());
+        assert!(!reloaded.remove(ip).await.unwrap());
+        assert!(BanList::load(&path).list().is_empty());
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,340 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+// Caps that keep a misbehaving client from growing server memory
+pub const MAX_EVENTS_PER_BEACON: usize = 50;
+const MAX_TRACKED_SESSIONS: usize = 10_000;
+const MAX_DISTINCT_ERRORS: usize = 100;
+const SESSION_IDLE_SECS: u64 = 3600;
+const MAX_DISTINCT_PLATFORMS: usize = 32;
+// Below this much audio buffered a player is one network hiccup away from stalling
+const LOW_BUFFER_SECS: f64 = 2.0;
+
+/// Client-side player event reported through `POST /api/beacon`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BeaconEvent {
+    BufferUnderrun {
+        #[serde(default)]
+        duration_ms: u64,
+    },
+    Play,
+    Pause,
+    Volume {
+        level: f64, // 0.0 - 1.0
+    },
+    Error {
+        #[serde(default)]
+        code: Option<u32>,
+        #[serde(default)]
+        message: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BeaconPayload {
+    pub session: String,
+    pub sig: String,
+    #[serde(default)]
+    pub platform: Option<String>,
+    pub events: Vec<BeaconEvent>,
+}
+
+/// Periodic buffer report posted by a player to `POST /api/telemetry`: how much
+/// audio it holds ahead of the playhead and the stalls since its last report
+#[derive(Debug, Deserialize)]
+pub struct TelemetryReport {
+    pub session: String,
+    pub sig: String,
+    #[serde(default)]
+    pub platform: Option<String>,
+    pub buffered_seconds: f64,
+    /// Duration of each stall, in milliseconds
+    #[serde(default)]
+    pub stalls: Vec<u64>,
+}
+
+/// Buffer levels and stalls reported by the players of one platform
+#[derive(Debug, Default)]
+struct PlatformBuffers {
+    reports: u64,
+    buffered_seconds_total: f64,
+    low_buffer_reports: u64,
+    stalls: u64,
+    stall_ms_total: u64,
+    longest_stall_ms: u64,
+}
+
+#[derive(Debug)]
+struct SessionInfo {
+    platform: String,
+    last_seen: Instant,
+    underruns: u64,
+}
+
+/// Aggregated listener-experience telemetry reported by players
+#[derive(Debug, Default)]
+pub struct BeaconStats {
+    beacons_received: AtomicU64,
+    beacons_rejected: AtomicU64,
+    buffer_underruns: AtomicU64,
+    underrun_ms_total: AtomicU64,
+    plays: AtomicU64,
+    pauses: AtomicU64,
+    volume_changes: AtomicU64,
+    volume_permille_total: AtomicU64, // Sum of reported volumes × 1000 for averaging
+    errors: AtomicU64,
+    error_messages: DashMap<String, u64>,
+    sessions: DashMap<String, SessionInfo>,
+    telemetry_reports: AtomicU64,
+    platform_buffers: DashMap<String, PlatformBuffers>,
+}
+
+impl BeaconStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_rejected(&self) {
+        self.beacons_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record(&self, session: &str, platform: Option<&str>, events: &[BeaconEvent]) {
+        self.beacons_received.fetch_add(1, Ordering::Relaxed);
+
+        let mut session_underruns = 0;
+        for event in events.iter().take(MAX_EVENTS_PER_BEACON) {
+            match event {
+                BeaconEvent::BufferUnderrun { duration_ms } => {
+                    self.buffer_underruns.fetch_add(1, Ordering::Relaxed);
+                    self.underrun_ms_total.fetch_add(*duration_ms, Ordering::Relaxed);
+                    session_underruns += 1;
+                }
+                BeaconEvent::Play => {
+                    self.plays.fetch_add(1, Ordering::Relaxed);
+                }
+                BeaconEvent::Pause => {
+                    self.pauses.fetch_add(1, Ordering::Relaxed);
+                }
+                BeaconEvent::Volume { level } => {
+                    self.volume_changes.fetch_add(1, Ordering::Relaxed);
+                    let permille = (level.clamp(0.0, 1.0) * 1000.0) as u64;
+                    self.volume_permille_total.fetch_add(permille, Ordering::Relaxed);
+                }
+                BeaconEvent::Error { code, message } => {
+                    self.errors.fetch_add(1, Ordering::Relaxed);
+                    let key = match code {
+                        Some(code) => format!("{}: {}", code, truncate(message, 80)),
+                        None => truncate(message, 80).to_string(),
+                    };
+                    if let Some(mut count) = self.error_messages.get_mut(&key) {
+                        *count += 1;
+                    } else if self.error_messages.len() < MAX_DISTINCT_ERRORS {
+                        self.error_messages.insert(key, 1);
+                    }
+                }
+            }
+        }
+
+        self.touch_session(session, platform, session_underruns);
+    }
+
+    /// A player's buffer report. Its stalls count as buffer underruns too.
+    pub fn record_telemetry(&self, report: &TelemetryReport) {
+        self.telemetry_reports.fetch_add(1, Ordering::Relaxed);
+
+        let stalls = &report.stalls[..report.stalls.len().min(MAX_EVENTS_PER_BEACON)];
+        let stall_ms: u64 = stalls.iter().sum();
+        self.buffer_underruns.fetch_add(stalls.len() as u64, Ordering::Relaxed);
+        self.underrun_ms_total.fetch_add(stall_ms, Ordering::Relaxed);
+
+        let platform = platform_name(report.platform.as_deref());
+        if self.platform_buffers.contains_key(platform) || self.platform_buffers.len() < MAX_DISTINCT_PLATFORMS {
+            let buffered = if report.buffered_seconds.is_finite() { report.buffered_seconds.max(0.0) } else { 0.0 };
+            let mut buffers = self.platform_buffers.entry(platform.to_string()).or_default();
+            buffers.reports += 1;
+            buffers.buffered_seconds_total += buffered;
+            if buffered < LOW_BUFFER_SECS {
+                buffers.low_buffer_reports += 1;
+            }
+            buffers.stalls += stalls.len() as u64;
+            buffers.stall_ms_total += stall_ms;
+            buffers.longest_stall_ms = buffers.longest_stall_ms.max(stalls.iter().copied().max().unwrap_or(0));
+        }
+        self.touch_session(&report.session, report.platform.as_deref(), stalls.len() as u64);
+    }
+
+    fn touch_session(&self, session: &str, platform: Option<&str>, underruns: u64) {
+        if let Some(mut info) = self.sessions.get_mut(session) {
+            info.last_seen = Instant::now();
+            info.underruns += underruns;
+            return;
+        }
+
+        if self.sessions.len() >= MAX_TRACKED_SESSIONS {
+            self.prune_sessions();
+        }
+        if self.sessions.len() < MAX_TRACKED_SESSIONS {
+            self.sessions.insert(session.to_string(), SessionInfo {
+                platform: platform_name(platform).to_string(),
+                last_seen: Instant::now(),
+                underruns,
+            });
+        }
+    }
+
+    fn prune_sessions(&self) {
+        self.sessions.retain(|_, info| info.last_seen.elapsed().as_secs() < SESSION_IDLE_SECS);
+    }
+
+    pub fn snapshot(&self) -> serde_json::Value {
+        self.prune_sessions();
+
+        let volume_changes = self.volume_changes.load(Ordering::Relaxed);
+        let average_volume = if volume_changes > 0 {
+            Some(self.volume_permille_total.load(Ordering::Relaxed) as f64 / volume_changes as f64 / 1000.0)
+        } else {
+            None
+        };
+
+        let mut platforms: std::collections::BTreeMap<String, serde_json::Value> = Default::default();
+        for entry in self.sessions.iter() {
+            let slot = platforms.entry(entry.platform.clone())
+                .or_insert_with(|| serde_json::json!({ "sessions": 0, "buffer_underruns": 0 }));
+            slot["sessions"] = (slot["sessions"].as_u64().unwrap_or(0) + 1).into();
+            slot["buffer_underruns"] = (slot["buffer_underruns"].as_u64().unwrap_or(0) + entry.underruns).into();
+        }
+        // What players on each platform actually hold, for sizing its burst profile
+        for entry in self.platform_buffers.iter() {
+            let buffers = entry.value();
+            let reports = buffers.reports.max(1) as f64;
+            let slot = platforms.entry(entry.key().clone())
+                .or_insert_with(|| serde_json::json!({ "sessions": 0, "buffer_underruns": 0 }));
+            slot["buffer_reports"] = buffers.reports.into();
+            slot["average_buffered_seconds"] = (buffers.buffered_seconds_total / reports).into();
+            slot["low_buffer_share"] = (buffers.low_buffer_reports as f64 / reports).into();
+            slot["stalls"] = buffers.stalls.into();
+            slot["stall_seconds_total"] = (buffers.stall_ms_total as f64 / 1000.0).into();
+            slot["longest_stall_seconds"] = (buffers.longest_stall_ms as f64 / 1000.0).into();
+        }
+
+        let errors: serde_json::Map<String, serde_json::Value> = self.error_messages.iter()
+            .map(|entry| (entry.key().clone(), (*entry.value()).into()))
+            .collect();
+
+        serde_json::json!({
+            "beacons_received": self.beacons_received.load(Ordering::Relaxed),
+            "beacons_rejected": self.beacons_rejected.load(Ordering::Relaxed),
+            "telemetry_reports": self.telemetry_reports.load(Ordering::Relaxed),
+            "active_sessions": self.sessions.len(),
+            "buffer_underruns": self.buffer_underruns.load(Ordering::Relaxed),
+            "underrun_seconds_total": self.underrun_ms_total.load(Ordering::Relaxed) as f64 / 1000.0,
+            "plays": self.plays.load(Ordering::Relaxed),
+            "pauses": self.pauses.load(Ordering::Relaxed),
+            "volume_changes": volume_changes,
+            "average_volume": average_volume,
+            "errors": self.errors.load(Ordering::Relaxed),
+            "error_messages": errors,
+            "platforms": platforms,
+        })
+    }
+}
+
+fn platform_name(platform: Option<&str>) -> &str {
+    platform.map(|p| truncate(p, 32)).unwrap_or("unknown")
+}
+
+fn truncate(value: &str, max_chars: usize) -> &str {
+    match value.char_indices().nth(max_chars) {
+        Some((idx, _)) => &value[..idx],
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_deserialization() {
+        let json = r#"[
+            {"type": "buffer_underrun", "duration_ms": 1200},
+            {"type": "play"},
+            {"type": "volume", "level": 0.5},
+            {"type": "error", "code": 3, "message": "decode failed"}
+        ]"#;
+        let events: Vec<BeaconEvent> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(events[0], BeaconEvent::BufferUnderrun { duration_ms: 1200 });
+        assert_eq!(events[1], BeaconEvent::Play);
+        assert_eq!(events[2], BeaconEvent::Volume { level: 0.5 });
+        assert!(matches!(events[3], BeaconEvent::Error { code: Some(3), .. }));
+    }
+
+    #[test]
+    fn test_aggregation() {
+        let stats = BeaconStats::new();
+        stats.record("a", Some("ios"), &[
+            BeaconEvent::Play,
+            BeaconEvent::BufferUnderrun { duration_ms: 500 },
+            BeaconEvent::Volume { level: 0.2 },
+        ]);
+        stats.record("b", Some("desktop"), &[
+            BeaconEvent::BufferUnderrun { duration_ms: 1500 },
+            BeaconEvent::Volume { level: 0.8 },
+            BeaconEvent::Error { code: None, message: "network".to_string() },
+        ]);
+        stats.record("a", None, &[BeaconEvent::Pause]);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot["beacons_received"], 3);
+        assert_eq!(snapshot["active_sessions"], 2);
+        assert_eq!(snapshot["buffer_underruns"], 2);
+        assert_eq!(snapshot["underrun_seconds_total"], 2.0);
+        assert_eq!(snapshot["plays"], 1);
+        assert_eq!(snapshot["pauses"], 1);
+        assert_eq!(snapshot["average_volume"], 0.5);
+        assert_eq!(snapshot["error_messages"]["network"], 1);
+        assert_eq!(snapshot["platforms"]["ios"]["buffer_underruns"], 1);
+    }
+
+    #[test]
+    fn test_buffer_reports_per_platform() {
+        let report = |session: &str, platform: &str, buffered_seconds: f64, stalls: Vec<u64>| TelemetryReport {
+            session: session.to_string(),
+            sig: String::new(),
+            platform: Some(platform.to_string()),
+            buffered_seconds,
+            stalls,
+        };
+        let stats = BeaconStats::new();
+        stats.record_telemetry(&report("a", "ios", 1.0, vec![800, 1200]));
+        stats.record_telemetry(&report("a", "ios", 5.0, vec![]));
+        stats.record_telemetry(&report("b", "desktop", 12.0, vec![]));
+        stats.record_telemetry(&report("b", "desktop", f64::NAN, vec![]));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot["telemetry_reports"], 4);
+        assert_eq!(snapshot["buffer_underruns"], 2);
+        let ios = &snapshot["platforms"]["ios"];
+        assert_eq!(ios["sessions"], 1);
+        assert_eq!(ios["buffer_underruns"], 2);
+        assert_eq!(ios["buffer_reports"], 2);
+        assert_eq!(ios["average_buffered_seconds"], 3.0);
+        assert_eq!(ios["low_buffer_share"], 0.5);
+        assert_eq!(ios["stall_seconds_total"], 2.0);
+        assert_eq!(ios["longest_stall_seconds"], 1.2);
+        assert_eq!(snapshot["platforms"]["desktop"]["average_buffered_seconds"], 6.0);
+    }
+
+    #[test]
+    fn test_events_per_beacon_are_capped() {
+        let stats = BeaconStats::new();
+        let events = vec![BeaconEvent::Play; MAX_EVENTS_PER_BEACON * 2];
+        stats.record("flood", None, &events);
+
+        assert_eq!(stats.snapshot()["plays"], MAX_EVENTS_PER_BEACON as u64);
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use bytes::Bytes;
+use tracing::warn;
+
+use crate::{error::Result, radio::AudioChunk};
+
+/// File signature of a chunk log
+const MAGIC: &[u8; 8] = b"WRCHUNK1";
+// elapsed_us: u64, duration_ms: f64, position_ms: f64, len: u32 (all little-endian)
+const RECORD_HEADER_LEN: usize = 8 + 8 + 8 + 4;
+
+/// One broadcast chunk as captured in a chunk log
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedChunk {
+    pub at: Duration, // Time since recording started
+    pub duration_ms: f64,
+    pub position_ms: f64,
+    pub data: Bytes,
+}
+
+/// Records every broadcast chunk, byte for byte and with its send time, so a
+/// production stream can be replayed later through the full listener path
+pub struct ChunkLogWriter {
+    file: Mutex<File>,
+    started: Instant,
+    failed: AtomicBool, // Stop after the first write error instead of logging one per chunk
+}
+
+impl ChunkLogWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut file = File::create(path)?;
+        file.write_all(MAGIC)?;
+        Ok(Self {
+            file: Mutex::new(file),
+            started: Instant::now(),
+            failed: AtomicBool::new(false),
+        })
+    }
+
+    pub fn write(&self, chunk: &AudioChunk) {
+        if self.failed.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + chunk.data.len());
+        record.extend_from_slice(&(self.started.elapsed().as_micros() as u64).to_le_bytes());
+        record.extend_from_slice(&chunk.duration_ms.to_le_bytes());
+        record.extend_from_slice(&chunk.position_ms.to_le_bytes());
+        record.extend_from_slice(&(chunk.data.len() as u32).to_le_bytes());
+        record.extend_from_slice(&chunk.data);
+
+        // One write per record, so a crash loses at most the chunk being written
+        if let Err(e) = self.file.lock().unwrap().write_all(&record) {
+            warn!("Chunk log write failed, recording stopped: {}", e);
+            self.failed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Parse a chunk log. A truncated final record (recording interrupted) is ignored.
+pub fn parse(data: &[u8]) -> std::result::Result<Vec<LoggedChunk>, String> {
+    let mut rest = data.strip_prefix(MAGIC.as_slice()).ok_or("not a chunk log")?;
+    let mut chunks = Vec::new();
+
+    while rest.len() >= RECORD_HEADER_LEN {
+        let field = |range: std::ops::Range<usize>| -> [u8; 8] { rest[range].try_into().unwrap() };
+        let at = Duration::from_micros(u64::from_le_bytes(field(0..8)));
+        let duration_ms = f64::from_le_bytes(field(8..16));
+        let position_ms = f64::from_le_bytes(field(16..24));
+        let len = u32::from_le_bytes(rest[24..28].try_into().unwrap()) as usize;
+
+        let Some(payload) = rest.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + len) else {
+            break;
+        };
+        chunks.push(LoggedChunk { at, duration_ms, position_ms, data: Bytes::copy_from_slice(payload) });
+        rest = &rest[RECORD_HEADER_LEN + len..];
+    }
+
+    Ok(chunks)
+}
+
+pub async fn load(path: &Path) -> Result<Vec<LoggedChunk>> {
+    let data = tokio::fs::read(path).await?;
+    parse(&data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(data: &'static [u8], position_ms: f64) -> AudioChunk {
+        AudioChunk { data: Bytes::from_static(data), duration_ms: 26.0, position_ms, checksum: 0, generation: 0 }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = std::env::temp_dir().join(format!("webradio_chunklog_{}.bin", uuid::Uuid::new_v4()));
+        let writer = ChunkLogWriter::create(&path).unwrap();
+        writer.write(&chunk(b"first", 0.0));
+        std::thread::sleep(Duration::from_millis(5));
+        writer.write(&chunk(b"second", 26.0));
+        drop(writer);
+
+        let chunks = parse(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].data, Bytes::from_static(b"first"));
+        assert_eq!(chunks[1].data, Bytes::from_static(b"second"));
+        assert_eq!(chunks[1].position_ms, 26.0);
+        assert_eq!(chunks[1].duration_ms, 26.0);
+        assert!(chunks[1].at >= chunks[0].at + Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_truncated_and_invalid_logs() {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&26.0f64.to_le_bytes());
+        data.extend_from_slice(&0.0f64.to_le_bytes());
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(b"abcd");
+        data.extend_from_slice(&[1, 2, 3]); // Partial record header
+
+        assert_eq!(parse(&data).unwrap().len(), 1);
+        assert_eq!(parse(&data[..data.len() - 5]).unwrap().len(), 0);
+        assert!(parse(b"ID3 not a log").is_err());
+    }
+}
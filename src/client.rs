@@ -0,0 +1,416 @@
+use serde::{de::DeserializeOwned, Deserialize};
+use thiserror::Error;
+
+use crate::{
+    archive::{ArchiveQuery, Chapter},
+    beacon::BeaconEvent,
+    types::{Health, Listeners, NextUp, NowPlaying, PlaylistDto, SearchResults, SkipVote, StatsDto},
+};
+
+/// Typed async client for the WebRadio HTTP API, pinned to `/api/v1`
+///
+/// ```no_run
+/// # async fn example() -> Result<(), webradio::client::ClientError> {
+/// let client = webradio::client::Client::new("https://radio.example.com")
+///     .with_admin_token("secret");
+/// let now = client.now_playing().await?;
+/// println!("{} - {}", now.artist, now.title);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    http: reqwest::Client,
+    admin_token: Option<String>,
+}
+
+pub type ClientResult<T> = std::result::Result<T, ClientError>;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    /// The server answered with a non-success status
+    #[error("Server returned {status}: {message}")]
+    Status {
+        status: u16,
+        code: Option<String>, // Machine-readable error code, e.g. "track_not_found"
+        message: String,
+        retry_after_secs: Option<u64>, // From Retry-After on 429/503
+    },
+}
+
+impl ClientError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Status { status, .. } => Some(*status),
+            ClientError::Request(e) => e.status().map(|s| s.as_u16()),
+        }
+    }
+
+    /// The server's error code (see the README's error code list)
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ClientError::Status { code, .. } => code.as_deref(),
+            ClientError::Request(_) => None,
+        }
+    }
+}
+
+/// One show in the `/api/archive` listing
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchivedShow {
+    pub id: String,
+    pub show: String,
+    pub title: Option<String>,
+    pub started_at: u64,
+    pub date: String,
+    pub duration: Option<u64>,
+    pub size: u64,
+    #[serde(default)]
+    pub recording: bool,
+    pub stream_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchiveChapters {
+    pub id: String,
+    pub show: String,
+    pub duration: Option<u64>,
+    pub chapters: Vec<Chapter>,
+}
+
+/// `/api/sync`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyncTime {
+    pub t0: Option<f64>,
+    pub server_time_ms: u64,
+    pub epoch_ms: u64,
+    pub delay_ms: u64,
+    pub live_position_ms: f64,
+    pub target_position_ms: f64,
+    pub epoch_shifts: u64,
+    pub listener_offset_ms: Option<f64>,
+}
+
+/// `/api/beacon/session`
+#[derive(Debug, Clone, Deserialize)]
+pub struct BeaconSession {
+    pub session: String,
+    pub sig: String,
+    pub max_events: usize,
+}
+
+/// `/api/server-info`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerInfo {
+    pub station_name: String,
+    pub version: String,
+    pub public_ip: Option<PublicAddr>,
+    pub public_url: Option<String>,
+    pub stream_url: Option<String>, // Absolute, for sharing
+    pub local_urls: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PublicAddr {
+    pub ip: std::net::IpAddr,
+    pub source: String,
+    pub discovered_at: u64,
+}
+
+/// A minted stream URL from `POST /api/stream-token`
+#[derive(Debug, Clone, Deserialize)]
+pub struct StreamToken {
+    pub token: String,
+    pub expires: u64,
+    pub user: Option<String>,
+    pub query: String,
+    pub url: String, // Relative to the server root
+}
+
+impl Client {
+    /// `base_url` is the server root, e.g. `http://localhost:8000`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Use a preconfigured reqwest client (timeouts, proxies, custom roots)
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http,
+            admin_token: None,
+        }
+    }
+
+    /// Token sent as `Authorization: Bearer` for admin endpoints (ADMIN_TOKEN)
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Absolute URL for a server path such as `/stream` or a minted token URL
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    // Public endpoints
+
+    pub async fn now_playing(&self) -> ClientResult<NowPlaying> {
+        self.get_json("/api/v1/now-playing", &[]).await
+    }
+
+    /// Now-playing for the audio the listener behind `listener` (X-Listener-Id) is hearing
+    pub async fn now_playing_for(&self, listener: &str) -> ClientResult<NowPlaying> {
+        self.get_json("/api/v1/now-playing", &[("listener", listener.to_string())]).await
+    }
+
+    pub async fn listeners(&self) -> ClientResult<Listeners> {
+        self.get_json("/api/v1/listeners", &[]).await
+    }
+
+    pub async fn playlist(&self) -> ClientResult<PlaylistDto> {
+        self.get_json("/api/v1/playlist", &[]).await
+    }
+
+    /// Library search over title, artist and album, ignoring case and diacritics
+    pub async fn search(&self, query: &str, limit: usize) -> ClientResult<SearchResults> {
+        self.get_json("/api/v1/search", &[("q", query.to_string()), ("limit", limit.to_string())]).await
+    }
+
+    /// The next `count` tracks in rotation (1-50)
+    pub async fn next_up(&self, count: usize) -> ClientResult<NextUp> {
+        self.get_json("/api/v1/next-up", &[("count", count.to_string())]).await
+    }
+
+    pub async fn stats(&self) -> ClientResult<StatsDto> {
+        self.get_json("/api/v1/stats", &[]).await
+    }
+
+    pub async fn health(&self) -> ClientResult<Health> {
+        self.get_json("/api/v1/health", &[]).await
+    }
+
+    pub async fn server_info(&self) -> ClientResult<ServerInfo> {
+        self.get_json("/api/v1/server-info", &[]).await
+    }
+
+    pub async fn archive(&self, filter: &ArchiveQuery) -> ClientResult<Vec<ArchivedShow>> {
+        #[derive(Deserialize)]
+        struct Listing {
+            shows: Vec<ArchivedShow>,
+        }
+        let date = |d: Option<chrono::NaiveDate>| d.map(|d| d.format("%Y-%m-%d").to_string());
+        let query: Vec<(&str, String)> = [
+            ("show", filter.show.clone()),
+            ("q", filter.q.clone()),
+            ("date", date(filter.date)),
+            ("from", date(filter.from)),
+            ("to", date(filter.to)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect();
+        let listing: Listing = self.get_json("/api/v1/archive", &query).await?;
+        Ok(listing.shows)
+    }
+
+    pub async fn archive_chapters(&self, id: &str) -> ClientResult<ArchiveChapters> {
+        self.get_json(&format!("/api/v1/archive/{}/chapters", id), &[]).await
+    }
+
+    /// Chapters of an archived show as a CUE sheet
+    pub async fn archive_cue(&self, id: &str) -> ClientResult<String> {
+        let response = self.send(self.http.get(self.url(&format!("/api/v1/archive/{}/chapters", id))).query(&[("format", "cue")])).await?;
+        Ok(response.text().await?)
+    }
+
+    /// `t0` is the caller's clock in ms; `listener` the X-Listener-Id of its stream
+    pub async fn sync(&self, t0: Option<f64>, listener: Option<&str>) -> ClientResult<SyncTime> {
+        let mut query = Vec::new();
+        if let Some(t0) = t0 {
+            query.push(("t0", t0.to_string()));
+        }
+        if let Some(listener) = listener {
+            query.push(("listener", listener.to_string()));
+        }
+        self.get_json("/api/v1/sync", &query).await
+    }
+
+    /// Vote to skip the current track as the listener behind `listener` (X-Listener-Id)
+    pub async fn vote_skip(&self, listener: &str) -> ClientResult<SkipVote> {
+        let request = self.http.post(self.url("/api/v1/vote-skip")).query(&[("listener", listener)]);
+        Ok(self.send(request).await?.json().await?)
+    }
+
+    pub async fn beacon_session(&self) -> ClientResult<BeaconSession> {
+        self.get_json("/api/v1/beacon/session", &[]).await
+    }
+
+    pub async fn send_beacon(&self, session: &BeaconSession, platform: Option<&str>, events: &[BeaconEvent]) -> ClientResult<()> {
+        let body = serde_json::json!({
+            "session": session.session,
+            "sig": session.sig,
+            "platform": platform,
+            "events": events,
+        });
+        self.send(self.http.post(self.url("/api/v1/beacon")).json(&body)).await?;
+        Ok(())
+    }
+
+    /// Report the player's buffer level and the stalls (in ms) since the last report
+    pub async fn send_telemetry(&self, session: &BeaconSession, platform: Option<&str>, buffered_seconds: f64, stalls: &[u64]) -> ClientResult<()> {
+        let body = serde_json::json!({
+            "session": session.session,
+            "sig": session.sig,
+            "platform": platform,
+            "buffered_seconds": buffered_seconds,
+            "stalls": stalls,
+        });
+        self.send(self.http.post(self.url("/api/v1/telemetry")).json(&body)).await?;
+        Ok(())
+    }
+
+    // Admin endpoints
+
+    pub async fn debug(&self) -> ClientResult<serde_json::Value> {
+        let response = self.send(self.admin(self.http.get(self.url("/api/v1/debug")))).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Mint a signed stream URL; `None` uses the server's default TTL
+    pub async fn mint_stream_token(&self, user: Option<&str>, ttl_secs: Option<u64>) -> ClientResult<StreamToken> {
+        let body = serde_json::json!({ "user": user, "ttl_secs": ttl_secs });
+        let request = self.admin(self.http.post(self.url("/api/v1/stream-token")).json(&body));
+        Ok(self.send(request).await?.json().await?)
+    }
+
+    /// Monthly royalty report CSV; `month` is YYYY-MM, `format` soundexchange or prs
+    pub async fn royalty_report(&self, month: &str, format: &str) -> ClientResult<String> {
+        let request = self.http.get(self.url("/api/v1/reports/royalty")).query(&[("month", month), ("format", format)]);
+        Ok(self.send(self.admin(request)).await?.text().await?)
+    }
+
+    /// CPU profile of the server; `format` is `svg` (flamegraph) or `folded`
+    pub async fn cpu_profile(&self, seconds: u64, format: &str) -> ClientResult<String> {
+        let request = self.http.get(self.url("/api/v1/admin/profile"))
+            .query(&[("seconds", seconds.to_string()), ("format", format.to_string())]);
+        Ok(self.send(self.admin(request)).await?.text().await?)
+    }
+
+    /// Put a file from the music directory into rotation; `path` relative to the music directory
+    pub async fn add_track(&self, path: &str) -> ClientResult<PlaylistDto> {
+        let request = self.http.post(self.url("/api/v1/playlist/tracks")).json(&serde_json::json!({ "path": path }));
+        Ok(self.send(self.admin(request)).await?.json().await?)
+    }
+
+    /// Put a track taken out of rotation back, by its id from `excluded` in `playlist()`
+    pub async fn restore_track(&self, id: &str) -> ClientResult<PlaylistDto> {
+        let request = self.http.post(self.url("/api/v1/playlist/tracks")).json(&serde_json::json!({ "id": id }));
+        Ok(self.send(self.admin(request)).await?.json().await?)
+    }
+
+    /// Take a track out of rotation; rescans leave it out until it is added again
+    pub async fn remove_track(&self, id: &str) -> ClientResult<PlaylistDto> {
+        let request = self.http.delete(self.url("/api/v1/playlist/tracks")).query(&[("id", id)]);
+        Ok(self.send(self.admin(request)).await?.json().await?)
+    }
+
+    /// Reorder the playlist; `ids` must list every track once
+    pub async fn reorder_playlist(&self, ids: &[&str]) -> ClientResult<PlaylistDto> {
+        let request = self.http.put(self.url("/api/v1/playlist/order")).json(&serde_json::json!({ "ids": ids }));
+        Ok(self.send(self.admin(request)).await?.json().await?)
+    }
+
+    /// Play track `id` after the current track
+    pub async fn play_next(&self, id: &str) -> ClientResult<PlaylistDto> {
+        let request = self.http.post(self.url("/api/v1/playlist/play-next")).json(&serde_json::json!({ "id": id }));
+        Ok(self.send(self.admin(request)).await?.json().await?)
+    }
+
+    /// Bench a track (`enabled: false`) or return it to rotation
+    pub async fn set_track_enabled(&self, id: &str, enabled: bool) -> ClientResult<PlaylistDto> {
+        let request = self.http.put(self.url("/api/v1/playlist/enabled"))
+            .json(&serde_json::json!({ "id": id, "enabled": enabled }));
+        Ok(self.send(self.admin(request)).await?.json().await?)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> ClientResult<T> {
+        let response = self.send(self.http.get(self.url(path)).query(query)).await?;
+        Ok(response.json().await?)
+    }
+
+    fn admin(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.admin_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> ClientResult<reqwest::Response> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let retry_after_secs = response.headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok());
+        let body = response.text().await.unwrap_or_default();
+        Err(ClientError::Status {
+            status: status.as_u16(),
+            code: error_code(&body),
+            message: error_message(&body),
+            retry_after_secs,
+        })
+    }
+}
+
+// Error bodies are `{"error": {"code", "message"}}`; older servers sent a flat
+// `{"error", "message"}` or plain text
+fn error_code(body: &str) -> Option<String> {
+    let json = serde_json::from_str::<serde_json::Value>(body).ok()?;
+    json.pointer("/error/code").and_then(|c| c.as_str()).map(str::to_string)
+}
+
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| {
+            json.pointer("/error/message")
+                .or_else(|| json.get("message"))
+                .and_then(|m| m.as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_message() {
+        assert_eq!(error_message("Not found"), "Not found");
+        assert_eq!(error_message(r#"{"error":"too_many_requests","message":"slow down","retry_after":1}"#), "slow down");
+        assert_eq!(error_message(r#"{"error":{"code":"rate_limited","message":"slow down","retry_after":1}}"#), "slow down");
+        assert_eq!(error_code(r#"{"error":{"code":"rate_limited","message":"slow down"}}"#).as_deref(), Some("rate_limited"));
+        assert_eq!(error_code("Not found"), None);
+    }
+
+    #[test]
+    fn test_urls() {
+        let client = Client::new("http://localhost:8000/");
+        assert_eq!(client.base_url(), "http://localhost:8000");
+        assert_eq!(client.url("/stream"), "http://localhost:8000/stream");
+    }
+}
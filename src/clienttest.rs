@@ -0,0 +1,266 @@
+// Client playback test matrix (/debug/client-test): a run hands the client a set
+// of short tone streams that differ in bitrate, chunk size and ICY framing, and
+// records how far each one was delivered and what the player reported, to narrow
+// down platform-specific playback problems
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::Stream;
+use serde::Serialize;
+
+use crate::{relay::IcyMuxer, tone};
+
+const CASE_SECONDS: u32 = 3;
+const ICY_METAINT: usize = 8192;
+const MAX_RUNS: usize = 100;
+const RUN_TTL: Duration = Duration::from_secs(3600);
+const MAX_ERROR_LEN: usize = 200;
+
+/// One stream in the matrix
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TestCase {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub bitrate_kbps: u32,
+    pub chunk_bytes: usize,
+    pub icy: bool, // Interleave ICY metadata when the client asks for it
+}
+
+pub const CASES: &[TestCase] = &[
+    TestCase { id: "baseline", description: "128kbps, 8KB chunks", bitrate_kbps: 128, chunk_bytes: 8192, icy: false },
+    TestCase { id: "low-bitrate", description: "64kbps, 8KB chunks", bitrate_kbps: 64, chunk_bytes: 8192, icy: false },
+    TestCase { id: "high-bitrate", description: "192kbps, 8KB chunks", bitrate_kbps: 192, chunk_bytes: 8192, icy: false },
+    TestCase { id: "small-chunks", description: "128kbps, 1KB chunks", bitrate_kbps: 128, chunk_bytes: 1024, icy: false },
+    TestCase { id: "large-chunks", description: "128kbps, 32KB chunks", bitrate_kbps: 128, chunk_bytes: 32768, icy: false },
+    TestCase { id: "icy", description: "128kbps, 8KB chunks, ICY metadata every 8KB", bitrate_kbps: 128, chunk_bytes: 8192, icy: true },
+];
+
+pub fn case(id: &str) -> Option<&'static TestCase> {
+    CASES.iter().find(|case| case.id == id)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delivery {
+    Pending,   // Not requested yet
+    Streaming,
+    Completed, // Every byte was handed to the connection
+    Aborted,   // The client went away early
+}
+
+/// What the player said about a case (`POST /debug/client-test/{run}/{case}`)
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerReport {
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResult {
+    #[serde(flatten)]
+    pub case: TestCase,
+    pub delivery: Delivery,
+    pub requests: u32,                // Players often probe or retry
+    pub icy_requested: Option<bool>,  // Whether the last request sent Icy-MetaData: 1
+    pub bytes_sent: usize,
+    pub total_bytes: usize,
+    pub player: Option<PlayerReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientTestRun {
+    pub run: String,
+    pub user_agent: String,
+    pub age_secs: u64,
+    pub cases: Vec<CaseResult>,
+}
+
+#[derive(Debug)]
+struct Run {
+    created: Instant,
+    user_agent: String,
+    cases: Vec<CaseResult>,
+}
+
+/// Test runs, kept for an hour; cheap to clone into response streams
+#[derive(Debug, Clone, Default)]
+pub struct ClientTests {
+    runs: Arc<DashMap<String, Run>>,
+}
+
+impl ClientTests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a run for the client identified by `user_agent`
+    pub fn create_run(&self, user_agent: &str) -> ClientTestRun {
+        self.runs.retain(|_, run| run.created.elapsed() < RUN_TTL);
+        while self.runs.len() >= MAX_RUNS {
+            let oldest = self.runs.iter().min_by_key(|run| run.created).map(|run| run.key().clone());
+            match oldest {
+                Some(id) => self.runs.remove(&id),
+                None => break,
+            };
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let run = Run {
+            created: Instant::now(),
+            user_agent: user_agent.to_string(),
+            cases: CASES.iter().map(|&case| CaseResult {
+                case,
+                delivery: Delivery::Pending,
+                requests: 0,
+                icy_requested: None,
+                bytes_sent: 0,
+                total_bytes: 0,
+                player: None,
+            }).collect(),
+        };
+        let snapshot = snapshot(&id, &run);
+        self.runs.insert(id, run);
+        snapshot
+    }
+
+    pub fn run(&self, run: &str) -> Option<ClientTestRun> {
+        self.runs.get(run).map(|entry| snapshot(run, &entry))
+    }
+
+    pub fn contains(&self, run: &str) -> bool {
+        self.runs.contains_key(run)
+    }
+
+    /// Record the player's verdict; false if the run or case is unknown
+    pub fn report(&self, run: &str, case_id: &str, ok: bool, error: Option<String>) -> bool {
+        self.update(run, case_id, |result| {
+            let error = error.map(|e| e.chars().take(MAX_ERROR_LEN).collect());
+            result.player = Some(PlayerReport { ok, error });
+        })
+    }
+
+    /// The body for one case: `body` sent in `case.chunk_bytes` pieces at the audio's
+    /// own pace, with delivery recorded as it goes
+    pub fn stream(
+        &self,
+        run: &str,
+        case: &'static TestCase,
+        body: Bytes,
+        icy_requested: bool,
+    ) -> impl Stream<Item = std::io::Result<Bytes>> {
+        let total = body.len();
+        self.update(run, case.id, |result| {
+            result.requests += 1;
+            result.icy_requested = Some(icy_requested);
+            result.delivery = Delivery::Streaming;
+            result.bytes_sent = 0;
+            result.total_bytes = total;
+        });
+
+        let mut progress = Progress { tests: self.clone(), run: run.to_string(), case: case.id, sent: 0, total };
+        let chunk_duration = Duration::from_secs_f64(case.chunk_bytes as f64 * 8.0 / (case.bitrate_kbps as f64 * 1000.0));
+        async_stream::stream! {
+            let mut ticker = tokio::time::interval(chunk_duration);
+            for chunk in body.chunks(case.chunk_bytes) {
+                ticker.tick().await;
+                yield Ok(body.slice_ref(chunk));
+                progress.sent += chunk.len();
+                progress.record(Delivery::Streaming);
+            }
+        }
+    }
+
+    fn update(&self, run: &str, case_id: &str, edit: impl FnOnce(&mut CaseResult)) -> bool {
+        let Some(mut run) = self.runs.get_mut(run) else {
+            return false;
+        };
+        match run.cases.iter_mut().find(|result| result.case.id == case_id) {
+            Some(result) => {
+                edit(result);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Delivery of one response; settles as completed or aborted when the body is dropped
+struct Progress {
+    tests: ClientTests,
+    run: String,
+    case: &'static str,
+    sent: usize,
+    total: usize,
+}
+
+impl Progress {
+    fn record(&self, delivery: Delivery) {
+        let sent = self.sent;
+        self.tests.update(&self.run, self.case, |result| {
+            result.bytes_sent = sent;
+            result.delivery = delivery;
+        });
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.record(if self.sent == self.total { Delivery::Completed } else { Delivery::Aborted });
+    }
+}
+
+fn snapshot(id: &str, run: &Run) -> ClientTestRun {
+    ClientTestRun {
+        run: id.to_string(),
+        user_agent: run.user_agent.clone(),
+        age_secs: run.created.elapsed().as_secs(),
+        cases: run.cases.clone(),
+    }
+}
+
+/// The audio for a case, with ICY metadata interleaved if the client asked for it
+pub fn case_body(case: &TestCase, icy_requested: bool) -> std::io::Result<Bytes> {
+    let audio = tone::sine_mp3(tone::DEFAULT_FREQUENCY_HZ, CASE_SECONDS, case.bitrate_kbps)?;
+    if !(case.icy && icy_requested) {
+        return Ok(audio);
+    }
+    let title = format!("Client test: {}", case.description);
+    Ok(IcyMuxer::new(ICY_METAINT).push(&audio, &title))
+}
+
+pub fn icy_metaint(case: &TestCase, icy_requested: bool) -> Option<usize> {
+    (case.icy && icy_requested).then_some(ICY_METAINT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_aborted_and_completed_delivery() {
+        use futures::StreamExt;
+
+        let tests = ClientTests::new();
+        let run = tests.create_run("test-agent").run;
+        let case = case("small-chunks").unwrap();
+        let body = Bytes::from(vec![0u8; case.chunk_bytes * 2 + 10]);
+
+        let mut stream = Box::pin(tests.stream(&run, case, body.clone(), false));
+        stream.next().await.unwrap().unwrap();
+        drop(stream);
+        let result = |tests: &ClientTests| tests.run(&run).unwrap().cases.into_iter().find(|r| r.case.id == case.id).unwrap();
+        assert_eq!(result(&tests).delivery, Delivery::Aborted);
+
+        let stream = tests.stream(&run, case, body.clone(), false);
+        let received: usize = stream.map(|chunk| chunk.unwrap().len()).collect::<Vec<_>>().await.into_iter().sum();
+        assert_eq!(received, body.len());
+        let result = result(&tests);
+        assert_eq!(result.delivery, Delivery::Completed);
+        assert_eq!(result.requests, 2);
+        assert_eq!(result.bytes_sent, body.len());
+    }
+}
@@ -0,0 +1,404 @@
+// Per-listener codec negotiation: /stream serves MP3 straight from the broadcast,
+// or Ogg/Opus or AAC from an ffmpeg encoder fed with the broadcast, depending on
+// the client's Accept header or ?codec=
+
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::stream::Stream;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::{
+    config::{Codec, Config},
+    error::AppError,
+    radio::AudioChunk,
+    simulcast::ListenerGuard,
+    transcoder::{self, EncoderSpec, WarmPool},
+};
+
+const CHANNEL_CAPACITY: usize = 64;
+
+impl Codec {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::Opus => "audio/ogg",
+            Self::Aac => "audio/aac",
+        }
+    }
+
+    /// Codec a concrete media type names, e.g. `audio/ogg` or `audio/aac`
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "audio/mpeg" | "audio/mp3" | "audio/mpeg3" | "audio/x-mpeg" => Some(Self::Mp3),
+            "audio/ogg" | "audio/opus" | "application/ogg" => Some(Self::Opus),
+            "audio/aac" | "audio/aacp" | "audio/x-aac" => Some(Self::Aac),
+            _ => None,
+        }
+    }
+
+    /// ffmpeg command encoding the MP3 broadcast to this codec
+    fn encoder_spec(&self, ffmpeg: &Path, bitrate_kbps: u32) -> Option<EncoderSpec> {
+        let bitrate = format!("{}k", bitrate_kbps);
+        match self {
+            Self::Mp3 => None,
+            // One Opus packet per Ogg page keeps pages small, so listeners start quickly
+            Self::Opus => Some(EncoderSpec::ffmpeg("opus", ffmpeg, "ogg", &["-c:a", "libopus", "-b:a", &bitrate, "-page_duration", "20000"])),
+            Self::Aac => Some(EncoderSpec::ffmpeg("aac", ffmpeg, "adts", &["-c:a", "aac", "-b:a", &bitrate])),
+        }
+    }
+}
+
+/// Pick the codec for a listener from `available` (MP3 first). `?codec=` must name
+/// an available codec; otherwise the best Accept match wins, with ties going to
+/// the earlier codec. Accept headers naming nothing available get MP3.
+pub fn negotiate(accept: Option<&str>, requested: Option<&str>, available: &[Codec]) -> Result<Codec, AppError> {
+    if let Some(requested) = requested {
+        let codec: Codec = requested.parse().map_err(AppError::BadRequest)?;
+        if !available.contains(&codec) {
+            return Err(AppError::NotAcceptable(format!("This station doesn't serve {}", codec.name())));
+        }
+        return Ok(codec);
+    }
+
+    let Some(accept) = accept else {
+        return Ok(Codec::Mp3);
+    };
+    let ranges: Vec<(String, f32)> = accept.split(',').filter_map(media_range).collect();
+    let quality = |codec: Codec| {
+        // The most specific matching range decides
+        let exact = ranges.iter().filter(|(range, _)| Codec::from_media_type(range) == Some(codec)).map(|(_, q)| *q).reduce(f32::max);
+        exact
+            .or_else(|| ranges.iter().find(|(range, _)| range == "audio/*").map(|(_, q)| *q))
+            .or_else(|| ranges.iter().find(|(range, _)| range == "*/*").map(|(_, q)| *q))
+            .unwrap_or(0.0)
+    };
+
+    let mut best = (Codec::Mp3, 0.0);
+    for &codec in available {
+        let q = quality(codec);
+        if q > best.1 {
+            best = (codec, q);
+        }
+    }
+    Ok(best.0)
+}
+
+// "audio/ogg; codecs=opus; q=0.8" -> ("audio/ogg", 0.8)
+fn media_range(item: &str) -> Option<(String, f32)> {
+    let mut parts = item.split(';').map(str::trim);
+    let range = parts.next().filter(|range| !range.is_empty())?.to_ascii_lowercase();
+    let q = parts
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+        .and_then(|(_, value)| value.trim().parse::<f32>().ok())
+        .unwrap_or(1.0);
+    Some((range, q.clamp(0.0, 1.0)))
+}
+
+/// The broadcast in one non-MP3 codec, and its listeners
+pub struct CodecOutput {
+    codec: Codec,
+    bitrate_kbps: u32,
+    pool: Arc<WarmPool>,
+    tx: broadcast::Sender<Bytes>,
+    header: Mutex<Vec<Bytes>>, // Ogg header pages a listener needs before any audio
+    listeners: Arc<AtomicUsize>,
+}
+
+impl CodecOutput {
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    pub fn bitrate_kbps(&self) -> u32 {
+        self.bitrate_kbps
+    }
+
+    pub fn listener_count(&self) -> usize {
+        self.listeners.load(Ordering::Relaxed)
+    }
+
+    /// A listener's stream: the stream headers if the format has any, then live
+    /// audio. A listener that falls behind skips ahead.
+    pub fn listen(&self) -> impl Stream<Item = Bytes> + Send + 'static {
+        let (header, mut receiver) = {
+            let header = self.header.lock().unwrap();
+            (header.clone(), self.tx.subscribe())
+        };
+        let guard = ListenerGuard::new(&self.listeners);
+        let name = self.codec.name();
+        async_stream::stream! {
+            let _guard = guard;
+            for data in header {
+                yield data;
+            }
+            loop {
+                match receiver.recv().await {
+                    Ok(data) => yield data,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("{} listener lagged by {} blocks", name, skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    // Re-publish encoder output. Ogg is cut at page boundaries and its header pages
+    // are kept for late joiners; ADTS frames carry their own sync words.
+    async fn publish(self: Arc<Self>, mut encoded: broadcast::Receiver<Bytes>) {
+        let mut pager = OggPager::default();
+        let mut in_header = false;
+        loop {
+            let data = match encoded.recv().await {
+                Ok(data) => data,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if self.codec != Codec::Opus {
+                let _ = self.tx.send(data);
+                continue;
+            }
+            for page in pager.push(&data) {
+                // Held while sending so a new listener's headers and live pages don't overlap
+                let mut header = self.header.lock().unwrap();
+                if page.bos {
+                    // A (re)started encoder begins a new chained stream
+                    header.clear();
+                    in_header = true;
+                }
+                if in_header && page.granule == 0 {
+                    header.push(page.data.clone());
+                } else {
+                    in_header = false;
+                }
+                let _ = self.tx.send(page.data);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> serde_json::Value {
+        serde_json::json!({
+            "codec": self.codec.name(),
+            "content_type": self.codec.content_type(),
+            "bitrate_kbps": self.bitrate_kbps,
+            "listeners": self.listener_count(),
+            "encoder": self.pool.stats(),
+        })
+    }
+}
+
+/// The formats /stream can negotiate besides MP3 (STREAM_CODECS)
+pub struct CodecOutputs {
+    outputs: Vec<Arc<CodecOutput>>,
+}
+
+impl CodecOutputs {
+    pub fn new(config: &Config) -> Self {
+        let mut outputs: Vec<Arc<CodecOutput>> = Vec::new();
+        for &codec in &config.stream_codecs {
+            let Some(spec) = codec.encoder_spec(&config.ffmpeg_path, config.codec_bitrate_kbps) else {
+                continue; // MP3 is always served
+            };
+            if outputs.iter().any(|output| output.codec == codec) {
+                continue;
+            }
+            let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+            outputs.push(Arc::new(CodecOutput {
+                codec,
+                bitrate_kbps: config.codec_bitrate_kbps,
+                pool: WarmPool::new(spec, config.transcoder_standby),
+                tx,
+                header: Mutex::new(Vec::new()),
+                listeners: Arc::new(AtomicUsize::new(0)),
+            }));
+        }
+        Self { outputs }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.outputs.is_empty()
+    }
+
+    /// Codecs a listener can get, MP3 first
+    pub fn available(&self) -> Vec<Codec> {
+        std::iter::once(Codec::Mp3).chain(self.outputs.iter().map(|output| output.codec)).collect()
+    }
+
+    pub fn output(&self, codec: Codec) -> Option<&Arc<CodecOutput>> {
+        self.outputs.iter().find(|output| output.codec == codec)
+    }
+
+    pub fn outputs(&self) -> &[Arc<CodecOutput>] {
+        &self.outputs
+    }
+
+    pub fn listener_count(&self) -> usize {
+        self.outputs.iter().map(|output| output.listener_count()).sum()
+    }
+
+    /// Start an encoder per output on `input`; each runs until the broadcast closes
+    pub fn spawn(&self, input: broadcast::Receiver<AudioChunk>) {
+        for output in &self.outputs {
+            info!("Encoding {} at {}kbps for negotiated streams", output.codec.name(), output.bitrate_kbps);
+            let (encoded_tx, encoded_rx) = broadcast::channel(CHANNEL_CAPACITY);
+            tokio::spawn(transcoder::run_output(Arc::clone(&output.pool), input.resubscribe(), encoded_tx));
+            tokio::spawn(Arc::clone(output).publish(encoded_rx));
+        }
+    }
+}
+
+struct OggPage {
+    data: Bytes,
+    granule: u64,
+    bos: bool, // First page of a logical stream
+}
+
+/// Splits an Ogg byte stream into whole pages
+#[derive(Default)]
+struct OggPager {
+    buf: BytesMut,
+}
+
+impl OggPager {
+    const HEADER_LEN: usize = 27;
+
+    fn push(&mut self, data: &[u8]) -> Vec<OggPage> {
+        self.buf.extend_from_slice(data);
+        let mut pages = Vec::new();
+        loop {
+            // Resync on the capture pattern
+            match self.buf.windows(4).position(|window| window == b"OggS") {
+                Some(start) => self.buf.advance(start),
+                None => {
+                    let keep = self.buf.len().min(3);
+                    self.buf.advance(self.buf.len() - keep);
+                    return pages;
+                }
+            }
+            if self.buf.len() < Self::HEADER_LEN {
+                return pages;
+            }
+            let segments = self.buf[26] as usize;
+            if self.buf.len() < Self::HEADER_LEN + segments {
+                return pages;
+            }
+            let body: usize = self.buf[Self::HEADER_LEN..Self::HEADER_LEN + segments].iter().map(|&len| len as usize).sum();
+            let len = Self::HEADER_LEN + segments + body;
+            if self.buf.len() < len {
+                return pages;
+            }
+            let bos = self.buf[5] & 0x02 != 0;
+            let granule = u64::from_le_bytes(self.buf[6..14].try_into().unwrap());
+            if !page_crc_valid(&self.buf[..len]) {
+                // Corrupt, or a false capture pattern: resync past it
+                warn!("Skipping Ogg page with a bad CRC");
+                self.buf.advance(1);
+                continue;
+            }
+            pages.push(OggPage { data: self.buf.split_to(len).freeze(), granule, bos });
+        }
+    }
+}
+
+/// Ogg's CRC-32 (polynomial 0x04c11db7, unreflected, no final XOR)
+pub fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |mut crc, &byte| {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04c1_1db7 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+/// Whether a whole page's checksum (bytes 22..26, computed as zero) matches
+pub fn page_crc_valid(page: &[u8]) -> bool {
+    if page.len() < OggPager::HEADER_LEN {
+        return false;
+    }
+    let stored = u32::from_le_bytes(page[22..26].try_into().unwrap());
+    let mut zeroed = page.to_vec();
+    zeroed[22..26].fill(0);
+    ogg_crc(&zeroed) == stored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOTH: [Codec; 3] = [Codec::Mp3, Codec::Opus, Codec::Aac];
+
+    #[test]
+    fn test_negotiate_accept() {
+        assert_eq!(negotiate(None, None, &BOTH).unwrap(), Codec::Mp3);
+        assert_eq!(negotiate(Some("audio/ogg; codecs=opus"), None, &BOTH).unwrap(), Codec::Opus);
+        assert_eq!(negotiate(Some("audio/aac, audio/mpeg;q=0.5"), None, &BOTH).unwrap(), Codec::Aac);
+        assert_eq!(negotiate(Some("audio/*"), None, &BOTH).unwrap(), Codec::Mp3);
+        // Specific ranges beat wildcards
+        assert_eq!(negotiate(Some("audio/mpeg;q=0, audio/*;q=0.8"), None, &BOTH).unwrap(), Codec::Opus);
+        // Not offered: MP3 rather than a refusal
+        assert_eq!(negotiate(Some("audio/ogg"), None, &[Codec::Mp3]).unwrap(), Codec::Mp3);
+        assert_eq!(negotiate(Some("video/webm"), None, &BOTH).unwrap(), Codec::Mp3);
+    }
+
+    #[test]
+    fn test_negotiate_query() {
+        assert_eq!(negotiate(Some("audio/mpeg"), Some("opus"), &BOTH).unwrap(), Codec::Opus);
+        assert!(matches!(negotiate(None, Some("aac"), &[Codec::Mp3]), Err(AppError::NotAcceptable(_))));
+        assert!(matches!(negotiate(None, Some("wav"), &BOTH), Err(AppError::BadRequest(_))));
+    }
+
+    fn page(header_type: u8, granule: u64, body: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS".to_vec();
+        page.extend_from_slice(&[0, header_type]);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&[0; 12]); // Serial, sequence, CRC
+        page.push(1);
+        page.push(body.len() as u8);
+        page.extend_from_slice(body);
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        page
+    }
+
+    #[test]
+    fn test_ogg_pager_splits_pages() {
+        let mut stream = b"junk".to_vec();
+        stream.extend(page(0x02, 0, b"OpusHead"));
+        stream.extend(page(0, 0, b"OpusTags"));
+        stream.extend(page(0, 960, b"audio"));
+
+        let mut pager = OggPager::default();
+        let mut pages = Vec::new();
+        for piece in stream.chunks(7) {
+            pages.extend(pager.push(piece));
+        }
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0].bos && !pages[1].bos);
+        assert_eq!(pages[1].granule, 0);
+        assert_eq!(pages[2].granule, 960);
+        assert!(pages[2].data.ends_with(b"audio"));
+        assert_eq!(pages[0].data.len(), 27 + 1 + 8);
+    }
+
+    #[test]
+    fn test_ogg_pager_drops_corrupt_pages() {
+        let mut corrupt = page(0, 960, b"audio");
+        corrupt[30] ^= 0xff;
+        let mut stream = corrupt;
+        stream.extend(page(0, 1920, b"more audio"));
+
+        let pages = OggPager::default().push(&stream);
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].granule, 1920);
+        assert!(page_crc_valid(&pages[0].data));
+    }
+}
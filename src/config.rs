@@ -1,4 +1,6 @@
-use std::path::PathBuf;
+use std::{net::IpAddr, path::PathBuf};
+
+use crate::rotation::FieldRule;
 
 /// Configuration for the WebRadio server
 /// Can be loaded from environment variables using `Config::from_env()`
@@ -6,39 +8,329 @@ use std::path::PathBuf;
 pub struct Config {
     pub host: String,
     pub port: u16,
+    pub tls_cert_path: Option<PathBuf>, // PEM certificate chain; with TLS_KEY, serve HTTPS directly
+    pub tls_key_path: Option<PathBuf>,
+    pub acme_domains: Vec<String>,              // `acme` feature: obtain and renew TLS_CERT/TLS_KEY for these names
+    pub acme_email: Option<String>,             // Contact address of the ACME account
+    pub acme_directory: String,                 // ACME server directory URL
+    pub acme_http_port: u16,                    // Plain HTTP listener for HTTP-01 challenges
+    pub acme_account_key_path: Option<PathBuf>, // Defaults to acme-account.key beside TLS_KEY
+    pub acme_renew_days: u64,                   // Renew once the certificate file is this old
     pub music_dir: PathBuf,
+    pub watch_music_dir: bool,        // Rescan music_dir when MP3s are added or removed
+    pub preflight_strict: bool,       // Refuse to start when a startup check fails, instead of running degraded
+    pub preflight_min_free_mb: u64,   // Free disk space below this fails the startup check
+    pub ready_max_chunk_age_ms: u64,  // /readyz fails once no audio has been published for this long
+    pub station_name: String,
+    pub station_genre: String,               // icy-genre
+    pub station_url: Option<String>,         // icy-url (station homepage); defaults to the public URL
+    pub station_description: Option<String>, // icy-description
+    pub station_public: bool,                // icy-pub: allow directory services to list the stream
+    pub play_history_path: PathBuf,   // Append-only log of completed plays (used for royalty reports)
+    pub audience_log_path: PathBuf,   // Append-only per-minute listener counts and bytes sent
+    pub ban_list_path: PathBuf,       // Listener addresses banned through the admin API
+    pub archive_dir: PathBuf,         // Recorded shows served by /api/archive
+    pub archive_record: bool,         // Continuously record the broadcast into archive_dir
+    pub archive_show: String,         // Show name (and file name prefix) of the recordings
+    pub archive_rotation: ArchiveRotation,
+    pub archive_retention_days: u64,  // Delete recordings older than this; 0 = keep forever
 
     // Streaming configuration
     pub initial_buffer_kb: usize,      // Initial buffer size for new listeners (KB)
     pub minimum_buffer_kb: usize,      // Minimum buffer before starting playback (KB)
     pub chunk_interval_ms: u64,        // Interval between chunks (milliseconds)
     pub stream_rate_multiplier: f64,   // Stream faster than bitrate to build client buffers (1.10 = 10% faster)
+    pub adaptive_stream_rate: bool,    // Vary the rate between 1.0 and stream_rate_multiplier as listener buffers need
     pub initial_buffer_timeout_ms: u64, // Timeout for initial buffer collection
+    pub burst_history_secs: u64,       // Recent broadcast kept to burst new listeners from at once; 0 = wait for live audio
+    pub prefetch_secs: u64,            // Audio of the next track read ahead while the current one plays; 0 = no prefetch
     pub broadcast_channel_capacity: usize, // Capacity of broadcast channel
+    pub max_listeners: usize,              // Concurrent listener cap; 0 = unlimited
+    pub listener_retry_after_secs: u64,    // Retry-After sent when the cap is reached
+    pub max_streams_per_ip: usize,         // Simultaneous /stream and /ws connections per client IP; 0 = unlimited
+    pub api_requests_per_sec: f64,         // /api/* requests per second per client IP; 0 = unlimited
+    pub churn_max_per_min: usize,          // Short-lived streams per client IP per minute before tarpitting; 0 = off
+    pub churn_short_secs: u64,             // Streams closing sooner than this count as churn
+    pub churn_tarpit_ms: u64,              // Delay before serving a stream to a churning IP
+    pub churn_ban_secs: u64,               // Ban length once an IP churns at twice the limit
+    pub skip_vote_fraction: f64,           // Share of current listeners whose votes skip a track; 0 = no voting
+    pub idle_mode: IdleMode,               // Playout while nobody is listening
+    pub artist_separation: usize,          // Other tracks between two by the same artist; 0 = off
+    pub track_separation_hours: f64,       // Before a track may play again; 0 = off
+    pub rotation_exclude: Vec<FieldRule>,  // Tracks whose tags match one of these stay out of rotation
+
+    // Performance metrics history (/api/metrics/history)
+    pub metrics_sample_secs: u64,     // Sampling interval; 0 = no history
+    pub metrics_history_minutes: u64, // How much history is kept
+
+    // Drift compensation for long-lived listeners
+    pub drift_max_ms: u64,        // Lag behind live (beyond the burst) before chunks are trimmed; 0 = off
+    pub drift_min_buffer_ms: u64, // Fill broadcast gaps with silence to keep this much buffered; 0 = off
+    pub realtime_max_drift_ms: u64, // Tighter drift bound for realtime-clocked listeners
+    pub timeshift_minutes: u64,     // Broadcast history kept for `/stream?rewind=`; 0 = off
+    pub resume_window_secs: u64,    // How long a disconnected listener can resume where it left off; 0 = off
+    pub timeshift_catchup_percent: f64, // Share of replayed audio skipped to drift back to live; 0 = stay behind
+
+    // Hold audio broadcast while the playlist is empty or every track fails
+    pub hold_audio_file: Option<PathBuf>,        // MP3 looped while on hold (silence when unset)
+    pub hold_announcement_file: Option<PathBuf>, // MP3 played periodically while on hold
+    pub hold_announcement_interval_secs: u64,
+    pub hold_retry_secs: u64,                    // How long to hold before retrying the playlist
+    pub hold_title: String,                      // Now-playing title while on hold
+    pub alert_webhook_url: Option<String>,       // POSTed a JSON alert when hold starts and ends
+
+    // Relay of an upstream Icecast/HTTP MP3 stream
+    pub relay_url: Option<String>,  // Upstream stream URL; unset = no relay
+    pub relay_mode: RelayMode,
+    pub relay_retry_secs: u64,      // Pause before reconnecting in primary mode
+
+    // Developer chunk log
+    pub chunk_log_record: Option<PathBuf>,       // Record every broadcast chunk with its timing to this file
+    pub chunk_log_replay: Option<PathBuf>,       // Broadcast a recorded chunk log instead of the playlist
+
+    // Encoder processes for ffmpeg-backed outputs
+    pub ffmpeg_path: PathBuf,
+    pub transcoder_standby: usize, // Warm spare encoders kept per output
+    pub stream_codecs: Vec<Codec>,  // Formats /stream negotiates besides MP3
+    pub codec_bitrate_kbps: u32,    // Bitrate of those formats
+
+    // Extra MP3 qualities re-encoded from the broadcast, one mount each
+    pub simulcast_mounts: Vec<SimulcastMount>,
+    pub transcode_bitrate_kbps: u32, // MP3 bitrate FLAC/Vorbis tracks are re-encoded to for the broadcast
+    pub normalize: bool,             // Apply each track's analyzed or ReplayGain gain on the broadcast
+    pub trim_silence: bool,          // Skip the leading/trailing silence `webradio analyze` found
+    pub silence_threshold_db: f64,   // Level (dBFS) below which `webradio analyze` counts audio as silence
+
+    // Multi-room sync
+    pub sync_delay_ms: u64, // How far behind the broadcast timeline synced players play
+
+    // WebSocket streaming
+    pub ws_ping_interval_secs: u64, // Ping interval; clients that miss a pong are disconnected
+
+    // Burst-on-connect settings per client profile
+    pub burst_default: BurstConfig,
+    pub burst_ios: BurstConfig,
+    pub burst_embedded: BurstConfig,
+    pub burst_low_latency: BurstConfig,
+
+    // MQTT publishing (disabled unless a broker is set)
+    pub mqtt_broker: Option<String>,    // host:port
+    pub mqtt_topic_prefix: String,
+    pub mqtt_client_id: String,
+    pub mqtt_username: Option<String>,
+    pub mqtt_password: Option<String>,
+    pub mqtt_publish_interval_secs: u64, // Health topic publish interval
+
+    // Chat now-playing notifications (each channel off unless configured)
+    pub notify_discord_webhook_url: Option<String>,
+    pub notify_slack_webhook_url: Option<String>,
+    pub notify_telegram_bot_token: Option<String>,
+    pub notify_telegram_chat_id: Option<String>,
+    pub notify_min_interval_secs: u64, // At most one message per interval; changes in between are merged
+
+    // Signing
+    pub signing_secret: Option<String>, // HMAC secret for client tokens (random per process when unset)
+    pub admin_token: Option<String>,    // Bearer token / API key for admin routes (refused when unset)
+    pub admin_localhost: bool,          // Without a token, serve admin routes to loopback socket peers
+    pub require_signed_streams: bool,   // Audio endpoints need a token minted by /api/stream-token
+    pub stream_token_ttl_secs: u64,     // Default lifetime of minted stream tokens
+    pub intercom_token: Option<String>, // Token for the DJ side of /intercom (intercom disabled when unset)
+
+    // GeoIP and /stream access rules
+    pub geoip_country_db: Option<PathBuf>, // MaxMind .mmdb with country data
+    pub geoip_asn_db: Option<PathBuf>,     // MaxMind .mmdb with ASN data
+    pub stats_geoip: bool,                 // Country/region breakdown in /api/stats and the audience log
+    pub stats_geoip_anonymize: bool,       // Look up truncated addresses and report only large country groups
+    pub stream_allow_countries: Vec<String>, // ISO country codes; empty = everywhere
+    pub stream_deny_countries: Vec<String>,
+    pub stream_allow_asns: Vec<u32>,
+    pub stream_deny_asns: Vec<u32>,
+    pub stream_block_message: Option<String>, // Replaces the generated 451 explanation
+    pub trust_forwarded_for: bool,         // Take the client address from X-Forwarded-For (behind a proxy)
+    pub trusted_proxy_hops: usize,         // Proxies appending to X-Forwarded-For; the client is that many hops from the right
+
+    // Public address discovery (/api/server-info and the startup banner)
+    pub public_url: Option<String>,        // Base URL listeners use (e.g. behind a proxy); overrides discovery
+    pub public_ip: Option<IpAddr>,         // Fixed public IP; skips STUN discovery
+    pub public_ip_stun_servers: Vec<String>, // host:port STUN servers; empty = no discovery
+    pub public_ip_refresh_secs: u64,       // Re-run discovery this often; 0 = once at startup
+
+    // Logging (RUST_LOG still sets the levels)
+    pub log_format: LogFormat,
+    pub log_file: Option<PathBuf>,  // Write logs to this file instead of stdout
+    pub log_rotation: LogRotation,  // When log_file starts a new file
 }
 
+// Used when PUBLIC_IP_STUN_SERVERS is unset
+const DEFAULT_STUN_SERVERS: [&str; 2] = ["stun.l.google.com:19302", "stun.cloudflare.com:3478"];
+
 impl Config {
     pub fn from_env() -> Self {
+        let music_dir = std::env::var("MUSIC_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("music"));
+
+        // Streaming defaults optimized for stable radio streaming
+        let initial_buffer_kb = std::env::var("INITIAL_BUFFER_KB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(120);  // 120KB = ~5 seconds at 192kbps
+        let minimum_buffer_kb = std::env::var("MINIMUM_BUFFER_KB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(80);   // 80KB = ~3.3 seconds minimum (ensure solid buffer)
+        let initial_buffer_timeout_ms = std::env::var("INITIAL_BUFFER_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(6000); // 6 seconds to collect initial buffer (120KB at 211kbps)
+
+        // The default profile keeps the historical "send everything instantly" burst
+        // Default for every profile's BURST_<PROFILE>_EGRESS_CAP
+        let egress_cap = std::env::var("EGRESS_CAP").ok().and_then(|v| parse_egress_cap(&v)).unwrap_or(0.0);
+        // Default for every profile's BURST_<PROFILE>_SIZE_KB
+        let burst_size_kb = std::env::var("BURST_SIZE_KB").ok().and_then(|v| v.parse().ok());
+        // Default for every profile's BURST_<PROFILE>_LAG_POLICY
+        let lag = std::env::var("LAG_POLICY").ok().and_then(|v| v.parse().ok()).unwrap_or(LagPolicy::DropToLive);
+        let default_burst = BurstConfig {
+            burst_kb: initial_buffer_kb,
+            minimum_kb: minimum_buffer_kb,
+            timeout_ms: initial_buffer_timeout_ms,
+            pacing_kbps: 0,
+            catch_up: CatchUp::Queue,
+            clock: StreamClock::BufferBuilding,
+            egress_cap,
+            lag,
+            size_kb: burst_size_kb,
+            types: vec!["default".to_string()],
+            user_agents: Vec::new(), // Fallback for clients no other profile matches
+        };
+        // iOS power management stalls playback on buffers desktop players get by with.
+        // Sized on its own rather than from the default profile; client_telemetry in
+        // /api/stats shows what iOS players actually hold.
+        let ios_burst = BurstConfig {
+            burst_kb: 240,
+            minimum_kb: 160,
+            timeout_ms: 12000,
+            types: vec!["ios".to_string()],
+            user_agents: vec!["iPhone".to_string(), "iPad".to_string(), "iPod".to_string()],
+            ..default_burst.clone()
+        };
+        // Small, paced burst for embedded players with little RAM
+        let embedded_burst = BurstConfig {
+            burst_kb: 32,
+            minimum_kb: 16,
+            timeout_ms: initial_buffer_timeout_ms,
+            pacing_kbps: 48, // ~2x realtime at 192kbps
+            catch_up: CatchUp::SkipToLive,
+            clock: StreamClock::BufferBuilding,
+            egress_cap,
+            lag,
+            size_kb: burst_size_kb,
+            types: vec!["embedded".to_string()],
+            user_agents: Vec::new(),
+        };
+        // Studio monitoring: a fraction of a second of burst, then realtime delivery
+        // so the player's buffer never grows beyond it
+        let low_latency_burst = BurstConfig {
+            burst_kb: 8, // ~0.3 seconds at 192kbps
+            minimum_kb: 4,
+            timeout_ms: 500,
+            pacing_kbps: 0,
+            catch_up: CatchUp::SkipToLive,
+            clock: StreamClock::Realtime,
+            egress_cap,
+            lag,
+            size_kb: burst_size_kb,
+            types: vec!["low_latency".to_string(), "monitor".to_string()],
+            user_agents: Vec::new(),
+        };
+
         Self {
             host: std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
             port: std::env::var("PORT")
                 .ok()
                 .and_then(|p| p.parse().ok())
                 .unwrap_or(8000),
-            music_dir: std::env::var("MUSIC_DIR")
+            tls_cert_path: std::env::var("TLS_CERT").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+            tls_key_path: std::env::var("TLS_KEY").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+            acme_domains: parse_list("ACME_DOMAINS", |v| Some(v.to_ascii_lowercase())),
+            acme_email: std::env::var("ACME_EMAIL").ok().filter(|v| !v.is_empty()),
+            acme_directory: std::env::var("ACME_DIRECTORY")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| "https://acme-v02.api.letsencrypt.org/directory".to_string()),
+            acme_http_port: std::env::var("ACME_HTTP_PORT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(80),
+            acme_account_key_path: std::env::var("ACME_ACCOUNT_KEY").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+            acme_renew_days: std::env::var("ACME_RENEW_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            station_name: std::env::var("STATION_NAME").unwrap_or_else(|_| "WebRadio".to_string()),
+            station_genre: std::env::var("STATION_GENRE").unwrap_or_else(|_| "Various".to_string()),
+            station_url: std::env::var("STATION_URL").ok().filter(|v| !v.is_empty()),
+            station_description: std::env::var("STATION_DESCRIPTION").ok().filter(|v| !v.is_empty()),
+            station_public: std::env::var("STATION_PUBLIC")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            play_history_path: std::env::var("PLAY_HISTORY_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| music_dir.join("play_history.jsonl")),
+            audience_log_path: std::env::var("AUDIENCE_LOG_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| music_dir.join("audience.jsonl")),
+            ban_list_path: std::env::var("BAN_LIST_PATH")
                 .map(PathBuf::from)
-                .unwrap_or_else(|_| PathBuf::from("music")),
+                .unwrap_or_else(|_| music_dir.join("bans.json")),
+            music_dir,
+            watch_music_dir: std::env::var("WATCH_MUSIC_DIR")
+                .map(|v| !(v == "0" || v.eq_ignore_ascii_case("false")))
+                .unwrap_or(true),
+            preflight_strict: std::env::var("PREFLIGHT_STRICT")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            preflight_min_free_mb: std::env::var("PREFLIGHT_MIN_FREE_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100),
+            ready_max_chunk_age_ms: std::env::var("READY_MAX_CHUNK_AGE_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&ms| ms > 0)
+                .unwrap_or(5000),
+            archive_dir: std::env::var("ARCHIVE_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("archive")),
+            archive_record: std::env::var("ARCHIVE_RECORD")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            archive_show: std::env::var("ARCHIVE_SHOW")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .unwrap_or_else(|| "aircheck".to_string()),
+            archive_rotation: std::env::var("ARCHIVE_ROTATION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(ArchiveRotation::Hourly),
+            archive_retention_days: std::env::var("ARCHIVE_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+
+            initial_buffer_kb,
+            minimum_buffer_kb,
 
-            // Streaming defaults optimized for stable radio streaming
-            initial_buffer_kb: std::env::var("INITIAL_BUFFER_KB")
+            burst_history_secs: std::env::var("BURST_HISTORY_SECS")
                 .ok()
                 .and_then(|v| v.parse().ok())
-                .unwrap_or(120),  // 120KB = ~5 seconds at 192kbps
+                .unwrap_or(30),
 
-            minimum_buffer_kb: std::env::var("MINIMUM_BUFFER_KB")
+            prefetch_secs: std::env::var("PREFETCH_SECS")
                 .ok()
                 .and_then(|v| v.parse().ok())
-                .unwrap_or(80),   // 80KB = ~3.3 seconds minimum (ensure solid buffer)
+                .unwrap_or(5),
 
             chunk_interval_ms: std::env::var("CHUNK_INTERVAL_MS")
                 .ok()
@@ -49,20 +341,614 @@ impl Config {
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(1.10), // 10% faster than bitrate
+            adaptive_stream_rate: std::env::var("ADAPTIVE_STREAM_RATE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
 
-            initial_buffer_timeout_ms: std::env::var("INITIAL_BUFFER_TIMEOUT_MS")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(6000), // 6 seconds to collect initial buffer (120KB at 211kbps)
+            initial_buffer_timeout_ms,
 
             broadcast_channel_capacity: std::env::var("BROADCAST_CHANNEL_CAPACITY")
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(32768), // 32K messages capacity
+
+            max_listeners: std::env::var("MAX_LISTENERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+
+            listener_retry_after_secs: std::env::var("LISTENER_RETRY_AFTER_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+
+            max_streams_per_ip: std::env::var("MAX_STREAMS_PER_IP")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+
+            api_requests_per_sec: std::env::var("API_RATE_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.0),
+
+            churn_max_per_min: std::env::var("CHURN_MAX_PER_MIN")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+
+            churn_short_secs: std::env::var("CHURN_SHORT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+
+            churn_tarpit_ms: std::env::var("CHURN_TARPIT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3000),
+
+            churn_ban_secs: std::env::var("CHURN_BAN_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(600),
+
+            skip_vote_fraction: std::env::var("SKIP_VOTE_FRACTION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&v: &f64| (0.0..=1.0).contains(&v))
+                .unwrap_or(0.5),
+
+            artist_separation: std::env::var("ARTIST_SEPARATION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            track_separation_hours: std::env::var("TRACK_SEPARATION_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&hours: &f64| hours.is_finite() && hours >= 0.0)
+                .unwrap_or(0.0),
+            rotation_exclude: parse_list("ROTATION_EXCLUDE", FieldRule::parse),
+
+            idle_mode: std::env::var("IDLE_MODE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(IdleMode::Broadcast),
+
+            metrics_sample_secs: std::env::var("METRICS_SAMPLE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            metrics_history_minutes: std::env::var("METRICS_HISTORY_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+
+            drift_max_ms: std::env::var("DRIFT_MAX_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30_000), // 30 seconds
+            drift_min_buffer_ms: std::env::var("DRIFT_MIN_BUFFER_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000),
+
+            realtime_max_drift_ms: std::env::var("REALTIME_MAX_DRIFT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2000),
+
+            timeshift_minutes: std::env::var("TIMESHIFT_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            resume_window_secs: std::env::var("RESUME_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            timeshift_catchup_percent: std::env::var("TIMESHIFT_CATCHUP_PERCENT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&v: &f64| (0.0..100.0).contains(&v))
+                .unwrap_or(5.0),
+
+            hold_audio_file: std::env::var("HOLD_AUDIO_FILE").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+            hold_announcement_file: std::env::var("HOLD_ANNOUNCEMENT_FILE").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+            hold_announcement_interval_secs: std::env::var("HOLD_ANNOUNCEMENT_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            hold_retry_secs: std::env::var("HOLD_RETRY_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&v| v > 0)
+                .unwrap_or(10),
+            hold_title: std::env::var("HOLD_TITLE").unwrap_or_else(|_| "Stand by".to_string()),
+            alert_webhook_url: std::env::var("ALERT_WEBHOOK_URL").ok().filter(|v| !v.trim().is_empty()),
+
+            relay_url: std::env::var("RELAY_URL").ok().filter(|v| !v.trim().is_empty()),
+            relay_mode: std::env::var("RELAY_MODE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(RelayMode::Fallback),
+            relay_retry_secs: std::env::var("RELAY_RETRY_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+
+            chunk_log_record: std::env::var("CHUNK_LOG_RECORD").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+            chunk_log_replay: std::env::var("CHUNK_LOG_REPLAY").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+
+            ffmpeg_path: std::env::var("FFMPEG_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("ffmpeg")),
+            transcoder_standby: std::env::var("TRANSCODER_STANDBY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1),
+            stream_codecs: parse_list("STREAM_CODECS", |v| v.parse().ok()),
+            codec_bitrate_kbps: std::env::var("CODEC_BITRATE_KBPS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&kbps| kbps > 0)
+                .unwrap_or(96),
+
+            simulcast_mounts: parse_list("SIMULCAST_MOUNTS", |v| v.parse().ok()),
+            transcode_bitrate_kbps: std::env::var("TRANSCODE_BITRATE_KBPS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&kbps| crate::encode::lame_bitrate(kbps).is_some())
+                .unwrap_or(192),
+            normalize: std::env::var("NORMALIZE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(true),
+            trim_silence: std::env::var("TRIM_SILENCE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(true),
+            silence_threshold_db: std::env::var("SILENCE_THRESHOLD_DB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|db: &f64| db.is_finite() && *db < 0.0)
+                .unwrap_or(-50.0),
+
+            sync_delay_ms: std::env::var("SYNC_DELAY_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3000),
+
+            ws_ping_interval_secs: std::env::var("WS_PING_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15),
+
+            burst_default: BurstConfig::from_env(ClientProfile::Default, default_burst),
+            burst_ios: BurstConfig::from_env(ClientProfile::Ios, ios_burst),
+            burst_embedded: BurstConfig::from_env(ClientProfile::Embedded, embedded_burst),
+            burst_low_latency: BurstConfig::from_env(ClientProfile::LowLatency, low_latency_burst),
+
+            mqtt_broker: std::env::var("MQTT_BROKER").ok().filter(|v| !v.is_empty()),
+            mqtt_topic_prefix: std::env::var("MQTT_TOPIC_PREFIX").unwrap_or_else(|_| "webradio".to_string()),
+            mqtt_client_id: std::env::var("MQTT_CLIENT_ID").unwrap_or_else(|_| "webradio".to_string()),
+            mqtt_username: std::env::var("MQTT_USERNAME").ok(),
+            mqtt_password: std::env::var("MQTT_PASSWORD").ok(),
+            mqtt_publish_interval_secs: std::env::var("MQTT_PUBLISH_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+
+            notify_discord_webhook_url: std::env::var("NOTIFY_DISCORD_WEBHOOK_URL").ok().filter(|v| !v.trim().is_empty()),
+            notify_slack_webhook_url: std::env::var("NOTIFY_SLACK_WEBHOOK_URL").ok().filter(|v| !v.trim().is_empty()),
+            notify_telegram_bot_token: std::env::var("NOTIFY_TELEGRAM_BOT_TOKEN").ok().filter(|v| !v.trim().is_empty()),
+            notify_telegram_chat_id: std::env::var("NOTIFY_TELEGRAM_CHAT_ID").ok().filter(|v| !v.trim().is_empty()),
+            notify_min_interval_secs: std::env::var("NOTIFY_MIN_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+
+            signing_secret: std::env::var("SIGNING_SECRET").ok().filter(|v| !v.is_empty()),
+            admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|v| !v.is_empty()),
+            admin_localhost: std::env::var("ADMIN_LOCALHOST")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            require_signed_streams: std::env::var("REQUIRE_SIGNED_STREAMS")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            stream_token_ttl_secs: std::env::var("STREAM_TOKEN_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+            intercom_token: std::env::var("INTERCOM_TOKEN").ok().filter(|v| !v.is_empty()),
+
+            geoip_country_db: std::env::var("GEOIP_COUNTRY_DB").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+            geoip_asn_db: std::env::var("GEOIP_ASN_DB").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+            stats_geoip: std::env::var("STATS_GEOIP")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            stats_geoip_anonymize: std::env::var("STATS_GEOIP_ANONYMIZE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            stream_allow_countries: parse_list("STREAM_ALLOW_COUNTRIES", |v| Some(v.to_ascii_uppercase())),
+            stream_deny_countries: parse_list("STREAM_DENY_COUNTRIES", |v| Some(v.to_ascii_uppercase())),
+            stream_allow_asns: parse_list("STREAM_ALLOW_ASNS", parse_asn),
+            stream_deny_asns: parse_list("STREAM_DENY_ASNS", parse_asn),
+            stream_block_message: std::env::var("STREAM_BLOCK_MESSAGE").ok().filter(|v| !v.is_empty()),
+            trust_forwarded_for: std::env::var("TRUST_X_FORWARDED_FOR")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            trusted_proxy_hops: std::env::var("TRUSTED_PROXY_HOPS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&hops| hops > 0)
+                .unwrap_or(1),
+
+            public_url: std::env::var("PUBLIC_URL").ok()
+                .map(|v| v.trim_end_matches('/').to_string())
+                .filter(|v| !v.is_empty()),
+            public_ip: std::env::var("PUBLIC_IP").ok().and_then(|v| v.parse().ok()),
+            public_ip_stun_servers: std::env::var("PUBLIC_IP_STUN_SERVERS")
+                .map(|v| v.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect())
+                .unwrap_or_else(|_| DEFAULT_STUN_SERVERS.iter().map(|s| s.to_string()).collect()),
+            public_ip_refresh_secs: std::env::var("PUBLIC_IP_REFRESH_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+
+            log_format: std::env::var("LOG_FORMAT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(LogFormat::Pretty),
+            log_file: std::env::var("LOG_FILE").ok().filter(|v| !v.is_empty()).map(PathBuf::from),
+            log_rotation: std::env::var("LOG_ROTATION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(LogRotation::Daily),
+        }
+    }
+
+    pub fn burst(&self, profile: ClientProfile) -> &BurstConfig {
+        match profile {
+            ClientProfile::Default => &self.burst_default,
+            ClientProfile::Ios => &self.burst_ios,
+            ClientProfile::Embedded => &self.burst_embedded,
+            ClientProfile::LowLatency => &self.burst_low_latency,
+        }
+    }
+
+    /// The profile whose `?type=` values list `client_type`, else the first whose
+    /// user-agent substrings occur in `user_agent`, else the default profile
+    pub fn profile_for(&self, client_type: Option<&str>, user_agent: &str) -> ClientProfile {
+        let by_type = client_type.and_then(|client_type| ClientProfile::ALL.into_iter()
+            .find(|&profile| self.burst(profile).types.iter().any(|t| t.eq_ignore_ascii_case(client_type))));
+        by_type
+            .or_else(|| ClientProfile::ALL.into_iter()
+                .find(|&profile| self.burst(profile).user_agents.iter().any(|ua| user_agent.contains(ua.as_str()))))
+            .unwrap_or(ClientProfile::Default)
+    }
+}
+
+/// Listener client classes that get their own burst-on-connect settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientProfile {
+    Default,
+    Ios,
+    Embedded,
+    LowLatency,
+}
+
+impl ClientProfile {
+    /// In matching order
+    pub const ALL: [ClientProfile; 4] = [Self::LowLatency, Self::Embedded, Self::Ios, Self::Default];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Ios => "ios",
+            Self::Embedded => "embedded",
+            Self::LowLatency => "low_latency",
+        }
+    }
+}
+
+/// What playout does while no listeners are connected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleMode {
+    Broadcast, // Keep decoding and broadcasting into the void
+    Pause,     // Stop playout; resume where the schedule would be now, as if it had kept playing
+}
+
+impl IdleMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Broadcast => "broadcast",
+            Self::Pause => "pause",
+        }
+    }
+}
+
+impl std::str::FromStr for IdleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "broadcast" | "continue" => Ok(Self::Broadcast),
+            "pause" => Ok(Self::Pause),
+            other => Err(format!("Unknown idle mode '{}'", other)),
+        }
+    }
+}
+
+/// When the archiver starts a new recording file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveRotation {
+    Hourly,   // At the top of every hour (UTC)
+    PerTrack, // Whenever the playing track changes
+}
+
+impl std::str::FromStr for ArchiveRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hourly" | "hour" => Ok(Self::Hourly),
+            "track" | "per_track" | "per-track" => Ok(Self::PerTrack),
+            other => Err(format!("Unknown archive rotation '{}'", other)),
+        }
+    }
+}
+
+/// How log lines are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Pretty, // Human-readable lines for terminals and journalctl
+    Json,   // One JSON object per line, with the request span's fields, for Loki/ELK
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pretty" | "text" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            other => Err(format!("Unknown log format '{}' (expected pretty or json)", other)),
+        }
+    }
+}
+
+/// When the log file is rolled over; rolled files get a date (and hour) suffix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl std::str::FromStr for LogRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hourly" | "hour" => Ok(Self::Hourly),
+            "daily" | "day" => Ok(Self::Daily),
+            "never" | "off" => Ok(Self::Never),
+            other => Err(format!("Unknown log rotation '{}'", other)),
+        }
+    }
+}
+
+/// How a configured relay stream is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayMode {
+    Primary,  // Rebroadcast the upstream; the playlist only fills in while it is down
+    Fallback, // Rebroadcast the upstream only while the playlist is empty
+}
+
+impl std::str::FromStr for RelayMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "primary" => Ok(Self::Primary),
+            "fallback" => Ok(Self::Fallback),
+            other => Err(format!("Unknown relay mode '{}'", other)),
+        }
+    }
+}
+
+/// A simulcast quality served at `/stream-<name>`, written `name:kbps` in SIMULCAST_MOUNTS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulcastMount {
+    pub name: String,
+    pub bitrate_kbps: u32,
+}
+
+impl std::str::FromStr for SimulcastMount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, kbps) = s.split_once(':').ok_or_else(|| format!("Expected name:kbps, got '{}'", s))?;
+        let name = name.trim().to_ascii_lowercase();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Invalid mount name '{}'", name));
+        }
+        let bitrate_kbps = kbps.trim().parse().map_err(|_| format!("Invalid bitrate '{}'", kbps))?;
+        Ok(Self { name, bitrate_kbps })
+    }
+}
+
+/// What happens to live data that queued up while a paced burst was being sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUp {
+    Queue,      // Deliver it all; the listener stays that far behind live
+    SkipToLive, // Drop it and continue from the live edge
+}
+
+impl std::str::FromStr for CatchUp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "queue" => Ok(Self::Queue),
+            "skip" | "skip_to_live" | "live" => Ok(Self::SkipToLive),
+            other => Err(format!("Unknown catch-up strategy '{}'", other)),
         }
     }
 }
 
+/// What a listener's stream does after falling so far behind that the broadcast
+/// channel dropped chunks it hadn't received yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    DropToLive, // Discard the backlog and carry on from the live edge
+    Disconnect, // End the stream; the client reconnects and rebuffers
+    Reburst,    // Rejoin live with a fresh burst from the recent broadcast
+}
+
+impl LagPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DropToLive => "drop_to_live",
+            Self::Disconnect => "disconnect",
+            Self::Reburst => "reburst",
+        }
+    }
+}
+
+impl std::str::FromStr for LagPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "drop" | "drop_to_live" | "live" => Ok(Self::DropToLive),
+            "disconnect" => Ok(Self::Disconnect),
+            "reburst" | "burst" => Ok(Self::Reburst),
+            other => Err(format!("Unknown lag policy '{}' (expected drop_to_live, disconnect or reburst)", other)),
+        }
+    }
+}
+
+/// Audio format of a listener's stream, negotiated on /stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+    Mp3,
+    Opus, // In Ogg
+    Aac,  // ADTS
+}
+
+impl Codec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            Self::Opus => "opus",
+            Self::Aac => "aac",
+        }
+    }
+}
+
+impl std::str::FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mp3" | "mpeg" => Ok(Self::Mp3),
+            "opus" | "ogg" => Ok(Self::Opus),
+            "aac" => Ok(Self::Aac),
+            other => Err(format!("Unknown codec '{}' (expected mp3, opus or aac)", other)),
+        }
+    }
+}
+
+/// How a listener's stream is clocked once the initial burst has been sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamClock {
+    Realtime,       // Paced to playback speed so every such listener stays a fixed distance from live (sync-sensitive clients)
+    BufferBuilding, // Delivered as fast as the broadcast runs (stream_rate_multiplier) to grow the client buffer
+}
+
+impl StreamClock {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Realtime => "realtime",
+            Self::BufferBuilding => "buffer_building",
+        }
+    }
+}
+
+impl std::str::FromStr for StreamClock {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "realtime" | "sync" => Ok(Self::Realtime),
+            "buffer" | "buffer_building" | "buffered" => Ok(Self::BufferBuilding),
+            other => Err(format!("Unknown stream clock '{}' (expected realtime or buffer)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurstConfig {
+    pub burst_kb: usize,    // Data collected and sent to a new listener before live streaming
+    pub minimum_kb: usize,  // Start anyway after the timeout once this much is collected
+    pub timeout_ms: u64,
+    pub pacing_kbps: u64,   // Burst send rate in KB/s (0 = all at once)
+    pub catch_up: CatchUp,
+    pub clock: StreamClock, // Post-burst delivery clock
+    pub egress_cap: f64,    // Post-burst delivery limit as a multiple of realtime (0 = unshaped)
+    pub lag: LagPolicy,     // What happens when the listener falls out of the broadcast channel
+    pub size_kb: Option<usize>, // Exact burst from recent history, sent without waiting for live audio
+    pub types: Vec<String>,       // `?type=` values that select this profile
+    pub user_agents: Vec<String>, // User-agent substrings that select it when `?type=` doesn't
+}
+
+impl BurstConfig {
+    /// Override `defaults` from BURST_<PROFILE>_{KB,MIN_KB,TIMEOUT_MS,PACING_KBPS,CATCH_UP,
+    /// EGRESS_CAP,LAG_POLICY,SIZE_KB,TYPES,USER_AGENTS} and STREAM_CLOCK_<PROFILE>
+    fn from_env(profile: ClientProfile, defaults: BurstConfig) -> Self {
+        let name = profile.name().to_ascii_uppercase();
+        let prefix = format!("BURST_{}", name);
+        let var = |suffix: &str| std::env::var(format!("{}_{}", prefix, suffix)).ok();
+
+        Self {
+            burst_kb: var("KB").and_then(|v| v.parse().ok()).unwrap_or(defaults.burst_kb),
+            minimum_kb: var("MIN_KB").and_then(|v| v.parse().ok()).unwrap_or(defaults.minimum_kb),
+            timeout_ms: var("TIMEOUT_MS").and_then(|v| v.parse().ok()).unwrap_or(defaults.timeout_ms),
+            pacing_kbps: var("PACING_KBPS").and_then(|v| v.parse().ok()).unwrap_or(defaults.pacing_kbps),
+            catch_up: var("CATCH_UP").and_then(|v| v.parse().ok()).unwrap_or(defaults.catch_up),
+            clock: std::env::var(format!("STREAM_CLOCK_{}", name)).ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.clock),
+            egress_cap: var("EGRESS_CAP").and_then(|v| parse_egress_cap(&v)).unwrap_or(defaults.egress_cap),
+            lag: var("LAG_POLICY").and_then(|v| v.parse().ok()).unwrap_or(defaults.lag),
+            size_kb: var("SIZE_KB").and_then(|v| v.parse().ok()).or(defaults.size_kb),
+            // Set but empty clears the defaults
+            types: var("TYPES").map(|v| split_list(&v)).unwrap_or(defaults.types),
+            user_agents: var("USER_AGENTS").map(|v| split_list(&v)).unwrap_or(defaults.user_agents),
+        }
+    }
+}
+
+// Comma-separated list, skipping blanks and entries that fail to parse
+fn parse_list<T>(name: &str, parse: impl Fn(&str) -> Option<T>) -> Vec<T> {
+    std::env::var(name)
+        .map(|v| v.split(',').map(str::trim).filter(|item| !item.is_empty()).filter_map(&parse).collect())
+        .unwrap_or_default()
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+}
+
+// 0 (unshaped) or at least realtime; a lower cap would starve the listener
+fn parse_egress_cap(value: &str) -> Option<f64> {
+    value.parse().ok().filter(|&cap: &f64| cap == 0.0 || (cap.is_finite() && cap >= 1.0))
+}
+
+// Accepts "64512" or "AS64512"
+fn parse_asn(value: &str) -> Option<u32> {
+    let digits = value.strip_prefix("AS").or_else(|| value.strip_prefix("as")).unwrap_or(value);
+    digits.parse().ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,25 +959,152 @@ mod tests {
         // Clear any environment variables that might affect the test
         env::remove_var("HOST");
         env::remove_var("PORT");
+        env::remove_var("ACME_DOMAINS");
+        env::remove_var("ACME_DIRECTORY");
+        env::remove_var("ACME_HTTP_PORT");
+        env::remove_var("ACME_RENEW_DAYS");
         env::remove_var("MUSIC_DIR");
+        env::remove_var("WATCH_MUSIC_DIR");
+        env::remove_var("PREFLIGHT_STRICT");
+        env::remove_var("PREFLIGHT_MIN_FREE_MB");
+        env::remove_var("READY_MAX_CHUNK_AGE_MS");
+        env::remove_var("STATION_NAME");
+        env::remove_var("STATION_GENRE");
+        env::remove_var("STATION_URL");
+        env::remove_var("STATION_DESCRIPTION");
+        env::remove_var("STATION_PUBLIC");
+        env::remove_var("INTERCOM_TOKEN");
+        env::remove_var("ADMIN_LOCALHOST");
+        env::remove_var("TRUST_X_FORWARDED_FOR");
+        env::remove_var("TRUSTED_PROXY_HOPS");
+        env::remove_var("SIMULCAST_MOUNTS");
+        env::remove_var("STREAM_CODECS");
+        env::remove_var("CODEC_BITRATE_KBPS");
+        env::remove_var("TRANSCODE_BITRATE_KBPS");
+        env::remove_var("NORMALIZE");
+        env::remove_var("TRIM_SILENCE");
+        env::remove_var("SILENCE_THRESHOLD_DB");
+        env::remove_var("EGRESS_CAP");
+        env::remove_var("LAG_POLICY");
+        env::remove_var("CHURN_MAX_PER_MIN");
+        env::remove_var("CHURN_SHORT_SECS");
+        env::remove_var("CHURN_TARPIT_MS");
+        env::remove_var("CHURN_BAN_SECS");
+        env::remove_var("PLAY_HISTORY_PATH");
+        env::remove_var("AUDIENCE_LOG_PATH");
+        env::remove_var("STATS_GEOIP");
+        env::remove_var("STATS_GEOIP_ANONYMIZE");
+        env::remove_var("ARCHIVE_DIR");
+        env::remove_var("ARCHIVE_RECORD");
+        env::remove_var("ARCHIVE_ROTATION");
+        env::remove_var("ARCHIVE_RETENTION_DAYS");
         env::remove_var("INITIAL_BUFFER_KB");
         env::remove_var("MINIMUM_BUFFER_KB");
         env::remove_var("CHUNK_INTERVAL_MS");
         env::remove_var("STREAM_RATE_MULTIPLIER");
+        env::remove_var("ADAPTIVE_STREAM_RATE");
         env::remove_var("INITIAL_BUFFER_TIMEOUT_MS");
+        env::remove_var("BURST_HISTORY_SECS");
+        env::remove_var("PREFETCH_SECS");
         env::remove_var("BROADCAST_CHANNEL_CAPACITY");
+        env::remove_var("HOLD_AUDIO_FILE");
+        env::remove_var("HOLD_RETRY_SECS");
+        env::remove_var("ALERT_WEBHOOK_URL");
+        env::remove_var("RELAY_URL");
+        env::remove_var("RELAY_MODE");
+        env::remove_var("RELAY_RETRY_SECS");
+        env::remove_var("MAX_LISTENERS");
+        env::remove_var("MAX_STREAMS_PER_IP");
+        env::remove_var("SKIP_VOTE_FRACTION");
+        env::remove_var("ARTIST_SEPARATION");
+        env::remove_var("TRACK_SEPARATION_HOURS");
+        env::remove_var("ROTATION_EXCLUDE");
+        env::remove_var("IDLE_MODE");
+        env::remove_var("API_RATE_LIMIT");
+        env::remove_var("TIMESHIFT_MINUTES");
+        env::remove_var("RESUME_WINDOW_SECS");
+        env::remove_var("METRICS_SAMPLE_SECS");
+        env::remove_var("METRICS_HISTORY_MINUTES");
+        env::remove_var("PUBLIC_IP");
+        env::remove_var("PUBLIC_IP_STUN_SERVERS");
+        env::remove_var("LOG_FORMAT");
+        env::remove_var("LOG_FILE");
+        env::remove_var("LOG_ROTATION");
 
         let config = Config::from_env();
 
         assert_eq!(config.host, "0.0.0.0");
         assert_eq!(config.port, 8000);
+        assert!(config.acme_domains.is_empty());
+        assert_eq!(config.acme_directory, "https://acme-v02.api.letsencrypt.org/directory");
+        assert_eq!(config.acme_http_port, 80);
+        assert_eq!(config.acme_renew_days, 60);
         assert_eq!(config.music_dir, PathBuf::from("music"));
+        assert!(config.watch_music_dir);
+        assert!(!config.preflight_strict);
+        assert_eq!(config.preflight_min_free_mb, 100);
+        assert_eq!(config.ready_max_chunk_age_ms, 5000);
+        assert_eq!(config.station_name, "WebRadio");
+        assert_eq!(config.station_genre, "Various");
+        assert!(config.station_url.is_none());
+        assert!(config.station_description.is_none());
+        assert!(!config.station_public);
+        assert_eq!(config.intercom_token, None);
+        assert!(!config.admin_localhost);
+        assert!(!config.trust_forwarded_for);
+        assert_eq!(config.trusted_proxy_hops, 1);
+        assert!(config.simulcast_mounts.is_empty());
+        assert!(config.stream_codecs.is_empty());
+        assert_eq!(config.codec_bitrate_kbps, 96);
+        assert_eq!(config.transcode_bitrate_kbps, 192);
+        assert!(config.normalize);
+        assert!(config.trim_silence);
+        assert_eq!(config.silence_threshold_db, -50.0);
+        assert_eq!(config.burst(ClientProfile::Default).egress_cap, 0.0);
+        assert_eq!(config.play_history_path, PathBuf::from("music/play_history.jsonl"));
+        assert_eq!(config.audience_log_path, PathBuf::from("music/audience.jsonl"));
+        assert!(!config.stats_geoip);
+        assert!(!config.stats_geoip_anonymize);
+        assert_eq!(config.archive_dir, PathBuf::from("archive"));
+        assert!(!config.archive_record);
+        assert_eq!(config.archive_rotation, ArchiveRotation::Hourly);
+        assert_eq!(config.archive_retention_days, 30);
         assert_eq!(config.initial_buffer_kb, 120);
         assert_eq!(config.minimum_buffer_kb, 80);
         assert_eq!(config.chunk_interval_ms, 100);
         assert_eq!(config.stream_rate_multiplier, 1.10);
+        assert!(!config.adaptive_stream_rate);
         assert_eq!(config.initial_buffer_timeout_ms, 6000);
         assert_eq!(config.broadcast_channel_capacity, 32768);
+        assert_eq!(config.hold_audio_file, None);
+        assert_eq!(config.hold_retry_secs, 10);
+        assert_eq!(config.alert_webhook_url, None);
+        assert_eq!(config.relay_url, None);
+        assert_eq!(config.relay_mode, RelayMode::Fallback);
+        assert_eq!(config.relay_retry_secs, 5);
+        assert_eq!(config.max_listeners, 0);
+        assert_eq!(config.max_streams_per_ip, 0);
+        assert_eq!(config.skip_vote_fraction, 0.5);
+        assert_eq!(config.artist_separation, 0);
+        assert_eq!(config.track_separation_hours, 0.0);
+        assert!(config.rotation_exclude.is_empty());
+        assert_eq!(config.idle_mode, IdleMode::Broadcast);
+        assert_eq!(config.api_requests_per_sec, 0.0);
+        assert_eq!(config.churn_max_per_min, 0);
+        assert_eq!(config.churn_short_secs, 10);
+        assert_eq!(config.churn_tarpit_ms, 3000);
+        assert_eq!(config.churn_ban_secs, 600);
+        assert_eq!(config.burst_history_secs, 30);
+        assert_eq!(config.prefetch_secs, 5);
+        assert_eq!(config.timeshift_minutes, 10);
+        assert_eq!(config.resume_window_secs, 30);
+        assert_eq!(config.metrics_sample_secs, 10);
+        assert_eq!(config.metrics_history_minutes, 60);
+        assert_eq!(config.public_ip, None);
+        assert_eq!(config.public_ip_stun_servers.len(), 2);
+        assert_eq!(config.log_format, LogFormat::Pretty);
+        assert_eq!(config.log_file, None);
+        assert_eq!(config.log_rotation, LogRotation::Daily);
     }
 
     #[test]
@@ -130,6 +1143,140 @@ mod tests {
         env::remove_var("BROADCAST_CHANNEL_CAPACITY");
     }
 
+    #[test]
+    fn test_config_burst_profiles() {
+        env::set_var("BURST_EMBEDDED_KB", "16");
+        env::set_var("BURST_EMBEDDED_PACING_KBPS", "24");
+        env::set_var("BURST_EMBEDDED_CATCH_UP", "queue");
+
+        let config = Config::from_env();
+        let embedded = config.burst(ClientProfile::Embedded);
+        assert_eq!(embedded.burst_kb, 16);
+        assert_eq!(embedded.pacing_kbps, 24);
+        assert_eq!(embedded.catch_up, CatchUp::Queue);
+        assert_eq!(embedded.minimum_kb, 16);
+
+        let default = config.burst(ClientProfile::Default);
+        assert_eq!(config.burst(ClientProfile::Ios).timeout_ms, 12000);
+        assert_eq!(default.pacing_kbps, 0);
+        assert_eq!(default.egress_cap, 0.0);
+        assert_eq!(default.lag, LagPolicy::DropToLive);
+
+        env::remove_var("BURST_EMBEDDED_KB");
+        env::remove_var("BURST_EMBEDDED_PACING_KBPS");
+        env::remove_var("BURST_EMBEDDED_CATCH_UP");
+    }
+
+    #[test]
+    fn test_config_profile_matching() {
+        env::set_var("BURST_EMBEDDED_USER_AGENTS", "ESP32, Sonos");
+        env::set_var("BURST_IOS_TYPES", "ios,safari");
+
+        let config = Config::from_env();
+        let iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X)";
+        assert_eq!(config.profile_for(None, iphone), ClientProfile::Ios);
+        assert_eq!(config.profile_for(Some("unknown"), iphone), ClientProfile::Ios);
+        assert_eq!(config.profile_for(Some("default"), iphone), ClientProfile::Default);
+        assert_eq!(config.profile_for(Some("Safari"), "curl/8.0"), ClientProfile::Ios);
+        assert_eq!(config.profile_for(Some("embedded"), iphone), ClientProfile::Embedded);
+        assert_eq!(config.profile_for(None, "Sonos/80.1"), ClientProfile::Embedded);
+        assert_eq!(config.profile_for(None, "Mozilla/5.0 (Windows NT 10.0)"), ClientProfile::Default);
+
+        env::remove_var("BURST_EMBEDDED_USER_AGENTS");
+        env::remove_var("BURST_IOS_TYPES");
+    }
+
+    #[test]
+    fn test_config_low_latency_profile() {
+        let config = Config::from_env();
+        assert_eq!(config.profile_for(Some("monitor"), "Mozilla/5.0 (iPhone)"), ClientProfile::LowLatency);
+        let low_latency = config.burst(ClientProfile::LowLatency);
+        assert!(low_latency.burst_kb < config.burst(ClientProfile::Embedded).burst_kb);
+        assert_eq!(low_latency.clock, StreamClock::Realtime);
+        assert_eq!(low_latency.catch_up, CatchUp::SkipToLive);
+    }
+
+    #[test]
+    fn test_config_egress_cap() {
+        env::set_var("BURST_EMBEDDED_EGRESS_CAP", "1.5");
+        env::set_var("BURST_IOS_EGRESS_CAP", "0.5"); // below realtime: ignored
+
+        let config = Config::from_env();
+        assert_eq!(config.burst(ClientProfile::Embedded).egress_cap, 1.5);
+        assert_eq!(config.burst(ClientProfile::Ios).egress_cap, 0.0);
+        assert_eq!(parse_egress_cap("4"), Some(4.0));
+        assert_eq!(parse_egress_cap("0"), Some(0.0));
+        assert_eq!(parse_egress_cap("inf"), None);
+
+        env::remove_var("BURST_EMBEDDED_EGRESS_CAP");
+        env::remove_var("BURST_IOS_EGRESS_CAP");
+    }
+
+    #[test]
+    fn test_config_lag_policy() {
+        env::set_var("BURST_IOS_LAG_POLICY", "reburst");
+        env::set_var("BURST_EMBEDDED_LAG_POLICY", "disconnect");
+
+        let config = Config::from_env();
+        assert_eq!(config.burst(ClientProfile::Ios).lag, LagPolicy::Reburst);
+        assert_eq!(config.burst(ClientProfile::Embedded).lag, LagPolicy::Disconnect);
+        assert_eq!("drop".parse::<LagPolicy>(), Ok(LagPolicy::DropToLive));
+        assert!("queue".parse::<LagPolicy>().is_err());
+
+        env::remove_var("BURST_IOS_LAG_POLICY");
+        env::remove_var("BURST_EMBEDDED_LAG_POLICY");
+    }
+
+    #[test]
+    fn test_config_stream_clock() {
+        env::set_var("STREAM_CLOCK_IOS", "realtime");
+
+        let config = Config::from_env();
+        assert_eq!(config.burst(ClientProfile::Ios).clock, StreamClock::Realtime);
+        assert_eq!(config.burst(ClientProfile::Embedded).clock, StreamClock::BufferBuilding);
+        assert_eq!("buffer".parse::<StreamClock>().unwrap(), StreamClock::BufferBuilding);
+        assert!("fast".parse::<StreamClock>().is_err());
+
+        env::remove_var("STREAM_CLOCK_IOS");
+    }
+
+    #[test]
+    fn test_config_log_options() {
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert_eq!("text".parse::<LogFormat>().unwrap(), LogFormat::Pretty);
+        assert!("xml".parse::<LogFormat>().is_err());
+        assert_eq!("hourly".parse::<LogRotation>().unwrap(), LogRotation::Hourly);
+        assert_eq!("never".parse::<LogRotation>().unwrap(), LogRotation::Never);
+        assert!("weekly".parse::<LogRotation>().is_err());
+    }
+
+    #[test]
+    fn test_config_access_rule_lists() {
+        env::set_var("STREAM_ALLOW_COUNTRIES", "gb, IE,,");
+        env::set_var("STREAM_DENY_ASNS", "AS64512,13335,bogus");
+
+        let config = Config::from_env();
+        assert_eq!(config.stream_allow_countries, vec!["GB", "IE"]);
+        assert_eq!(config.stream_deny_asns, vec![64512, 13335]);
+        assert!(config.stream_deny_countries.is_empty());
+
+        env::remove_var("STREAM_ALLOW_COUNTRIES");
+        env::remove_var("STREAM_DENY_ASNS");
+    }
+
+    #[test]
+    fn test_config_simulcast_mounts() {
+        env::set_var("SIMULCAST_MOUNTS", "Low:64, high:192, bad, a/b:96");
+
+        let config = Config::from_env();
+        assert_eq!(config.simulcast_mounts, vec![
+            SimulcastMount { name: "low".to_string(), bitrate_kbps: 64 },
+            SimulcastMount { name: "high".to_string(), bitrate_kbps: 192 },
+        ]);
+
+        env::remove_var("SIMULCAST_MOUNTS");
+    }
+
     #[test]
     fn test_config_invalid_port_uses_default() {
         env::set_var("PORT", "invalid");
@@ -0,0 +1,127 @@
+use std::time::Instant;
+
+/// Server-side estimate of how far a listener has drifted from the live edge.
+///
+/// The broadcast runs faster than realtime, so audio accumulates in the client's
+/// buffer (audio delivered minus wall-clock time played) and in the listener's
+/// broadcast queue. Once the total exceeds `target + max_drift` whole chunks are
+/// trimmed until the listener is back at `target`.
+#[derive(Debug)]
+pub struct DriftTracker {
+    started: Instant,
+    delivered_ms: f64,
+    target_ms: f64,
+    max_drift_ms: f64,
+    trimming: bool,
+}
+
+impl DriftTracker {
+    /// `target_ms` is the lead a listener is expected to have (its initial burst)
+    pub fn new(started: Instant, target_ms: f64, max_drift_ms: f64) -> Self {
+        Self {
+            started,
+            delivered_ms: 0.0,
+            target_ms,
+            max_drift_ms,
+            trimming: false,
+        }
+    }
+
+    pub fn delivered_ms(&self) -> f64 {
+        self.delivered_ms
+    }
+
+    pub fn record_delivered(&mut self, duration_ms: f64) {
+        self.delivered_ms += duration_ms;
+    }
+
+    /// Audio the client holds beyond what realtime playback has consumed
+    pub fn buffered_ms(&self, now: Instant) -> f64 {
+        self.delivered_ms - now.saturating_duration_since(self.started).as_secs_f64() * 1000.0
+    }
+
+    /// Lag behind the live edge in excess of the expected lead
+    pub fn drift_ms(&self, now: Instant, queued_ms: f64) -> f64 {
+        self.buffered_ms(now) + queued_ms - self.target_ms
+    }
+
+    /// Whether the next chunk should be dropped to pull the listener back towards live
+    pub fn should_trim(&mut self, now: Instant, queued_ms: f64) -> bool {
+        if self.max_drift_ms <= 0.0 {
+            return false;
+        }
+        let drift = self.drift_ms(now, queued_ms);
+        if drift > self.max_drift_ms {
+            self.trimming = true;
+        } else if drift <= 0.0 {
+            self.trimming = false;
+        }
+        self.trimming
+    }
+
+    /// Silence needed to keep at least `min_buffered_ms` in the client's buffer
+    pub fn silence_needed_ms(&self, now: Instant, min_buffered_ms: f64) -> f64 {
+        (min_buffered_ms - self.buffered_ms(now)).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_buffered_tracks_delivery_against_realtime() {
+        let start = Instant::now();
+        let mut tracker = DriftTracker::new(start, 5000.0, 30_000.0);
+        tracker.record_delivered(5000.0);
+
+        assert_eq!(tracker.buffered_ms(start), 5000.0);
+        assert_eq!(tracker.buffered_ms(start + Duration::from_secs(2)), 3000.0);
+        assert_eq!(tracker.drift_ms(start, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_trims_with_hysteresis() {
+        let start = Instant::now();
+        let mut tracker = DriftTracker::new(start, 5000.0, 30_000.0);
+        // An hour at 1.1x realtime leaves the listener six minutes ahead of playback
+        tracker.record_delivered(5000.0 + 3_960_000.0);
+        let now = start + Duration::from_secs(3600);
+
+        assert!(tracker.should_trim(now, 0.0));
+
+        // Still trimming while drift is positive, even below the threshold
+        tracker.delivered_ms = 5000.0 + 3_600_000.0 + 10_000.0;
+        assert!(tracker.should_trim(now, 0.0));
+
+        tracker.delivered_ms = 5000.0 + 3_600_000.0;
+        assert!(!tracker.should_trim(now, 0.0));
+        assert!(!tracker.should_trim(now, 20_000.0));
+    }
+
+    #[test]
+    fn test_queued_audio_counts_as_drift() {
+        let start = Instant::now();
+        let mut tracker = DriftTracker::new(start, 0.0, 10_000.0);
+        assert!(tracker.should_trim(start, 15_000.0));
+    }
+
+    #[test]
+    fn test_disabled_never_trims() {
+        let start = Instant::now();
+        let mut tracker = DriftTracker::new(start, 0.0, 0.0);
+        tracker.record_delivered(1_000_000.0);
+        assert!(!tracker.should_trim(start, 0.0));
+    }
+
+    #[test]
+    fn test_silence_needed() {
+        let start = Instant::now();
+        let mut tracker = DriftTracker::new(start, 5000.0, 30_000.0);
+        tracker.record_delivered(5000.0);
+
+        assert_eq!(tracker.silence_needed_ms(start, 1000.0), 0.0);
+        assert_eq!(tracker.silence_needed_ms(start + Duration::from_millis(4500), 1000.0), 500.0);
+    }
+}
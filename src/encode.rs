@@ -0,0 +1,190 @@
+// MP3 encoding with LAME for audio the server produces itself: the test tone,
+// simulcast mounts and library tracks in other formats (FLAC, Ogg Vorbis)
+
+use std::{io, num::NonZeroU32};
+use mp3lame_encoder::{Bitrate, Builder, Encoder, FlushNoGap, InterleavedPcm, Mode, Quality};
+use symphonia::core::{
+    audio::{AudioBufferRef, SampleBuffer},
+    codecs::{CodecParameters, Decoder, DecoderOptions},
+    errors::Error as SymphoniaError,
+    formats::Packet,
+};
+
+pub(crate) fn lame_bitrate(kbps: u32) -> Option<Bitrate> {
+    Some(match kbps {
+        32 => Bitrate::Kbps32,
+        48 => Bitrate::Kbps48,
+        64 => Bitrate::Kbps64,
+        80 => Bitrate::Kbps80,
+        96 => Bitrate::Kbps96,
+        112 => Bitrate::Kbps112,
+        128 => Bitrate::Kbps128,
+        160 => Bitrate::Kbps160,
+        192 => Bitrate::Kbps192,
+        224 => Bitrate::Kbps224,
+        256 => Bitrate::Kbps256,
+        320 => Bitrate::Kbps320,
+        _ => return None,
+    })
+}
+
+/// The MPEG-1 sample rate (32, 44.1 or 48 kHz) audio at `input_hz` is encoded at:
+/// the same rate where possible, otherwise the nearest family (88.2 kHz -> 44.1 kHz)
+pub fn mpeg1_sample_rate(input_hz: u32) -> u32 {
+    match input_hz {
+        32_000 | 44_100 | 48_000 => input_hz,
+        hz if 44_100 % hz == 0 || hz % 44_100 == 0 => 44_100,
+        hz if 32_000 % hz == 0 => 32_000,
+        _ => 48_000,
+    }
+}
+
+/// CBR stereo LAME encoder for PCM at `sample_rate`, without a Xing/LAME tag so every
+/// frame is audio. The output is MPEG-1 at `mpeg1_sample_rate`: left to itself LAME
+/// resamples low bitrates to MPEG-2 rates, which the broadcast doesn't carry.
+pub(crate) fn cbr_encoder(bitrate_kbps: u32, sample_rate: u32) -> io::Result<Encoder> {
+    let bitrate = lame_bitrate(bitrate_kbps)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("unsupported bitrate {}kbps", bitrate_kbps)))?;
+    Builder::new()
+        .ok_or_else(|| io::Error::other("failed to create LAME encoder"))?
+        .with_num_channels(2).map_err(lame_error)?
+        .with_sample_rate(sample_rate).map_err(lame_error)?
+        .with_output_sample_rate(NonZeroU32::new(mpeg1_sample_rate(sample_rate))).map_err(lame_error)?
+        .with_brate(bitrate).map_err(lame_error)?
+        .with_mode(Mode::JointStereo).map_err(lame_error)?
+        .with_quality(Quality::Good).map_err(lame_error)?
+        .with_to_write_vbr_tag(false).map_err(lame_error)?
+        .build().map_err(lame_error)
+}
+
+/// Append the MP3 for interleaved stereo `pcm` to `output`
+pub(crate) fn encode(encoder: &mut Encoder, pcm: &[i16], output: &mut Vec<u8>) -> io::Result<()> {
+    output.reserve(mp3lame_encoder::max_required_buffer_size(pcm.len() / 2));
+    encoder.encode_to_vec(InterleavedPcm(pcm), output).map_err(lame_error)?;
+    Ok(())
+}
+
+/// Append the frames the encoder still holds to `output`
+pub(crate) fn flush(encoder: &mut Encoder, output: &mut Vec<u8>) -> io::Result<()> {
+    output.reserve(7200); // Enough for the final frames
+    encoder.flush_to_vec::<FlushNoGap>(output).map_err(lame_error)?;
+    Ok(())
+}
+
+/// Decoded audio as interleaved 16-bit stereo: mono is duplicated and channels
+/// beyond the first two are dropped. Returns the samples and their rate.
+pub fn stereo_pcm(decoded: AudioBufferRef<'_>) -> (u32, Vec<i16>) {
+    let spec = *decoded.spec();
+    let channels = spec.channels.count();
+    let mut samples = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+    samples.copy_interleaved_ref(decoded);
+    let pcm = match channels {
+        1 => samples.samples().iter().flat_map(|&sample| [sample, sample]).collect(),
+        2 => samples.samples().to_vec(),
+        _ => samples.samples().chunks_exact(channels).flat_map(|frame| [frame[0], frame[1]]).collect(),
+    };
+    (spec.rate, pcm)
+}
+
+/// Re-encodes a track in another codec (FLAC, Vorbis) to MP3 packet by packet, so it can
+/// go out on the broadcast like the library's MP3s
+pub struct TrackTranscoder {
+    decoder: Box<dyn Decoder>,
+    encoder: Option<Encoder>, // Created on the first decoded packet, once the sample rate is known
+    bitrate_kbps: u32,
+    gain: f32, // Linear, applied to the decoded audio
+}
+
+impl TrackTranscoder {
+    pub fn new(params: &CodecParameters, bitrate_kbps: u32) -> io::Result<Self> {
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .map_err(|e| io::Error::other(format!("No decoder for track: {}", e)))?;
+        Ok(Self { decoder, encoder: None, bitrate_kbps, gain: 1.0 })
+    }
+
+    /// Scale the audio by `gain_db` before encoding it, clipping at full scale
+    pub fn with_gain_db(mut self, gain_db: f64) -> Self {
+        self.gain = 10f64.powf(gain_db / 20.0) as f32;
+        self
+    }
+
+    /// Decode `packet` and append the MP3 it encodes to. LAME buffers about a
+    /// frame, so the first packets may add nothing.
+    pub fn push(&mut self, packet: &Packet, output: &mut Vec<u8>) -> io::Result<()> {
+        let decoded = match self.decoder.decode(packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => return Ok(()), // Skip a corrupt packet
+            Err(e) => return Err(io::Error::other(format!("Decode failed: {}", e))),
+        };
+        let (sample_rate, mut pcm) = stereo_pcm(decoded);
+        if self.gain != 1.0 {
+            for sample in &mut pcm {
+                *sample = (*sample as f32 * self.gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            }
+        }
+        let encoder = match &mut self.encoder {
+            Some(encoder) => encoder,
+            None => self.encoder.insert(cbr_encoder(self.bitrate_kbps, sample_rate)?),
+        };
+        encode(encoder, &pcm, output)
+    }
+
+    /// Append the remaining frames at the end of the track
+    pub fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        match &mut self.encoder {
+            Some(encoder) => flush(encoder, output),
+            None => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn lame_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("LAME: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp3;
+    use symphonia::core::{formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint};
+
+    fn transcode(path: &str, bitrate_kbps: u32) -> Vec<u8> {
+        let file = std::fs::File::open(path).unwrap();
+        let source = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut format = symphonia::default::get_probe()
+            .format(&Hint::new(), source, &FormatOptions::default(), &MetadataOptions::default())
+            .unwrap()
+            .format;
+        let mut transcoder = TrackTranscoder::new(&format.default_track().unwrap().codec_params, bitrate_kbps).unwrap();
+
+        let mut mp3 = Vec::new();
+        while let Ok(packet) = format.next_packet() {
+            transcoder.push(&packet, &mut mp3).unwrap();
+        }
+        transcoder.finish(&mut mp3).unwrap();
+        mp3
+    }
+
+    #[test]
+    fn test_transcodes_flac_and_ogg_to_mp3() {
+        for path in ["tests/fixtures/tone-8k-mono.flac", "tests/fixtures/tone-8k-mono-flac.ogg"] {
+            // 8 kHz mono comes out as 32 kHz stereo MPEG-1
+            let mp3 = transcode(path, 128);
+            let frames = mp3::split_frames(&mp3);
+            assert!(frames.iter().all(|(header, _)| header.bitrate_kbps == 128 && header.sample_rate == 32_000), "{}", path);
+            let duration_ms: f64 = frames.iter().map(|(header, _)| header.duration_ms()).sum();
+            assert!((950.0..1200.0).contains(&duration_ms), "{}: {}", path, duration_ms);
+        }
+    }
+
+    #[test]
+    fn test_mpeg1_sample_rate() {
+        assert_eq!(mpeg1_sample_rate(44_100), 44_100);
+        assert_eq!(mpeg1_sample_rate(48_000), 48_000);
+        assert_eq!(mpeg1_sample_rate(88_200), 44_100);
+        assert_eq!(mpeg1_sample_rate(22_050), 44_100);
+        assert_eq!(mpeg1_sample_rate(96_000), 48_000);
+        assert_eq!(mpeg1_sample_rate(16_000), 32_000);
+    }
+}
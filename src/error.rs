@@ -1,8 +1,10 @@
+use std::path::PathBuf;
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
 };
 use thiserror::Error;
+use tracing::error;
 
 pub type Result<T> = std::result::Result<T, AppError>;
 
@@ -20,22 +22,128 @@ pub enum AppError {
     
     #[error("Not found")]
     NotFound,
+
+    #[error("Playlist is empty")]
+    PlaylistEmpty,
+
+    #[error("Track not found: {0}")]
+    TrackNotFound(String), // The path or id asked for
+
+    #[error("Cannot decode {}: {reason}", path.display())]
+    DecodeError { path: PathBuf, reason: String },
+
+    #[error("Stream closed: {0}")]
+    StreamClosed(String),
+
+    #[error("Bad request: {0}")]
+    BadRequest(String),
     
+    #[error("Forbidden")]
+    Forbidden,
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Not acceptable: {0}")]
+    NotAcceptable(String),
+
+    #[error("Unavailable for legal reasons: {0}")]
+    UnavailableForLegalReasons(String),
+
+    #[error("Rate limited: {message}")]
+    RateLimited { message: String, retry_after_secs: u64 },
+
+    #[error("Service unavailable: {message}")]
+    ServiceUnavailable { message: String, retry_after_secs: u64 },
+
+    #[error("Preflight failed: {0}")]
+    Preflight(String),
+
     #[error("Internal server error")]
     Internal,
 }
 
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Io(_) | AppError::Http(_) | AppError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Serialization(_) | AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound | AppError::PlaylistEmpty | AppError::TrackNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::DecodeError { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::StreamClosed(_) | AppError::ServiceUnavailable { .. } | AppError::Preflight(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            AppError::UnavailableForLegalReasons(_) => StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    /// Stable identifier for the kind of failure, for clients to branch on
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Io(_) => "io_error",
+            AppError::Serialization(_) => "invalid_data",
+            AppError::Http(_) => "http_error",
+            AppError::NotFound => "not_found",
+            AppError::PlaylistEmpty => "playlist_empty",
+            AppError::TrackNotFound(_) => "track_not_found",
+            AppError::DecodeError { .. } => "decode_error",
+            AppError::StreamClosed(_) => "stream_closed",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Forbidden => "forbidden",
+            AppError::Unauthorized => "unauthorized",
+            AppError::NotAcceptable(_) => "not_acceptable",
+            AppError::UnavailableForLegalReasons(_) => "unavailable_for_legal_reasons",
+            AppError::RateLimited { .. } => "rate_limited",
+            AppError::ServiceUnavailable { .. } => "service_unavailable",
+            AppError::Preflight(_) => "preflight_failed",
+            AppError::Internal => "internal",
+        }
+    }
+
+    // Server-side failures keep their details (paths, OS errors) in the log
+    fn message(&self) -> String {
+        match self {
+            AppError::Io(_) => "IO error".to_string(),
+            AppError::Http(_) => "HTTP error".to_string(),
+            AppError::Internal => "Internal error".to_string(),
+            AppError::Serialization(_) => "Invalid data".to_string(),
+            AppError::BadRequest(message)
+            | AppError::NotAcceptable(message)
+            | AppError::UnavailableForLegalReasons(message)
+            | AppError::RateLimited { message, .. }
+            | AppError::ServiceUnavailable { message, .. } => message.clone(),
+            other => other.to_string(),
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            AppError::NotFound => (StatusCode::NOT_FOUND, "Not found"),
-            AppError::Io(_) => (StatusCode::INTERNAL_SERVER_ERROR, "IO error"),
-            AppError::Serialization(_) => (StatusCode::BAD_REQUEST, "Invalid data"),
-            AppError::Http(_) => (StatusCode::INTERNAL_SERVER_ERROR, "HTTP error"),
-            AppError::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
-        };
+        let status = self.status();
+        if status.is_server_error() {
+            error!("{} ({})", self, status);
+        }
+        // {"error": {"code": "...", "message": "..."}}; codes are listed in the README
+        let mut body = serde_json::json!({
+            "error": {
+                "code": self.code(),
+                "message": self.message(),
+            }
+        });
 
-        (status, message).into_response()
+        let mut response = match &self {
+            AppError::RateLimited { retry_after_secs, .. } | AppError::ServiceUnavailable { retry_after_secs, .. } => {
+                body["error"]["retry_after"] = (*retry_after_secs).into();
+                (status, [(header::RETRY_AFTER, retry_after_secs.to_string())], axum::Json(body)).into_response()
+            }
+            _ => (status, axum::Json(body)).into_response(),
+        };
+        if let AppError::Unauthorized = self {
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+        }
+        response
     }
 }
 
@@ -89,6 +197,39 @@ mod tests {
         let response = error.into_response();
         assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
 
+        // Test BadRequest
+        let error = AppError::BadRequest("invalid month".to_string());
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Test Forbidden
+        let error = AppError::Forbidden;
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Test Unauthorized
+        let error = AppError::Unauthorized;
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+
+        // Test ServiceUnavailable
+        let error = AppError::ServiceUnavailable { message: "full".to_string(), retry_after_secs: 30 };
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+
+        // Test RateLimited
+        let error = AppError::RateLimited { message: "slow down".to_string(), retry_after_secs: 1 };
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+
+        // Test UnavailableForLegalReasons
+        let error = AppError::UnavailableForLegalReasons("not licensed here".to_string());
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS);
+
         // Test IO error
         let io_error = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "access denied");
         let error = AppError::from(io_error);
@@ -105,6 +246,45 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_typed_errors_have_json_bodies() {
+        async fn body(error: AppError) -> (StatusCode, serde_json::Value) {
+            let response = error.into_response();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&bytes).unwrap())
+        }
+
+        let (status, json) = body(AppError::PlaylistEmpty).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["error"]["code"], "playlist_empty");
+
+        let (status, json) = body(AppError::TrackNotFound("a.mp3".to_string())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["error"]["code"], "track_not_found");
+        assert_eq!(json["error"]["message"], "Track not found: a.mp3");
+
+        let error = AppError::DecodeError { path: PathBuf::from("b.flac"), reason: "bad header".to_string() };
+        let (status, json) = body(error).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json["error"]["code"], "decode_error");
+        assert_eq!(json["error"]["message"], "Cannot decode b.flac: bad header");
+
+        let (status, json) = body(AppError::StreamClosed("upstream closed the stream".to_string())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["error"]["code"], "stream_closed");
+
+        let (_, json) = body(AppError::RateLimited { message: "slow down".to_string(), retry_after_secs: 2 }).await;
+        assert_eq!(json["error"]["code"], "rate_limited");
+        assert_eq!(json["error"]["retry_after"], 2);
+
+        // Server-side details stay out of the response
+        let (status, json) = body(std::io::Error::other("/secret/path missing").into()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(json["error"]["code"], "io_error");
+        assert_eq!(json["error"]["message"], "IO error");
+    }
+
     #[test]
     fn test_result_type_alias() {
         // Test that Result<T> is properly aliased
@@ -150,7 +330,7 @@ mod tests {
     #[test]
     fn test_multiple_error_conversions() {
         // Test that automatic conversions work through the From trait
-        let io_error = std::io::Error::new(std::io::ErrorKind::Other, "test error");
+        let io_error = std::io::Error::other("test error");
         let _app_error: AppError = io_error.into();
 
         let json_err: std::result::Result<(), serde_json::Error> =
@@ -0,0 +1,96 @@
+// Entity tags for the JSON endpoints clients poll (/api/playlist, /api/now-playing):
+// a client revalidating with If-None-Match gets a bodyless 304 while nothing changed
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::error::{AppError, Result};
+
+/// Quoted tag for `data`: the first 64 bits of its SHA-256, in hex
+pub fn tag(data: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, data);
+    let hex: String = digest.as_ref()[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("\"{}\"", hex)
+}
+
+/// Whether an If-None-Match value lists `etag`. Uses the weak comparison
+/// If-None-Match calls for, so `W/"x"` and `"x"` match.
+pub fn matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let wanted = opaque(etag);
+    if_none_match.trim() == "*" || if_none_match.split(',').any(|tag| opaque(tag) == wanted)
+}
+
+/// `value` as JSON with a strong ETag over the body
+pub fn json<T: Serialize>(headers: &HeaderMap, value: &T) -> Result<Response> {
+    let body = serde_json::to_vec(value).map_err(|_| AppError::Internal)?;
+    let etag = tag(&body);
+    Ok(respond(headers, etag, || body))
+}
+
+/// `value` as JSON with a weak ETag over `key` only, for bodies that also carry
+/// fields (like clocks) that change on every request without changing what the
+/// response means. The body isn't serialized when the client already has it.
+pub fn weak_json<K: Serialize, T: Serialize>(headers: &HeaderMap, key: &K, value: &T) -> Result<Response> {
+    let etag = format!("W/{}", tag(&serde_json::to_vec(key).map_err(|_| AppError::Internal)?));
+    let body = serde_json::to_vec(value).map_err(|_| AppError::Internal)?;
+    Ok(respond(headers, etag, || body))
+}
+
+fn respond(headers: &HeaderMap, etag: String, body: impl FnOnce() -> Vec<u8>) -> Response {
+    let not_modified = headers.get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| matches(value, &etag));
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        ([(header::CONTENT_TYPE, "application/json")], body()).into_response()
+    };
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    // Cacheable, but only after checking back
+    response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_follow_content() {
+        assert_eq!(tag(b"abc"), tag(b"abc"));
+        assert_ne!(tag(b"abc"), tag(b"abd"));
+        assert_eq!(tag(b"abc").len(), 18);
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        assert!(matches("\"a\"", "\"a\""));
+        assert!(matches("\"b\", W/\"a\"", "\"a\""));
+        assert!(matches("\"a\"", "W/\"a\""));
+        assert!(matches("*", "\"a\""));
+        assert!(!matches("\"b\"", "\"a\""));
+        assert!(!matches("", "\"a\""));
+    }
+
+    #[test]
+    fn test_not_modified_has_no_body() {
+        let mut headers = HeaderMap::new();
+        let fresh = json(&headers, &serde_json::json!({ "a": 1 })).unwrap();
+        assert_eq!(fresh.status(), StatusCode::OK);
+        let etag = fresh.headers()[header::ETAG].clone();
+
+        headers.insert(header::IF_NONE_MATCH, etag.clone());
+        let cached = json(&headers, &serde_json::json!({ "a": 1 })).unwrap();
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(cached.headers()[header::ETAG], etag);
+
+        let changed = json(&headers, &serde_json::json!({ "a": 2 })).unwrap();
+        assert_eq!(changed.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,122 @@
+// Numbered SSE events for `/events`. Changes a client must not miss (track
+// changes, playlist edits, alerts) get monotonically increasing ids and are
+// kept for a while, so a client reconnecting with Last-Event-ID is replayed
+// what it missed before live events resume.
+
+use std::{collections::VecDeque, sync::Mutex};
+use axum::response::sse::Event;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events kept for replay; a client that missed more gets the most recent ones
+pub const EVENT_LOG_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct LoggedEvent {
+    pub id: u64,
+    pub name: &'static str,
+    pub data: serde_json::Value,
+}
+
+impl LoggedEvent {
+    pub fn to_sse(&self) -> Event {
+        Event::default()
+            .id(self.id.to_string())
+            .event(self.name)
+            .json_data(&self.data)
+            .unwrap()
+    }
+}
+
+pub struct EventLog {
+    capacity: usize,
+    recent: Mutex<VecDeque<LoggedEvent>>, // Oldest first
+    next_id: Mutex<u64>,
+    tx: broadcast::Sender<LoggedEvent>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self {
+            capacity,
+            recent: Mutex::new(VecDeque::with_capacity(capacity)),
+            next_id: Mutex::new(1),
+            tx,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LoggedEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Number the event, keep it for replay and send it to connected clients
+    pub fn publish(&self, name: &'static str, data: impl Serialize) -> u64 {
+        let data = serde_json::to_value(data).unwrap_or_default();
+        // Held while sending so subscribers see ids in order
+        let mut next_id = self.next_id.lock().unwrap();
+        let id = *next_id;
+        *next_id += 1;
+        let event = LoggedEvent { id, name, data };
+
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == self.capacity {
+            recent.pop_front();
+        }
+        recent.push_back(event.clone());
+        drop(recent);
+
+        let _ = self.tx.send(event); // No SSE clients is fine
+        id
+    }
+
+    /// The id of the last event published, 0 before the first
+    pub fn latest_id(&self) -> u64 {
+        *self.next_id.lock().unwrap() - 1
+    }
+
+    /// Events after `last_id`. An id from the future (a previous run of the
+    /// server) is stale, so everything kept is replayed.
+    pub fn since(&self, last_id: u64) -> Vec<LoggedEvent> {
+        let last_id = if last_id > self.latest_id() { 0 } else { last_id };
+        self.recent.lock().unwrap()
+            .iter()
+            .filter(|event| event.id > last_id)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_increase_and_replay_since() {
+        let log = EventLog::new(8);
+        let mut live = log.subscribe();
+        assert_eq!(log.publish("track-change", "a"), 1);
+        assert_eq!(log.publish("alert", "b"), 2);
+        assert_eq!(log.publish("track-change", "c"), 3);
+
+        let missed: Vec<_> = log.since(1).iter().map(|event| (event.id, event.name)).collect();
+        assert_eq!(missed, vec![(2, "alert"), (3, "track-change")]);
+        assert!(log.since(3).is_empty());
+        assert_eq!(log.latest_id(), 3);
+        assert_eq!(live.try_recv().unwrap().id, 1);
+    }
+
+    #[test]
+    fn test_replay_is_bounded_and_stale_ids_get_everything() {
+        let log = EventLog::new(2);
+        for n in 0..5 {
+            log.publish("track-change", n);
+        }
+        let ids = |events: Vec<LoggedEvent>| events.iter().map(|event| event.id).collect::<Vec<_>>();
+        assert_eq!(ids(log.since(0)), vec![4, 5]);
+        assert_eq!(ids(log.since(1)), vec![4, 5]);
+
+        // Last-Event-ID from before a restart
+        assert_eq!(ids(log.since(100)), vec![4, 5]);
+    }
+}
@@ -0,0 +1,342 @@
+// Chromaprint-style audio fingerprints, computed by `webradio analyze`, and the
+// duplicate search behind /api/admin/duplicates. A fingerprint is one 32-bit
+// word per ~124ms of the first two minutes, each bit comparing chroma (energy
+// per pitch class) within a frame or against the frame before. Those comparisons
+// survive re-encoding at another bitrate or format: copies of a recording under
+// different names agree on well over 90% of their bits, different recordings on
+// around two thirds (pitch classes both near silent compare equal).
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use base64::Engine;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::playlist::Track;
+
+const SAMPLE_RATE: f64 = 11025.0;
+const FRAME_LEN: usize = 4096;
+const HOP: usize = FRAME_LEN / 3;
+const MAX_SECONDS: f64 = 120.0;
+const MIN_FREQ: f64 = 28.0;
+const MAX_FREQ: f64 = 3520.0;
+const MARGIN: f64 = 0.02; // Chroma differences below this compare as equal, so noise doesn't flip bits
+
+/// Share of matching bits from which two fingerprints count as the same recording
+pub const DUPLICATE_SIMILARITY: f64 = 0.85;
+// Alignment searched when comparing, for copies with more or less leading silence
+const MAX_OFFSET: usize = 80; // ~10s
+const MIN_OVERLAP: usize = 40; // ~5s
+// Only tracks of about the same length are compared
+const MAX_DURATION_DIFF_SECS: u64 = 10;
+
+/// Builds a fingerprint from decoded audio pushed in any rate and channel layout
+pub struct Fingerprinter {
+    channels: usize,
+    step: f64,        // Output samples per input frame
+    position: f64,
+    sum: f64,
+    count: u32,
+    mono: Vec<f32>,   // Resampled to 11025Hz, up to MAX_SECONDS
+    window: Vec<f64>,
+    twiddles: Vec<(f64, f64)>, // FFT (sin, cos) for each k / FRAME_LEN turn
+}
+
+impl Fingerprinter {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            channels: channels.max(1),
+            step: SAMPLE_RATE / sample_rate.max(1) as f64,
+            position: 0.0,
+            sum: 0.0,
+            count: 0,
+            mono: Vec::new(),
+            window: (0..FRAME_LEN).map(|i| 0.54 - 0.46 * (2.0 * PI * i as f64 / (FRAME_LEN - 1) as f64).cos()).collect(),
+            twiddles: (0..FRAME_LEN / 2).map(|k| (-2.0 * PI * k as f64 / FRAME_LEN as f64).sin_cos()).collect(),
+        }
+    }
+
+    /// Add interleaved samples; anything past the first two minutes is ignored
+    pub fn push(&mut self, samples: &[f32]) {
+        let limit = (SAMPLE_RATE * MAX_SECONDS) as usize;
+        for frame in samples.chunks_exact(self.channels) {
+            if self.mono.len() >= limit {
+                return;
+            }
+            // Downmix, then average down to the target rate
+            self.sum += frame.iter().map(|&s| s as f64).sum::<f64>() / self.channels as f64;
+            self.count += 1;
+            self.position += self.step;
+            if self.position >= 1.0 {
+                self.position -= 1.0;
+                self.mono.push((self.sum / self.count as f64) as f32);
+                self.sum = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+
+    /// The fingerprint words; empty for less than a second of audio
+    pub fn finish(self) -> Vec<u32> {
+        let mut chroma = Vec::new();
+        let mut start = 0;
+        while start + FRAME_LEN <= self.mono.len() {
+            chroma.push(self.chroma(&self.mono[start..start + FRAME_LEN]));
+            start += HOP;
+        }
+        chroma.windows(2).map(|pair| sub_fingerprint(&pair[0], &pair[1])).collect()
+    }
+
+    // Energy per pitch class of one frame, scaled to unit length
+    fn chroma(&self, frame: &[f32]) -> [f64; 12] {
+        let mut re: Vec<f64> = frame.iter().zip(&self.window).map(|(&s, w)| s as f64 * w).collect();
+        let mut im = vec![0.0; FRAME_LEN];
+        fft(&mut re, &mut im, &self.twiddles);
+
+        let mut chroma = [0.0; 12];
+        let bin_hz = SAMPLE_RATE / FRAME_LEN as f64;
+        let first = (MIN_FREQ / bin_hz).ceil() as usize;
+        let last = (MAX_FREQ / bin_hz).floor() as usize;
+        for bin in first..=last {
+            let freq = bin as f64 * bin_hz;
+            let note = 12.0 * (freq / 440.0).log2() + 69.0; // MIDI note number
+            let class = (note.round() as i64).rem_euclid(12) as usize;
+            chroma[class] += re[bin] * re[bin] + im[bin] * im[bin];
+        }
+        let norm = chroma.iter().map(|e| e * e).sum::<f64>().sqrt();
+        if norm > 1e-9 {
+            chroma.iter_mut().for_each(|e| *e /= norm);
+        }
+        chroma
+    }
+}
+
+// 12 bits against the next pitch class, 12 against the previous frame, 8 against
+// the pitch class a minor third up
+fn sub_fingerprint(previous: &[f64; 12], current: &[f64; 12]) -> u32 {
+    let above = |a: f64, b: f64| (a > b + MARGIN) as u32;
+    let mut word = 0u32;
+    for i in 0..12 {
+        word |= above(current[i], current[(i + 1) % 12]) << i;
+        word |= above(current[i], previous[i]) << (12 + i);
+    }
+    for i in 0..8 {
+        word |= above(current[i], current[(i + 3) % 12]) << (24 + i);
+    }
+    word
+}
+
+// In-place radix-2 FFT; the length must be a power of two, with half as many twiddles
+fn fft(re: &mut [f64], im: &mut [f64], twiddles: &[(f64, f64)]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let stride = n / len;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = twiddles[k * stride];
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// As stored in playlist.json: base64 of the little-endian words
+pub fn encode(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+pub fn decode(encoded: &str) -> Option<Vec<u32>> {
+    let bytes = base64::engine::general_purpose::STANDARD.decode(encoded).ok()?;
+    Some(bytes.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+}
+
+/// Share of matching bits (1.0 for identical audio) at the best alignment of `a`
+/// against `b`
+pub fn similarity(a: &[u32], b: &[u32]) -> f64 {
+    let min_overlap = MIN_OVERLAP.min(a.len()).min(b.len()).max(1);
+    let mut best = 0.0;
+    for offset in -(MAX_OFFSET as isize)..=MAX_OFFSET as isize {
+        let (a, b) = if offset >= 0 {
+            (a.get(offset as usize..).unwrap_or_default(), b)
+        } else {
+            (a, b.get((-offset) as usize..).unwrap_or_default())
+        };
+        let overlap = a.len().min(b.len());
+        if overlap < min_overlap {
+            continue;
+        }
+        let differing: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+        let score = 1.0 - differing as f64 / (overlap * 32) as f64;
+        if score > best {
+            best = score;
+        }
+    }
+    best
+}
+
+/// Copies of one recording in the library
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub keep: Uuid,           // The copy with the highest bitrate
+    pub similarity: f64,      // Lowest match between the copies grouped together
+    pub tracks: Vec<Uuid>,    // Best copy first
+}
+
+/// Groups of fingerprinted tracks that are the same recording. Tracks without a
+/// fingerprint (not analyzed yet) are left out.
+pub fn find_duplicates(tracks: &[Track]) -> Vec<DuplicateGroup> {
+    let mut candidates: Vec<(&Track, Vec<u32>)> = tracks.iter()
+        .filter_map(|track| {
+            let analysis = track.analysis.as_ref()?;
+            Some((track, decode(&analysis.fingerprint).filter(|words| !words.is_empty())?))
+        })
+        .collect();
+    let duration = |track: &Track| track.analysis.as_ref().map_or(0, |a| a.duration_ms / 1000);
+    candidates.sort_by_key(|(track, _)| duration(track));
+
+    // Union-find over matching pairs, only comparing tracks of about the same length
+    let mut parent: Vec<usize> = (0..candidates.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut weakest: BTreeMap<usize, f64> = BTreeMap::new();
+    for i in 0..candidates.len() {
+        for j in i + 1..candidates.len() {
+            if duration(candidates[j].0) - duration(candidates[i].0) > MAX_DURATION_DIFF_SECS {
+                break;
+            }
+            let score = similarity(&candidates[i].1, &candidates[j].1);
+            if score < DUPLICATE_SIMILARITY {
+                continue;
+            }
+            let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+            let lowest = score.min(weakest.remove(&a).unwrap_or(1.0)).min(weakest.remove(&b).unwrap_or(1.0));
+            parent[b] = a;
+            weakest.insert(a, lowest);
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<&Track>> = BTreeMap::new();
+    for (i, (track, _)) in candidates.iter().enumerate() {
+        let group = root(&mut parent, i);
+        groups.entry(group).or_default().push(*track);
+    }
+    groups.into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(group, mut members)| {
+            // Highest bitrate first, then by path
+            members.sort_by(|a, b| b.bitrate.cmp(&a.bitrate).then_with(|| a.path.cmp(&b.path)));
+            DuplicateGroup {
+                keep: members[0].id,
+                similarity: weakest.get(&group).copied().unwrap_or(1.0),
+                tracks: members.iter().map(|track| track.id).collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TrackAnalysis;
+
+    // A few seconds per chord, each a mix of three sines
+    fn melody(rate: u32, chords: &[[f64; 3]], noise: f32) -> Vec<f32> {
+        let mut seed = 0x2545_f491_u32;
+        let mut samples = Vec::new();
+        for (c, chord) in chords.iter().enumerate() {
+            for i in 0..rate as usize * 3 {
+                let t = (c * rate as usize * 3 + i) as f64 / rate as f64;
+                let value: f64 = chord.iter().map(|f| (2.0 * PI * f * t).sin() * 0.2).sum();
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let dither = (seed as f32 / u32::MAX as f32 - 0.5) * noise;
+                samples.push(value as f32 + dither);
+            }
+        }
+        samples
+    }
+
+    fn fingerprint(rate: u32, samples: &[f32]) -> Vec<u32> {
+        let mut fingerprinter = Fingerprinter::new(rate, 1);
+        fingerprinter.push(samples);
+        fingerprinter.finish()
+    }
+
+    const SONG: [[f64; 3]; 8] = [
+        [261.6, 329.6, 392.0], [220.0, 261.6, 329.6], [174.6, 220.0, 261.6], [196.0, 246.9, 293.7],
+        [261.6, 329.6, 392.0], [293.7, 349.2, 440.0], [196.0, 246.9, 293.7], [261.6, 329.6, 392.0],
+    ];
+    const OTHER: [[f64; 3]; 8] = [
+        [277.2, 349.2, 415.3], [311.1, 370.0, 466.2], [233.1, 277.2, 349.2], [185.0, 233.1, 277.2],
+        [207.7, 261.6, 311.1], [277.2, 349.2, 415.3], [246.9, 311.1, 370.0], [185.0, 233.1, 277.2],
+    ];
+
+    #[test]
+    fn test_copies_match_and_other_audio_does_not() {
+        let original = fingerprint(44_100, &melody(44_100, &SONG, 0.0));
+        assert!(original.len() > 150);
+        assert_eq!(decode(&encode(&original)).unwrap(), original);
+
+        // Another sample rate with added noise, and the same starting 1.5s later
+        let copy = fingerprint(22_050, &melody(22_050, &SONG, 0.05));
+        let late = fingerprint(44_100, &[vec![0.0; 66_150], melody(44_100, &SONG, 0.0)].concat());
+        let other = fingerprint(44_100, &melody(44_100, &OTHER, 0.0));
+
+        assert!(similarity(&original, &copy) > DUPLICATE_SIMILARITY, "{}", similarity(&original, &copy));
+        assert!(similarity(&original, &late) > DUPLICATE_SIMILARITY, "{}", similarity(&original, &late));
+        assert!(similarity(&original, &other) < 0.8, "{}", similarity(&original, &other));
+    }
+
+    #[test]
+    fn test_find_duplicates_keeps_the_highest_bitrate() {
+        let track = |path: &str, bitrate: u64, fingerprint: &[u32]| Track {
+            id: Uuid::new_v4(),
+            path: path.into(),
+            bitrate: Some(bitrate),
+            analysis: Some(TrackAnalysis { duration_ms: 24_000, fingerprint: encode(fingerprint), ..Default::default() }),
+            ..Default::default()
+        };
+        let song = fingerprint(44_100, &melody(44_100, &SONG, 0.0));
+        let other = fingerprint(44_100, &melody(44_100, &OTHER, 0.0));
+        let tracks = vec![
+            track("song-128.mp3", 128_000, &song),
+            track("other.mp3", 192_000, &other),
+            track("song-320.mp3", 320_000, &song),
+            Track { analysis: None, ..track("unanalyzed.mp3", 320_000, &song) },
+        ];
+
+        let groups = find_duplicates(&tracks);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].keep, tracks[2].id);
+        assert_eq!(groups[0].tracks, vec![tracks[2].id, tracks[0].id]);
+        assert_eq!(groups[0].similarity, 1.0);
+    }
+}
@@ -0,0 +1,608 @@
+// GeoIP lookups against MaxMind DB (.mmdb) files, e.g. GeoLite2-Country and GeoLite2-ASN
+// Only the reader side of the format is implemented: search tree traversal plus the data
+// section decoder (https://maxmind.github.io/MaxMind-DB/)
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::{config::Config, types::GeoBreakdown};
+
+const METADATA_MARKER: &[u8] = b"\xAB\xCD\xEFMaxMind.com";
+const DATA_SECTION_SEPARATOR: usize = 16;
+// Anonymized breakdowns fold locations with fewer listeners than this into "other"
+const MIN_REPORTED_GROUP: usize = 3;
+
+/// What is known about a client address
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeoInfo {
+    pub country: Option<String>,     // ISO 3166-1 alpha-2, upper case
+    pub region: Option<String>,      // ISO 3166-2, e.g. "GB-ENG" (city databases only)
+    pub asn: Option<u32>,
+    pub as_org: Option<String>,
+}
+
+/// All configured GeoIP databases; lookups merge results across them
+#[derive(Default)]
+pub struct GeoIp {
+    readers: Vec<MmdbReader>,
+}
+
+impl GeoIp {
+    /// Load the country and ASN databases named in the config. Missing or unreadable
+    /// files are logged and skipped so the station still starts
+    pub fn from_config(config: &Config) -> Self {
+        let mut readers = Vec::new();
+        for path in [&config.geoip_country_db, &config.geoip_asn_db].into_iter().flatten() {
+            match MmdbReader::open(path) {
+                Ok(reader) => {
+                    info!("Loaded GeoIP database {} ({})", path.display(), reader.database_type);
+                    readers.push(reader);
+                }
+                Err(e) => warn!("Failed to load GeoIP database {}: {}", path.display(), e),
+            }
+        }
+        Self { readers }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.readers.is_empty()
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> GeoInfo {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+
+        let mut info = GeoInfo::default();
+        for reader in &self.readers {
+            let record = match reader.lookup(ip) {
+                Ok(Some(record)) => record,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("GeoIP lookup for {} failed: {}", ip, e);
+                    continue;
+                }
+            };
+
+            if info.country.is_none() {
+                info.country = ["country", "registered_country"].iter()
+                    .find_map(|key| record.get(key)?.get("iso_code")?.as_str())
+                    .map(|code| code.to_ascii_uppercase());
+            }
+            if info.region.is_none() {
+                info.region = match record.get("subdivisions") {
+                    Some(Value::Array(subdivisions)) => subdivisions.first()
+                        .and_then(|subdivision| subdivision.get("iso_code")?.as_str())
+                        .zip(info.country.as_deref())
+                        .map(|(code, country)| format!("{}-{}", country, code.to_ascii_uppercase())),
+                    _ => None,
+                };
+            }
+            if info.asn.is_none() {
+                info.asn = record.get("autonomous_system_number")
+                    .and_then(Value::as_u64)
+                    .and_then(|asn| u32::try_from(asn).ok());
+            }
+            if info.as_org.is_none() {
+                info.as_org = record.get("autonomous_system_organization")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+            }
+        }
+        info
+    }
+}
+
+/// Drop the host part of an address (/24 for IPv4, /48 for IPv6) so it no longer
+/// identifies a listener but still locates them
+pub fn anonymize(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => IpAddr::V4(Ipv4Addr::from(u32::from(v4) & 0xFFFF_FF00)),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => anonymize(IpAddr::V4(v4)),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(v6) & !((1u128 << 80) - 1))),
+        },
+    }
+}
+
+/// Count listeners by country and region. Anonymized breakdowns leave regions
+/// out and fold countries with fewer than MIN_REPORTED_GROUP listeners into "other".
+pub fn breakdown<'a>(locations: impl IntoIterator<Item = &'a GeoInfo>, anonymized: bool) -> GeoBreakdown {
+    let mut countries: BTreeMap<String, usize> = BTreeMap::new();
+    let mut regions: BTreeMap<String, usize> = BTreeMap::new();
+    for info in locations {
+        *countries.entry(info.country.clone().unwrap_or_else(|| "unknown".to_string())).or_default() += 1;
+        if let (Some(region), false) = (&info.region, anonymized) {
+            *regions.entry(region.clone()).or_default() += 1;
+        }
+    }
+    if anonymized {
+        let small: Vec<String> = countries.iter()
+            .filter(|(_, &count)| count < MIN_REPORTED_GROUP)
+            .map(|(country, _)| country.clone())
+            .collect();
+        for country in small {
+            let count = countries.remove(&country).unwrap_or(0);
+            *countries.entry("other".to_string()).or_default() += count;
+        }
+    }
+    GeoBreakdown { countries, regions }
+}
+
+/// Decoded MMDB data section value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Double(f64),
+    Bytes(Vec<u8>),
+    Uint(u128),
+    Int(i32),
+    Map(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Bool(bool),
+    Float(f32),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Uint(n) => u64::try_from(*n).ok(),
+            Value::Int(n) => u64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+}
+
+pub struct MmdbReader {
+    buf: Vec<u8>,
+    node_count: usize,
+    record_size: usize,
+    ip_version: u16,
+    database_type: String,
+    data_start: usize,
+    ipv4_start: usize,
+}
+
+impl MmdbReader {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let buf = std::fs::read(path)?;
+        Self::from_bytes(buf).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn from_bytes(buf: Vec<u8>) -> Result<Self, String> {
+        let marker = buf.windows(METADATA_MARKER.len())
+            .rposition(|w| w == METADATA_MARKER)
+            .ok_or("metadata marker not found")?;
+        let metadata_start = marker + METADATA_MARKER.len();
+        let (metadata, _) = Decoder { data: &buf[metadata_start..] }.decode(0)?;
+
+        let field = |key: &str| metadata.get(key).and_then(Value::as_u64).ok_or(format!("metadata missing '{}'", key));
+        let node_count = field("node_count")? as usize;
+        let record_size = field("record_size")? as usize;
+        let ip_version = field("ip_version")? as u16;
+        let database_type = metadata.get("database_type").and_then(Value::as_str).unwrap_or("unknown").to_string();
+
+        if ![24, 28, 32].contains(&record_size) {
+            return Err(format!("unsupported record size {}", record_size));
+        }
+        let tree_size = node_count * record_size * 2 / 8;
+        let data_start = tree_size + DATA_SECTION_SEPARATOR;
+        if data_start > marker {
+            return Err("search tree larger than file".to_string());
+        }
+
+        let mut reader = Self {
+            buf,
+            node_count,
+            record_size,
+            ip_version,
+            database_type,
+            data_start,
+            ipv4_start: 0,
+        };
+
+        // IPv4 addresses live under ::/96 in IPv6 trees
+        if ip_version == 6 {
+            let mut node = 0;
+            for _ in 0..96 {
+                if node >= node_count {
+                    break;
+                }
+                node = reader.read_record(node, 0)?;
+            }
+            reader.ipv4_start = node;
+        }
+
+        Ok(reader)
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Result<Option<Value>, String> {
+        let (bytes, mut node): (Vec<u8>, usize) = match ip {
+            IpAddr::V4(v4) => (v4.octets().to_vec(), self.ipv4_start),
+            IpAddr::V6(v6) if self.ip_version == 6 => (v6.octets().to_vec(), 0),
+            IpAddr::V6(_) => return Ok(None),
+        };
+
+        for bit_index in 0..bytes.len() * 8 {
+            if node >= self.node_count {
+                break;
+            }
+            let bit = (bytes[bit_index / 8] >> (7 - bit_index % 8)) & 1;
+            node = self.read_record(node, bit as usize)?;
+        }
+
+        if node == self.node_count {
+            return Ok(None);
+        }
+        if node < self.node_count {
+            return Err("search tree did not resolve".to_string());
+        }
+
+        let offset = (node - self.node_count).checked_sub(DATA_SECTION_SEPARATOR)
+            .ok_or("invalid data pointer in search tree")?;
+        let decoder = Decoder { data: &self.buf[self.data_start..] };
+        decoder.decode(offset).map(|(value, _)| Some(value))
+    }
+
+    fn read_record(&self, node: usize, side: usize) -> Result<usize, String> {
+        let node_bytes = self.record_size * 2 / 8;
+        let start = node * node_bytes;
+        let b = self.buf.get(start..start + node_bytes).ok_or("node out of bounds")?;
+        let be = |bytes: &[u8]| bytes.iter().fold(0usize, |acc, &x| (acc << 8) | x as usize);
+
+        Ok(match (self.record_size, side) {
+            (24, 0) => be(&b[0..3]),
+            (24, _) => be(&b[3..6]),
+            (28, 0) => ((b[3] as usize & 0xF0) << 20) | be(&b[0..3]),
+            (28, _) => ((b[3] as usize & 0x0F) << 24) | be(&b[4..7]),
+            (_, 0) => be(&b[0..4]),
+            (_, _) => be(&b[4..8]),
+        })
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl Decoder<'_> {
+    fn byte(&self, offset: usize) -> Result<u8, String> {
+        self.data.get(offset).copied().ok_or_else(|| "unexpected end of data".to_string())
+    }
+
+    fn slice(&self, offset: usize, len: usize) -> Result<&[u8], String> {
+        self.data.get(offset..offset + len).ok_or_else(|| "unexpected end of data".to_string())
+    }
+
+    /// Decode the value at `offset`, returning it and the offset just past it
+    fn decode(&self, offset: usize) -> Result<(Value, usize), String> {
+        let ctrl = self.byte(offset)?;
+        let mut offset = offset + 1;
+        let mut type_num = ctrl >> 5;
+
+        if type_num == 1 {
+            // Pointer: follow it, but continue after the pointer itself
+            let size = ((ctrl >> 3) & 0x3) as usize;
+            let vvv = (ctrl & 0x7) as usize;
+            let raw = self.slice(offset, size + 1)?.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            let target = match size {
+                0 => (vvv << 8) | raw,
+                1 => ((vvv << 16) | raw) + 2048,
+                2 => ((vvv << 24) | raw) + 526_336,
+                _ => raw,
+            };
+            let (value, _) = self.decode(target)?;
+            return Ok((value, offset + size + 1));
+        }
+
+        if type_num == 0 {
+            type_num = 7 + self.byte(offset)?;
+            offset += 1;
+        }
+
+        let mut size = (ctrl & 0x1F) as usize;
+        if size >= 29 {
+            let extra = size - 28;
+            let raw = self.slice(offset, extra)?.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            size = match extra {
+                1 => 29 + raw,
+                2 => 285 + raw,
+                _ => 65_821 + raw,
+            };
+            offset += extra;
+        }
+
+        let uint = |bytes: &[u8]| bytes.iter().fold(0u128, |acc, &b| (acc << 8) | b as u128);
+
+        match type_num {
+            2 => {
+                let s = String::from_utf8_lossy(self.slice(offset, size)?).into_owned();
+                Ok((Value::String(s), offset + size))
+            }
+            3 => {
+                let bytes: [u8; 8] = self.slice(offset, 8)?.try_into().map_err(|_| "bad double")?;
+                Ok((Value::Double(f64::from_be_bytes(bytes)), offset + 8))
+            }
+            4 => Ok((Value::Bytes(self.slice(offset, size)?.to_vec()), offset + size)),
+            5 | 6 | 9 | 10 => Ok((Value::Uint(uint(self.slice(offset, size)?)), offset + size)),
+            7 => {
+                let mut entries = Vec::with_capacity(size);
+                for _ in 0..size {
+                    let (key, next) = self.decode(offset)?;
+                    let (value, next) = self.decode(next)?;
+                    let key = match key {
+                        Value::String(key) => key,
+                        other => return Err(format!("map key is not a string: {:?}", other)),
+                    };
+                    entries.push((key, value));
+                    offset = next;
+                }
+                Ok((Value::Map(entries), offset))
+            }
+            8 => {
+                // Sign-extend from however many bytes were stored
+                let raw = uint(self.slice(offset, size)?) as u32;
+                let value = if size > 0 && size < 4 && raw & (1 << (size * 8 - 1)) != 0 {
+                    (raw | (u32::MAX << (size * 8))) as i32
+                } else {
+                    raw as i32
+                };
+                Ok((Value::Int(value), offset + size))
+            }
+            11 => {
+                let mut items = Vec::with_capacity(size);
+                for _ in 0..size {
+                    let (value, next) = self.decode(offset)?;
+                    items.push(value);
+                    offset = next;
+                }
+                Ok((Value::Array(items), offset))
+            }
+            14 => Ok((Value::Bool(size != 0), offset)),
+            15 => {
+                let bytes: [u8; 4] = self.slice(offset, 4)?.try_into().map_err(|_| "bad float")?;
+                Ok((Value::Float(f32::from_be_bytes(bytes)), offset + 4))
+            }
+            other => Err(format!("unsupported data type {}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_db {
+    //! Writer for tiny IPv4 databases used by tests
+
+    use std::net::Ipv4Addr;
+
+    const EMPTY: u32 = u32::MAX;
+
+    pub fn string(s: &str, out: &mut Vec<u8>) {
+        if s.len() < 29 {
+            out.push((2 << 5) | s.len() as u8);
+        } else {
+            assert!(s.len() < 285);
+            out.push((2 << 5) | 29);
+            out.push((s.len() - 29) as u8);
+        }
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn map_header(len: usize, out: &mut Vec<u8>) {
+        out.push((7 << 5) | len as u8);
+    }
+
+    // Arrays are an extended type: 11 - 7 in the byte after the control byte
+    pub fn array_header(len: usize, out: &mut Vec<u8>) {
+        out.push(len as u8);
+        out.push(4);
+    }
+
+    pub fn uint32(value: u32, out: &mut Vec<u8>) {
+        out.push((6 << 5) | 4);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn uint16(value: u16, out: &mut Vec<u8>) {
+        out.push((5 << 5) | 2);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn pointer(target: usize, out: &mut Vec<u8>) {
+        assert!(target < 2048);
+        out.push((1 << 5) | ((target >> 8) as u8 & 0x7));
+        out.push(target as u8);
+    }
+
+    /// Build an IPv4 database with 24-bit records. `data` is the raw data section and
+    /// each network maps to an offset within it
+    pub fn build(networks: &[(Ipv4Addr, u8, usize)], data: &[u8]) -> Vec<u8> {
+        let mut nodes: Vec<[u32; 2]> = vec![[EMPTY, EMPTY]];
+        let mut leaves: Vec<(usize, usize, usize)> = Vec::new(); // (node, side, data offset)
+
+        for &(network, prefix_len, offset) in networks {
+            let bits = u32::from(network);
+            let mut node = 0;
+            for i in 0..prefix_len as usize {
+                let side = ((bits >> (31 - i)) & 1) as usize;
+                if i + 1 == prefix_len as usize {
+                    leaves.push((node, side, offset));
+                } else {
+                    if nodes[node][side] == EMPTY {
+                        nodes.push([EMPTY, EMPTY]);
+                        nodes[node][side] = (nodes.len() - 1) as u32;
+                    }
+                    node = nodes[node][side] as usize;
+                }
+            }
+        }
+
+        let node_count = nodes.len() as u32;
+        for node in nodes.iter_mut() {
+            for record in node.iter_mut() {
+                if *record == EMPTY {
+                    *record = node_count;
+                }
+            }
+        }
+        for (node, side, offset) in leaves {
+            nodes[node][side] = node_count + 16 + offset as u32;
+        }
+
+        let mut out = Vec::new();
+        for [left, right] in nodes {
+            out.extend_from_slice(&left.to_be_bytes()[1..]);
+            out.extend_from_slice(&right.to_be_bytes()[1..]);
+        }
+        out.extend_from_slice(&[0u8; 16]);
+        out.extend_from_slice(data);
+        out.extend_from_slice(super::METADATA_MARKER);
+        map_header(4, &mut out);
+        string("node_count", &mut out);
+        uint32(node_count, &mut out);
+        string("record_size", &mut out);
+        uint16(24, &mut out);
+        string("ip_version", &mut out);
+        uint16(4, &mut out);
+        string("database_type", &mut out);
+        string("Test-Country", &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_db::*;
+    use std::net::Ipv4Addr;
+
+    fn country_db() -> MmdbReader {
+        let mut data = Vec::new();
+        // Shared "GB" string referenced through a pointer, as real databases do
+        string("GB", &mut data);
+        let gb_record = data.len();
+        map_header(1, &mut data);
+        string("country", &mut data);
+        map_header(1, &mut data);
+        string("iso_code", &mut data);
+        pointer(0, &mut data);
+
+        let asn_record = data.len();
+        map_header(2, &mut data);
+        string("autonomous_system_number", &mut data);
+        uint32(64_512, &mut data);
+        string("autonomous_system_organization", &mut data);
+        string("Example Net", &mut data);
+
+        let db = build(&[
+            (Ipv4Addr::new(81, 0, 0, 0), 8, gb_record),
+            (Ipv4Addr::new(203, 0, 113, 0), 24, asn_record),
+        ], &data);
+        MmdbReader::from_bytes(db).unwrap()
+    }
+
+    #[test]
+    fn test_lookup_follows_pointers() {
+        let reader = country_db();
+        let record = reader.lookup("81.2.69.160".parse().unwrap()).unwrap().unwrap();
+        assert_eq!(record.get("country").unwrap().get("iso_code").unwrap().as_str(), Some("GB"));
+    }
+
+    #[test]
+    fn test_lookup_miss() {
+        let reader = country_db();
+        assert!(reader.lookup("8.8.8.8".parse().unwrap()).unwrap().is_none());
+        assert!(reader.lookup("2001:db8::1".parse().unwrap()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_geoip_merges_country_and_asn() {
+        let geoip = GeoIp { readers: vec![country_db()] };
+
+        let info = geoip.lookup("81.2.69.160".parse().unwrap());
+        assert_eq!(info.country.as_deref(), Some("GB"));
+        assert_eq!(info.asn, None);
+
+        // IPv4-mapped IPv6 addresses from dual-stack sockets resolve like IPv4
+        let info = geoip.lookup("::ffff:203.0.113.9".parse().unwrap());
+        assert_eq!(info.asn, Some(64_512));
+        assert_eq!(info.as_org.as_deref(), Some("Example Net"));
+    }
+
+    #[test]
+    fn test_region_from_subdivisions() {
+        let mut data = Vec::new();
+        map_header(2, &mut data);
+        string("country", &mut data);
+        map_header(1, &mut data);
+        string("iso_code", &mut data);
+        string("GB", &mut data);
+        string("subdivisions", &mut data);
+        array_header(1, &mut data);
+        map_header(1, &mut data);
+        string("iso_code", &mut data);
+        string("sct", &mut data);
+        let reader = MmdbReader::from_bytes(build(&[(Ipv4Addr::new(81, 0, 0, 0), 8, 0)], &data)).unwrap();
+        let geoip = GeoIp { readers: vec![reader] };
+
+        let info = geoip.lookup("81.2.69.160".parse().unwrap());
+        assert_eq!(info.country.as_deref(), Some("GB"));
+        assert_eq!(info.region.as_deref(), Some("GB-SCT"));
+    }
+
+    #[test]
+    fn test_anonymize_truncates() {
+        assert_eq!(anonymize("81.2.69.160".parse().unwrap()), "81.2.69.0".parse::<IpAddr>().unwrap());
+        assert_eq!(anonymize("::ffff:81.2.69.160".parse().unwrap()), "81.2.69.0".parse::<IpAddr>().unwrap());
+        assert_eq!(anonymize("2001:db8:1:2::5".parse().unwrap()), "2001:db8:1::".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let located = |country: Option<&str>, region: Option<&str>| GeoInfo {
+            country: country.map(str::to_string),
+            region: region.map(str::to_string),
+            ..Default::default()
+        };
+        let listeners = vec![
+            located(Some("GB"), Some("GB-ENG")),
+            located(Some("GB"), Some("GB-SCT")),
+            located(Some("GB"), Some("GB-ENG")),
+            located(Some("DE"), None),
+            located(None, None),
+        ];
+
+        let full = breakdown(&listeners, false);
+        assert_eq!(full.countries["GB"], 3);
+        assert_eq!(full.countries["DE"], 1);
+        assert_eq!(full.countries["unknown"], 1);
+        assert_eq!(full.regions["GB-ENG"], 2);
+
+        let anonymized = breakdown(&listeners, true);
+        assert_eq!(anonymized.countries.len(), 2);
+        assert_eq!(anonymized.countries["GB"], 3);
+        assert_eq!(anonymized.countries["other"], 2);
+        assert!(anonymized.regions.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        assert!(MmdbReader::from_bytes(b"not a database".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+use tracing::warn;
+
+use crate::error::Result;
+
+/// A single completed play of a track, as written to the play history log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayRecord {
+    pub started_at: u64,        // Unix timestamp (seconds) when the track went on air
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    #[serde(default)]
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub composer: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    pub played_seconds: u64,
+    pub peak_listeners: usize,  // Highest concurrent listener count during the play
+    pub listener_seconds: u64,  // Sum of listeners × seconds while the track was on air
+}
+
+/// Append-only play history stored as JSON lines next to the playlist
+pub struct PlayHistory {
+    path: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl PlayHistory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn append(&self, record: &PlayRecord) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+
+        let _guard = self.write_lock.lock().await;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    /// Load all records whose start time falls within `[from, to)`
+    pub async fn load_range(&self, from: u64, to: u64) -> Result<Vec<PlayRecord>> {
+        let data = match fs::read_to_string(&self.path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut records = Vec::new();
+        for (line_no, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<PlayRecord>(line) {
+                Ok(record) if record.started_at >= from && record.started_at < to => records.push(record),
+                Ok(_) => {}
+                Err(e) => warn!("Skipping malformed play history line {}: {}", line_no + 1, e),
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(started_at: u64, title: &str) -> PlayRecord {
+        PlayRecord {
+            started_at,
+            path: PathBuf::from(format!("{}.mp3", title)),
+            title: title.to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            isrc: None,
+            composer: None,
+            label: None,
+            played_seconds: 180,
+            peak_listeners: 2,
+            listener_seconds: 360,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_append_and_load_range() {
+        let path = std::env::temp_dir().join(format!("webradio_history_{}.jsonl", uuid::Uuid::new_v4()));
+        let history = PlayHistory::new(&path);
+
+        history.append(&record(100, "Early")).await.unwrap();
+        history.append(&record(200, "Middle")).await.unwrap();
+        history.append(&record(300, "Late")).await.unwrap();
+
+        let records = history.load_range(150, 300).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].title, "Middle");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_missing_history_is_empty() {
+        let history = PlayHistory::new("/nonexistent/webradio/history.jsonl");
+        let records = history.load_range(0, u64::MAX).await.unwrap();
+        assert!(records.is_empty());
+    }
+}
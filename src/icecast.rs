@@ -0,0 +1,184 @@
+// Icecast's status documents (/status-json.xsl and /admin/stats), so dashboards,
+// stream directories and monitoring probes written for Icecast can read the station
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+// Stock icecast.xml values; tools that show them expect something there
+const ADMIN: &str = "icemaster@localhost";
+const LOCATION: &str = "Earth";
+
+/// The station as Icecast reports a server
+#[derive(Debug, Clone)]
+pub struct Status {
+    pub host: String,
+    pub server_id: String,
+    pub started: DateTime<Utc>,
+    pub sources: Vec<Source>,
+}
+
+/// One mount, which Icecast calls a source
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub mount: String,
+    pub listenurl: String,
+    pub server_name: String,
+    pub server_description: Option<String>,
+    pub server_url: String,
+    pub genre: String,
+    pub server_type: String, // Content type
+    pub bitrate_kbps: u64,
+    pub title: String,       // "Artist - Title", as in ICY metadata
+    pub listeners: usize,
+    pub listener_peak: usize,
+    pub max_listeners: Option<usize>,
+    pub public: bool,
+    pub total_bytes_sent: u64,
+}
+
+// Icecast writes dates both ways
+fn rfc822(time: &DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S %z").to_string()
+}
+
+fn iso8601(time: &DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%z").to_string()
+}
+
+impl Status {
+    /// `/status-json.xsl`. Like Icecast, `source` is an object for a single mount
+    /// and an array for several.
+    pub fn json(&self) -> serde_json::Value {
+        let sources: Vec<serde_json::Value> = self.sources.iter().map(|source| json!({
+            "audio_info": format!("bitrate={}", source.bitrate_kbps),
+            "bitrate": source.bitrate_kbps,
+            "genre": source.genre,
+            "listener_peak": source.listener_peak,
+            "listeners": source.listeners,
+            "listenurl": source.listenurl,
+            "server_description": source.server_description.clone().unwrap_or_default(),
+            "server_name": source.server_name,
+            "server_type": source.server_type,
+            "server_url": source.server_url,
+            "stream_start": rfc822(&self.started),
+            "stream_start_iso8601": iso8601(&self.started),
+            "title": source.title,
+            "dummy": null,
+        })).collect();
+        let source = match sources.len() {
+            1 => sources.into_iter().next().unwrap_or_default(),
+            _ => sources.into(),
+        };
+        json!({
+            "icestats": {
+                "admin": ADMIN,
+                "host": self.host,
+                "location": LOCATION,
+                "server_id": self.server_id,
+                "server_start": rfc822(&self.started),
+                "server_start_iso8601": iso8601(&self.started),
+                "source": source,
+            }
+        })
+    }
+
+    /// `/admin/stats`: the XML document Icecast's admin interface serves
+    pub fn xml(&self) -> String {
+        let element = |name: &str, value: &str| format!("<{name}>{}</{name}>", escape(value));
+        let listeners: usize = self.sources.iter().map(|source| source.listeners).sum();
+
+        let mut xml = String::from("<?xml version=\"1.0\"?>\n<icestats>");
+        xml += &element("admin", ADMIN);
+        xml += &element("host", &self.host);
+        xml += &element("listeners", &listeners.to_string());
+        xml += &element("location", LOCATION);
+        xml += &element("server_id", &self.server_id);
+        xml += &element("server_start", &rfc822(&self.started));
+        xml += &element("server_start_iso8601", &iso8601(&self.started));
+        xml += &element("sources", &self.sources.len().to_string());
+        for source in &self.sources {
+            xml += &format!("<source mount=\"{}\">", escape(&source.mount));
+            xml += &element("audio_info", &format!("bitrate={}", source.bitrate_kbps));
+            xml += &element("bitrate", &source.bitrate_kbps.to_string());
+            xml += &element("genre", &source.genre);
+            xml += &element("listener_peak", &source.listener_peak.to_string());
+            xml += &element("listeners", &source.listeners.to_string());
+            xml += &element("listenurl", &source.listenurl);
+            xml += &element("max_listeners", &source.max_listeners.map_or("unlimited".to_string(), |max| max.to_string()));
+            xml += &element("public", if source.public { "1" } else { "0" });
+            xml += &element("server_description", source.server_description.as_deref().unwrap_or_default());
+            xml += &element("server_name", &source.server_name);
+            xml += &element("server_type", &source.server_type);
+            xml += &element("server_url", &source.server_url);
+            xml += &element("stream_start", &rfc822(&self.started));
+            xml += &element("stream_start_iso8601", &iso8601(&self.started));
+            xml += &element("title", &source.title);
+            xml += &element("total_bytes_sent", &source.total_bytes_sent.to_string());
+            xml += "</source>";
+        }
+        xml += "</icestats>\n";
+        xml
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(mount: &str, listeners: usize) -> Source {
+        Source {
+            mount: mount.to_string(),
+            listenurl: format!("http://radio.example{}", mount),
+            server_name: "Test FM".to_string(),
+            server_description: None,
+            server_url: "http://radio.example".to_string(),
+            genre: "Jazz".to_string(),
+            server_type: "audio/mpeg".to_string(),
+            bitrate_kbps: 192,
+            title: "Artist - Song & Dance".to_string(),
+            listeners,
+            listener_peak: 5,
+            max_listeners: None,
+            public: false,
+            total_bytes_sent: 1024,
+        }
+    }
+
+    fn status(sources: Vec<Source>) -> Status {
+        Status {
+            host: "radio.example".to_string(),
+            server_id: "webradio 5.0.0".to_string(),
+            started: DateTime::from_timestamp(1_709_623_800, 0).unwrap(),
+            sources,
+        }
+    }
+
+    #[test]
+    fn test_json_source_is_an_object_for_one_mount() {
+        let json = status(vec![source("/stream", 3)]).json();
+        let icestats = &json["icestats"];
+        assert_eq!(icestats["server_start_iso8601"], "2024-03-05T07:30:00+0000");
+        assert_eq!(icestats["server_start"], "Tue, 05 Mar 2024 07:30:00 +0000");
+        assert_eq!(icestats["source"]["listeners"], 3);
+        assert_eq!(icestats["source"]["audio_info"], "bitrate=192");
+        assert_eq!(icestats["source"]["listenurl"], "http://radio.example/stream");
+
+        let json = status(vec![source("/stream", 3), source("/stream-low", 1)]).json();
+        assert_eq!(json["icestats"]["source"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_admin_stats_xml() {
+        let xml = status(vec![source("/stream", 3), source("/stream-low", 1)]).xml();
+        assert!(xml.starts_with("<?xml version=\"1.0\"?>\n<icestats><admin>"));
+        assert!(xml.contains("<listeners>4</listeners>"));
+        assert!(xml.contains("<sources>2</sources>"));
+        assert!(xml.contains("<source mount=\"/stream-low\">"));
+        assert!(xml.contains("<title>Artist - Song &amp; Dance</title>"));
+        assert!(xml.contains("<max_listeners>unlimited</max_listeners>"));
+    }
+}
@@ -0,0 +1,161 @@
+// ID3v2 text frame rewriting, for tag corrections made through the admin API.
+// Frames other than the ones written (pictures, comments, TXXX...) are kept as
+// they are; the audio after the tag is not touched.
+
+use std::io;
+
+/// Tag written to files that have none
+const DEFAULT_VERSION: u8 = 3;
+
+/// `mp3` (a whole file) with the text frames in `frames`, e.g. `("TIT2", "Title")`,
+/// set to the given values. An existing ID3v2.3 or 2.4 tag keeps its other frames;
+/// a file without a tag gets an ID3v2.3 one.
+pub fn set_text_frames(mp3: &[u8], frames: &[(&str, &str)]) -> io::Result<Vec<u8>> {
+    let Tag { version, frames: kept, audio } = parse(mp3)?.unwrap_or(Tag { version: DEFAULT_VERSION, frames: Vec::new(), audio: mp3 });
+
+    let mut body = Vec::new();
+    for (id, value) in frames {
+        write_frame(&mut body, version, id, &text(version, value));
+    }
+    for (id, flags, data) in kept.into_iter().filter(|(id, _, _)| !frames.iter().any(|(set, _)| set.as_bytes() == id)) {
+        body.extend_from_slice(&id);
+        body.extend_from_slice(&frame_size(version, data.len()));
+        body.extend_from_slice(&flags);
+        body.extend_from_slice(data);
+    }
+
+    let mut out = Vec::with_capacity(10 + body.len() + audio.len());
+    out.extend_from_slice(&[b'I', b'D', b'3', version, 0, 0]);
+    out.extend_from_slice(&syncsafe(body.len()));
+    out.extend_from_slice(&body);
+    out.extend_from_slice(audio);
+    Ok(out)
+}
+
+// A file's ID3v2 tag and the bytes after it
+struct Tag<'a> {
+    version: u8,
+    frames: Vec<([u8; 4], [u8; 2], &'a [u8])>, // Id, flags, data
+    audio: &'a [u8],
+}
+
+// `None` without a tag
+fn parse(mp3: &[u8]) -> io::Result<Option<Tag<'_>>> {
+    if mp3.len() < 10 || &mp3[..3] != b"ID3" {
+        return Ok(None);
+    }
+    let (version, flags) = (mp3[3], mp3[5]);
+    if version != 3 && version != 4 {
+        return Err(unsupported(format!("ID3v2.{} tags can't be rewritten", version)));
+    }
+    if flags & 0x80 != 0 {
+        return Err(unsupported("unsynchronised ID3 tags can't be rewritten".to_string()));
+    }
+    let size = read_syncsafe(&mp3[6..10]);
+    let footer = if flags & 0x10 != 0 { 10 } else { 0 };
+    let end = 10 + size;
+    if mp3.len() < end + footer {
+        return Err(unsupported("the ID3 tag is truncated".to_string()));
+    }
+
+    // The extended header only describes the old tag; it is dropped
+    let mut offset = 10;
+    if flags & 0x40 != 0 && end >= 14 {
+        offset += match version {
+            3 => 4 + u32::from_be_bytes(mp3[10..14].try_into().unwrap()) as usize,
+            _ => read_syncsafe(&mp3[10..14]),
+        };
+    }
+
+    let mut frames = Vec::new();
+    while offset + 10 <= end && mp3[offset] != 0 {
+        let id: [u8; 4] = mp3[offset..offset + 4].try_into().unwrap();
+        let size = match version {
+            3 => u32::from_be_bytes(mp3[offset + 4..offset + 8].try_into().unwrap()) as usize,
+            _ => read_syncsafe(&mp3[offset + 4..offset + 8]),
+        };
+        let flags: [u8; 2] = mp3[offset + 8..offset + 10].try_into().unwrap();
+        let data = mp3.get(offset + 10..offset + 10 + size).filter(|_| offset + 10 + size <= end)
+            .ok_or_else(|| unsupported(format!("ID3 frame {} overruns the tag", String::from_utf8_lossy(&id))))?;
+        frames.push((id, flags, data));
+        offset += 10 + size;
+    }
+    Ok(Some(Tag { version, frames, audio: &mp3[end + footer..] }))
+}
+
+// A text frame body: UTF-8 in v2.4, UTF-16 with a byte order mark in v2.3
+fn text(version: u8, value: &str) -> Vec<u8> {
+    match version {
+        4 => [&[3u8][..], value.as_bytes()].concat(),
+        _ => {
+            let mut data = vec![1, 0xFF, 0xFE];
+            data.extend(value.encode_utf16().flat_map(u16::to_le_bytes));
+            data
+        }
+    }
+}
+
+fn write_frame(body: &mut Vec<u8>, version: u8, id: &str, data: &[u8]) {
+    body.extend_from_slice(id.as_bytes());
+    body.extend_from_slice(&frame_size(version, data.len()));
+    body.extend_from_slice(&[0, 0]);
+    body.extend_from_slice(data);
+}
+
+fn frame_size(version: u8, size: usize) -> [u8; 4] {
+    match version {
+        3 => (size as u32).to_be_bytes(),
+        _ => syncsafe(size),
+    }
+}
+
+fn syncsafe(size: usize) -> [u8; 4] {
+    [(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F]
+}
+
+fn read_syncsafe(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0usize, |acc, &b| (acc << 7) | (b & 0x7F) as usize)
+}
+
+fn unsupported(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (key, value) pairs as symphonia reads the tag back
+    fn read_tags(mp3: Vec<u8>) -> Vec<(String, String)> {
+        use symphonia::core::{formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint};
+        let source = MediaSourceStream::new(Box::new(std::io::Cursor::new(mp3)), Default::default());
+        let mut probed = symphonia::default::get_probe()
+            .format(Hint::new().with_extension("mp3"), source, &FormatOptions::default(), &MetadataOptions::default())
+            .unwrap();
+        let metadata = probed.metadata.get().unwrap();
+        metadata.current().unwrap().tags().iter().map(|tag| (tag.key.clone(), tag.value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_set_text_frames_keeps_other_frames() {
+        let mp3 = std::fs::read("music/Singing Birds.mp3").unwrap();
+        let audio = &mp3[crate::mp3::id3v2_len(&mp3)..][..100_000];
+
+        // No tag: a v2.3 tag is added in front of the audio
+        let tagged = set_text_frames(audio, &[("TIT2", "Singing Bírds"), ("TCON", "Ambient")]).unwrap();
+        assert_eq!(&tagged[tagged.len() - audio.len()..], audio);
+        let tags = read_tags(tagged.clone());
+        assert!(tags.contains(&("TIT2".to_string(), "Singing Bírds".to_string())), "{:?}", tags);
+
+        // Rewriting one frame keeps the rest
+        let retitled = set_text_frames(&tagged, &[("TIT2", "Birdsong")]).unwrap();
+        assert_eq!(&retitled[retitled.len() - audio.len()..], audio);
+        let tags = read_tags(retitled);
+        assert!(tags.contains(&("TIT2".to_string(), "Birdsong".to_string())));
+        assert!(tags.contains(&("TCON".to_string(), "Ambient".to_string())));
+        assert_eq!(tags.len(), 2);
+
+        let v22 = [b"ID3\x02\x00\x00\x00\x00\x00\x00".as_slice(), audio].concat();
+        assert!(set_text_frames(&v22, &[("TIT2", "x")]).is_err());
+    }
+}
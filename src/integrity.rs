@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use crate::mp3;
+
+const RECENT_CHUNKS: usize = 64;
+
+/// CRC-32 (IEEE 802.3) lookup table
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 as used by zip/gzip/PNG, so clients can check chunks with standard tools
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+/// Whether `data` is a whole number of MP3 frames, starting at its first byte
+pub fn is_frame_aligned(data: &[u8]) -> bool {
+    let mut offset = 0;
+    while offset < data.len() {
+        match mp3::FrameHeader::parse(&data[offset..]) {
+            Some(header) => offset += header.frame_size(),
+            None => return false,
+        }
+    }
+    offset == data.len()
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChunkRecord {
+    pub position_ms: f64,
+    pub bytes: usize,
+    pub crc32: String, // Hex, as printed by `crc32`/`rhash`
+}
+
+/// Checksums and sanity counters for every chunk the broadcast emits, to tell
+/// server-side glitches (bad or repeated chunks) from network ones
+#[derive(Debug, Default)]
+pub struct ChunkIntegrity {
+    chunks: AtomicU64,
+    empty: AtomicU64,
+    malformed: AtomicU64, // Not a whole number of MP3 frames
+    duplicate: AtomicU64, // Same checksum as the previous chunk
+    recent: Mutex<VecDeque<ChunkRecord>>,
+}
+
+impl ChunkIntegrity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check a chunk about to be broadcast and return its CRC-32
+    pub fn inspect(&self, data: &[u8], position_ms: f64) -> u32 {
+        let checksum = crc32(data);
+        self.chunks.fetch_add(1, Ordering::Relaxed);
+
+        if data.is_empty() {
+            self.empty.fetch_add(1, Ordering::Relaxed);
+        } else if !is_frame_aligned(data) {
+            self.malformed.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut recent = self.recent.lock().unwrap();
+        let hex = format!("{:08x}", checksum);
+        // Repeated silence is expected to repeat, so only count non-silent repeats
+        if !data.is_empty() && recent.back().is_some_and(|last| last.crc32 == hex) && !is_silence(data) {
+            self.duplicate.fetch_add(1, Ordering::Relaxed);
+        }
+        if recent.len() == RECENT_CHUNKS {
+            recent.pop_front();
+        }
+        recent.push_back(ChunkRecord { position_ms, bytes: data.len(), crc32: hex });
+
+        checksum
+    }
+
+    pub fn recent(&self) -> Vec<ChunkRecord> {
+        self.recent.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn snapshot(&self) -> serde_json::Value {
+        serde_json::json!({
+            "chunks": self.chunks.load(Ordering::Relaxed),
+            "empty": self.empty.load(Ordering::Relaxed),
+            "malformed": self.malformed.load(Ordering::Relaxed),
+            "duplicate": self.duplicate.load(Ordering::Relaxed),
+        })
+    }
+}
+
+// Frames whose bodies are all zero, as produced by `mp3::silent_frame`
+fn is_silence(data: &[u8]) -> bool {
+    let mut offset = 0;
+    while offset < data.len() {
+        let Some(header) = mp3::FrameHeader::parse(&data[offset..]) else {
+            return false;
+        };
+        let end = (offset + header.frame_size()).min(data.len());
+        if data[offset + 4..end].iter().any(|&b| b != 0) {
+            return false;
+        }
+        offset = end;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_counters() {
+        let header = mp3::FrameHeader::parse(&[0xFF, 0xFB, 0x90, 0x64]).unwrap();
+        let mut frame = mp3::silent_frame(&header);
+        let integrity = ChunkIntegrity::new();
+
+        integrity.inspect(&frame, 0.0);
+        integrity.inspect(&frame, 26.0); // Repeated silence is fine
+        frame[100] = 0x55;
+        integrity.inspect(&frame, 52.0);
+        integrity.inspect(&frame, 78.0); // Repeated audio is not
+        integrity.inspect(&frame[..200], 104.0);
+        integrity.inspect(&[], 130.0);
+
+        let snapshot = integrity.snapshot();
+        assert_eq!(snapshot["chunks"], 6);
+        assert_eq!(snapshot["duplicate"], 1);
+        assert_eq!(snapshot["malformed"], 1);
+        assert_eq!(snapshot["empty"], 1);
+        assert_eq!(integrity.recent().len(), 6);
+        assert_eq!(integrity.recent()[0].crc32, format!("{:08x}", crc32(&mp3::silent_frame(&header))));
+    }
+}
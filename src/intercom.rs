@@ -0,0 +1,183 @@
+// DJ/studio intercom: a talkback channel between a remote DJ and the studio
+// operator over WebSocket. Audio frames are relayed as-is (the clients agree on
+// a codec, e.g. Opus from MediaRecorder) and never reach the broadcast.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use bytes::Bytes;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+// Small on purpose: a peer that falls behind skips stale audio instead of queueing it
+const CHANNEL_CAPACITY: usize = 32;
+pub const MAX_FRAME_BYTES: usize = 64 * 1024;
+pub const MAX_TEXT_BYTES: usize = 4 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Dj,
+    Studio,
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Dj => "dj",
+            Role::Studio => "studio",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum IntercomMessage {
+    Audio(Bytes),
+    Text(String), // JSON control messages (codec, talk/mute, chat)
+}
+
+/// A message and who sent it; `None` for server presence updates, which go to everyone
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    pub from: Option<Role>,
+    pub message: IntercomMessage,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Presence {
+    pub dj: usize,
+    pub studio: usize,
+}
+
+#[derive(Debug)]
+pub struct Intercom {
+    tx: broadcast::Sender<Envelope>,
+    djs: AtomicUsize,
+    studios: AtomicUsize,
+}
+
+impl Intercom {
+    pub fn new() -> Arc<Self> {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Arc::new(Self { tx, djs: AtomicUsize::new(0), studios: AtomicUsize::new(0) })
+    }
+
+    pub fn presence(&self) -> Presence {
+        Presence { dj: self.djs.load(Ordering::Relaxed), studio: self.studios.load(Ordering::Relaxed) }
+    }
+
+    /// Connect as `role`; everyone is told about the new presence
+    pub fn join(self: &Arc<Self>, role: Role) -> IntercomMember {
+        let rx = self.tx.subscribe();
+        self.counter(role).fetch_add(1, Ordering::Relaxed);
+        self.announce();
+        IntercomMember { intercom: self.clone(), role, rx }
+    }
+
+    fn counter(&self, role: Role) -> &AtomicUsize {
+        match role {
+            Role::Dj => &self.djs,
+            Role::Studio => &self.studios,
+        }
+    }
+
+    fn announce(&self) {
+        let presence = self.presence();
+        let text = serde_json::json!({ "type": "presence", "dj": presence.dj, "studio": presence.studio }).to_string();
+        let _ = self.tx.send(Envelope { from: None, message: IntercomMessage::Text(text) });
+    }
+}
+
+/// One connected peer; leaving (drop) updates the presence
+pub struct IntercomMember {
+    intercom: Arc<Intercom>,
+    role: Role,
+    rx: broadcast::Receiver<Envelope>,
+}
+
+impl IntercomMember {
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Relay to the other side. Text must be a JSON object; it is tagged with the
+    /// sender's role. Returns false for messages that aren't accepted.
+    pub fn send(&self, message: IntercomMessage) -> bool {
+        let message = match message {
+            IntercomMessage::Audio(frame) if frame.len() <= MAX_FRAME_BYTES => IntercomMessage::Audio(frame),
+            IntercomMessage::Text(text) if text.len() <= MAX_TEXT_BYTES => {
+                let Ok(serde_json::Value::Object(mut object)) = serde_json::from_str(&text) else {
+                    return false;
+                };
+                object.insert("from".to_string(), self.role.name().into());
+                IntercomMessage::Text(serde_json::Value::Object(object).to_string())
+            }
+            _ => return false,
+        };
+        let _ = self.intercom.tx.send(Envelope { from: Some(self.role), message });
+        true
+    }
+
+    /// The next message for this peer: the other side's messages and presence
+    /// updates. Returns how many messages were skipped because this peer fell behind.
+    pub async fn recv(&mut self) -> Option<(IntercomMessage, u64)> {
+        let mut skipped = 0;
+        loop {
+            match self.rx.recv().await {
+                Ok(envelope) if envelope.from == Some(self.role) => continue,
+                Ok(envelope) => return Some((envelope.message, skipped)),
+                Err(broadcast::error::RecvError::Lagged(n)) => skipped += n,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for IntercomMember {
+    fn drop(&mut self) {
+        self.intercom.counter(self.role).fetch_sub(1, Ordering::Relaxed);
+        self.intercom.announce();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(message: IntercomMessage) -> serde_json::Value {
+        match message {
+            IntercomMessage::Text(text) => serde_json::from_str(&text).unwrap(),
+            IntercomMessage::Audio(_) => panic!("expected text"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relays_between_roles_only() {
+        let intercom = Intercom::new();
+        let mut dj = intercom.join(Role::Dj);
+        let mut studio = intercom.join(Role::Studio);
+        assert_eq!(intercom.presence(), Presence { dj: 1, studio: 1 });
+
+        // The DJ saw both joins; the studio only its own
+        assert_eq!(text(dj.recv().await.unwrap().0)["studio"], 0);
+        assert_eq!(text(dj.recv().await.unwrap().0)["studio"], 1);
+        assert_eq!(text(studio.recv().await.unwrap().0)["studio"], 1);
+
+        assert!(dj.send(IntercomMessage::Audio(Bytes::from_static(b"opus"))));
+        assert!(dj.send(IntercomMessage::Text(r#"{"type":"talk","on":true}"#.to_string())));
+        assert!(!dj.send(IntercomMessage::Text("not json".to_string())));
+        assert!(!dj.send(IntercomMessage::Audio(Bytes::from(vec![0; MAX_FRAME_BYTES + 1]))));
+
+        match studio.recv().await.unwrap().0 {
+            IntercomMessage::Audio(frame) => assert_eq!(&frame[..], b"opus"),
+            IntercomMessage::Text(text) => panic!("unexpected {}", text),
+        }
+        let talk = text(studio.recv().await.unwrap().0);
+        assert_eq!((talk["type"].as_str(), talk["from"].as_str()), (Some("talk"), Some("dj")));
+
+        drop(dj);
+        assert_eq!(intercom.presence(), Presence { dj: 0, studio: 1 });
+        assert_eq!(text(studio.recv().await.unwrap().0)["dj"], 0);
+    }
+}
@@ -1,13 +1,69 @@
 // Library exports for webradio crate
 // This allows integration tests to access the public API
 
+#[cfg(feature = "acme")]
+pub mod acme;
+pub mod access;
+pub mod alert;
+pub mod analyze;
+pub mod archive;
+pub mod archiver;
+pub mod audience;
+pub mod auth;
+pub mod bans;
+pub mod beacon;
+pub mod chunklog;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod clienttest;
+pub mod codec;
 pub mod config;
+pub mod drift;
+pub mod encode;
 pub mod error;
+pub mod etag;
+pub mod events;
+pub mod fingerprint;
+pub mod geoip;
+pub mod history;
+pub mod icecast;
+pub mod id3;
+pub mod integrity;
+pub mod intercom;
+pub mod listen;
+pub mod logging;
+pub mod monitor;
+pub mod mp3;
+pub mod mqtt;
+pub mod netif;
+pub mod notify;
+pub mod openapi;
 pub mod playlist;
+pub mod prefetch;
+pub mod preflight;
+pub mod profile;
+pub mod publicip;
 pub mod radio;
+pub mod ratecontrol;
+pub mod ratelimit;
+pub mod relay;
+pub mod resume;
+pub mod rotation;
+pub mod royalty;
+pub mod search;
+pub mod server;
+pub mod signing;
+pub mod simulcast;
+pub mod sync;
+pub mod timeshift;
+pub mod tls;
+pub mod tone;
+pub mod transcoder;
+pub mod watcher;
 
 // Re-export commonly used types
 pub use config::Config;
 pub use radio::RadioStation;
-pub use playlist::{Playlist, Track};
+pub use playlist::{Playlist, SharedPlaylist, Track};
 pub use error::{AppError, Result};
+pub use webradio_types as types;
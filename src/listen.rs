@@ -0,0 +1,72 @@
+// "Open network stream" playlists (/listen.m3u, /listen.pls) for desktop players
+// such as VLC, Winamp, foobar2000 and iTunes
+
+/// What the playlist points at
+#[derive(Debug, Clone)]
+pub struct ListenLink {
+    pub stream_url: String,
+    pub station_name: String,
+    pub bitrate_kbps: u64,
+}
+
+impl ListenLink {
+    // Station name on one line
+    fn name(&self) -> String {
+        let name: String = self.station_name.chars().filter(|c| !c.is_control()).collect();
+        name.trim().to_string()
+    }
+
+    // Entry title, as players show it before the first ICY metadata arrives
+    fn title(&self) -> String {
+        format!("{} ({} kbps)", self.name(), self.bitrate_kbps)
+    }
+
+    /// Extended M3U; -1 marks a stream of unknown length
+    pub fn m3u(&self) -> String {
+        format!(
+            "#EXTM3U\n#PLAYLIST:{name}\n#EXTINF:-1,{title}\n{url}\n",
+            name = self.name(),
+            title = self.title(),
+            url = self.stream_url,
+        )
+    }
+
+    /// PLS version 2
+    pub fn pls(&self) -> String {
+        format!(
+            "[playlist]\nNumberOfEntries=1\nFile1={url}\nTitle1={title}\nLength1=-1\nVersion=2\n",
+            url = self.stream_url,
+            title = self.title(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link() -> ListenLink {
+        ListenLink {
+            stream_url: "https://radio.example.com/stream".to_string(),
+            station_name: "Night\nOwl FM".to_string(),
+            bitrate_kbps: 192,
+        }
+    }
+
+    #[test]
+    fn test_m3u() {
+        assert_eq!(
+            link().m3u(),
+            "#EXTM3U\n#PLAYLIST:NightOwl FM\n#EXTINF:-1,NightOwl FM (192 kbps)\nhttps://radio.example.com/stream\n"
+        );
+    }
+
+    #[test]
+    fn test_pls() {
+        let pls = link().pls();
+        assert!(pls.starts_with("[playlist]\nNumberOfEntries=1\n"));
+        assert!(pls.contains("File1=https://radio.example.com/stream\n"));
+        assert!(pls.contains("Title1=NightOwl FM (192 kbps)\n"));
+        assert!(pls.ends_with("Length1=-1\nVersion=2\n"));
+    }
+}
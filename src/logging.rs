@@ -0,0 +1,57 @@
+// Tracing subscriber setup for the server binary: human-readable or JSON lines,
+// to stdout or to a rolling file (LOG_FORMAT, LOG_FILE, LOG_ROTATION)
+
+use std::io;
+use tracing_appender::{non_blocking::WorkerGuard, rolling::{RollingFileAppender, Rotation}};
+use tracing_subscriber::{fmt::{self, writer::BoxMakeWriter}, prelude::*, EnvFilter};
+
+use crate::config::{Config, LogFormat, LogRotation};
+
+/// Levels when RUST_LOG is unset
+const DEFAULT_FILTER: &str = "webradio=debug,tower_http=info,axum=info";
+
+/// Install the global subscriber. With a log file, lines are written from a
+/// background thread; keep the returned guard alive so they are flushed on exit.
+pub fn init(config: &Config) -> io::Result<Option<WorkerGuard>> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| DEFAULT_FILTER.into());
+    let (writer, guard) = match &config.log_file {
+        Some(path) => {
+            let (writer, guard) = tracing_appender::non_blocking(file_appender(path, config.log_rotation)?);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(io::stdout), None),
+    };
+
+    let layer = fmt::layer().with_writer(writer).with_ansi(config.log_file.is_none());
+    let layer = match config.log_format {
+        LogFormat::Pretty => layer.boxed(),
+        // Event fields at the top level next to the level and message, and the
+        // request span (request id, method, path) under "span"
+        LogFormat::Json => layer.json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(layer.with_filter(filter))
+        .try_init()
+        .map_err(io::Error::other)?;
+    Ok(guard)
+}
+
+// `path` itself when never rotated, otherwise `path.YYYY-MM-DD[-HH]`
+fn file_appender(path: &std::path::Path, rotation: LogRotation) -> io::Result<RollingFileAppender> {
+    let name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("LOG_FILE {} has no file name", path.display())))?;
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+    RollingFileAppender::builder()
+        .rotation(match rotation {
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        })
+        .filename_prefix(name.to_string_lossy())
+        .build(dir)
+        .map_err(io::Error::other)
+}
@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// Shortest window CPU usage is measured over; requests within it reuse the last figure
+const MIN_WINDOW: Duration = Duration::from_secs(1);
+
+/// Parts of the server whose busy time is tracked separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Decode,    // Reading packets from the current track
+    Publish,   // Checksumming, logging and sending broadcast chunks
+    Listeners, // Per-listener chunk handling (drift, trims, bookkeeping)
+    Archiver,  // Writing the broadcast to disk
+    Api,       // /api/* request handlers
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 5] = [
+        Subsystem::Decode,
+        Subsystem::Publish,
+        Subsystem::Listeners,
+        Subsystem::Archiver,
+        Subsystem::Api,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::Decode => "decode",
+            Subsystem::Publish => "publish",
+            Subsystem::Listeners => "listeners",
+            Subsystem::Archiver => "archiver",
+            Subsystem::Api => "api",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SubsystemTimer {
+    calls: AtomicU64,
+    busy_ns: AtomicU64,
+    max_ns: AtomicU64,
+}
+
+/// Counters at the start of the current measurement window
+#[derive(Debug)]
+struct Window {
+    at: Instant,
+    cpu: Option<Duration>,
+    busy_ns: [u64; Subsystem::ALL.len()],
+    // Results for the last completed window
+    cpu_percent: Option<f64>,
+    busy_percent: [f64; Subsystem::ALL.len()],
+    seconds: f64,
+}
+
+/// One point of the rolling metrics history
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSample {
+    pub timestamp: u64, // Unix seconds
+    pub cpu_percent: Option<f64>,
+    pub rss_mb: Option<f64>,
+    pub listeners: usize,
+    pub busy_percent: BTreeMap<&'static str, f64>,
+}
+
+/// Process CPU usage and per-subsystem busy time. Usage figures cover the time
+/// since the previous measurement (at least `MIN_WINDOW`).
+#[derive(Debug)]
+pub struct PerformanceMonitor {
+    timers: [SubsystemTimer; Subsystem::ALL.len()],
+    window: Mutex<Window>,
+    cores: usize,
+    history: Mutex<VecDeque<MetricsSample>>,
+    history_len: usize,
+}
+
+impl PerformanceMonitor {
+    /// Keeps the last `history_len` samples taken with `sample`
+    pub fn new(history_len: usize) -> Self {
+        Self {
+            timers: Default::default(),
+            window: Mutex::new(Window {
+                at: Instant::now(),
+                cpu: process_cpu_time(),
+                busy_ns: [0; Subsystem::ALL.len()],
+                cpu_percent: None,
+                busy_percent: [0.0; Subsystem::ALL.len()],
+                seconds: 0.0,
+            }),
+            cores: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            history: Mutex::new(VecDeque::with_capacity(history_len)),
+            history_len,
+        }
+    }
+
+    pub fn record(&self, subsystem: Subsystem, elapsed: Duration) {
+        let timer = &self.timers[subsystem as usize];
+        let ns = elapsed.as_nanos() as u64;
+        timer.calls.fetch_add(1, Ordering::Relaxed);
+        timer.busy_ns.fetch_add(ns, Ordering::Relaxed);
+        timer.max_ns.fetch_max(ns, Ordering::Relaxed);
+    }
+
+    /// Run `f`, counting its wall time against `subsystem`
+    pub fn time<T>(&self, subsystem: Subsystem, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(subsystem, start.elapsed());
+        result
+    }
+
+    /// Process CPU usage as a percentage of one core (400% = four busy cores),
+    /// or `None` where CPU time can't be read
+    pub fn get_cpu_usage(&self) -> Option<f64> {
+        self.measure().cpu_percent
+    }
+
+    pub fn cores(&self) -> usize {
+        self.cores
+    }
+
+    /// Close the measurement window if it is long enough and return the latest results
+    fn measure(&self) -> std::sync::MutexGuard<'_, Window> {
+        let mut window = self.window.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(window.at);
+        if elapsed < MIN_WINDOW && window.seconds > 0.0 {
+            return window;
+        }
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+
+        let cpu = process_cpu_time();
+        window.cpu_percent = match (window.cpu, cpu) {
+            (Some(before), Some(after)) => Some(after.saturating_sub(before).as_secs_f64() / seconds * 100.0),
+            _ => None,
+        };
+        for subsystem in Subsystem::ALL {
+            let index = subsystem as usize;
+            let busy_ns = self.timers[index].busy_ns.load(Ordering::Relaxed);
+            window.busy_percent[index] = busy_ns.saturating_sub(window.busy_ns[index]) as f64 / 1e9 / seconds * 100.0;
+            window.busy_ns[index] = busy_ns;
+        }
+        window.at = now;
+        window.cpu = cpu;
+        window.seconds = seconds;
+        window
+    }
+
+    /// Close the current window and append it to the history
+    pub fn sample(&self, timestamp: u64, listeners: usize) -> MetricsSample {
+        let sample = {
+            let window = self.measure();
+            MetricsSample {
+                timestamp,
+                cpu_percent: window.cpu_percent,
+                rss_mb: resident_memory_bytes().map(|b| b as f64 / 1_048_576.0),
+                listeners,
+                busy_percent: Subsystem::ALL.iter()
+                    .map(|&subsystem| (subsystem.name(), window.busy_percent[subsystem as usize]))
+                    .collect(),
+            }
+        };
+        if self.history_len > 0 {
+            let mut history = self.history.lock().unwrap();
+            if history.len() == self.history_len {
+                history.pop_front();
+            }
+            history.push_back(sample.clone());
+        }
+        sample
+    }
+
+    /// Samples taken at or after `since` (unix seconds), oldest first
+    pub fn history(&self, since: u64) -> Vec<MetricsSample> {
+        let history = self.history.lock().unwrap();
+        history.iter().filter(|sample| sample.timestamp >= since).cloned().collect()
+    }
+
+    pub fn snapshot(&self) -> serde_json::Value {
+        let window = self.measure();
+        let subsystems: serde_json::Map<String, serde_json::Value> = Subsystem::ALL.iter()
+            .map(|&subsystem| {
+                let timer = &self.timers[subsystem as usize];
+                let calls = timer.calls.load(Ordering::Relaxed);
+                let busy_ns = timer.busy_ns.load(Ordering::Relaxed);
+                (subsystem.name().to_string(), serde_json::json!({
+                    "calls": calls,
+                    "busy_seconds": busy_ns as f64 / 1e9,
+                    "busy_percent": window.busy_percent[subsystem as usize],
+                    "avg_us": if calls > 0 { busy_ns as f64 / calls as f64 / 1000.0 } else { 0.0 },
+                    "max_us": timer.max_ns.load(Ordering::Relaxed) as f64 / 1000.0,
+                }))
+            })
+            .collect();
+
+        serde_json::json!({
+            "cpu": {
+                "usage_percent": window.cpu_percent,
+                "usage_percent_of_total": window.cpu_percent.map(|p| p / self.cores as f64),
+                "cores": self.cores,
+                "cpu_seconds": process_cpu_time().map(|t| t.as_secs_f64()),
+                "window_seconds": window.seconds,
+            },
+            "memory": {
+                "rss_mb": resident_memory_bytes().map(|b| b as f64 / 1_048_576.0),
+            },
+            "subsystems": subsystems,
+        })
+    }
+}
+
+/// CPU time (user + system, all threads) consumed by this process
+#[cfg(unix)]
+pub fn process_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: `ts` is a valid, writable timespec
+    let rc = unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut ts) };
+    (rc == 0).then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+pub fn process_cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Resident set size, from /proc/self/statm
+#[cfg(target_os = "linux")]
+fn resident_memory_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf has no preconditions
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * page_size.max(0) as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_memory_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsystem_timing() {
+        let monitor = PerformanceMonitor::new(0);
+        monitor.record(Subsystem::Publish, Duration::from_micros(100));
+        monitor.record(Subsystem::Publish, Duration::from_micros(300));
+        assert_eq!(monitor.time(Subsystem::Decode, || 42), 42);
+
+        let snapshot = monitor.snapshot();
+        let publish = &snapshot["subsystems"]["publish"];
+        assert_eq!(publish["calls"], 2);
+        assert_eq!(publish["avg_us"], 200.0);
+        assert_eq!(publish["max_us"], 300.0);
+        assert_eq!(snapshot["subsystems"]["decode"]["calls"], 1);
+        assert_eq!(snapshot["subsystems"]["api"]["calls"], 0);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let monitor = PerformanceMonitor::new(3);
+        for t in 0..5 {
+            let sample = monitor.sample(1000 + t, t as usize);
+            assert!(sample.busy_percent.contains_key("publish"));
+        }
+        let history = monitor.history(0);
+        assert_eq!(history.iter().map(|s| s.timestamp).collect::<Vec<_>>(), vec![1002, 1003, 1004]);
+        assert_eq!(monitor.history(1004).len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cpu_usage_reflects_load() {
+        let monitor = PerformanceMonitor::new(0);
+        let before = process_cpu_time().unwrap();
+
+        // Spin for a little over the minimum window
+        let start = Instant::now();
+        let mut x: u64 = 0;
+        while start.elapsed() < MIN_WINDOW + Duration::from_millis(100) {
+            x = std::hint::black_box(x.wrapping_mul(6364136223846793005).wrapping_add(1));
+        }
+
+        assert!(process_cpu_time().unwrap() > before);
+        let usage = monitor.get_cpu_usage().unwrap();
+        assert!(usage > 20.0, "usage {}", usage);
+    }
+}
@@ -0,0 +1,523 @@
+// MPEG audio frame header parsing (MPEG-1, 2 and 2.5, Layers I to III), generation
+// of silent frames and lossless gain changes
+
+const MPEG1_BITRATES_KBPS: [[u32; 16]; 3] = [
+    [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448, 0], // Layer I
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 0],    // Layer II
+    [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 0],     // Layer III
+];
+const MPEG2_BITRATES_KBPS: [[u32; 16]; 3] = [
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256, 0], // Layer I
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0],      // Layers II and III
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0],
+];
+const MPEG1_SAMPLE_RATES: [u32; 4] = [44100, 48000, 32000, 0];
+
+/// Each global_gain step scales the decoded audio by 2^(1/4)
+pub const GAIN_STEP_DB: f64 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpegVersion {
+    Mpeg1,
+    Mpeg2,  // Half the MPEG-1 sample rates
+    Mpeg25, // A quarter of them
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub version: MpegVersion,
+    pub layer: u8, // 1, 2 or 3
+    pub bitrate_kbps: u32,
+    pub sample_rate: u32,
+    pub padding: bool,
+    pub protected: bool, // CRC follows the header
+    pub channel_mode: u8, // 0 stereo, 1 joint stereo, 2 dual channel, 3 mono
+    raw: [u8; 4],
+}
+
+impl FrameHeader {
+    /// Parse the 4-byte header at the start of `bytes`
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let raw: [u8; 4] = bytes.get(..4)?.try_into().ok()?;
+
+        // 11-bit frame sync
+        if raw[0] != 0xFF || raw[1] & 0xE0 != 0xE0 {
+            return None;
+        }
+        let (version, divisor) = match (raw[1] >> 3) & 0x3 {
+            0x3 => (MpegVersion::Mpeg1, 1),
+            0x2 => (MpegVersion::Mpeg2, 2),
+            0x0 => (MpegVersion::Mpeg25, 4),
+            _ => return None, // Reserved
+        };
+        let layer = match (raw[1] >> 1) & 0x3 {
+            0 => return None, // Reserved
+            bits => 4 - bits,
+        };
+
+        let bitrates = match version {
+            MpegVersion::Mpeg1 => &MPEG1_BITRATES_KBPS,
+            _ => &MPEG2_BITRATES_KBPS,
+        };
+        let bitrate_kbps = bitrates[layer as usize - 1][(raw[2] >> 4) as usize];
+        let sample_rate = MPEG1_SAMPLE_RATES[((raw[2] >> 2) & 0x3) as usize] / divisor;
+        if bitrate_kbps == 0 || sample_rate == 0 {
+            return None; // Free format or reserved
+        }
+
+        Some(Self {
+            version,
+            layer,
+            bitrate_kbps,
+            sample_rate,
+            padding: raw[2] & 0x02 != 0,
+            protected: raw[1] & 0x01 == 0,
+            channel_mode: raw[3] >> 6,
+            raw,
+        })
+    }
+
+    pub fn samples_per_frame(&self) -> u32 {
+        match (self.layer, self.version) {
+            (1, _) => 384,
+            (3, MpegVersion::Mpeg2 | MpegVersion::Mpeg25) => 576,
+            _ => 1152,
+        }
+    }
+
+    pub fn frame_size(&self) -> usize {
+        self.unpadded_size() + match (self.padding, self.layer) {
+            (false, _) => 0,
+            (true, 1) => 4, // Layer I pads by a 4-byte slot
+            (true, _) => 1,
+        }
+    }
+
+    fn unpadded_size(&self) -> usize {
+        let bytes = self.samples_per_frame() / 8 * self.bitrate_kbps * 1000 / self.sample_rate;
+        match self.layer {
+            1 => (bytes / 4 * 4) as usize, // Whole 4-byte slots
+            _ => bytes as usize,
+        }
+    }
+
+    pub fn duration_ms(&self) -> f64 {
+        self.samples_per_frame() as f64 * 1000.0 / self.sample_rate as f64
+    }
+}
+
+/// Size in bytes of the frame starting at `bytes`, or `None` if no valid header is there
+pub fn calculate_frame_size(bytes: &[u8]) -> Option<usize> {
+    FrameHeader::parse(bytes).map(|header| header.frame_size())
+}
+
+/// Length of the ID3v2 tag at the start of `data` (10-byte header, syncsafe size), or 0
+pub fn id3v2_len(data: &[u8]) -> usize {
+    if data.len() < 10 || &data[..3] != b"ID3" {
+        return 0;
+    }
+    let size = data[6..10].iter().fold(0usize, |acc, &b| (acc << 7) | (b & 0x7F) as usize);
+    let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+    10 + size + footer
+}
+
+/// Length of all ID3v2 tags at the start of `data`: some taggers add a second tag in
+/// front of an existing one rather than rewriting it
+pub fn leading_tags_len(data: &[u8]) -> usize {
+    let mut len = 0;
+    while len < data.len() {
+        match id3v2_len(&data[len..]) {
+            0 => break,
+            tag => len += tag,
+        }
+    }
+    len
+}
+
+/// How much of a file's end `trailing_tags_len` needs to see
+pub const TRAILING_TAGS_PROBE: usize = 128 + 32;
+
+/// Length of the tags at the end of a file whose last bytes are `tail` (at least
+/// `TRAILING_TAGS_PROBE` of them, or the whole file): an ID3v1 tag (the last 128
+/// bytes, starting "TAG") and an APEv2 or APEv1 tag or appended ID3v2.4 tag before
+/// it, found by their "APETAGEX" and "3DI" footers. Those can be longer than `tail`;
+/// callers clamp to the file.
+pub fn trailing_tags_len(tail: &[u8]) -> usize {
+    let mut end = tail.len();
+    let mut len = 0;
+    if end >= 128 && &tail[end - 128..end - 125] == b"TAG" {
+        end -= 128;
+        len += 128;
+    }
+    if end >= 32 && &tail[end - 32..end - 24] == b"APETAGEX" {
+        let footer = &tail[end - 32..end];
+        let size = u32::from_le_bytes(footer[12..16].try_into().unwrap()) as usize; // Items and footer
+        let flags = u32::from_le_bytes(footer[20..24].try_into().unwrap());
+        let header = if flags & 0x8000_0000 != 0 { 32 } else { 0 };
+        len += size.max(32) + header;
+    } else if end >= 10 && &tail[end - 10..end - 7] == b"3DI" {
+        // Footer, header and the frames between them
+        len += 20 + tail[end - 4..end].iter().fold(0usize, |acc, &b| (acc << 7) | (b & 0x7F) as usize);
+    }
+    len
+}
+
+/// The part of an MP3 file between its leading ID3v2 tags and any trailing tags
+pub fn audio_bytes(data: &[u8]) -> &[u8] {
+    let start = leading_tags_len(data).min(data.len());
+    let tail = &data[data.len().saturating_sub(TRAILING_TAGS_PROBE)..];
+    let end = data.len().saturating_sub(trailing_tags_len(tail)).max(start);
+    &data[start..end]
+}
+
+/// Split a file into whole frames, skipping ID3v2, ID3v1 and APE tags and resyncing
+/// past anything else that isn't a frame (junk, truncated frames)
+pub fn split_frames(data: &[u8]) -> Vec<(FrameHeader, &[u8])> {
+    let data = audio_bytes(data);
+    let mut frames = Vec::new();
+    let mut offset = 0;
+    while offset + 4 <= data.len() {
+        match FrameHeader::parse(&data[offset..]) {
+            Some(header) if offset + header.frame_size() <= data.len() => {
+                frames.push((header, &data[offset..offset + header.frame_size()]));
+                offset += header.frame_size();
+            }
+            _ => offset += 1,
+        }
+    }
+    frames
+}
+
+/// Where `data` can be cut so that everything before the cut is whole frames, and
+/// how long those frames play. A trailing partial frame stays after the cut.
+/// `None` when `data` isn't a plain run of frames, so there is no frame boundary to cut at.
+pub fn whole_frames(data: &[u8]) -> Option<(usize, f64)> {
+    let mut offset = 0;
+    let mut duration_ms = 0.0;
+    while offset < data.len() {
+        let rest = &data[offset..];
+        match FrameHeader::parse(rest) {
+            Some(header) if header.frame_size() <= rest.len() => {
+                offset += header.frame_size();
+                duration_ms += header.duration_ms();
+            }
+            Some(_) => break,
+            None if rest.len() < 4 && rest[0] == 0xFF => break, // The next header, cut short
+            None => return None,
+        }
+    }
+    Some((offset, duration_ms))
+}
+
+/// A frame with the same format as `header` that decodes to silence: no CRC, no
+/// padding and an all-zero body (part2_3_length = 0 for every Layer III granule, no
+/// bits allocated to any Layer I/II subband)
+pub fn silent_frame(header: &FrameHeader) -> Vec<u8> {
+    let mut frame_header = header.raw;
+    frame_header[1] |= 0x01;  // Protection bit set = no CRC
+    frame_header[2] &= !0x02; // No padding
+
+    let mut frame = vec![0u8; header.unpadded_size()];
+    frame[..4].copy_from_slice(&frame_header);
+    frame
+}
+
+/// Whole silent frames covering at least `duration_ms`; returns the data and its exact duration
+pub fn silence(header: &FrameHeader, duration_ms: f64) -> (Vec<u8>, f64) {
+    let frame = silent_frame(header);
+    let frames = (duration_ms / header.duration_ms()).ceil().max(0.0) as usize;
+    (frame.repeat(frames), frames as f64 * header.duration_ms())
+}
+
+/// Whole global_gain steps closest to `gain_db`, rounded down when boosting so the
+/// track's peak limit is never overshot
+pub fn gain_steps(gain_db: f64) -> i32 {
+    let steps = gain_db / GAIN_STEP_DB;
+    if steps > 0.0 { steps.floor() as i32 } else { steps.round() as i32 }
+}
+
+/// Change the loudness of the frame at the start of `frame` by `steps` x 1.5 dB without
+/// decoding it, mp3gain-style: every granule's global_gain moves by `steps` and the CRC,
+/// if any, is recomputed. Returns false, leaving the bytes alone, if there is no whole
+/// Layer III frame (Layers I and II have no global gain).
+pub fn apply_gain(frame: &mut [u8], steps: i32) -> bool {
+    let Some(header) = FrameHeader::parse(frame) else {
+        return false;
+    };
+    if header.layer != 3 || frame.len() < header.frame_size() {
+        return false;
+    }
+
+    let channels = if header.channel_mode == 3 { 1 } else { 2 };
+    let side_info_start = if header.protected { 6 } else { 4 };
+    // main_data_begin, private bits and (MPEG-1 only) scfsi come before the granules,
+    // of which MPEG-2 and 2.5 frames have one instead of two, with a longer scalefac_compress
+    let (side_info_len, prefix_bits, granules, granule_bits) = match (header.version, channels) {
+        (MpegVersion::Mpeg1, 1) => (17, 18, 2, 59),
+        (MpegVersion::Mpeg1, _) => (32, 20, 2, 59),
+        (_, 1) => (9, 9, 1, 63),
+        _ => (17, 10, 1, 63),
+    };
+    let granules_start = side_info_start * 8 + prefix_bits;
+    for granule in 0..granules * channels {
+        let offset = granules_start + granule * granule_bits;
+        if read_bits(frame, offset, 12) == 0 {
+            continue; // part2_3_length 0: the granule is silent
+        }
+        let gain = read_bits(frame, offset + 21, 8) as i32;
+        write_bits(frame, offset + 21, 8, (gain + steps).clamp(0, 255) as u32);
+    }
+
+    if header.protected {
+        // CRC-16 of the header's last two bytes and the side information
+        let crc = crc16(frame[2..4].iter().chain(&frame[6..6 + side_info_len]));
+        frame[4..6].copy_from_slice(&crc.to_be_bytes());
+    }
+    true
+}
+
+fn read_bits(data: &[u8], offset: usize, count: usize) -> u32 {
+    (offset..offset + count).fold(0, |acc, bit| (acc << 1) | ((data[bit / 8] >> (7 - bit % 8)) & 1) as u32)
+}
+
+fn write_bits(data: &mut [u8], offset: usize, count: usize, value: u32) {
+    for (i, bit) in (offset..offset + count).enumerate() {
+        let mask = 0x80 >> (bit % 8);
+        if (value >> (count - 1 - i)) & 1 != 0 {
+            data[bit / 8] |= mask;
+        } else {
+            data[bit / 8] &= !mask;
+        }
+    }
+}
+
+// CRC-16 with polynomial 0x8005 and initial value 0xFFFF, as MPEG audio protects frames
+fn crc16<'a>(bytes: impl Iterator<Item = &'a u8>) -> u16 {
+    bytes.fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 128kbps, 44.1kHz, joint stereo, no CRC
+    const HEADER_128K: [u8; 4] = [0xFF, 0xFB, 0x90, 0x64];
+
+    #[test]
+    fn test_parse_header() {
+        let header = FrameHeader::parse(&HEADER_128K).unwrap();
+        assert_eq!(header.bitrate_kbps, 128);
+        assert_eq!(header.sample_rate, 44100);
+        assert!(!header.padding);
+        assert!(!header.protected);
+        assert_eq!(header.channel_mode, 1);
+        assert_eq!(header.frame_size(), 417);
+        assert!((header.duration_ms() - 26.122).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_parse_other_versions_and_layers() {
+        // MPEG-2 Layer III, 64kbps, 22.05kHz, mono: half the samples of an MPEG-1 frame
+        let header = FrameHeader::parse(&[0xFF, 0xF3, 0x80, 0xC4]).unwrap();
+        assert_eq!((header.version, header.layer, header.bitrate_kbps, header.sample_rate), (MpegVersion::Mpeg2, 3, 64, 22050));
+        assert_eq!(header.frame_size(), 208);
+        assert!((header.duration_ms() - 26.122).abs() < 0.001);
+
+        // MPEG-2.5 Layer III, 8kbps, 8kHz, as low-bitrate voice recordings use
+        let header = FrameHeader::parse(&[0xFF, 0xE3, 0x18, 0xC4]).unwrap();
+        assert_eq!((header.version, header.sample_rate), (MpegVersion::Mpeg25, 8000));
+        assert_eq!(header.frame_size(), 72);
+        assert_eq!(header.duration_ms(), 72.0);
+
+        // MPEG-1 Layer II, 192kbps, 44.1kHz, padded by a byte
+        let header = FrameHeader::parse(&[0xFF, 0xFD, 0xA2, 0x04]).unwrap();
+        assert_eq!((header.layer, header.bitrate_kbps), (2, 192));
+        assert_eq!(header.frame_size(), 627);
+
+        // MPEG-1 Layer I, 384kbps, 44.1kHz, padded by a slot
+        let header = FrameHeader::parse(&[0xFF, 0xFF, 0xC2, 0x04]).unwrap();
+        assert_eq!((header.layer, header.bitrate_kbps), (1, 384));
+        assert_eq!(header.frame_size(), 420);
+        assert!((header.duration_ms() - 8.707).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_mpeg2_chunks_are_paced_by_their_frames() {
+        let header = FrameHeader::parse(&[0xFF, 0xF3, 0x80, 0xC4]).unwrap();
+        let (data, duration_ms) = silence(&header, 1000.0);
+        assert_eq!(data.len(), 39 * 208);
+        let (cut, whole_ms) = whole_frames(&data[..data.len() - 100]).unwrap();
+        assert_eq!(cut, 38 * 208);
+        assert!((whole_ms - duration_ms * 38.0 / 39.0).abs() < 0.001);
+
+        // No global gain in Layer II frames; Layer III LSF frames have one granule per channel
+        let mut layer2 = silent_frame(&FrameHeader::parse(&[0xFF, 0xFD, 0xA0, 0x04]).unwrap());
+        assert!(!apply_gain(&mut layer2, 2));
+        let mut frame = data[..208].to_vec();
+        write_bits(&mut frame, 32 + 9, 12, 100); // part2_3_length
+        assert!(apply_gain(&mut frame, 2));
+        assert_eq!(read_bits(&frame, 32 + 9 + 21, 8), 2);
+    }
+
+    #[test]
+    fn test_frame_size_with_padding() {
+        assert_eq!(calculate_frame_size(&[0xFF, 0xFB, 0x92, 0x64]), Some(418));
+        // 320kbps at 48kHz
+        assert_eq!(calculate_frame_size(&[0xFF, 0xFB, 0xE4, 0x00]), Some(960));
+    }
+
+    #[test]
+    fn test_rejects_invalid_headers() {
+        assert!(FrameHeader::parse(&[0x49, 0x44, 0x33, 0x04]).is_none()); // "ID3"
+        assert!(FrameHeader::parse(&[0xFF, 0xEB, 0x90, 0x64]).is_none()); // Reserved version
+        assert!(FrameHeader::parse(&[0xFF, 0xF9, 0x90, 0x64]).is_none()); // Reserved layer
+        assert!(FrameHeader::parse(&[0xFF, 0xFB, 0x9C, 0x64]).is_none()); // Reserved sample rate
+        assert!(FrameHeader::parse(&[0xFF, 0xFB, 0x00, 0x64]).is_none()); // Free format
+        assert!(FrameHeader::parse(&[0xFF, 0xFB]).is_none());
+    }
+
+    #[test]
+    fn test_split_frames() {
+        let header = FrameHeader::parse(&HEADER_128K).unwrap();
+        let frame = silent_frame(&header);
+
+        // ID3v2 tag with a 6-byte body, two frames, junk, a frame and a truncated frame
+        let mut data = vec![b'I', b'D', b'3', 4, 0, 0, 0, 0, 0, 6, 1, 2, 3, 4, 5, 6];
+        assert_eq!(id3v2_len(&data), 16);
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(b"junk");
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(&frame[..100]);
+
+        let frames = split_frames(&data);
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().all(|(_, bytes)| bytes.len() == 417));
+    }
+
+    #[test]
+    fn test_whole_frames() {
+        let header = FrameHeader::parse(&HEADER_128K).unwrap();
+        let frame = silent_frame(&header);
+
+        let mut data = frame.repeat(2);
+        assert_eq!(whole_frames(&data), Some((834, 2.0 * header.duration_ms())));
+        data.extend_from_slice(&frame[..2]);
+        assert_eq!(whole_frames(&data).unwrap().0, 834);
+        data.extend_from_slice(&frame[2..100]);
+        assert_eq!(whole_frames(&data).unwrap().0, 834);
+        assert_eq!(whole_frames(&frame[..100]), Some((0, 0.0)));
+        assert_eq!(whole_frames(&[frame.clone(), b"junk".to_vec()].concat()), None);
+        assert_eq!(whole_frames(b"junk"), None);
+    }
+
+    #[test]
+    fn test_silent_frame() {
+        let padded_with_crc = FrameHeader::parse(&[0xFF, 0xFA, 0x92, 0x64]).unwrap();
+        let frame = silent_frame(&padded_with_crc);
+
+        let header = FrameHeader::parse(&frame).unwrap();
+        assert!(!header.protected);
+        assert!(!header.padding);
+        assert_eq!(frame.len(), header.frame_size());
+        assert!(frame[4..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_silence_covers_duration() {
+        let header = FrameHeader::parse(&HEADER_128K).unwrap();
+        let (data, duration) = silence(&header, 100.0);
+
+        assert_eq!(data.len(), 4 * 417);
+        assert!(duration >= 100.0);
+        assert_eq!(silence(&header, 0.0).0.len(), 0);
+    }
+
+    // Peak sample of MPEG audio, decoded
+    fn decoded_peak(mp3: Vec<u8>) -> f32 {
+        use symphonia::core::{audio::SampleBuffer, codecs::DecoderOptions, formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint};
+        let source = MediaSourceStream::new(Box::new(std::io::Cursor::new(mp3)), Default::default());
+        let mut format = symphonia::default::get_probe()
+            .format(Hint::new().with_extension("mp3"), source, &FormatOptions::default(), &MetadataOptions::default())
+            .unwrap()
+            .format;
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&format.default_track().unwrap().codec_params, &DecoderOptions::default())
+            .unwrap();
+        let mut peak = 0f32;
+        while let Ok(packet) = format.next_packet() {
+            let decoded = decoder.decode(&packet).unwrap();
+            let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+            samples.copy_interleaved_ref(decoded);
+            peak = samples.samples().iter().fold(peak, |peak, sample| peak.max(sample.abs()));
+        }
+        peak
+    }
+
+    #[test]
+    fn test_apply_gain() {
+        let mp3 = std::fs::read("music/Singing Birds.mp3").unwrap();
+        let original: Vec<u8> = split_frames(&mp3).into_iter().take(200).flat_map(|(_, frame)| frame.to_vec()).collect();
+
+        // -6 dB halves every sample
+        let mut quieter = original.clone();
+        let mut offset = 0;
+        while offset < quieter.len() {
+            let size = calculate_frame_size(&quieter[offset..]).unwrap();
+            assert!(apply_gain(&mut quieter[offset..offset + size], gain_steps(-6.0)));
+            offset += size;
+        }
+        assert_eq!(quieter.len(), original.len());
+        let ratio = decoded_peak(quieter) / decoded_peak(original);
+        assert!((ratio - 0.5).abs() < 0.01, "ratio {}", ratio);
+
+        assert!(!apply_gain(&mut HEADER_128K.to_vec(), 1)); // Header only
+        assert_eq!(gain_steps(4.0), 2);
+        assert_eq!(gain_steps(-4.0), -3);
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789".iter()), 0xAEE7);
+    }
+
+    #[test]
+    fn test_trailing_tags_are_not_frames() {
+        let frame = silent_frame(&FrameHeader::parse(&HEADER_128K).unwrap());
+        let mut id3v1 = b"TAG".to_vec();
+        id3v1.resize(128, 0xFF); // 0xFF bytes that look like frame sync
+        let mut ape = b"APETAGEX".to_vec();
+        ape.extend_from_slice(&2000u32.to_le_bytes()); // Version
+        ape.extend_from_slice(&(32u32 + 40).to_le_bytes()); // One 40-byte item and the footer
+        ape.extend_from_slice(&1u32.to_le_bytes());
+        ape.extend_from_slice(&0x8000_0000u32.to_le_bytes()); // With a header
+        ape.extend_from_slice(&[0; 8]);
+        let ape_tag = [ape.clone(), vec![0xFF; 40], ape].concat();
+
+        let file = [frame.repeat(3), ape_tag.clone(), id3v1.clone()].concat();
+        assert_eq!(trailing_tags_len(&file[file.len() - TRAILING_TAGS_PROBE..]), ape_tag.len() + 128);
+        assert_eq!(audio_bytes(&file), frame.repeat(3).as_slice());
+        assert_eq!(split_frames(&file).len(), 3);
+
+        let file = [frame.repeat(2), id3v1].concat();
+        assert_eq!(audio_bytes(&file).len(), frame.len() * 2);
+        assert_eq!(trailing_tags_len(&frame), 0);
+    }
+
+    #[test]
+    fn test_stacked_and_appended_id3v2_tags_are_not_frames() {
+        let frame = silent_frame(&FrameHeader::parse(&HEADER_128K).unwrap());
+        // Tags with an 8-byte body of frame-sync bytes, as a picture can hold
+        let tag = |id: &[u8; 3], flags: u8| [&id[..], &[4, 0, flags, 0, 0, 0, 8], &[0xFF; 8]].concat();
+        let appended = [tag(b"ID3", 0x10), b"3DI\x04\x00\x10\x00\x00\x00\x08".to_vec()].concat();
+
+        let file = [tag(b"ID3", 0), tag(b"ID3", 0), frame.repeat(2), appended.clone()].concat();
+        assert_eq!(leading_tags_len(&file), 36);
+        assert_eq!(trailing_tags_len(&file), appended.len());
+        assert_eq!(audio_bytes(&file), frame.repeat(2).as_slice());
+    }
+}
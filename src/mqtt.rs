@@ -0,0 +1,269 @@
+// Minimal MQTT 3.1.1 publisher (QoS 0, retained) for now-playing, listener and health topics
+// Only the handful of packets needed to publish are implemented: CONNECT, PUBLISH, PINGREQ
+
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::{interval, sleep, timeout, MissedTickBehavior},
+};
+use tracing::{info, warn, debug};
+
+use crate::{config::Config, radio::RadioStation};
+
+const KEEP_ALIVE_SECS: u16 = 30;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct MqttPublisher {
+    broker: String,
+    client_id: String,
+    topic_prefix: String,
+    username: Option<String>,
+    password: Option<String>,
+    publish_interval: Duration,
+}
+
+impl MqttPublisher {
+    /// Returns `None` when no broker is configured
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let broker = config.mqtt_broker.clone()?;
+        Some(Self {
+            broker,
+            client_id: config.mqtt_client_id.clone(),
+            topic_prefix: config.mqtt_topic_prefix.trim_end_matches('/').to_string(),
+            username: config.mqtt_username.clone(),
+            password: config.mqtt_password.clone(),
+            publish_interval: Duration::from_secs(config.mqtt_publish_interval_secs.max(1)),
+        })
+    }
+
+    pub fn spawn(self, station: Arc<RadioStation>) {
+        tokio::spawn(async move {
+            let mut delay = Duration::from_secs(1);
+            loop {
+                info!("Connecting to MQTT broker {}", self.broker);
+                if let Err(e) = self.run_session(&station).await {
+                    warn!("MQTT session ended: {} (reconnecting in {}s)", e, delay.as_secs());
+                }
+                sleep(delay).await;
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        });
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.topic_prefix, name)
+    }
+
+    async fn run_session(&self, station: &RadioStation) -> std::io::Result<()> {
+        let mut stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(&self.broker)).await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "connect timed out"))??;
+
+        // Broker publishes "offline" on our behalf if the connection drops uncleanly
+        let will = (self.topic("health"), br#"{"status":"offline"}"#.to_vec());
+        stream.write_all(&encode_connect(
+            &self.client_id,
+            KEEP_ALIVE_SECS,
+            self.username.as_deref(),
+            self.password.as_deref(),
+            Some((&will.0, &will.1)),
+        )).await?;
+
+        let mut connack = [0u8; 4];
+        timeout(CONNECT_TIMEOUT, stream.read_exact(&mut connack)).await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "CONNACK timed out"))??;
+        if connack[0] != 0x20 || connack[3] != 0 {
+            return Err(std::io::Error::other(format!("broker refused connection (code {})", connack[3])));
+        }
+        info!("MQTT connected, publishing under '{}/'", self.topic_prefix);
+
+        let (mut reader, mut writer) = stream.into_split();
+
+        // Drain PINGRESP and anything else the broker sends; EOF ends the session
+        let mut drain = tokio::spawn(async move {
+            let mut buf = [0u8; 256];
+            loop {
+                match reader.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+            }
+        });
+
+        let mut ticker = interval(Duration::from_secs(1));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut last_now_playing = String::new();
+        let mut last_listeners = usize::MAX;
+        let mut last_health = tokio::time::Instant::now() - self.publish_interval;
+        let mut last_packet = tokio::time::Instant::now();
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = &mut drain => return Err(std::io::Error::other("broker closed the connection")),
+            }
+
+            let now_playing = station.get_now_playing();
+            let track_key = format!("{}|{}", now_playing.artist, now_playing.title);
+            if track_key != last_now_playing {
+                debug!("MQTT publishing now-playing");
+                writer.write_all(&encode_publish(&self.topic("now-playing"), serde_json::to_string(&now_playing)?.as_bytes(), true)).await?;
+                last_now_playing = track_key;
+                last_packet = tokio::time::Instant::now();
+            }
+
+            let listeners = station.listener_count();
+            if listeners != last_listeners {
+                let payload = serde_json::json!({ "listeners": listeners }).to_string();
+                writer.write_all(&encode_publish(&self.topic("listeners"), payload.as_bytes(), true)).await?;
+                last_listeners = listeners;
+                last_packet = tokio::time::Instant::now();
+            }
+
+            if last_health.elapsed() >= self.publish_interval {
+                let payload = serde_json::json!({
+                    "status": "online",
+                    "is_broadcasting": station.is_broadcasting(),
+                    "listeners": listeners,
+                    "uptime": station.uptime_seconds(),
+                }).to_string();
+                writer.write_all(&encode_publish(&self.topic("health"), payload.as_bytes(), true)).await?;
+                last_health = tokio::time::Instant::now();
+                last_packet = last_health;
+            }
+
+            // Keep the session alive when nothing else was sent
+            if last_packet.elapsed() >= Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2) {
+                writer.write_all(&[0xC0, 0x00]).await?; // PINGREQ
+                last_packet = tokio::time::Instant::now();
+            }
+        }
+    }
+}
+
+fn encode_remaining_length(mut len: usize, out: &mut Vec<u8>) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+fn push_str(value: &str, out: &mut Vec<u8>) {
+    push_bytes(value.as_bytes(), out);
+}
+
+fn push_bytes(value: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    out.extend_from_slice(value);
+}
+
+fn encode_connect(
+    client_id: &str,
+    keep_alive: u16,
+    username: Option<&str>,
+    password: Option<&str>,
+    will: Option<(&str, &[u8])>,
+) -> Vec<u8> {
+    let mut flags = 0x02; // Clean session
+    if will.is_some() {
+        flags |= 0x04 | 0x20; // Will flag, will retain (QoS 0)
+    }
+    if username.is_some() {
+        flags |= 0x80;
+    }
+    if password.is_some() {
+        flags |= 0x40;
+    }
+
+    let mut body = Vec::new();
+    push_str("MQTT", &mut body);
+    body.push(0x04); // Protocol level 3.1.1
+    body.push(flags);
+    body.extend_from_slice(&keep_alive.to_be_bytes());
+
+    push_str(client_id, &mut body);
+    if let Some((topic, message)) = will {
+        push_str(topic, &mut body);
+        push_bytes(message, &mut body);
+    }
+    if let Some(username) = username {
+        push_str(username, &mut body);
+    }
+    if let Some(password) = password {
+        push_str(password, &mut body);
+    }
+
+    let mut packet = vec![0x10];
+    encode_remaining_length(body.len(), &mut packet);
+    packet.extend_from_slice(&body);
+    packet
+}
+
+fn encode_publish(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+    push_str(topic, &mut body);
+    body.extend_from_slice(payload);
+
+    let mut packet = vec![if retain { 0x31 } else { 0x30 }];
+    encode_remaining_length(body.len(), &mut packet);
+    packet.extend_from_slice(&body);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remaining_length_encoding() {
+        let cases: [(usize, &[u8]); 4] = [
+            (0, &[0x00]),
+            (127, &[0x7F]),
+            (128, &[0x80, 0x01]),
+            (16_383, &[0xFF, 0x7F]),
+        ];
+        for (len, expected) in cases {
+            let mut out = Vec::new();
+            encode_remaining_length(len, &mut out);
+            assert_eq!(out, expected, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_publish_packet() {
+        let packet = encode_publish("a/b", b"hi", true);
+        assert_eq!(packet, vec![0x31, 0x07, 0x00, 0x03, b'a', b'/', b'b', b'h', b'i']);
+
+        let packet = encode_publish("t", b"", false);
+        assert_eq!(packet[0], 0x30);
+    }
+
+    #[test]
+    fn test_connect_packet_flags() {
+        let packet = encode_connect("radio", 30, Some("user"), Some("pass"), Some(("radio/health", b"offline")));
+
+        assert_eq!(packet[0], 0x10);
+        // Fixed header (2) + protocol name (6) + level (1) => flags at index 9
+        assert_eq!(&packet[2..8], &[0x00, 0x04, b'M', b'Q', b'T', b'T']);
+        assert_eq!(packet[8], 0x04);
+        assert_eq!(packet[9], 0x80 | 0x40 | 0x20 | 0x04 | 0x02);
+        assert_eq!(&packet[10..12], &30u16.to_be_bytes());
+        assert_eq!(packet[1] as usize, packet.len() - 2);
+    }
+
+    #[test]
+    fn test_connect_packet_minimal() {
+        let packet = encode_connect("id", 60, None, None, None);
+        assert_eq!(packet[9], 0x02);
+        assert_eq!(&packet[12..], &[0x00, 0x02, b'i', b'd']);
+    }
+}
@@ -0,0 +1,162 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// An address assigned to a local network interface
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct InterfaceAddr {
+    pub name: String, // eth0, wlan0, en0, ... ("default" where interfaces can't be listed)
+    pub addr: IpAddr,
+}
+
+impl InterfaceAddr {
+    /// Reachable from other machines: not loopback, unspecified or IPv6
+    /// link-local (those need a zone id that URLs can't carry portably)
+    pub fn is_shareable(&self) -> bool {
+        match self.addr {
+            IpAddr::V4(v4) => !v4.is_loopback() && !v4.is_unspecified() && !v4.is_link_local(),
+            IpAddr::V6(v6) => !v6.is_loopback() && !v6.is_unspecified() && (v6.segments()[0] & 0xffc0) != 0xfe80,
+        }
+    }
+
+    pub fn url(&self, scheme: &str, port: u16) -> String {
+        url(scheme, self.addr, port)
+    }
+}
+
+/// Base URL for an address, bracketing IPv6
+pub fn url(scheme: &str, addr: IpAddr, port: u16) -> String {
+    match addr {
+        IpAddr::V4(v4) => format!("{}://{}:{}", scheme, v4, port),
+        IpAddr::V6(v6) => format!("{}://[{}]:{}", scheme, v6, port),
+    }
+}
+
+/// Addresses of all interfaces that are up, IPv4 first, in interface order
+pub fn interfaces() -> std::io::Result<Vec<InterfaceAddr>> {
+    let mut addrs = list()?;
+    addrs.sort_by_key(|iface| iface.addr.is_ipv6());
+    Ok(addrs)
+}
+
+/// Addresses other devices can use to reach this host
+pub fn shareable_addrs() -> Vec<InterfaceAddr> {
+    interfaces()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(InterfaceAddr::is_shareable)
+        .collect()
+}
+
+/// Listen on all IPv6 and IPv4 addresses with one socket (IPv4 clients show up as
+/// v4-mapped addresses); fails where the host has no IPv6 support
+pub fn bind_dual_stack(port: u16) -> std::io::Result<std::net::TcpListener> {
+    use socket2::{Domain, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, None)?;
+    socket.set_only_v6(false)?;
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&std::net::SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+#[cfg(unix)]
+fn list() -> std::io::Result<Vec<InterfaceAddr>> {
+    let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs allocates a list that is released with freeifaddrs below
+    if unsafe { libc::getifaddrs(&mut head) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut addrs = Vec::new();
+    let mut cursor = head;
+    while !cursor.is_null() {
+        // SAFETY: every node comes from getifaddrs and stays valid until freeifaddrs
+        let ifa = unsafe { &*cursor };
+        cursor = ifa.ifa_next;
+        if ifa.ifa_addr.is_null() || ifa.ifa_flags & libc::IFF_UP as libc::c_uint == 0 {
+            continue;
+        }
+        // SAFETY: ifa_addr is non-null and its family says which sockaddr it is
+        let addr = unsafe {
+            match (*ifa.ifa_addr).sa_family as libc::c_int {
+                libc::AF_INET => {
+                    let sin = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    IpAddr::V4(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)))
+                }
+                libc::AF_INET6 => {
+                    let sin6 = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                    IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr))
+                }
+                _ => continue,
+            }
+        };
+        // SAFETY: ifa_name is a NUL-terminated string owned by the list
+        let name = unsafe { std::ffi::CStr::from_ptr(ifa.ifa_name) }.to_string_lossy().into_owned();
+        addrs.push(InterfaceAddr { name, addr });
+    }
+
+    // SAFETY: head came from a successful getifaddrs call and is freed once
+    unsafe { libc::freeifaddrs(head) };
+    Ok(addrs)
+}
+
+// Without getifaddrs, ask the routing table which local address reaches the
+// internet; connecting a UDP socket sends no packets
+#[cfg(not(unix))]
+fn list() -> std::io::Result<Vec<InterfaceAddr>> {
+    let probe = |bind: &str, target: &str| -> Option<IpAddr> {
+        let socket = std::net::UdpSocket::bind(bind).ok()?;
+        socket.connect(target).ok()?;
+        Some(socket.local_addr().ok()?.ip())
+    };
+    Ok([probe("0.0.0.0:0", "192.0.2.1:80"), probe("[::]:0", "[2001:db8::1]:80")]
+        .into_iter()
+        .flatten()
+        .map(|addr| InterfaceAddr { name: "default".to_string(), addr })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iface(addr: &str) -> InterfaceAddr {
+        InterfaceAddr { name: "eth0".to_string(), addr: addr.parse().unwrap() }
+    }
+
+    #[test]
+    fn test_shareable() {
+        assert!(iface("192.168.1.20").is_shareable());
+        assert!(iface("2001:db8::20").is_shareable());
+        assert!(iface("fd00::1").is_shareable());
+        assert!(!iface("127.0.0.1").is_shareable());
+        assert!(!iface("::1").is_shareable());
+        assert!(!iface("fe80::1").is_shareable());
+        assert!(!iface("169.254.10.1").is_shareable());
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(iface("10.0.0.5").url("http", 8000), "http://10.0.0.5:8000");
+        assert_eq!(iface("2001:db8::20").url("https", 443), "https://[2001:db8::20]:443");
+    }
+
+    #[test]
+    fn test_dual_stack_accepts_ipv4() {
+        let Ok(listener) = bind_dual_stack(0) else {
+            return; // No IPv6 on this host
+        };
+        let port = listener.local_addr().unwrap().port();
+        assert!(std::net::TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lists_loopback() {
+        let addrs = interfaces().unwrap();
+        assert!(addrs.iter().any(|iface| iface.addr.is_loopback()), "{:?}", addrs);
+        // IPv4 addresses come first
+        assert!(addrs.windows(2).all(|pair| pair[0].addr.is_ipv4() || pair[1].addr.is_ipv6()));
+    }
+}
@@ -0,0 +1,345 @@
+// Now-playing posts to chat: Discord and Slack incoming webhooks and a Telegram
+// bot, so the station's community sees what's on air
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use serde_json::{json, Value};
+use tokio::time::{sleep, sleep_until, Instant};
+use tracing::{debug, info, warn};
+
+use crate::{config::Config, playlist, radio::RadioStation, types::NowPlaying};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(2); // Doubled after each failed attempt
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+const TELEGRAM_API: &str = "https://api.telegram.org";
+
+#[derive(Debug, Clone)]
+enum Channel {
+    Discord(String), // Webhook URL
+    Slack(String),   // Incoming webhook URL
+    Telegram { bot_url: String, chat_id: String }, // bot_url: https://api.telegram.org/bot<token>
+}
+
+impl Channel {
+    fn name(&self) -> &'static str {
+        match self {
+            Channel::Discord(_) => "Discord",
+            Channel::Slack(_) => "Slack",
+            Channel::Telegram { .. } => "Telegram",
+        }
+    }
+
+    /// Where to POST the message and the JSON body
+    fn request(&self, message: &TrackMessage) -> (String, Value) {
+        match self {
+            Channel::Discord(url) => (url.clone(), discord_payload(message)),
+            Channel::Slack(url) => (url.clone(), slack_payload(message)),
+            Channel::Telegram { bot_url, chat_id } => {
+                let (method, body) = telegram_payload(message, chat_id);
+                (format!("{}/{}", bot_url, method), body)
+            }
+        }
+    }
+}
+
+/// A track change as posted to chat
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackMessage {
+    pub station: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration: Option<u64>, // Seconds
+    pub artwork_url: Option<String>,
+    pub listen_url: Option<String>,
+}
+
+impl TrackMessage {
+    fn headline(&self) -> String {
+        match self.artist.as_str() {
+            "" => self.title.clone(),
+            artist => format!("{} - {}", artist, self.title),
+        }
+    }
+
+    /// "Album · 3:45", leaving out what isn't known
+    fn details(&self) -> String {
+        let mut details = Vec::new();
+        if !self.album.is_empty() && self.album != "Unknown" {
+            details.push(self.album.clone());
+        }
+        if let Some(duration) = self.duration.filter(|&d| d > 0) {
+            details.push(format_duration(duration));
+        }
+        details.join(" · ")
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    match secs / 3600 {
+        0 => format!("{}:{:02}", secs / 60, secs % 60),
+        hours => format!("{}:{:02}:{:02}", hours, secs / 60 % 60, secs % 60),
+    }
+}
+
+fn discord_payload(message: &TrackMessage) -> Value {
+    let mut embed = json!({
+        "author": { "name": format!("Now playing on {}", message.station) },
+        "title": message.title,
+        "description": message.artist,
+    });
+    if !message.details().is_empty() {
+        embed["footer"] = json!({ "text": message.details() });
+    }
+    if let Some(url) = &message.artwork_url {
+        embed["thumbnail"] = json!({ "url": url });
+    }
+    if let Some(url) = &message.listen_url {
+        embed["url"] = url.clone().into();
+    }
+    json!({ "username": message.station, "embeds": [embed] })
+}
+
+fn slack_payload(message: &TrackMessage) -> Value {
+    let escape = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    let mut text = format!("*{}*\n{}", escape(&message.title), escape(&message.artist));
+    if !message.details().is_empty() {
+        text += &format!("\n{}", escape(&message.details()));
+    }
+    if let Some(url) = &message.listen_url {
+        text += &format!("\n<{}|Listen live>", url);
+    }
+    let mut section = json!({ "type": "section", "text": { "type": "mrkdwn", "text": text } });
+    if let Some(url) = &message.artwork_url {
+        section["accessory"] = json!({ "type": "image", "image_url": url, "alt_text": "Album art" });
+    }
+    json!({
+        // Shown in notifications, where blocks aren't
+        "text": format!("Now playing on {}: {}", message.station, message.headline()),
+        "blocks": [section],
+    })
+}
+
+/// The Bot API method and its body: a photo with a caption when there's
+/// artwork, else a text message
+fn telegram_payload(message: &TrackMessage, chat_id: &str) -> (&'static str, Value) {
+    let escape = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    let mut text = format!("🎵 <b>{}</b>\n{}", escape(&message.title), escape(&message.artist));
+    if !message.details().is_empty() {
+        text += &format!("\n<i>{}</i>", escape(&message.details()));
+    }
+    if let Some(url) = &message.listen_url {
+        text += &format!("\n<a href=\"{}\">Listen live on {}</a>", escape(url), escape(&message.station));
+    }
+    match &message.artwork_url {
+        Some(url) => ("sendPhoto", json!({ "chat_id": chat_id, "photo": url, "caption": text, "parse_mode": "HTML" })),
+        None => ("sendMessage", json!({
+            "chat_id": chat_id,
+            "text": text,
+            "parse_mode": "HTML",
+            "disable_web_page_preview": true,
+        })),
+    }
+}
+
+/// Seconds to wait before retrying a rate-limited request: Retry-After, or the
+/// `retry_after` Discord and Telegram put in the body
+fn retry_after(headers: &reqwest::header::HeaderMap, body: &Value) -> Option<Duration> {
+    let secs = headers.get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .or_else(|| body["retry_after"].as_f64())
+        .or_else(|| body["parameters"]["retry_after"].as_f64())?;
+    Some(Duration::from_secs_f64(secs.max(0.0)).min(MAX_RETRY_AFTER))
+}
+
+pub struct Notifier {
+    channels: Vec<Channel>,
+    min_interval: Duration,
+    station_name: String,
+    public_url: Option<String>,
+    music_dir: PathBuf,
+    http: reqwest::Client,
+}
+
+impl Notifier {
+    /// Returns `None` when no chat is configured
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let mut channels = Vec::new();
+        if let Some(url) = &config.notify_discord_webhook_url {
+            channels.push(Channel::Discord(url.clone()));
+        }
+        if let Some(url) = &config.notify_slack_webhook_url {
+            channels.push(Channel::Slack(url.clone()));
+        }
+        match (&config.notify_telegram_bot_token, &config.notify_telegram_chat_id) {
+            (Some(token), Some(chat_id)) => channels.push(Channel::Telegram {
+                bot_url: format!("{}/bot{}", TELEGRAM_API, token),
+                chat_id: chat_id.clone(),
+            }),
+            (Some(_), None) | (None, Some(_)) => {
+                warn!("Telegram notifications need both NOTIFY_TELEGRAM_BOT_TOKEN and NOTIFY_TELEGRAM_CHAT_ID");
+            }
+            (None, None) => {}
+        }
+        if channels.is_empty() {
+            return None;
+        }
+
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Some(Self {
+            channels,
+            min_interval: Duration::from_secs(config.notify_min_interval_secs),
+            station_name: config.station_name.clone(),
+            public_url: config.public_url.as_ref().map(|url| url.trim_end_matches('/').to_string()),
+            music_dir: config.music_dir.clone(),
+            http,
+        })
+    }
+
+    /// Post every track change, at most one message per NOTIFY_MIN_INTERVAL_SECS:
+    /// tracks that come and go within the interval are skipped, and what's on
+    /// air when it ends is posted
+    pub fn spawn(self, station: Arc<RadioStation>) {
+        let names: Vec<_> = self.channels.iter().map(Channel::name).collect();
+        info!("Posting now-playing to {}", names.join(", "));
+        tokio::spawn(async move {
+            let mut changes = station.track_changes_for(None);
+            let mut last_posted = None;
+            let mut last_sent: Option<Instant> = None;
+            while changes.changed().await.is_ok() {
+                if let Some(sent) = last_sent {
+                    sleep_until(sent + self.min_interval).await;
+                }
+                changes.borrow_and_update();
+
+                if station.current_track().is_none() && station.metadata_override().is_none() {
+                    continue;
+                }
+                let now_playing = station.get_now_playing();
+                let key = (now_playing.artist.clone(), now_playing.title.clone());
+                if last_posted.as_ref() == Some(&key) {
+                    continue;
+                }
+                last_posted = Some(key);
+                last_sent = Some(Instant::now());
+
+                let message = self.message(&station, &now_playing).await;
+                debug!("Posting now-playing: {}", message.headline());
+                futures::future::join_all(self.channels.iter().map(|channel| self.deliver(channel, &message))).await;
+            }
+        });
+    }
+
+    async fn message(&self, station: &RadioStation, now_playing: &NowPlaying) -> TrackMessage {
+        // Artwork is linked, so chat services need the public URL to fetch it
+        let track = station.metadata_override().is_none().then(|| station.current_track()).flatten();
+        let artwork_url = match (&self.public_url, track) {
+            (Some(base), Some(track)) if !track.id.is_nil() => {
+                let path = if track.path.is_absolute() { track.path.clone() } else { self.music_dir.join(&track.path) };
+                let has_artwork = tokio::task::spawn_blocking(move || playlist::read_artwork(&path).is_some())
+                    .await
+                    .unwrap_or(false);
+                has_artwork.then(|| format!("{}/api/v1/tracks/{}/artwork", base, track.id))
+            }
+            _ => None,
+        };
+        TrackMessage {
+            station: self.station_name.clone(),
+            title: now_playing.title.clone(),
+            artist: now_playing.artist.clone(),
+            album: now_playing.album.clone(),
+            duration: now_playing.duration,
+            artwork_url,
+            listen_url: self.public_url.as_ref().map(|base| format!("{}/", base)),
+        }
+    }
+
+    /// POST to one channel, retrying network errors, 5xx and 429 (after the wait
+    /// the service asks for)
+    async fn deliver(&self, channel: &Channel, message: &TrackMessage) {
+        let (url, body) = channel.request(message);
+        let mut delay = RETRY_DELAY;
+        for attempt in 1..=ATTEMPTS {
+            match self.http.post(&url).json(&body).send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!("{} notification delivered", channel.name());
+                    return;
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let headers = response.headers().clone();
+                    let reply: Value = response.json().await.unwrap_or_default();
+                    if status != reqwest::StatusCode::TOO_MANY_REQUESTS && !status.is_server_error() {
+                        warn!("{} notification rejected with {}: {}", channel.name(), status, reply);
+                        return;
+                    }
+                    if let Some(wait) = retry_after(&headers, &reply) {
+                        delay = wait;
+                    }
+                    warn!("{} notification failed with {} (attempt {}/{})", channel.name(), status, attempt, ATTEMPTS);
+                }
+                // Not the URL: Telegram's carries the bot token
+                Err(e) => warn!("{} notification failed: {} (attempt {}/{})", channel.name(), e.without_url(), attempt, ATTEMPTS),
+            }
+            if attempt < ATTEMPTS {
+                sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> TrackMessage {
+        TrackMessage {
+            station: "Test FM".to_string(),
+            title: "Song <1> & more".to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            duration: Some(225),
+            artwork_url: Some("https://radio.example/api/v1/tracks/1/artwork".to_string()),
+            listen_url: Some("https://radio.example/".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_payloads() {
+        let discord = discord_payload(&message());
+        assert_eq!(discord["embeds"][0]["title"], "Song <1> & more");
+        assert_eq!(discord["embeds"][0]["footer"]["text"], "Album · 3:45");
+        assert_eq!(discord["embeds"][0]["thumbnail"]["url"], "https://radio.example/api/v1/tracks/1/artwork");
+
+        let slack = slack_payload(&message());
+        assert_eq!(slack["text"], "Now playing on Test FM: Artist - Song <1> & more");
+        let text = slack["blocks"][0]["text"]["text"].as_str().unwrap();
+        assert!(text.starts_with("*Song &lt;1&gt; &amp; more*\nArtist\nAlbum · 3:45"));
+        assert_eq!(slack["blocks"][0]["accessory"]["type"], "image");
+
+        let (method, body) = telegram_payload(&message(), "-100123");
+        assert_eq!(method, "sendPhoto");
+        assert_eq!(body["chat_id"], "-100123");
+        assert!(body["caption"].as_str().unwrap().contains("<b>Song &lt;1&gt; &amp; more</b>"));
+        let (method, body) = telegram_payload(&TrackMessage { artwork_url: None, duration: None, ..message() }, "42");
+        assert_eq!(method, "sendMessage");
+        assert!(body["text"].as_str().unwrap().contains("<i>Album</i>"));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(retry_after(&headers, &json!({ "retry_after": 1.5 })), Some(Duration::from_millis(1500)));
+        assert_eq!(retry_after(&headers, &json!({ "parameters": { "retry_after": 7 } })), Some(Duration::from_secs(7)));
+        assert_eq!(retry_after(&headers, &Value::Null), None);
+        headers.insert(reqwest::header::RETRY_AFTER, "3600".parse().unwrap());
+        assert_eq!(retry_after(&headers, &Value::Null), Some(MAX_RETRY_AFTER));
+        assert_eq!(format_duration(3725), "1:02:05");
+    }
+}
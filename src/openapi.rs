@@ -0,0 +1,750 @@
+// OpenAPI 3.0 description of the /api/v1 routes, served at /api/v1/openapi.json
+// and browsable at /api/v1/docs. The route table below is the annotation for each
+// handler: adding or changing an /api route means updating its entry here, and
+// the schemas mirror the webradio-types structs (a test keeps them in step).
+
+use serde_json::{json, Map, Value};
+
+/// Where a parameter is read from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum In {
+    Path,
+    Query,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: &'static str,
+    pub location: In,
+    pub kind: &'static str, // JSON Schema type: "string", "integer", "number", "boolean"
+    pub required: bool,
+    pub description: &'static str,
+}
+
+/// Successful response of an endpoint
+#[derive(Debug, Clone, Copy)]
+pub enum Reply {
+    Schema(&'static str),   // JSON described by a schema in `components/schemas`
+    Object,                 // JSON whose shape follows server internals
+    Media(&'static str),    // Non-JSON body with this content type
+    NoContent,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Endpoint {
+    pub method: &'static str,
+    pub path: &'static str, // Under API_PREFIX; OpenAPI template, `{id}` rather than axum's `:id`
+    pub tag: &'static str,
+    pub summary: &'static str,
+    pub admin: bool,
+    pub params: &'static [Param],
+    pub body: Option<&'static str>, // Request body schema
+    pub reply: Reply,
+}
+
+/// Current API version. The unversioned `/api` paths serve the same routes as v1
+pub const API_PREFIX: &str = "/api/v1";
+
+const fn query(name: &'static str, kind: &'static str, description: &'static str) -> Param {
+    Param { name, location: In::Query, kind, required: false, description }
+}
+
+const fn required_query(name: &'static str, kind: &'static str, description: &'static str) -> Param {
+    Param { name, location: In::Query, kind, required: true, description }
+}
+
+const fn path(name: &'static str, kind: &'static str, description: &'static str) -> Param {
+    Param { name, location: In::Path, kind, required: true, description }
+}
+
+const LISTENER: Param = query("listener", "string", "X-Listener-Id of the caller's stream");
+
+pub const ENDPOINTS: &[Endpoint] = &[
+    Endpoint {
+        method: "get", path: "/now-playing", tag: "now-playing", admin: false,
+        summary: "Current track; with `listener`, the track that listener is hearing. Answers If-None-Match with 304 until the track or listener count changes",
+        params: &[LISTENER], body: None, reply: Reply::Schema("NowPlaying"),
+    },
+    Endpoint {
+        method: "get", path: "/listeners", tag: "now-playing", admin: false,
+        summary: "Listener count, MAX_LISTENERS and uptime",
+        params: &[], body: None, reply: Reply::Schema("Listeners"),
+    },
+    Endpoint {
+        method: "post", path: "/vote-skip", tag: "now-playing", admin: false,
+        summary: "Vote to skip the current track, one vote per connection per track",
+        params: &[Param { required: true, ..LISTENER }], body: None, reply: Reply::Schema("SkipVote"),
+    },
+    Endpoint {
+        method: "get", path: "/sync", tag: "now-playing", admin: false,
+        summary: "Multi-room sync clock",
+        params: &[query("t0", "number", "Client clock in ms, echoed back"), LISTENER],
+        body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/playlist", tag: "playlist", admin: false,
+        summary: "Full playlist with the next track in rotation. Answers If-None-Match with 304 while unchanged",
+        params: &[], body: None, reply: Reply::Schema("Playlist"),
+    },
+    Endpoint {
+        method: "get", path: "/next-up", tag: "playlist", admin: false,
+        summary: "The tracks that will play after the current one, without advancing the rotation",
+        params: &[query("count", "integer", "How many, 1-50 (default 5)")],
+        body: None, reply: Reply::Schema("NextUp"),
+    },
+    Endpoint {
+        method: "get", path: "/search", tag: "playlist", admin: false,
+        summary: "Library tracks matching every word in title, artist or album, ignoring case and diacritics",
+        params: &[
+            required_query("q", "string", "Search words"),
+            query("limit", "integer", "How many, 1-100 (default 20)"),
+        ],
+        body: None, reply: Reply::Schema("SearchResults"),
+    },
+    Endpoint {
+        method: "post", path: "/playlist/tracks", tag: "playlist", admin: true,
+        summary: "Put an excluded track (by id) or a file from the music directory (by path) into rotation",
+        params: &[], body: Some("TrackAddRequest"), reply: Reply::Schema("Playlist"),
+    },
+    Endpoint {
+        method: "delete", path: "/playlist/tracks", tag: "playlist", admin: true,
+        summary: "Take a track out of rotation",
+        params: &[required_query("id", "string", "Track id from /api/playlist")],
+        body: None, reply: Reply::Schema("Playlist"),
+    },
+    Endpoint {
+        method: "put", path: "/playlist/order", tag: "playlist", admin: true,
+        summary: "Reorder the playlist, listing every track once",
+        params: &[], body: Some("PlaylistOrderRequest"), reply: Reply::Schema("Playlist"),
+    },
+    Endpoint {
+        method: "post", path: "/playlist/play-next", tag: "playlist", admin: true,
+        summary: "Play a track after the current one",
+        params: &[], body: Some("TrackIdRequest"), reply: Reply::Schema("Playlist"),
+    },
+    Endpoint {
+        method: "put", path: "/playlist/enabled", tag: "playlist", admin: true,
+        summary: "Bench a track, optionally with a reason and an end time, or return it to rotation",
+        params: &[], body: Some("TrackEnabledRequest"), reply: Reply::Schema("Playlist"),
+    },
+    Endpoint {
+        method: "get", path: "/tracks/{id}/audio", tag: "playlist", admin: false,
+        summary: "A track's file as stored (Range supported); needs admin credentials or a signed expires/token pair",
+        params: &[
+            path("id", "string", "Track id from /api/playlist"),
+            query("expires", "integer", "From /api/stream-token"),
+            query("token", "string", "From /api/stream-token"),
+        ],
+        body: None, reply: Reply::Media("audio/mpeg"),
+    },
+    Endpoint {
+        method: "get", path: "/tracks/{id}/artwork", tag: "playlist", admin: false,
+        summary: "A track's embedded cover art (the front cover when there are several); 404 when it has none",
+        params: &[path("id", "string", "Track id from /api/playlist")],
+        body: None, reply: Reply::Media("image/*"),
+    },
+    Endpoint {
+        method: "get", path: "/stats", tag: "stats", admin: false,
+        summary: "Listener, stream health and client telemetry statistics",
+        params: &[], body: None, reply: Reply::Schema("Stats"),
+    },
+    Endpoint {
+        method: "get", path: "/stats/timeseries", tag: "stats", admin: false,
+        summary: "Per-minute listeners, peak concurrency and bytes sent, oldest first",
+        params: &[
+            query("from", "integer", "Unix seconds, default 24 hours before `to`"),
+            query("to", "integer", "Unix seconds, default now"),
+            query("step", "integer", "Bucket size in seconds, a multiple of 60"),
+        ],
+        body: None, reply: Reply::Schema("AudienceSeries"),
+    },
+    Endpoint {
+        method: "get", path: "/metrics", tag: "stats", admin: false,
+        summary: "Process CPU, memory and per-subsystem busy time",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/metrics/history", tag: "stats", admin: false,
+        summary: "Sampled metrics, oldest first",
+        params: &[query("minutes", "integer", "How far back to go")],
+        body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/health", tag: "stats", admin: false,
+        summary: "Liveness and broadcast state",
+        params: &[], body: None, reply: Reply::Schema("Health"),
+    },
+    Endpoint {
+        method: "get", path: "/server-info", tag: "stats", admin: false,
+        summary: "Station name, version, shareable URLs, simulcast mounts and codecs",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/archive", tag: "archive", admin: false,
+        summary: "Recorded shows, newest first",
+        params: &[
+            query("show", "string", "Show name"),
+            query("q", "string", "Search show names and titles"),
+            query("date", "string", "YYYY-MM-DD"),
+            query("from", "string", "YYYY-MM-DD"),
+            query("to", "string", "YYYY-MM-DD"),
+        ],
+        body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/archive/{id}/chapters", tag: "archive", admin: false,
+        summary: "Per-track chapters of an archived show, as JSON or a CUE sheet",
+        params: &[path("id", "string", "Archive id"), query("format", "string", "json (default) or cue")],
+        body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/beacon/session", tag: "telemetry", admin: false,
+        summary: "Signed telemetry session for the web player",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "post", path: "/beacon", tag: "telemetry", admin: false,
+        summary: "Report player events; the body may be sent as text/plain",
+        params: &[], body: Some("BeaconPayload"), reply: Reply::NoContent,
+    },
+    Endpoint {
+        method: "post", path: "/telemetry", tag: "telemetry", admin: false,
+        summary: "Report the player's buffered seconds and stalls since the last report",
+        params: &[], body: Some("TelemetryReport"), reply: Reply::NoContent,
+    },
+    Endpoint {
+        method: "post", path: "/stream-token", tag: "admin", admin: true,
+        summary: "Mint a signed, expiring stream URL",
+        params: &[], body: Some("StreamTokenRequest"), reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/reports/royalty", tag: "admin", admin: true,
+        summary: "Monthly royalty report",
+        params: &[
+            required_query("month", "string", "YYYY-MM"),
+            query("format", "string", "soundexchange (default) or prs"),
+        ],
+        body: None, reply: Reply::Media("text/csv"),
+    },
+    Endpoint {
+        method: "get", path: "/debug", tag: "admin", admin: true,
+        summary: "Verbose broadcast internals",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/admin/profile", tag: "admin", admin: true,
+        summary: "Capture a CPU profile",
+        params: &[
+            query("seconds", "integer", "1-60, default 10"),
+            query("format", "string", "svg (default) or folded"),
+        ],
+        body: None, reply: Reply::Media("image/svg+xml"),
+    },
+    Endpoint {
+        method: "get", path: "/admin/churn", tag: "admin", admin: true,
+        summary: "Client IPs with short-lived streams in the last minute",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/admin/listeners", tag: "admin", admin: true,
+        summary: "Connected MP3 listeners with address, user agent, connected time and bytes sent",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "delete", path: "/admin/listeners/{id}", tag: "admin", admin: true,
+        summary: "Disconnect a listener, optionally banning its address",
+        params: &[
+            path("id", "string", "Listener id, or an unambiguous prefix such as the 8 characters /api/stats shows"),
+            query("ban", "boolean", "Also ban the listener's address"),
+            query("reason", "string", "Recorded with the ban"),
+        ],
+        body: None, reply: Reply::NoContent,
+    },
+    Endpoint {
+        method: "get", path: "/admin/bans", tag: "admin", admin: true,
+        summary: "Addresses refused at stream accept time",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "post", path: "/admin/bans", tag: "admin", admin: true,
+        summary: "Ban an address and disconnect its listeners",
+        params: &[], body: Some("BanRequest"), reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/admin/duplicates", tag: "playlist", admin: true,
+        summary: "Tracks that are the same recording by audio fingerprint, grouped with the highest-bitrate copy first",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "patch", path: "/admin/tracks/{id}", tag: "playlist", admin: true,
+        summary: "Correct a track's title, artist, album or genre, optionally writing them to the MP3's ID3 tag",
+        params: &[path("id", "string", "Track id from /api/playlist")],
+        body: Some("TrackTagsRequest"), reply: Reply::Schema("Track"),
+    },
+    Endpoint {
+        method: "get", path: "/admin/disabled", tag: "playlist", admin: true,
+        summary: "Benched tracks, with why and until when",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/admin/quarantine", tag: "playlist", admin: true,
+        summary: "Tracks taken out of rotation because they failed to open or decode, with the error",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "post", path: "/admin/metadata", tag: "now-playing", admin: true,
+        summary: "Show a custom title as now-playing instead of the track until cleared, e.g. during live segments",
+        params: &[], body: Some("MetadataOverrideRequest"), reply: Reply::Schema("NowPlaying"),
+    },
+    Endpoint {
+        method: "delete", path: "/admin/metadata", tag: "now-playing", admin: true,
+        summary: "Clear the now-playing override",
+        params: &[], body: None, reply: Reply::NoContent,
+    },
+    Endpoint {
+        method: "delete", path: "/admin/bans/{ip}", tag: "admin", admin: true,
+        summary: "Lift a ban",
+        params: &[path("ip", "string", "Banned address")],
+        body: None, reply: Reply::NoContent,
+    },
+    Endpoint {
+        method: "get", path: "/intercom", tag: "admin", admin: true,
+        summary: "Connected intercom peers; 404 while the intercom is off",
+        params: &[], body: None, reply: Reply::Object,
+    },
+    Endpoint {
+        method: "get", path: "/openapi.json", tag: "meta", admin: false,
+        summary: "This document",
+        params: &[], body: None, reply: Reply::Object,
+    },
+];
+
+/// The OpenAPI document for this server
+pub fn document(station_name: &str) -> Value {
+    let mut paths = Map::new();
+    for endpoint in ENDPOINTS {
+        let item = paths.entry(endpoint.path).or_insert_with(|| json!({}));
+        item[endpoint.method] = operation(endpoint);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": format!("{} API", station_name),
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Now-playing, playlist, statistics and station administration. \
+                Errors share one JSON shape; see the README for the codes.",
+        },
+        "servers": [{ "url": API_PREFIX }],
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "responses": {
+                "Error": {
+                    "description": "Error with a machine-readable code",
+                    "content": { "application/json": { "schema": schema_ref("Error") } },
+                },
+            },
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-API-Key" },
+            },
+        },
+    })
+}
+
+fn operation(endpoint: &Endpoint) -> Value {
+    let mut responses = Map::new();
+    let success = match endpoint.reply {
+        Reply::Schema(name) => ("200", json!({
+            "description": "OK",
+            "content": { "application/json": { "schema": schema_ref(name) } },
+        })),
+        Reply::Object => ("200", json!({
+            "description": "OK",
+            "content": { "application/json": { "schema": { "type": "object" } } },
+        })),
+        Reply::Media(media) => ("200", json!({
+            "description": "OK",
+            "content": { media: { "schema": { "type": "string", "format": "binary" } } },
+        })),
+        Reply::NoContent => ("204", json!({ "description": "No content" })),
+    };
+    responses.insert(success.0.to_string(), success.1);
+    responses.insert("default".to_string(), json!({ "$ref": "#/components/responses/Error" }));
+
+    let mut operation = json!({
+        "tags": [endpoint.tag],
+        "summary": endpoint.summary,
+        "operationId": operation_id(endpoint),
+        "responses": responses,
+    });
+    if !endpoint.params.is_empty() {
+        operation["parameters"] = endpoint.params.iter()
+            .map(|param| json!({
+                "name": param.name,
+                "in": if param.location == In::Path { "path" } else { "query" },
+                "required": param.required,
+                "description": param.description,
+                "schema": { "type": param.kind },
+            }))
+            .collect();
+    }
+    if let Some(body) = endpoint.body {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": schema_ref(body) } },
+        });
+    }
+    if endpoint.admin {
+        operation["security"] = json!([{ "bearer": [] }, { "apiKey": [] }]);
+    }
+    operation
+}
+
+// `get /playlist/play-next` -> `get_playlist_play_next`
+fn operation_id(endpoint: &Endpoint) -> String {
+    let words: Vec<String> = endpoint.path
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect();
+    format!("{}_{}", endpoint.method, words.join("_"))
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn schemas() -> Value {
+    let string = json!({ "type": "string" });
+    let integer = json!({ "type": "integer" });
+    let number = json!({ "type": "number" });
+    let boolean = json!({ "type": "boolean" });
+    let nullable = |kind: &str| json!({ "type": kind, "nullable": true });
+    let tags = json!({ "type": "object", "additionalProperties": { "type": "string" } });
+    let object = json!({ "type": "object" });
+    let counts = json!({ "type": "object", "additionalProperties": { "type": "integer" } });
+
+    let mut schemas = json!({
+        "NowPlaying": {
+            "type": "object",
+            "properties": {
+                "title": string, "artist": string, "album": string,
+                "isrc": nullable("string"), "composer": nullable("string"), "label": nullable("string"),
+                "tags": tags,
+                "duration": nullable("integer"),
+                "bitrate": integer,
+                "position": integer,
+                "listeners": integer,
+                "server_time_ms": integer,
+                "sync_position_ms": integer,
+                "generation": integer,
+            },
+        },
+        "Track": {
+            "type": "object",
+            "required": ["id", "title", "artist", "album"],
+            "properties": {
+                "id": { "type": "string", "format": "uuid" }, "title": string, "artist": string, "album": string,
+                "duration": nullable("integer"),
+                "bitrate": nullable("integer"),
+                "isrc": nullable("string"), "composer": nullable("string"), "label": nullable("string"),
+                "tags": tags,
+                "replaygain_gain_db": nullable("number"), "replaygain_peak": nullable("number"),
+                "analysis": schema_ref("TrackAnalysis"),
+                "enabled": boolean,
+                "disabled_reason": nullable("string"), "disabled_until": nullable("integer"),
+                "quarantine": schema_ref("TrackQuarantine"),
+            },
+        },
+        "TrackQuarantine": {
+            "type": "object",
+            "properties": {
+                "error": string,
+                "attempts": integer,
+                "quarantined_at": integer,
+            },
+        },
+        "TrackAnalysis": {
+            "type": "object",
+            "properties": {
+                "loudness_lufs": nullable("number"),
+                "gain_db": nullable("number"),
+                "peak": number,
+                "peak_dbfs": number,
+                "duration_ms": integer,
+                "sha256": string,
+                "analyzed_at": integer,
+                "silence_threshold_db": nullable("number"),
+                "leading_silence_ms": integer,
+                "trailing_silence_ms": integer,
+            },
+        },
+        "Playlist": {
+            "type": "object",
+            "properties": {
+                "tracks": { "type": "array", "items": schema_ref("Track") },
+                "current_index": integer,
+                "excluded": { "type": "array", "items": schema_ref("ExcludedTrack") },
+            },
+        },
+        "ExcludedTrack": {
+            "type": "object",
+            "required": ["id", "title", "artist"],
+            "properties": { "id": { "type": "string", "format": "uuid" }, "title": string, "artist": string },
+        },
+        "SearchResults": {
+            "type": "object",
+            "properties": {
+                "total": integer,
+                "tracks": { "type": "array", "items": schema_ref("Track") },
+            },
+        },
+        "NextUp": {
+            "type": "object",
+            "properties": {
+                "tracks": { "type": "array", "items": schema_ref("Track") },
+            },
+        },
+        "Stats": {
+            "type": "object",
+            "properties": {
+                "uptime_seconds": integer,
+                "total_mb_sent": number,
+                "current_listeners": integer,
+                "is_broadcasting": boolean,
+                "listeners": { "type": "array", "items": schema_ref("Listener") },
+                "stream_health": schema_ref("StreamHealth"),
+                "buffer_config": object,
+                "client_telemetry": object,
+                "geo": { "allOf": [schema_ref("GeoBreakdown")], "nullable": true },
+            },
+        },
+        "Listener": {
+            "type": "object",
+            "properties": {
+                "id": string,
+                "connected_seconds": integer,
+                "mb_received": number,
+                "drift_seconds": number,
+                "trimmed_seconds": number,
+                "silence_seconds": number,
+                "profile": string,
+                "burst_kb": number,
+                "burst_seconds": number,
+            },
+        },
+        "StreamHealth": {
+            "type": "object",
+            "properties": {
+                "gaps_detected": integer,
+                "recovery_attempts": integer,
+                "ms_since_last_chunk": integer,
+                "is_streaming": boolean,
+                "drift_trimmed_seconds": number,
+                "silence_inserted_seconds": number,
+                "on_hold": boolean,
+                "hold_seconds": number,
+                "idle_mode": string,
+                "paused": boolean,
+                "paused_seconds": number,
+                "chunk_integrity": object,
+            },
+        },
+        "AudienceSeries": {
+            "type": "object",
+            "properties": {
+                "from": integer,
+                "to": integer,
+                "step": integer,
+                "peak_listeners": integer,
+                "bytes_sent": integer,
+                "points": { "type": "array", "items": schema_ref("AudiencePoint") },
+            },
+        },
+        "AudiencePoint": {
+            "type": "object",
+            "properties": {
+                "timestamp": integer,
+                "listeners": number,
+                "peak_listeners": integer,
+                "bytes_sent": integer,
+                "geo": schema_ref("GeoBreakdown"),
+            },
+        },
+        "GeoBreakdown": {
+            "type": "object",
+            "properties": {
+                "countries": counts,
+                "regions": counts,
+            },
+        },
+        "SkipVote": {
+            "type": "object",
+            "properties": { "votes": integer, "required": integer, "skipped": boolean },
+        },
+        "Listeners": {
+            "type": "object",
+            "properties": { "listeners": integer, "max_listeners": nullable("integer"), "uptime": integer },
+        },
+        "Health": {
+            "type": "object",
+            "properties": { "status": string, "is_broadcasting": boolean, "listeners": integer, "uptime": integer },
+        },
+        "BeaconPayload": {
+            "type": "object",
+            "required": ["session", "sig", "events"],
+            "properties": {
+                "session": string,
+                "sig": string,
+                "platform": string,
+                "events": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["type"],
+                        "properties": {
+                            "type": {
+                                "type": "string",
+                                "enum": ["buffer_underrun", "play", "pause", "volume", "error"],
+                            },
+                        },
+                    },
+                },
+            },
+        },
+        "TelemetryReport": {
+            "type": "object",
+            "required": ["session", "sig", "buffered_seconds"],
+            "properties": {
+                "session": string,
+                "sig": string,
+                "platform": string,
+                "buffered_seconds": number,
+                "stalls": { "type": "array", "items": integer },
+            },
+        },
+        "Error": {
+            "type": "object",
+            "properties": {
+                "error": {
+                    "type": "object",
+                    "required": ["code", "message"],
+                    "properties": {
+                        "code": string,
+                        "message": string,
+                        "retry_after": integer,
+                    },
+                },
+            },
+        },
+    });
+
+    // Admin request bodies, separate to keep json! under the recursion limit
+    let requests = json!({
+        "TrackAddRequest": {
+            "type": "object",
+            "description": "Exactly one of id (from the playlist's excluded) or path",
+            "properties": { "id": string, "path": string },
+        },
+        "TrackIdRequest": {
+            "type": "object",
+            "required": ["id"],
+            "properties": { "id": string },
+        },
+        "TrackEnabledRequest": {
+            "type": "object",
+            "required": ["id", "enabled"],
+            "properties": { "id": string, "enabled": boolean, "reason": string, "until": integer },
+        },
+        "TrackTagsRequest": {
+            "type": "object",
+            "properties": { "title": string, "artist": string, "album": string, "genre": string, "write_file": boolean },
+        },
+        "BanRequest": {
+            "type": "object",
+            "required": ["ip"],
+            "properties": { "ip": string, "reason": string },
+        },
+        "MetadataOverrideRequest": {
+            "type": "object",
+            "required": ["title"],
+            "properties": { "title": string },
+        },
+        "PlaylistOrderRequest": {
+            "type": "object",
+            "required": ["ids"],
+            "properties": { "ids": { "type": "array", "items": string } },
+        },
+        "StreamTokenRequest": {
+            "type": "object",
+            "properties": { "user": string, "ttl_secs": integer },
+        },
+    });
+    if let (Some(schemas), Value::Object(requests)) = (schemas.as_object_mut(), requests) {
+        schemas.extend(requests);
+    }
+    schemas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Health, Listeners, NextUp, NowPlaying, PlaylistDto, SearchResults, SkipVote, StatsDto, TrackAnalysis, TrackDto, TrackQuarantine};
+
+    // Every field the server serializes is documented, and nothing else
+    fn assert_documents(schema: &str, value: impl serde::Serialize) {
+        let value = serde_json::to_value(value).unwrap();
+        let schemas = schemas();
+        let mut documented: Vec<&String> = schemas[schema]["properties"].as_object().unwrap().keys().collect();
+        let mut served: Vec<&String> = value.as_object().unwrap().keys().collect();
+        documented.sort();
+        served.sort();
+        assert_eq!(documented, served, "schema {}", schema);
+    }
+
+    #[test]
+    fn test_schemas_match_types() {
+        assert_documents("NowPlaying", NowPlaying::default());
+        assert_documents("Track", TrackDto {
+            analysis: Some(TrackAnalysis::default()),
+            quarantine: Some(TrackQuarantine::default()),
+            ..Default::default()
+        });
+        assert_documents("TrackAnalysis", TrackAnalysis::default());
+        assert_documents("TrackQuarantine", TrackQuarantine::default());
+        assert_documents("Playlist", PlaylistDto::default());
+        assert_documents("NextUp", NextUp::default());
+        assert_documents("SearchResults", SearchResults::default());
+        let stats = StatsDto::default();
+        assert_documents("StreamHealth", &stats.stream_health);
+        assert_documents("Stats", stats);
+        assert_documents("Listener", crate::types::ListenerDto::default());
+        assert_documents("AudiencePoint", crate::audience::AudiencePoint {
+            timestamp: 0, listeners: 0.0, peak_listeners: 0, bytes_sent: 0, geo: Some(Default::default()),
+        });
+        assert_documents("GeoBreakdown", crate::types::GeoBreakdown::default());
+        assert_documents("SkipVote", SkipVote { votes: 1, required: 2, skipped: false });
+        assert_documents("Listeners", Listeners { listeners: 0, max_listeners: None, uptime: 0 });
+        assert_documents("Health", Health { status: "healthy".into(), is_broadcasting: true, listeners: 0, uptime: 0 });
+    }
+
+    #[test]
+    fn test_document_references_resolve() {
+        let document = document("Test Radio");
+        let text = document.to_string();
+        for reference in text.split("\"$ref\":\"").skip(1) {
+            let target = reference.split('"').next().unwrap();
+            let pointer = target.trim_start_matches('#');
+            assert!(document.pointer(pointer).is_some(), "dangling reference {}", target);
+        }
+
+        let now_playing = &document["paths"]["/now-playing"]["get"];
+        assert_eq!(now_playing["operationId"], "get_now_playing");
+        assert!(now_playing.get("security").is_none());
+        let remove = &document["paths"]["/playlist/tracks"]["delete"];
+        assert_eq!(remove["parameters"][0]["required"], true);
+        assert!(remove["security"].is_array());
+    }
+}
@@ -1,30 +1,227 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::{Path, PathBuf};
 use std::fs::File;
+use std::sync::{Arc, Mutex};
+use arc_swap::ArcSwap;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 use tracing::{info, warn};
-use symphonia::core::io::MediaSourceStream;
+use uuid::Uuid;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 use symphonia::core::formats::FormatOptions;
 
-use crate::error::Result;
+use crate::{error::{AppError, Result}, rotation::Rotation, search::SearchIndex, types::{ExcludedTrackDto, PlaylistDto, TrackAnalysis, TrackDto, TrackQuarantine}};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Playlist {
     pub tracks: Vec<Track>,
     #[serde(default)]
     current_index: usize,
+    // Taken out of rotation through the API; rescans don't add these back
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded: Vec<ExcludedTrack>,
+}
+
+/// A track taken out of rotation: its file keeps rescans from adding it back,
+/// and its id (kept when it returns) is how the API names it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredExclusion")]
+pub struct ExcludedTrack {
+    pub id: Uuid,
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredExclusion {
+    Track {
+        #[serde(default)]
+        id: Uuid,
+        path: PathBuf,
+        #[serde(default)]
+        title: String,
+        #[serde(default)]
+        artist: String,
+    },
+    // playlist.json from before excluded tracks had ids: just the path
+    Path(PathBuf),
+}
+
+impl From<StoredExclusion> for ExcludedTrack {
+    fn from(stored: StoredExclusion) -> Self {
+        match stored {
+            StoredExclusion::Track { id, path, title, artist } => Self { id, path, title, artist },
+            StoredExclusion::Path(path) => Self {
+                id: Uuid::nil(),
+                title: path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default(),
+                artist: String::new(),
+                path,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Track {
+    // Stable across restarts, rescans and renames; the API names tracks by it.
+    // Nil in playlist files from before ids, until assigned on load.
+    #[serde(default)]
+    pub id: Uuid,
     pub path: PathBuf,
     pub title: String,
     pub artist: String,
     pub album: String,
     pub duration: Option<u64>,
     pub bitrate: Option<u64>,
+    #[serde(default)]
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub composer: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    // Any other tag frames (e.g. TXXX, TCON, TDRC), keyed by their raw frame name
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    // REPLAYGAIN_TRACK_GAIN (dB) and REPLAYGAIN_TRACK_PEAK tags, from ID3 TXXX frames or Vorbis comments
+    #[serde(default)]
+    pub replaygain_gain_db: Option<f64>,
+    #[serde(default)]
+    pub replaygain_peak: Option<f64>,
+    // Loudness, peak and file hash from `webradio analyze`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<TrackAnalysis>,
+    // Benched tracks stay in the playlist but are skipped by the rotation
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    // Why a track is benched (e.g. "corrupt", "explicit") and, for a temporary removal,
+    // when it returns to rotation (Unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_until: Option<u64>,
+    // Why the broadcast benched the track after failing to play it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantine: Option<TrackQuarantine>,
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            path: PathBuf::new(),
+            title: String::new(),
+            artist: String::new(),
+            album: String::new(),
+            duration: None,
+            bitrate: None,
+            isrc: None,
+            composer: None,
+            label: None,
+            tags: BTreeMap::new(),
+            replaygain_gain_db: None,
+            replaygain_peak: None,
+            analysis: None,
+            enabled: true,
+            disabled_reason: None,
+            disabled_until: None,
+            quarantine: None,
+        }
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Corrections to a track's tags; `None` leaves a field as it is
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TagEdit {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+}
+
+impl TagEdit {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.artist.is_none() && self.album.is_none() && self.genre.is_none()
+    }
+
+    /// The edit as ID3v2 text frames
+    pub fn id3_frames(&self) -> Vec<(&'static str, &str)> {
+        [("TIT2", &self.title), ("TPE1", &self.artist), ("TALB", &self.album), ("TCON", &self.genre)]
+            .into_iter()
+            .filter_map(|(id, value)| value.as_deref().map(|value| (id, value)))
+            .collect()
+    }
+}
+
+impl Track {
+    fn same_recording(&self, other: &Track) -> bool {
+        (&self.title, &self.artist, &self.album, self.duration, self.bitrate, &self.isrc)
+            == (&other.title, &other.artist, &other.album, other.duration, other.bitrate, &other.isrc)
+    }
+
+    /// The part of the track between its leading and trailing silence, as (start, end)
+    /// in ms, once analysis has measured them
+    pub fn audible_ms(&self) -> Option<(u64, u64)> {
+        let analysis = self.analysis.as_ref().filter(|analysis| analysis.silence_threshold_db.is_some())?;
+        Some((analysis.leading_silence_ms, analysis.duration_ms.saturating_sub(analysis.trailing_silence_ms)))
+    }
+
+    /// Gain the broadcast applies to normalize the track's loudness: the analyzed gain,
+    /// else the ReplayGain tag, lowered where needed so the peak stays below full scale
+    pub fn gain_db(&self) -> Option<f64> {
+        let (gain_db, peak) = match &self.analysis {
+            Some(TrackAnalysis { gain_db: Some(gain_db), peak, .. }) => (*gain_db, Some(*peak)),
+            _ => (self.replaygain_gain_db?, self.replaygain_peak),
+        };
+        Some(match peak.filter(|&peak| peak > 0.0) {
+            Some(peak) => gain_db.min(-20.0 * peak.log10()),
+            None => gain_db,
+        })
+    }
+
+    /// Apply corrections. The genre goes in the tag it was read from (ID3 `TCON`
+    /// or a `GENRE` comment), else in `GENRE`.
+    pub fn apply_tags(&mut self, edit: &TagEdit) {
+        if let Some(title) = &edit.title {
+            self.title = title.clone();
+        }
+        if let Some(artist) = &edit.artist {
+            self.artist = artist.clone();
+        }
+        if let Some(album) = &edit.album {
+            self.album = album.clone();
+        }
+        if let Some(genre) = &edit.genre {
+            let key = self.tags.keys()
+                .find(|key| key.eq_ignore_ascii_case("TCON") || key.eq_ignore_ascii_case("GENRE"))
+                .cloned()
+                .unwrap_or_else(|| "GENRE".to_string());
+            self.tags.insert(key, genre.clone());
+        }
+    }
+
+    /// Look up a field by name, falling back to custom tags (case-insensitive).
+    /// `ROTATION_EXCLUDE` rules match tracks through it.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        match name.to_ascii_lowercase().as_str() {
+            "title" => Some(&self.title),
+            "artist" => Some(&self.artist),
+            "album" => Some(&self.album),
+            "isrc" => self.isrc.as_deref(),
+            "composer" => self.composer.as_deref(),
+            "label" => self.label.as_deref(),
+            _ => self.tags.iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str()),
+        }
+    }
 }
 
 impl Playlist {
@@ -34,8 +231,14 @@ impl Playlist {
         // Try to load existing playlist
         if playlist_path.exists() {
             match Self::load(&playlist_path).await {
-                Ok(playlist) => {
+                Ok(mut playlist) => {
                     info!("Loaded playlist with {} tracks", playlist.tracks.len());
+                    if playlist.assign_ids() {
+                        info!("Assigned ids to tracks without one");
+                        if let Err(e) = playlist.save(&playlist_path).await {
+                            warn!("Failed to save playlist: {}", e);
+                        }
+                    }
                     return Ok(playlist);
                 }
                 Err(e) => {
@@ -44,11 +247,11 @@ impl Playlist {
             }
         }
         
-        // Scan for MP3 files
-        info!("Scanning {} for MP3 files", music_dir.display());
+        // Scan for audio files
+        info!("Scanning {} for audio files", music_dir.display());
         let playlist = Self::scan_directory(music_dir).await?;
         
-        info!("Found {} MP3 files", playlist.tracks.len());
+        info!("Found {} audio files", playlist.tracks.len());
         
         // Log the tracks found
         for (i, track) in playlist.tracks.iter().enumerate() {
@@ -63,6 +266,18 @@ impl Playlist {
         Ok(playlist)
     }
     
+    // Give tracks (and excluded tracks) loaded without an id one; true if any were missing
+    fn assign_ids(&mut self) -> bool {
+        let mut assigned = false;
+        let ids = self.tracks.iter_mut().map(|track| &mut track.id)
+            .chain(self.excluded.iter_mut().map(|excluded| &mut excluded.id));
+        for id in ids.filter(|id| id.is_nil()) {
+            *id = Uuid::new_v4();
+            assigned = true;
+        }
+        assigned
+    }
+
     async fn load(path: &Path) -> Result<Self> {
         let data = fs::read_to_string(path).await?;
         let playlist = serde_json::from_str(&data)?;
@@ -76,94 +291,543 @@ impl Playlist {
     }
     
     async fn scan_directory(dir: &Path) -> Result<Self> {
-        use std::pin::Pin;
-        use std::future::Future;
+        let mut tracks = Vec::new();
+        for path in list_audio_files(dir).await? {
+            if let Some(track) = create_track_from_file(&path, dir).await {
+                tracks.push(track);
+            }
+        }
+        tracks.sort_by(|a, b| a.path.cmp(&b.path));
         
-        fn scan_directory_inner(
-            dir: PathBuf,
-        ) -> Pin<Box<dyn Future<Output = Result<Vec<Track>>> + Send>> {
-            Box::pin(async move {
-                let mut tracks = Vec::new();
-                let mut entries = fs::read_dir(&dir).await?;
+        Ok(Playlist {
+            tracks,
+            ..Default::default()
+        })
+    }
+
+    /// Bring the playlist in line with the audio files now in `dir`: tracks whose files are
+    /// gone are dropped, new files are read and appended in path order, and known
+    /// tracks keep their metadata and position. Returns `None` if nothing changed.
+    pub async fn rescan(&self, dir: &Path) -> Result<Option<Self>> {
+        let on_disk: std::collections::BTreeSet<PathBuf> = list_audio_files(dir).await?
+            .into_iter()
+            .filter_map(|path| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+            .collect();
+
+        let (mut tracks, mut vanished): (Vec<Track>, Vec<Track>) = self.tracks.iter()
+            .cloned()
+            .partition(|track| on_disk.contains(&track.path));
+        let removed = vanished.len();
+        let known: std::collections::HashSet<&PathBuf> = self.tracks.iter().map(|track| &track.path).collect();
+        let mut added = 0;
+        let new_files = on_disk.iter().filter(|path| !known.contains(path) && !self.is_excluded(path));
+        for path in new_files {
+            if let Some(mut track) = create_track_from_file(&dir.join(path), dir).await {
+                // A renamed file keeps its id, bench and analysis if its tags and length are
+                // unchanged (analyze re-measures it if the file's hash no longer matches)
+                if let Some(index) = vanished.iter().position(|old| old.same_recording(&track)) {
+                    let old = vanished.swap_remove(index);
+                    info!("{} was renamed to {}", old.path.display(), track.path.display());
+                    track.id = old.id;
+                    track.enabled = old.enabled;
+                    track.disabled_reason = old.disabled_reason;
+                    track.disabled_until = old.disabled_until;
+                    track.quarantine = old.quarantine;
+                    track.analysis = old.analysis;
+                }
+                tracks.push(track);
+                added += 1;
+            }
+        }
+
+        if added == 0 && removed == 0 {
+            return Ok(None);
+        }
+        info!("Playlist rescan: {} added, {} removed, {} tracks", added, removed, tracks.len());
+        Ok(Some(Playlist {
+            tracks,
+            current_index: self.current_index,
+            excluded: self.excluded.clone(),
+        }))
+    }
+
+    /// Write the playlist cache (`playlist.json`) in `music_dir`
+    pub async fn save_to(&self, music_dir: &Path) -> Result<()> {
+        self.save(&music_dir.join("playlist.json")).await
+    }
+    
+    fn position(&self, id: Uuid) -> Result<usize> {
+        if self.tracks.is_empty() {
+            return Err(AppError::PlaylistEmpty);
+        }
+        self.tracks.iter()
+            .position(|track| track.id == id)
+            .ok_or_else(|| AppError::TrackNotFound(id.to_string()))
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&Track> {
+        self.tracks.iter().find(|track| track.id == id)
+    }
+
+    // Take the track at `index` out of the list, keeping `current_index` on the
+    // same upcoming track
+    fn take(&mut self, index: usize) -> Track {
+        let track = self.tracks.remove(index);
+        if index < self.current_index {
+            self.current_index -= 1;
+        }
+        if self.current_index >= self.tracks.len() {
+            self.current_index = 0;
+        }
+        track
+    }
+
+    /// Swap in the tracks from a rescan, leaving out any taken out of rotation since
+    /// the rescan started and keeping the rotation on the same upcoming track
+    pub fn replace_tracks(&mut self, tracks: Vec<Track>) {
+        let upcoming = self.tracks.get(self.current_index).map(|track| track.id);
+        self.tracks = tracks.into_iter().filter(|track| !self.is_excluded(&track.path)).collect();
+        let fallback = if self.current_index < self.tracks.len() { self.current_index } else { 0 };
+        self.current_index = upcoming.and_then(|id| self.position(id).ok()).unwrap_or(fallback);
+    }
+
+    /// Put a track (back) into rotation at the end of the list. A track that was
+    /// taken out gets its old id back.
+    pub fn add(&mut self, mut track: Track) -> Result<()> {
+        if self.tracks.iter().any(|known| known.path == track.path) {
+            return Err(AppError::BadRequest(format!("{} is already in the playlist", track.path.display())));
+        }
+        if let Some(index) = self.excluded.iter().position(|excluded| excluded.path == track.path) {
+            track.id = self.excluded.remove(index).id;
+        }
+        self.tracks.push(track);
+        Ok(())
+    }
+
+    /// Take a track out of rotation; it stays out across rescans until added again
+    pub fn remove(&mut self, id: Uuid) -> Result<Track> {
+        let index = self.position(id)?;
+        let track = self.take(index);
+        if !self.is_excluded(&track.path) {
+            self.excluded.push(ExcludedTrack {
+                id: track.id,
+                path: track.path.clone(),
+                title: track.title.clone(),
+                artist: track.artist.clone(),
+            });
+        }
+        Ok(track)
+    }
+
+    /// A track taken out of rotation, by the id it had
+    pub fn excluded_track(&self, id: Uuid) -> Option<&ExcludedTrack> {
+        self.excluded.iter().find(|excluded| excluded.id == id)
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.excluded.iter().any(|excluded| excluded.path == path)
+    }
+
+    /// Rearrange the playlist into the order of `ids`, which must list every
+    /// track exactly once. The track that was due next still plays next.
+    pub fn reorder(&mut self, ids: &[Uuid]) -> Result<()> {
+        let unique: BTreeSet<&Uuid> = ids.iter().collect();
+        if unique.len() != ids.len() || ids.len() != self.tracks.len() {
+            return Err(AppError::BadRequest("order must list every track in the playlist exactly once".into()));
+        }
+        let mut tracks = Vec::with_capacity(ids.len());
+        for &id in ids {
+            let index = self.position(id)
+                .map_err(|_| AppError::BadRequest(format!("{} is not in the playlist", id)))?;
+            tracks.push(self.tracks[index].clone());
+        }
+        let upcoming = self.tracks.get(self.current_index).map(|track| track.id);
+        self.tracks = tracks;
+        self.current_index = upcoming.and_then(|id| self.position(id).ok()).unwrap_or(0);
+        Ok(())
+    }
+
+    /// Move a track so it plays after the current one; the rest of the rotation
+    /// continues from where it was
+    pub fn play_next(&mut self, id: Uuid) -> Result<()> {
+        let index = self.position(id)?;
+        let track = self.take(index);
+        self.tracks.insert(self.current_index, track);
+        Ok(())
+    }
+
+    /// Bench a track (`enabled: false`) or return it to rotation
+    pub fn set_enabled(&mut self, id: Uuid, enabled: bool) -> Result<()> {
+        if !enabled {
+            return self.disable(id, None, None);
+        }
+        let index = self.position(id)?;
+        let track = &mut self.tracks[index];
+        track.enabled = true;
+        track.disabled_reason = None;
+        track.disabled_until = None;
+        track.quarantine = None;
+        Ok(())
+    }
+
+    /// Bench a track, noting why and, for a temporary removal, until when (Unix seconds)
+    pub fn disable(&mut self, id: Uuid, reason: Option<String>, until: Option<u64>) -> Result<()> {
+        let index = self.position(id)?;
+        let track = &mut self.tracks[index];
+        track.enabled = false;
+        track.disabled_reason = reason;
+        track.disabled_until = until;
+        track.quarantine = None;
+        Ok(())
+    }
+
+    /// Bench a track that failed to play, as "quarantined", until it is enabled again
+    pub fn quarantine(&mut self, id: Uuid, quarantine: TrackQuarantine) -> Result<()> {
+        self.disable(id, Some("quarantined".to_string()), None)?;
+        let index = self.position(id)?;
+        self.tracks[index].quarantine = Some(quarantine);
+        Ok(())
+    }
+
+    /// Correct a track's tags in the playlist
+    pub fn edit_tags(&mut self, id: Uuid, edit: &TagEdit) -> Result<Track> {
+        let index = self.position(id)?;
+        self.tracks[index].apply_tags(edit);
+        Ok(self.tracks[index].clone())
+    }
+
+    /// Whether a temporary removal has run out by `now`
+    pub fn has_expired_removals(&self, now: u64) -> bool {
+        self.tracks.iter().any(|track| !track.enabled && track.disabled_until.is_some_and(|until| until <= now))
+    }
+
+    /// Return tracks whose temporary removal has run out by `now` to rotation
+    pub fn restore_expired(&mut self, now: u64) {
+        for track in &mut self.tracks {
+            if !track.enabled && track.disabled_until.is_some_and(|until| until <= now) {
+                info!("{} returns to rotation", track.path.display());
+                track.enabled = true;
+                track.disabled_reason = None;
+                track.disabled_until = None;
+            }
+        }
+    }
+
+    pub fn get_next_track(&mut self) -> Option<Track> {
+        let index = next_enabled(&self.tracks, self.current_index)?;
+        self.current_index = (index + 1) % self.tracks.len();
+        Some(self.tracks[index].clone())
+    }
+}
+
+// The first enabled track at or after `from`, wrapping around
+fn next_enabled(tracks: &[Track], from: usize) -> Option<usize> {
+    let len = tracks.len();
+    (0..len).map(|offset| (from % len.max(1) + offset) % len).find(|&index| tracks[index].enabled)
+}
+
+/// Library file extensions: MP3s are broadcast as they are, the others are re-encoded.
+/// Ogg files may hold Vorbis or FLAC, M4A files AAC (`.aac` is raw ADTS).
+pub const AUDIO_EXTENSIONS: [&str; 5] = ["mp3", "flac", "ogg", "m4a", "aac"];
+
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
+
+// All audio files under `dir`, recursively
+pub(crate) async fn list_audio_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    use std::pin::Pin;
+    use std::future::Future;
+    
+    fn list_inner(
+        dir: PathBuf,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<PathBuf>>> + Send>> {
+        Box::pin(async move {
+            let mut files = Vec::new();
+            let mut entries = fs::read_dir(&dir).await?;
+            
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
                 
-                while let Some(entry) = entries.next_entry().await? {
-                    let path = entry.path();
-                    
-                    if path.is_dir() {
-                        // Recursively scan subdirectories
-                        match scan_directory_inner(path).await {
-                            Ok(mut subtracks) => tracks.append(&mut subtracks),
-                            Err(e) => warn!("Failed to scan subdirectory: {}", e),
-                        }
-                    } else if path.extension().and_then(|s| s.to_str()) == Some("mp3") {
-                        if let Some(track) = create_track_from_file(&path, &dir).await {
-                            tracks.push(track);
-                        }
+                if path.is_dir() {
+                    // Recursively scan subdirectories
+                    match list_inner(path).await {
+                        Ok(mut subfiles) => files.append(&mut subfiles),
+                        Err(e) => warn!("Failed to scan subdirectory: {}", e),
                     }
+                } else if is_audio_file(&path) {
+                    files.push(path);
                 }
-                
-                Ok(tracks)
-            })
+            }
+            
+            Ok(files)
+        })
+    }
+
+    list_inner(dir.to_path_buf()).await
+}
+
+/// Read a track from `relative`, an audio file (see `AUDIO_EXTENSIONS`) inside `music_dir`
+pub async fn read_track(music_dir: &Path, relative: &Path) -> Result<Track> {
+    let escapes = relative.components().any(|c| !matches!(c, std::path::Component::Normal(_)));
+    if escapes || !is_audio_file(relative) {
+        return Err(AppError::BadRequest("path must be an .mp3, .flac, .ogg, .m4a or .aac file inside the music directory".into()));
+    }
+    let path = music_dir.join(relative);
+    if !fs::try_exists(&path).await? {
+        return Err(AppError::TrackNotFound(relative.display().to_string()));
+    }
+    create_track_from_file(&path, music_dir).await.ok_or_else(|| AppError::TrackNotFound(relative.display().to_string()))
+}
+
+// Read the metadata of `path`, a file inside `base_dir`; the track stores the path relative to it
+async fn create_track_from_file(path: &Path, base_dir: &Path) -> Option<Track> {
+    let relative_path = path.strip_prefix(base_dir).ok()?;
+
+    // Use symphonia to extract all metadata efficiently in one pass
+    let metadata = match extract_metadata_with_symphonia(path) {
+        Some(metadata) => metadata,
+        None => {
+            // Fallback: use filename as title
+            let title = path.file_stem()?.to_string_lossy().to_string();
+            ExtractedMetadata {
+                title,
+                artist: "Unknown".to_string(),
+                album: "Unknown".to_string(),
+                ..Default::default()
+            }
         }
-        
-        async fn create_track_from_file(path: &Path, base_dir: &Path) -> Option<Track> {
-            let relative_path = path.strip_prefix(base_dir).ok()?;
-
-            // Use symphonia to extract all metadata efficiently in one pass
-            let (title, artist, album, duration, bitrate) = match extract_metadata_with_symphonia(path) {
-                Some(metadata) => metadata,
-                None => {
-                    // Fallback: use filename as title
-                    let title = path.file_stem()?.to_string_lossy().to_string();
-                    (title, "Unknown".to_string(), "Unknown".to_string(), None, None)
-                }
+    };
+
+    info!("Track: {} - Bitrate: {}kbps, Duration: {}s",
+        relative_path.display(),
+        metadata.bitrate.unwrap_or(0) / 1000,
+        metadata.duration.unwrap_or(0)
+    );
+
+    Some(Track {
+        id: Uuid::new_v4(),
+        path: relative_path.to_path_buf(),
+        title: metadata.title,
+        artist: metadata.artist,
+        album: metadata.album,
+        duration: metadata.duration,
+        bitrate: metadata.bitrate,
+        isrc: metadata.isrc,
+        composer: metadata.composer,
+        label: metadata.label,
+        tags: metadata.tags,
+        replaygain_gain_db: metadata.replaygain_gain_db,
+        replaygain_peak: metadata.replaygain_peak,
+        analysis: None,
+        enabled: true,
+        disabled_reason: None,
+        disabled_until: None,
+        quarantine: None,
+    })
+}
+
+/// The station's playlist, shared by the broadcast loop, API handlers and editors.
+/// Readers take a snapshot without locking; edits (rescans, admin changes) build
+/// a modified copy and swap it in, so a long edit never stalls playback.
+#[derive(Debug)]
+pub struct SharedPlaylist {
+    state: ArcSwap<RotationState>,
+    save_lock: tokio::sync::Mutex<()>,
+    version: tokio::sync::watch::Sender<u64>, // Bumped by every edit
+    search_index: Mutex<Option<(Arc<Playlist>, Arc<SearchIndex>)>>, // For the snapshot it was built from
+    rotation: Rotation, // Separation rules; without any, tracks play in playlist order
+}
+
+// A snapshot and the rotation position in it, swapped as one so an edit and a
+// track change can't undo each other
+#[derive(Debug)]
+struct RotationState {
+    playlist: Arc<Playlist>,
+    next_index: usize, // Index of the next track to play
+}
+
+impl SharedPlaylist {
+    pub fn new(playlist: Playlist) -> Self {
+        let shared = Self {
+            state: ArcSwap::from_pointee(RotationState { next_index: playlist.current_index, playlist: Arc::new(playlist) }),
+            save_lock: tokio::sync::Mutex::new(()),
+            version: tokio::sync::watch::Sender::new(0),
+            search_index: Mutex::new(None),
+            rotation: Rotation::default(),
+        };
+        shared.search_index(&shared.snapshot());
+        shared
+    }
+
+    /// Choose tracks by `rotation`'s rules instead of strictly in playlist order
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn rotation(&self) -> &Rotation {
+        &self.rotation
+    }
+
+    /// Notified after every edit, with a version that increases per edit
+    pub fn subscribe(&self) -> tokio::sync::watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+
+    pub fn snapshot(&self) -> Arc<Playlist> {
+        Arc::clone(&self.state.load().playlist)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.load().playlist.tracks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copy-on-write edit. `edit` may run more than once if another edit lands
+    /// concurrently, so it must only depend on the playlist it is given.
+    pub fn update(&self, edit: impl Fn(&mut Playlist)) -> Arc<Playlist> {
+        let _ = self.try_update(|playlist| {
+            edit(playlist);
+            Ok(())
+        });
+        self.snapshot()
+    }
+
+    /// Copy-on-write edit that may be refused; nothing changes if `edit` fails.
+    /// The edit sees the rotation position as the playlist's `current_index` and
+    /// may move it (e.g. when tracks before it are removed).
+    pub fn try_update<T>(&self, edit: impl Fn(&mut Playlist) -> Result<T>) -> Result<T> {
+        loop {
+            let current = self.state.load_full();
+            let mut playlist = Playlist::clone(&current.playlist);
+            playlist.current_index = current.next_index % playlist.tracks.len().max(1);
+            let result = edit(&mut playlist)?;
+            let next_index = playlist.current_index;
+
+            // Fails if another edit or a track change landed meanwhile; redo the edit on top of it
+            let edited = Arc::new(RotationState { playlist: Arc::new(playlist), next_index });
+            let previous = self.state.compare_and_swap(&current, edited);
+            if Arc::ptr_eq(&previous, &current) {
+                self.search_index(&self.snapshot());
+                self.version.send_modify(|version| *version += 1);
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Write the latest playlist to `playlist.json` in `music_dir`. Saves are
+    /// serialized so an older snapshot never overwrites a newer one.
+    pub async fn save_to(&self, music_dir: &Path) -> Result<()> {
+        let _guard = self.save_lock.lock().await;
+        let state = self.state.load_full();
+        let mut playlist = Playlist::clone(&state.playlist);
+        playlist.current_index = state.next_index % playlist.tracks.len().max(1);
+        playlist.save_to(music_dir).await
+    }
+
+    /// The next track in rotation, wrapping around
+    pub fn next_track(&self) -> Option<Track> {
+        loop {
+            let current = self.state.load_full();
+            let tracks = &current.playlist.tracks;
+            if tracks.is_empty() {
+                return None;
+            }
+            let (index, next_index) = if self.rotation.rules().is_off() {
+                let index = next_enabled(tracks, current.next_index)?;
+                (index, (index + 1) % tracks.len())
+            } else {
+                self.rotation.next(tracks, current.next_index, chrono::Utc::now().timestamp() as u64)?
             };
 
-            info!("Track: {} - Bitrate: {}kbps, Duration: {}s",
-                relative_path.display(),
-                bitrate.unwrap_or(0) / 1000,
-                duration.unwrap_or(0)
-            );
+            // Advancing only moves the position: the snapshot itself is kept
+            let advanced = Arc::new(RotationState { playlist: Arc::clone(&current.playlist), next_index });
+            let previous = self.state.compare_and_swap(&current, advanced);
+            if Arc::ptr_eq(&previous, &current) {
+                return Some(tracks[index].clone());
+            }
+        }
+    }
 
-            Some(Track {
-                path: relative_path.to_path_buf(),
-                title,
-                artist,
-                album,
-                duration,
-                bitrate,
-            })
+    /// Tracks matching every word of `query` in title, artist or album, best first
+    pub fn search(&self, query: &str) -> Vec<Track> {
+        let playlist = self.snapshot();
+        self.search_index(&playlist)
+            .search(query)
+            .into_iter()
+            .map(|position| playlist.tracks[position].clone())
+            .collect()
+    }
+
+    // Rebuilt once per snapshot, as scans and edits swap one in
+    fn search_index(&self, playlist: &Arc<Playlist>) -> Arc<SearchIndex> {
+        let mut cached = self.search_index.lock().unwrap();
+        match &*cached {
+            Some((indexed, index)) if Arc::ptr_eq(indexed, playlist) => Arc::clone(index),
+            _ => {
+                let index = Arc::new(SearchIndex::build(&playlist.tracks));
+                *cached = Some((Arc::clone(playlist), Arc::clone(&index)));
+                index
+            }
         }
-        
-        let mut tracks = scan_directory_inner(dir.to_path_buf()).await?;
-        tracks.sort_by(|a, b| a.path.cmp(&b.path));
-        
-        Ok(Playlist {
-            tracks,
-            current_index: 0,
-        })
     }
-    
-    pub fn get_next_track(&mut self) -> Option<Track> {
-        if self.tracks.is_empty() {
-            return None;
+
+    /// The next `count` tracks in rotation without advancing it, each enabled
+    /// track at most once
+    pub fn upcoming(&self, count: usize) -> Vec<Track> {
+        let state = self.state.load();
+        let playlist = &state.playlist;
+        let len = playlist.tracks.len();
+        let from = state.next_index;
+        if !self.rotation.rules().is_off() {
+            return self.rotation.plan(&playlist.tracks, from, count, chrono::Utc::now().timestamp() as u64)
+                .into_iter()
+                .map(|index| playlist.tracks[index].clone())
+                .collect();
         }
-        
-        let track = self.tracks[self.current_index].clone();
-        self.current_index = (self.current_index + 1) % self.tracks.len();
-        
-        Some(track)
+        (0..len)
+            .map(|offset| (from + offset) % len)
+            .filter(|&index| playlist.tracks[index].enabled)
+            .take(count)
+            .map(|index| playlist.tracks[index].clone())
+            .collect()
+    }
+
+    pub fn to_dto(&self) -> PlaylistDto {
+        let state = self.state.load();
+        let mut dto = PlaylistDto::from(&*state.playlist);
+        dto.current_index = state.next_index % state.playlist.tracks.len().max(1);
+        dto
     }
 }
 
-// Extract all metadata efficiently using symphonia in one pass
-// Returns: (title, artist, album, duration_secs, bitrate_bps)
-fn extract_metadata_with_symphonia(path: &Path) -> Option<(String, String, String, Option<u64>, Option<u64>)> {
-    // Get file size for bitrate calculation
-    let file_size = std::fs::metadata(path).ok()?.len();
+#[derive(Debug, Default)]
+struct ExtractedMetadata {
+    title: String,
+    artist: String,
+    album: String,
+    duration: Option<u64>, // seconds
+    bitrate: Option<u64>,  // bits per second
+    isrc: Option<String>,
+    composer: Option<String>,
+    label: Option<String>,
+    tags: BTreeMap<String, String>,
+    replaygain_gain_db: Option<f64>,
+    replaygain_peak: Option<f64>,
+}
 
-    // Open the file
-    let file = File::open(path).ok()?;
+// Extract all metadata efficiently using symphonia in one pass
+fn extract_metadata_with_symphonia(path: &Path) -> Option<ExtractedMetadata> {
+    // Open the file without its trailing tags, which would count as audio in the
+    // duration estimate and the bitrate
+    let file = crate::prefetch::TrimmedFile::open(path).ok()?;
+    let file_size = file.byte_len()?;
     let media_source = MediaSourceStream::new(Box::new(file), Default::default());
 
     // Create a hint to help the probe guess the format
@@ -176,31 +840,59 @@ fn extract_metadata_with_symphonia(path: &Path) -> Option<(String, String, Strin
     let format_opts = FormatOptions::default();
     let metadata_opts = MetadataOptions::default();
 
-    let probed = symphonia::default::get_probe()
+    let mut probed = symphonia::default::get_probe()
         .format(&hint, media_source, &format_opts, &metadata_opts)
         .ok()?;
 
-    let mut format = probed.format;
+    // ID3v2 tags are read by the probe, Vorbis comments and MP4 atoms by the format reader
+    let mut tags = Vec::new();
+    if let Some(metadata) = probed.metadata.get() {
+        tags.extend(metadata.current().into_iter().flat_map(|rev| rev.tags().to_vec()));
+    }
+    tags.extend(probed.format.metadata().current().into_iter().flat_map(|rev| rev.tags().to_vec()));
+    let format = probed.format;
 
     // Extract metadata from tags
-    let mut title = String::from("Unknown");
-    let mut artist = String::from("Unknown");
-    let mut album = String::from("Unknown");
-
-    // Check for metadata in the format reader
-    if let Some(metadata_rev) = format.metadata().current() {
-        for tag in metadata_rev.tags() {
-            match tag.std_key {
-                Some(symphonia::core::meta::StandardTagKey::TrackTitle) => {
-                    title = tag.value.to_string();
-                }
-                Some(symphonia::core::meta::StandardTagKey::Artist) => {
-                    artist = tag.value.to_string();
-                }
-                Some(symphonia::core::meta::StandardTagKey::Album) => {
-                    album = tag.value.to_string();
+    let mut metadata = ExtractedMetadata {
+        title: String::from("Unknown"),
+        artist: String::from("Unknown"),
+        album: String::from("Unknown"),
+        ..Default::default()
+    };
+
+    for tag in tags {
+        use symphonia::core::meta::{StandardTagKey, Value};
+
+        match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => {
+                metadata.title = tag.value.to_string();
+            }
+            Some(StandardTagKey::Artist) => {
+                metadata.artist = tag.value.to_string();
+            }
+            Some(StandardTagKey::Album) => {
+                metadata.album = tag.value.to_string();
+            }
+            Some(StandardTagKey::IdentIsrc) => {
+                metadata.isrc = Some(tag.value.to_string().trim().to_uppercase());
+            }
+            Some(StandardTagKey::Composer) => {
+                metadata.composer = Some(tag.value.to_string());
+            }
+            Some(StandardTagKey::Label) => {
+                metadata.label = Some(tag.value.to_string());
+            }
+            Some(StandardTagKey::ReplayGainTrackGain) => {
+                metadata.replaygain_gain_db = parse_replaygain(&tag.value.to_string());
+            }
+            Some(StandardTagKey::ReplayGainTrackPeak) => {
+                metadata.replaygain_peak = parse_replaygain(&tag.value.to_string());
+            }
+            _ => {
+                // Keep everything else as raw key/value pairs, skipping binary payloads
+                if !matches!(tag.value, Value::Binary(_)) {
+                    metadata.tags.insert(tag.key, tag.value.to_string());
                 }
-                _ => {}
             }
         }
     }
@@ -223,17 +915,83 @@ fn extract_metadata_with_symphonia(path: &Path) -> Option<(String, String, Strin
     // Calculate bitrate from file size and duration
     // Symphonia doesn't always provide bit_rate in CodecParameters for all formats
     // This approach gives accurate average bitrate for the entire file
-    let bitrate = if let Some(dur) = duration {
-        if dur > 0 {
-            Some((file_size * 8) / dur)
-        } else {
-            None
+    metadata.bitrate = duration.and_then(|dur| (file_size * 8).checked_div(dur));
+    metadata.duration = duration;
+
+    Some(metadata)
+}
+
+// ReplayGain values as taggers write them: "-6.54 dB", "+1.20 dB", "0.988525"
+fn parse_replaygain(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let number = value.strip_suffix("dB").or_else(|| value.strip_suffix("db")).unwrap_or(value);
+    number.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// A file's embedded cover art as (media type, image bytes): the front cover
+/// when there are several pictures, else the first
+pub fn read_artwork(path: &Path) -> Option<(String, Vec<u8>)> {
+    let file = File::open(path).ok()?;
+    let media_source = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut probed = symphonia::default::get_probe()
+        .format(&hint, media_source, &FormatOptions::default(), &MetadataOptions::default())
+        .ok()?;
+
+    // ID3v2 tags are read by the probe, Vorbis comments and FLAC pictures by the format reader
+    let mut visuals = Vec::new();
+    if let Some(metadata) = probed.metadata.get() {
+        visuals.extend(metadata.current().into_iter().flat_map(|rev| rev.visuals().to_vec()));
+    }
+    visuals.extend(probed.format.metadata().current().into_iter().flat_map(|rev| rev.visuals().to_vec()));
+
+    let front = visuals.iter().position(|visual| visual.usage == Some(symphonia::core::meta::StandardVisualKey::FrontCover));
+    let visual = visuals.into_iter().nth(front.unwrap_or(0))?;
+    Some((visual.media_type, visual.data.into_vec()))
+}
+
+impl From<&Track> for TrackDto {
+    fn from(track: &Track) -> Self {
+        Self {
+            id: track.id.to_string(),
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            album: track.album.clone(),
+            duration: track.duration,
+            bitrate: track.bitrate,
+            isrc: track.isrc.clone(),
+            composer: track.composer.clone(),
+            label: track.label.clone(),
+            tags: track.tags.clone(),
+            replaygain_gain_db: track.replaygain_gain_db,
+            replaygain_peak: track.replaygain_peak,
+            // Fingerprints are kilobytes each; only the duplicate search needs them
+            analysis: track.analysis.clone().map(|analysis| TrackAnalysis { fingerprint: String::new(), ..analysis }),
+            enabled: track.enabled,
+            disabled_reason: track.disabled_reason.clone(),
+            disabled_until: track.disabled_until,
+            quarantine: track.quarantine.clone(),
         }
-    } else {
-        None
-    };
+    }
+}
 
-    Some((title, artist, album, duration, bitrate))
+impl From<&Playlist> for PlaylistDto {
+    fn from(playlist: &Playlist) -> Self {
+        Self {
+            tracks: playlist.tracks.iter().map(TrackDto::from).collect(),
+            current_index: playlist.current_index,
+            excluded: playlist.excluded.iter()
+                .map(|excluded| ExcludedTrackDto {
+                    id: excluded.id.to_string(),
+                    title: excluded.title.clone(),
+                    artist: excluded.artist.clone(),
+                })
+                .collect(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -249,6 +1007,7 @@ mod tests {
             album: "Test Album".to_string(),
             duration: Some(180),
             bitrate: Some(192000),
+            ..Default::default()
         };
 
         assert_eq!(track.title, "Test Song");
@@ -258,6 +1017,36 @@ mod tests {
         assert_eq!(track.bitrate, Some(192000));
     }
 
+    #[test]
+    fn test_read_artwork() {
+        // ID3v2.3 tag holding one APIC frame (front cover), then a few silent frames
+        let image = b"\x89PNG\r\n\x1a\nnot really a png";
+        let mut apic = vec![0u8];
+        apic.extend_from_slice(b"image/png\0");
+        apic.push(3); // Front cover
+        apic.push(0); // No description
+        apic.extend_from_slice(image);
+        let mut frame = b"APIC".to_vec();
+        frame.extend_from_slice(&(apic.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&apic);
+        let size = frame.len() as u32;
+        let mut data = b"ID3\x03\x00\x00".to_vec();
+        data.extend([(size >> 21) & 0x7f, (size >> 14) & 0x7f, (size >> 7) & 0x7f, size & 0x7f].map(|b| b as u8));
+        data.extend_from_slice(&frame);
+        let header = crate::mp3::FrameHeader::parse(&[0xFF, 0xFB, 0x90, 0x64]).unwrap();
+        data.extend_from_slice(&crate::mp3::silence(&header, 500.0).0);
+
+        let path = std::env::temp_dir().join(format!("webradio_artwork_{}.mp3", Uuid::new_v4()));
+        std::fs::write(&path, &data).unwrap();
+        let (media_type, bytes) = read_artwork(&path).expect("artwork");
+        assert_eq!(media_type, "image/png");
+        assert_eq!(bytes, image);
+        std::fs::remove_file(&path).ok();
+
+        assert!(read_artwork(Path::new("music/does-not-exist.mp3")).is_none());
+    }
+
     #[test]
     fn test_playlist_get_next_track() {
         let mut playlist = Playlist {
@@ -269,6 +1058,7 @@ mod tests {
                     album: "Album 1".to_string(),
                     duration: None,
                     bitrate: None,
+                    ..Default::default()
                 },
                 Track {
                     path: PathBuf::from("track2.mp3"),
@@ -277,6 +1067,7 @@ mod tests {
                     album: "Album 2".to_string(),
                     duration: None,
                     bitrate: None,
+                    ..Default::default()
                 },
                 Track {
                     path: PathBuf::from("track3.mp3"),
@@ -285,9 +1076,11 @@ mod tests {
                     album: "Album 3".to_string(),
                     duration: None,
                     bitrate: None,
+                    ..Default::default()
                 },
             ],
             current_index: 0,
+            ..Default::default()
         };
 
         // Get first track
@@ -311,11 +1104,83 @@ mod tests {
         assert_eq!(playlist.current_index, 1);
     }
 
+    #[test]
+    fn test_shared_playlist_copy_on_write() {
+        let track = |title: &str| Track { title: title.to_string(), ..Default::default() };
+        let shared = SharedPlaylist::new(Playlist {
+            tracks: vec![track("A"), track("B"), track("C")],
+            current_index: 1,
+            ..Default::default()
+        });
+        assert_eq!(shared.next_track().unwrap().title, "B");
+        assert_eq!(shared.to_dto().current_index, 2);
+
+        // Readers holding a snapshot keep it across edits
+        let before = shared.snapshot();
+        let mut changes = shared.subscribe();
+        shared.update(|playlist| playlist.tracks.truncate(2));
+        assert_eq!(before.tracks.len(), 3);
+        assert_eq!(shared.len(), 2);
+        assert_eq!(*changes.borrow_and_update(), 1);
+
+        // Refused edits change nothing
+        assert!(shared.try_update(|_| Err::<(), _>(AppError::PlaylistEmpty)).is_err());
+        assert!(!changes.has_changed().unwrap());
+
+        // The rotation index is clamped into the shorter list
+        assert_eq!(shared.next_track().unwrap().title, "A");
+        assert_eq!(shared.next_track().unwrap().title, "B");
+
+        shared.update(|playlist| playlist.tracks.clear());
+        assert!(shared.is_empty());
+        assert!(shared.next_track().is_none());
+        assert_eq!(shared.to_dto().current_index, 0);
+    }
+
+    #[test]
+    fn test_concurrent_edits_keep_rotation_position() {
+        let tracks = (0..10).map(|n| Track { title: n.to_string(), ..Default::default() }).collect();
+        let shared = Arc::new(SharedPlaylist::new(Playlist { tracks, ..Default::default() }));
+
+        let editors: Vec<_> = (0..4)
+            .map(|editor| {
+                let shared = Arc::clone(&shared);
+                std::thread::spawn(move || {
+                    for n in 0..500 {
+                        shared.update(|playlist| {
+                            playlist.excluded.push(ExcludedTrack {
+                                id: Uuid::new_v4(),
+                                path: PathBuf::from(format!("{}-{}", editor, n)),
+                                title: String::new(),
+                                artist: String::new(),
+                            });
+                        });
+                    }
+                })
+            })
+            .collect();
+        let mut played: Vec<usize> = Vec::new();
+        while !editors.iter().all(|editor| editor.is_finished()) {
+            played.push(shared.next_track().unwrap().title.parse().unwrap());
+        }
+        for editor in editors {
+            editor.join().unwrap();
+        }
+
+        // No track change was undone by an edit, and no edit was lost to another
+        for pair in played.windows(2) {
+            assert_eq!(pair[1], (pair[0] + 1) % 10, "{:?}", pair);
+        }
+        assert_eq!(shared.snapshot().excluded.len(), 2000);
+        assert_eq!(shared.to_dto().current_index, (played[played.len() - 1] + 1) % 10);
+    }
+
     #[test]
     fn test_playlist_empty() {
         let mut playlist = Playlist {
             tracks: vec![],
             current_index: 0,
+            ..Default::default()
         };
 
         assert!(playlist.get_next_track().is_none());
@@ -332,9 +1197,11 @@ mod tests {
                     album: "Only Album".to_string(),
                     duration: Some(200),
                     bitrate: Some(128000),
+                    ..Default::default()
                 },
             ],
             current_index: 0,
+            ..Default::default()
         };
 
         // Should keep returning the same track and index should wrap
@@ -356,9 +1223,11 @@ mod tests {
                     album: "Album".to_string(),
                     duration: Some(180),
                     bitrate: Some(192000),
+                    ..Default::default()
                 },
             ],
             current_index: 0,
+            ..Default::default()
         };
 
         // Serialize to JSON
@@ -382,6 +1251,7 @@ mod tests {
             album: "Wonderful Album".to_string(),
             duration: Some(240),
             bitrate: Some(320000),
+            ..Default::default()
         };
 
         // Serialize
@@ -396,4 +1266,322 @@ mod tests {
         assert_eq!(deserialized.bitrate, Some(320000));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_track_field_lookup() {
+        let mut tags = BTreeMap::new();
+        tags.insert("TCON".to_string(), "Ambient".to_string());
+
+        let track = Track {
+            title: "Song".to_string(),
+            artist: "Artist".to_string(),
+            isrc: Some("USRC17607839".to_string()),
+            label: Some("Label".to_string()),
+            tags,
+            ..Default::default()
+        };
+
+        assert_eq!(track.field("title"), Some("Song"));
+        assert_eq!(track.field("ISRC"), Some("USRC17607839"));
+        assert_eq!(track.field("label"), Some("Label"));
+        assert_eq!(track.field("composer"), None);
+        assert_eq!(track.field("tcon"), Some("Ambient"));
+        assert_eq!(track.field("missing"), None);
+    }
+
+    #[test]
+    fn test_track_deserializes_without_extended_tags() {
+        // playlist.json files written before ISRC/tag support must still load
+        let json = r#"{"path":"old.mp3","title":"Old","artist":"A","album":"B","duration":10,"bitrate":null}"#;
+        let track: Track = serde_json::from_str(json).unwrap();
+
+        assert_eq!(track.title, "Old");
+        assert!(track.isrc.is_none());
+        assert!(track.tags.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rescan_tracks_added_and_removed_files() {
+        let dir = std::env::temp_dir().join(format!("webradio_rescan_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let empty = Playlist::default();
+        assert!(empty.rescan(&dir).await.unwrap().is_none());
+
+        std::fs::copy("music/Dhiyana.mp3", dir.join("Dhiyana.mp3")).unwrap();
+        let added = empty.rescan(&dir).await.unwrap().expect("new file should be picked up");
+        assert_eq!(added.tracks.len(), 1);
+        assert_eq!(added.tracks[0].path, PathBuf::from("Dhiyana.mp3"));
+        assert!(added.rescan(&dir).await.unwrap().is_none());
+
+        std::fs::copy("tests/fixtures/tone-8k-mono.flac", dir.join("tone.flac")).unwrap();
+        let with_flac = added.rescan(&dir).await.unwrap().expect("FLAC should be picked up");
+        assert_eq!(with_flac.tracks[1].path, PathBuf::from("tone.flac"));
+        assert_eq!(with_flac.tracks[1].duration, Some(1));
+
+        std::fs::copy("tests/fixtures/tone-8k-mono-flac.ogg", dir.join("tone.ogg")).unwrap();
+        let with_ogg = with_flac.rescan(&dir).await.unwrap().expect("Ogg should be picked up");
+        assert_eq!(with_ogg.tracks[2].title, "Ogg Tone");
+        std::fs::remove_file(dir.join("tone.ogg")).unwrap();
+
+        std::fs::copy("tests/fixtures/tone-8k-mono.m4a", dir.join("tone.m4a")).unwrap();
+        let with_m4a = with_flac.rescan(&dir).await.unwrap().expect("M4A should be picked up");
+        assert_eq!(with_m4a.tracks[2].title, "AAC Tone");
+        assert_eq!(with_m4a.tracks[2].duration, Some(1));
+        std::fs::remove_file(dir.join("tone.m4a")).unwrap();
+
+        // A renamed file is the same track, still benched and analyzed
+        let mut benched = with_flac.clone();
+        let quarantine = TrackQuarantine { error: "Cannot decode tone.flac".to_string(), attempts: 3, quarantined_at: 1_000 };
+        benched.quarantine(benched.tracks[1].id, quarantine.clone()).unwrap();
+        benched.tracks[1].disabled_until = Some(2_000);
+        benched.tracks[1].analysis = Some(TrackAnalysis { sha256: "abc".to_string(), ..Default::default() });
+        std::fs::rename(dir.join("tone.flac"), dir.join("renamed.flac")).unwrap();
+        let renamed = benched.rescan(&dir).await.unwrap().expect("rename should be picked up");
+        let track = &renamed.tracks[1];
+        assert_eq!(track.path, PathBuf::from("renamed.flac"));
+        assert_eq!(track.id, with_flac.tracks[1].id);
+        assert!(!track.enabled);
+        assert_eq!(track.disabled_reason.as_deref(), Some("quarantined"));
+        assert_eq!(track.disabled_until, Some(2_000));
+        assert_eq!(track.quarantine, Some(quarantine));
+        assert_eq!(track.analysis.as_ref().map(|analysis| analysis.sha256.as_str()), Some("abc"));
+        std::fs::rename(dir.join("renamed.flac"), dir.join("tone.flac")).unwrap();
+
+        std::fs::remove_file(dir.join("Dhiyana.mp3")).unwrap();
+        std::fs::remove_file(dir.join("tone.flac")).unwrap();
+        let removed = with_flac.rescan(&dir).await.unwrap().expect("missing files should be dropped");
+        assert!(removed.tracks.is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_playlist_edits_keep_upcoming_track() {
+        let track = |name: &str| Track { path: PathBuf::from(format!("{}.mp3", name)), title: name.to_string(), ..Default::default() };
+        let mut playlist = Playlist {
+            tracks: vec![track("A"), track("B"), track("C"), track("D")],
+            current_index: 2, // C is next
+            ..Default::default()
+        };
+        let id = |playlist: &Playlist, title: &str| playlist.tracks.iter().find(|t| t.title == title).unwrap().id;
+
+        let removed = id(&playlist, "A");
+        playlist.remove(removed).unwrap();
+        assert_eq!(playlist.excluded_track(removed).map(|excluded| excluded.path.as_path()), Some(Path::new("A.mp3")));
+        assert!(!serde_json::to_string(&PlaylistDto::from(&playlist)).unwrap().contains("A.mp3"));
+        assert_eq!(playlist.tracks[playlist.current_index].title, "C");
+
+        playlist.play_next(id(&playlist, "D")).unwrap();
+        let order: Vec<_> = playlist.tracks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(order, ["B", "D", "C"]);
+        assert_eq!(playlist.tracks[playlist.current_index].title, "D");
+
+        let ids: Vec<Uuid> = ["C", "B", "D"].iter().map(|title| id(&playlist, title)).collect();
+        playlist.reorder(&ids).unwrap();
+        assert_eq!(playlist.tracks[playlist.current_index].title, "D");
+        assert!(playlist.reorder(&ids[..2]).is_err());
+
+        // A rescan from before the removal doesn't bring the track back
+        let mut rescanned = playlist.tracks.clone();
+        rescanned.insert(0, track("A"));
+        playlist.replace_tracks(rescanned);
+        assert_eq!(playlist.tracks.len(), 3);
+        assert_eq!(playlist.tracks[playlist.current_index].title, "D");
+
+        playlist.add(track("A")).unwrap();
+        assert!(playlist.excluded.is_empty());
+        assert_eq!(playlist.tracks[3].id, removed);
+        assert!(playlist.add(track("A")).is_err());
+        assert!(matches!(playlist.remove(Uuid::new_v4()), Err(AppError::TrackNotFound(_))));
+    }
+
+    #[test]
+    fn test_disabled_tracks_are_skipped() {
+        let track = |name: &str| Track { path: PathBuf::from(format!("{}.mp3", name)), title: name.to_string(), ..Default::default() };
+        let shared = SharedPlaylist::new(Playlist {
+            tracks: vec![track("A"), track("B"), track("C")],
+            ..Default::default()
+        });
+        let benched = shared.snapshot().tracks[1].id;
+        shared.try_update(|playlist| playlist.set_enabled(benched, false)).unwrap();
+
+        let played: Vec<_> = (0..4).map(|_| shared.next_track().unwrap().title).collect();
+        assert_eq!(played, ["A", "C", "A", "C"]);
+        assert!(!shared.to_dto().tracks[1].enabled);
+
+        shared.update(|playlist| playlist.tracks.iter_mut().for_each(|t| t.enabled = false));
+        assert!(shared.next_track().is_none());
+
+        // Files written before the flag existed load as enabled
+        let loaded: Track = serde_json::from_str(r#"{"path":"a.mp3","title":"A","artist":"","album":"","duration":null,"bitrate":null}"#).unwrap();
+        assert!(loaded.enabled);
+    }
+
+    #[test]
+    fn test_tracks_without_ids_get_one_once() {
+        let json = r#"{"tracks":[{"path":"a.mp3","title":"A","artist":"","album":"","duration":null,"bitrate":null}]}"#;
+        let mut playlist: Playlist = serde_json::from_str(json).unwrap();
+        assert!(playlist.tracks[0].id.is_nil());
+        assert!(playlist.assign_ids());
+        let id = playlist.tracks[0].id;
+        assert!(!id.is_nil());
+
+        let reloaded: Playlist = serde_json::from_str(&serde_json::to_string(&playlist).unwrap()).unwrap();
+        assert_eq!(reloaded.tracks[0].id, id);
+        assert!(!playlist.assign_ids());
+
+        // Excluded files were stored as bare paths
+        let mut playlist: Playlist = serde_json::from_str(r#"{"tracks":[],"excluded":["b/B Side.mp3"]}"#).unwrap();
+        assert!(playlist.assign_ids());
+        let excluded = &playlist.excluded[0];
+        assert!(!excluded.id.is_nil());
+        assert_eq!((excluded.path.as_path(), excluded.title.as_str()), (Path::new("b/B Side.mp3"), "B Side"));
+        let reloaded: Playlist = serde_json::from_str(&serde_json::to_string(&playlist).unwrap()).unwrap();
+        assert_eq!(reloaded.excluded, playlist.excluded);
+    }
+
+    #[test]
+    fn test_upcoming_looks_ahead_without_advancing() {
+        let track = |name: &str| Track { path: PathBuf::from(format!("{}.mp3", name)), title: name.to_string(), ..Default::default() };
+        let shared = SharedPlaylist::new(Playlist {
+            tracks: vec![track("A"), track("B"), track("C"), track("D")],
+            current_index: 2,
+            ..Default::default()
+        });
+        let benched = shared.snapshot().tracks[3].id;
+        shared.try_update(|playlist| playlist.set_enabled(benched, false)).unwrap();
+        let titles = |tracks: Vec<Track>| tracks.into_iter().map(|t| t.title).collect::<Vec<_>>();
+
+        assert_eq!(titles(shared.upcoming(2)), ["C", "A"]);
+        assert_eq!(titles(shared.upcoming(10)), ["C", "A", "B"]);
+        assert_eq!(shared.next_track().unwrap().title, "C");
+        assert_eq!(titles(shared.upcoming(1)), ["A"]);
+        assert!(SharedPlaylist::new(Playlist::default()).upcoming(5).is_empty());
+    }
+
+    #[test]
+    fn test_rotation_rules_choose_next_track() {
+        let track = |name: &str, artist: &str| Track { title: name.to_string(), artist: artist.to_string(), ..Default::default() };
+        let rules = crate::rotation::RotationRules { artist_separation: 1, ..Default::default() };
+        let shared = SharedPlaylist::new(Playlist {
+            tracks: vec![track("A1", "Alpha"), track("A2", "Alpha"), track("B1", "Beta")],
+            ..Default::default()
+        }).with_rotation(Rotation::new(rules));
+        let titles = |tracks: Vec<Track>| tracks.into_iter().map(|t| t.title).collect::<Vec<_>>();
+
+        assert_eq!(shared.next_track().unwrap().title, "A1");
+        assert_eq!(titles(shared.upcoming(2)), ["B1", "A2"]);
+        assert_eq!(shared.next_track().unwrap().title, "B1");
+        assert_eq!(shared.next_track().unwrap().title, "A2");
+    }
+
+    #[test]
+    fn test_temporary_removal_expires() {
+        let track = |name: &str| Track { path: PathBuf::from(format!("{}.mp3", name)), title: name.to_string(), ..Default::default() };
+        let mut playlist = Playlist { tracks: vec![track("A"), track("B")], ..Default::default() };
+        let (a, b) = (playlist.tracks[0].id, playlist.tracks[1].id);
+        playlist.disable(a, Some("explicit".to_string()), Some(1_000)).unwrap();
+        playlist.disable(b, Some("corrupt".to_string()), None).unwrap();
+
+        let saved: Playlist = serde_json::from_str(&serde_json::to_string(&playlist).unwrap()).unwrap();
+        assert_eq!(saved.tracks[0].disabled_reason.as_deref(), Some("explicit"));
+        assert!(playlist.get_next_track().is_none());
+        assert!(!playlist.has_expired_removals(999));
+
+        assert!(playlist.has_expired_removals(1_000));
+        playlist.restore_expired(1_000);
+        assert!(playlist.tracks[0].enabled && playlist.tracks[0].disabled_reason.is_none());
+        assert!(!playlist.tracks[1].enabled, "indefinite removals stay");
+        assert_eq!(playlist.get_next_track().unwrap().title, "A");
+
+        playlist.set_enabled(b, true).unwrap();
+        assert_eq!(playlist.tracks[1].disabled_reason, None);
+    }
+
+    #[test]
+    fn test_quarantine_lasts_until_enabled() {
+        let mut playlist = Playlist { tracks: vec![Track { path: PathBuf::from("bad.mp3"), ..Default::default() }], ..Default::default() };
+        let id = playlist.tracks[0].id;
+        let quarantine = TrackQuarantine { error: "Cannot decode bad.mp3".to_string(), attempts: 3, quarantined_at: 1_000 };
+        playlist.quarantine(id, quarantine.clone()).unwrap();
+
+        let saved: Playlist = serde_json::from_str(&serde_json::to_string(&playlist).unwrap()).unwrap();
+        assert_eq!(saved.tracks[0].quarantine, Some(quarantine));
+        assert_eq!(saved.tracks[0].disabled_reason.as_deref(), Some("quarantined"));
+        playlist.restore_expired(u64::MAX);
+        assert!(playlist.get_next_track().is_none());
+
+        playlist.set_enabled(id, true).unwrap();
+        assert!(playlist.tracks[0].quarantine.is_none());
+    }
+
+    #[test]
+    fn test_search_follows_edits() {
+        let track = |name: &str| Track { path: PathBuf::from(format!("{}.mp3", name)), title: name.to_string(), ..Default::default() };
+        let shared = SharedPlaylist::new(Playlist { tracks: vec![track("Café"), track("Tea")], ..Default::default() });
+        assert_eq!(shared.search("cafe")[0].title, "Café");
+
+        shared.update(|playlist| playlist.tracks.push(track("Cafe Racer")));
+        let titles: Vec<_> = shared.search("CAFE").into_iter().map(|t| t.title).collect();
+        assert_eq!(titles, ["Café", "Cafe Racer"]);
+    }
+
+    // An ID3v2.3 tag of TXXX frames
+    fn id3_txxx(frames: &[(&str, &str)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (description, value) in frames {
+            let data = [&[0u8][..], description.as_bytes(), &[0], value.as_bytes()].concat();
+            body.extend_from_slice(b"TXXX");
+            body.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            body.extend_from_slice(&[0, 0]);
+            body.extend_from_slice(&data);
+        }
+        let size = body.len() as u32;
+        let syncsafe = [(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F];
+        [&b"ID3\x03\x00\x00"[..], &syncsafe, &body].concat()
+    }
+
+    #[test]
+    fn test_reads_replaygain_tags() {
+        let mp3 = std::fs::read("music/Singing Birds.mp3").unwrap();
+        let frames = &mp3[crate::mp3::id3v2_len(&mp3)..];
+        let tag = id3_txxx(&[("REPLAYGAIN_TRACK_GAIN", "-6.54 dB"), ("REPLAYGAIN_TRACK_PEAK", "0.988525")]);
+        let path = std::env::temp_dir().join(format!("webradio_replaygain_{}.mp3", Uuid::new_v4()));
+        std::fs::write(&path, [&tag, &frames[..200_000]].concat()).unwrap();
+
+        let metadata = extract_metadata_with_symphonia(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(metadata.replaygain_gain_db, Some(-6.54));
+        assert_eq!(metadata.replaygain_peak, Some(0.988525));
+        assert!(metadata.tags.is_empty());
+
+        assert_eq!(parse_replaygain("+1.20 dB"), Some(1.2));
+        assert_eq!(parse_replaygain("-3db"), Some(-3.0));
+        assert_eq!(parse_replaygain("loud"), None);
+    }
+
+    #[test]
+    fn test_gain_prefers_analysis_and_avoids_clipping() {
+        let tagged = Track { replaygain_gain_db: Some(-4.0), replaygain_peak: Some(0.5), ..Default::default() };
+        assert_eq!(tagged.gain_db(), Some(-4.0));
+        // +9 dB would take a half-scale peak past full scale; about +6 dB is the most it can take
+        let quiet = Track { replaygain_gain_db: Some(9.0), ..tagged.clone() };
+        assert!((quiet.gain_db().unwrap() - 6.0206).abs() < 1e-3);
+
+        let analysis = TrackAnalysis { gain_db: Some(-2.5), peak: 0.9, ..Default::default() };
+        let analyzed = Track { analysis: Some(analysis.clone()), ..tagged.clone() };
+        assert_eq!(analyzed.gain_db(), Some(-2.5));
+        // Silence has no analyzed gain, so the tag still applies
+        let silent = Track { analysis: Some(TrackAnalysis { gain_db: None, ..analysis }), ..tagged };
+        assert_eq!(silent.gain_db(), Some(-4.0));
+        assert_eq!(Track::default().gain_db(), None);
+    }
+
+    #[test]
+    fn test_audible_range_skips_measured_silence() {
+        let analysis = TrackAnalysis { duration_ms: 200_000, leading_silence_ms: 1_500, trailing_silence_ms: 12_000, ..Default::default() };
+        let before = Track { analysis: Some(analysis.clone()), ..Default::default() };
+        assert_eq!(before.audible_ms(), None, "analyzed before silence detection");
+        let track = Track { analysis: Some(TrackAnalysis { silence_threshold_db: Some(-50.0), ..analysis }), ..Default::default() };
+        assert_eq!(track.audible_ms(), Some((1_500, 188_000)));
+    }
+}
@@ -0,0 +1,328 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use symphonia::core::{
+    codecs::CodecParameters,
+    errors::Error as DecodeError,
+    formats::{FormatOptions, FormatReader, Packet},
+    io::{MediaSource, MediaSourceStream},
+    meta::MetadataOptions,
+    probe::Hint,
+    units::TimeBase,
+};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{error::{AppError, Result}, mp3};
+
+/// A file cut short before its trailing ID3v1/APE tags, which would otherwise be
+/// read as (garbage) frames and broadcast. Positions are relative to `start`.
+pub struct TrimmedFile {
+    file: File,
+    start: u64,
+    position: u64,
+    end: u64,
+}
+
+impl TrimmedFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let probe = len.min(mp3::TRAILING_TAGS_PROBE as u64);
+        let mut tail = vec![0; probe as usize];
+        file.seek(SeekFrom::Start(len - probe))?;
+        file.read_exact(&mut tail)?;
+        file.rewind()?;
+        let end = len.saturating_sub(mp3::trailing_tags_len(&tail) as u64);
+        Ok(Self { file, start: 0, position: 0, end })
+    }
+
+    /// Only the audio: the leading ID3v2 tags are cut as well. Their embedded
+    /// artwork is served by the artwork endpoint, not broadcast.
+    pub fn audio(path: &Path) -> io::Result<Self> {
+        let mut trimmed = Self::open(path)?;
+        let mut header = Vec::with_capacity(10);
+        loop {
+            header.clear();
+            (&mut trimmed.file).take(10).read_to_end(&mut header)?;
+            match mp3::id3v2_len(&header) as u64 {
+                0 => break,
+                tag => trimmed.start = trimmed.file.seek(SeekFrom::Start(trimmed.start + tag))?.min(trimmed.end),
+            }
+        }
+        trimmed.file.seek(SeekFrom::Start(trimmed.start))?;
+        Ok(trimmed)
+    }
+}
+
+impl Read for TrimmedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = (self.end - self.start).saturating_sub(self.position).min(buf.len() as u64) as usize;
+        let read = self.file.read(&mut buf[..left])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for TrimmedFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => (self.end - self.start).checked_add_signed(offset),
+        };
+        let target = target.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start"))?;
+        self.position = self.file.seek(SeekFrom::Start(self.start + target))? - self.start;
+        Ok(self.position)
+    }
+}
+
+impl MediaSource for TrimmedFile {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.end - self.start)
+    }
+}
+
+/// A track file opened and probed, ready to stream. Packets read ahead by
+/// `warm_up` are handed out before the reader is touched again.
+pub struct OpenedTrack {
+    format: Box<dyn FormatReader>,
+    pub track_id: u32,
+    pub time_base: TimeBase,
+    pub codec_params: CodecParameters,
+    warm: VecDeque<Packet>,
+}
+
+impl OpenedTrack {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = TrimmedFile::audio(path)
+            .map_err(|e| AppError::DecodeError { path: path.to_path_buf(), reason: format!("failed to open file: {}", e) })?;
+        let media_source = MediaSourceStream::new(Box::new(file), Default::default());
+
+        // Create a hint to help the probe guess the format
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(ext);
+        }
+
+        let probed = symphonia::default::get_probe()
+            .format(&hint, media_source, &FormatOptions::default(), &MetadataOptions::default())
+            .map_err(|e| AppError::DecodeError { path: path.to_path_buf(), reason: format!("failed to probe file: {}", e) })?;
+        let format = probed.format;
+
+        // Get the default audio track
+        let track_info = format.default_track()
+            .ok_or_else(|| AppError::DecodeError { path: path.to_path_buf(), reason: "no audio track found".to_string() })?;
+        // Raw ADTS streams carry no time base; their packets are timed in samples
+        let time_base = track_info.codec_params.time_base
+            .or_else(|| track_info.codec_params.sample_rate.map(|rate| TimeBase::new(1, rate)))
+            .ok_or_else(|| AppError::DecodeError { path: path.to_path_buf(), reason: "no timebase available".to_string() })?;
+
+        Ok(Self {
+            track_id: track_info.id,
+            time_base,
+            codec_params: track_info.codec_params.clone(),
+            format,
+            warm: VecDeque::new(),
+        })
+    }
+
+    /// Read the first `ms` of audio packets into memory. A read error ends the
+    /// warm-up early; streaming runs into it again and handles it there.
+    fn warm_up(&mut self, ms: f64) {
+        let mut warmed_ms = 0.0;
+        while warmed_ms < ms {
+            let Ok(packet) = self.format.next_packet() else {
+                break;
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let time = self.time_base.calc_time(packet.dur());
+            warmed_ms += (time.seconds as f64 + time.frac) * 1000.0;
+            self.warm.push_back(packet);
+        }
+    }
+
+    pub fn next_packet(&mut self) -> std::result::Result<Packet, DecodeError> {
+        match self.warm.pop_front() {
+            Some(packet) => Ok(packet),
+            None => self.format.next_packet(),
+        }
+    }
+}
+
+/// Opens the upcoming track in the background while the current one plays, so
+/// moving on to it costs no disk reads or format probing in the broadcast loop
+pub struct Prefetcher {
+    warm_ms: f64,
+    pending: Mutex<Option<Pending>>,
+}
+
+struct Pending {
+    id: Uuid,
+    path: PathBuf,
+    task: JoinHandle<Result<OpenedTrack>>,
+}
+
+impl Prefetcher {
+    /// `warm_secs` of each prefetched track are read ahead; 0 turns prefetching off
+    pub fn new(warm_secs: u64) -> Self {
+        Self { warm_ms: warm_secs as f64 * 1000.0, pending: Mutex::new(None) }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.warm_ms > 0.0
+    }
+
+    /// Start opening the track expected to play next, replacing an earlier guess
+    pub fn prefetch(&self, id: Uuid, path: PathBuf) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut pending = self.pending.lock().unwrap();
+        if pending.as_ref().is_some_and(|pending| pending.id == id && pending.path == path) {
+            return;
+        }
+        debug!("Prefetching {}", path.display());
+        let warm_ms = self.warm_ms;
+        let task = tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || {
+                let mut track = OpenedTrack::open(&path)?;
+                track.warm_up(warm_ms);
+                Ok(track)
+            }
+        });
+        if let Some(stale) = pending.replace(Pending { id, path, task }) {
+            stale.task.abort();
+        }
+    }
+
+    /// The prefetched track if it is the one about to play. `None` when the guess
+    /// was wrong (the playlist changed) or prefetching failed; the caller opens the
+    /// file itself then, which reports any error properly.
+    pub async fn take(&self, id: Uuid, path: &Path) -> Option<OpenedTrack> {
+        let pending = self.pending.lock().unwrap().take()?;
+        if pending.id != id || pending.path != path {
+            pending.task.abort();
+            return None;
+        }
+        match pending.task.await {
+            Ok(Ok(track)) => Some(track),
+            Ok(Err(e)) => {
+                warn!("Prefetching {} failed: {}", path.display(), e);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_track() -> PathBuf {
+        std::fs::read_dir("music").unwrap()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .find(|path| path.extension().is_some_and(|ext| ext == "mp3"))
+            .expect("no mp3 in music/")
+    }
+
+    #[tokio::test]
+    async fn test_takes_only_the_expected_track() {
+        let prefetcher = Prefetcher::new(2);
+        let path = sample_track();
+        let id = Uuid::new_v4();
+
+        prefetcher.prefetch(id, path.clone());
+        assert!(prefetcher.take(Uuid::new_v4(), &path).await.is_none());
+
+        prefetcher.prefetch(id, path.clone());
+        let mut track = prefetcher.take(id, &path).await.unwrap();
+        assert!(!track.warm.is_empty());
+        assert!(track.next_packet().is_ok());
+        assert!(prefetcher.take(id, &path).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let prefetcher = Prefetcher::new(0);
+        let path = sample_track();
+        let id = Uuid::new_v4();
+        prefetcher.prefetch(id, path.clone());
+        assert!(prefetcher.take(id, &path).await.is_none());
+    }
+
+    #[test]
+    fn test_trailing_tags_are_not_streamed() {
+        let mp3 = std::fs::read(sample_track()).unwrap();
+        let frames = mp3::split_frames(&mp3);
+        let audio: Vec<u8> = frames.iter().take(50).flat_map(|(_, frame)| frame.to_vec()).collect();
+        // An APE tag whose binary item holds what looks like a whole frame, as cover art can
+        let item = frames[60].1;
+        let footer = |flags: u32| {
+            let mut footer = b"APETAGEX".to_vec();
+            footer.extend_from_slice(&2000u32.to_le_bytes());
+            footer.extend_from_slice(&(item.len() as u32 + 32).to_le_bytes());
+            footer.extend_from_slice(&1u32.to_le_bytes());
+            footer.extend_from_slice(&flags.to_le_bytes());
+            footer.extend_from_slice(&[0; 8]);
+            footer
+        };
+        let ape = [footer(0xA000_0000), item.to_vec(), footer(0x8000_0000)].concat();
+        assert_eq!(streamed_bytes(&[audio.clone(), ape].concat()), streamed_bytes(&audio));
+    }
+
+    #[test]
+    fn test_id3v2_tags_are_not_streamed() {
+        let mp3 = std::fs::read(sample_track()).unwrap();
+        let frames = mp3::split_frames(&mp3);
+        let audio: Vec<u8> = frames.iter().take(50).flat_map(|(_, frame)| frame.to_vec()).collect();
+        // Tags with a picture holding what looks like a frame: stacked in front of the
+        // audio and appended after it (ID3v2.4, found by its footer)
+        let picture = [b"\x00image/jpeg\x00\x03\x00".as_slice(), frames[60].1].concat();
+        let apic = [b"APIC".as_slice(), &(picture.len() as u32).to_be_bytes(), &[0, 0], &picture].concat();
+        let size = apic.len();
+        let syncsafe = [(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F];
+        let tag = [b"ID3\x03\x00\x00".as_slice(), &syncsafe, &apic].concat();
+        let appended = [b"ID3\x04\x00\x10".as_slice(), &syncsafe, &apic, b"3DI\x04\x00\x10", &syncsafe].concat();
+
+        let tagged = [tag.clone(), tag, audio.clone(), appended].concat();
+        assert_eq!(streamed_bytes(&tagged), streamed_bytes(&audio));
+    }
+
+    #[test]
+    fn test_opens_adts_without_time_base() {
+        let mut track = OpenedTrack::open(Path::new("tests/fixtures/tone-8k-mono.aac")).unwrap();
+        assert_eq!(track.time_base, TimeBase::new(1, 8000));
+        let mut samples = 0;
+        while let Ok(packet) = track.next_packet() {
+            samples += packet.dur();
+        }
+        assert_eq!(samples, 9 * 1024);
+    }
+
+    // Bytes of the packets a track file is streamed as
+    fn streamed_bytes(data: &[u8]) -> usize {
+        let path = std::env::temp_dir().join(format!("webradio_trimmed_{}.mp3", Uuid::new_v4()));
+        std::fs::write(&path, data).unwrap();
+        let mut track = OpenedTrack::open(&path).unwrap();
+        let mut bytes = 0;
+        while let Ok(packet) = track.next_packet() {
+            bytes += packet.buf().len();
+        }
+        std::fs::remove_file(&path).ok();
+        bytes
+    }
+}
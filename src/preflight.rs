@@ -0,0 +1,221 @@
+// Startup self-check: problems that would otherwise only show when the first
+// listener connects (unreadable music, nothing decodable, a full disk, an unset
+// clock) are found before the station starts and reported at /readyz, together
+// with the running station's own checks
+
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use serde::Serialize;
+use symphonia::core::{
+    codecs::DecoderOptions, formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+use tracing::{info, warn};
+
+use crate::{config::Config, playlist};
+
+// Files tried before concluding nothing in the library decodes
+const MAX_DECODE_ATTEMPTS: usize = 20;
+// A clock before this (2024-01-01) hasn't been set, e.g. a board without an RTC
+const EARLIEST_SANE_UNIX: u64 = 1_704_067_200;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, ok: true, detail: detail.into() }
+    }
+
+    pub fn fail(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, ok: false, detail: detail.into() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightReport {
+    pub status: &'static str, // "ready", or "degraded" when a check failed
+    pub checked_at: u64,      // Unix seconds
+    pub checks: Vec<Check>,
+}
+
+impl PreflightReport {
+    fn new(checks: Vec<Check>) -> Self {
+        let ready = checks.iter().all(|check| check.ok);
+        Self {
+            status: if ready { "ready" } else { "degraded" },
+            checked_at: unix_now(),
+            checks,
+        }
+    }
+
+    /// The preflight followed by `checks` of the running station, as /readyz reports them
+    pub fn with_checks(&self, checks: Vec<Check>) -> Self {
+        Self::new(self.checks.iter().cloned().chain(checks).collect())
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.checks.iter().all(|check| check.ok)
+    }
+
+    /// Failed checks as one line, for logs and startup errors
+    pub fn failures(&self) -> String {
+        self.checks.iter()
+            .filter(|check| !check.ok)
+            .map(|check| format!("{}: {}", check.name, check.detail))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    fn log(&self) {
+        for check in &self.checks {
+            if check.ok {
+                info!("Preflight {}: {}", check.name, check.detail);
+            } else {
+                warn!("Preflight {} FAILED: {}", check.name, check.detail);
+            }
+        }
+    }
+}
+
+/// Run every check against `config` and log the outcome
+pub async fn run(config: &Config) -> PreflightReport {
+    let mut checks = vec![check_music_dir(&config.music_dir)];
+    let music_dir = config.music_dir.clone();
+    let files = playlist::list_audio_files(&music_dir).await.map_err(|e| e.to_string());
+    checks.push(
+        tokio::task::spawn_blocking(move || check_decodable_track(&music_dir, files))
+            .await
+            .unwrap_or_else(|e| Check::fail("decodable_track", format!("check crashed: {}", e))),
+    );
+    checks.push(check_disk_space(&config.music_dir, config.preflight_min_free_mb));
+    checks.push(check_clock(unix_now()));
+
+    let report = PreflightReport::new(checks);
+    report.log();
+    report
+}
+
+fn check_music_dir(dir: &Path) -> Check {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => Check::pass("music_dir", format!("{} readable ({} entries)", dir.display(), entries.count())),
+        Err(e) => Check::fail("music_dir", format!("{} is not readable: {}", dir.display(), e)),
+    }
+}
+
+fn check_decodable_track(dir: &Path, files: Result<Vec<PathBuf>, String>) -> Check {
+    let files = match files {
+        Ok(files) if !files.is_empty() => files,
+        Ok(_) => return Check::fail("decodable_track", format!("no audio files in {}", dir.display())),
+        Err(e) => return Check::fail("decodable_track", format!("cannot list {}: {}", dir.display(), e)),
+    };
+
+    let attempts = files.len().min(MAX_DECODE_ATTEMPTS);
+    for path in files.iter().take(attempts) {
+        match decode_first_packet(path) {
+            Ok(()) => return Check::pass("decodable_track", format!("decoded {}", path.display())),
+            Err(e) => warn!("Preflight: cannot decode {}: {}", path.display(), e),
+        }
+    }
+    Check::fail("decodable_track", format!("none of {} audio files tried decoded", attempts))
+}
+
+fn decode_first_packet(path: &Path) -> Result<(), String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| e.to_string())?;
+    let mut format = probed.format;
+    let track = format.default_track().ok_or("no audio track")?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| e.to_string())?;
+    loop {
+        let packet = format.next_packet().map_err(|e| e.to_string())?;
+        if packet.track_id() == track_id {
+            return decoder.decode(&packet).map(|_| ()).map_err(|e| e.to_string());
+        }
+    }
+}
+
+fn check_disk_space(dir: &Path, min_free_mb: u64) -> Check {
+    match free_space_mb(dir) {
+        Some(free) if free >= min_free_mb => Check::pass("disk_space", format!("{}MB free", free)),
+        Some(free) => Check::fail("disk_space", format!("{}MB free, below the {}MB minimum", free, min_free_mb)),
+        None => Check::pass("disk_space", "free space unknown"),
+    }
+}
+
+#[cfg(unix)]
+fn free_space_mb(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid out-pointer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64 / (1024 * 1024))
+}
+
+#[cfg(not(unix))]
+fn free_space_mb(_dir: &Path) -> Option<u64> {
+    None
+}
+
+fn check_clock(now: u64) -> Check {
+    if now < EARLIEST_SANE_UNIX {
+        Check::fail("clock", format!("system time {} is before 2024; timestamps and signed URLs will be wrong", now))
+    } else {
+        Check::pass("clock", format!("system time {}", now))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_check() {
+        assert!(!check_clock(0).ok);
+        assert!(check_clock(unix_now()).ok);
+    }
+
+    #[tokio::test]
+    async fn test_missing_music_dir_degrades() {
+        let mut config = Config::from_env();
+        config.music_dir = std::env::temp_dir().join(format!("webradio_preflight_missing_{}", uuid::Uuid::new_v4()));
+        let report = run(&config).await;
+
+        assert!(!report.is_ready());
+        assert_eq!(report.status, "degraded");
+        let failed: Vec<&str> = report.checks.iter().filter(|check| !check.ok).map(|check| check.name).collect();
+        assert!(failed.contains(&"music_dir"));
+        assert!(failed.contains(&"decodable_track"));
+        assert!(report.failures().contains("music_dir"));
+    }
+
+    #[tokio::test]
+    async fn test_bundled_music_passes() {
+        let mut config = Config::from_env();
+        config.music_dir = "music".into();
+        config.preflight_min_free_mb = 0;
+        let report = run(&config).await;
+        assert!(report.is_ready(), "{}", report.failures());
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// Sampling rate; slightly off 100Hz so samples don't line up with periodic work
+pub const SAMPLE_HZ: u32 = 99;
+pub const MAX_SECONDS: u64 = 60;
+const MAX_DEPTH: usize = 128;
+
+/// Aggregated stacks from a CPU profile, root frame first
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub stacks: HashMap<Vec<String>, u64>,
+    pub samples: u64,
+    pub dropped: u64, // Samples that didn't fit in the preallocated buffer
+}
+
+impl Profile {
+    /// Collapsed stack format (`frame;frame;frame count`), as read by
+    /// flamegraph.pl, inferno and speedscope
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = self.stacks.iter()
+            .map(|(stack, count)| format!("{} {}", stack.join(";"), count))
+            .collect();
+        lines.sort();
+        lines.join("\n") + "\n"
+    }
+
+    /// Standalone SVG flamegraph (hover a frame for its name and share)
+    pub fn svg(&self, title: &str) -> String {
+        const WIDTH: f64 = 1200.0;
+        const ROW: f64 = 16.0;
+
+        // Merge stacks into a call tree
+        #[derive(Default)]
+        struct Node {
+            total: u64,
+            children: Vec<(String, Node)>,
+        }
+        let mut root = Node::default();
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        for (stack, &count) in stacks {
+            let mut node = &mut root;
+            node.total += count;
+            for frame in stack {
+                let index = match node.children.iter().position(|(name, _)| name == frame) {
+                    Some(index) => index,
+                    None => {
+                        node.children.push((frame.clone(), Node::default()));
+                        node.children.len() - 1
+                    }
+                };
+                node = &mut node.children[index].1;
+                node.total += count;
+            }
+        }
+
+        fn depth(node: &Node) -> usize {
+            node.children.iter().map(|(_, child)| depth(child) + 1).max().unwrap_or(0)
+        }
+        let rows = depth(&root) + 1;
+        let height = (rows as f64 + 2.0) * ROW;
+        let total = root.total.max(1) as f64;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{height}\" font-family=\"monospace\" font-size=\"11\">\n\
+             <text x=\"4\" y=\"12\">{} ({} samples)</text>\n",
+            escape(title), self.samples,
+        );
+
+        // Root at the bottom, callees stacked above their callers
+        struct Layout {
+            scale: f64, // Pixels per sample
+            total: f64,
+            height: f64,
+        }
+        fn draw(svg: &mut String, layout: &Layout, name: &str, node: &Node, x: f64, level: usize) {
+            let Layout { scale, total, height } = *layout;
+            let width = node.total as f64 * scale;
+            if width < 0.5 {
+                return;
+            }
+            let y = height - (level as f64 + 1.0) * ROW;
+            let hue = 20 + (name.bytes().fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32)) % 40);
+            let _ = write!(
+                svg,
+                "<g><title>{} ({} samples, {:.2}%)</title><rect x=\"{x:.1}\" y=\"{y:.1}\" width=\"{width:.1}\" height=\"{}\" fill=\"hsl({hue},90%,60%)\" stroke=\"white\" stroke-width=\"0.5\"/>",
+                escape(name), node.total, node.total as f64 * 100.0 / total, ROW - 1.0,
+            );
+            let max_chars = (width / 7.0) as usize;
+            if max_chars >= 3 {
+                let label: String = if name.chars().count() > max_chars {
+                    name.chars().take(max_chars - 2).collect::<String>() + ".."
+                } else {
+                    name.to_string()
+                };
+                let _ = write!(svg, "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>", x + 3.0, y + ROW - 4.0, escape(&label));
+            }
+            svg.push_str("</g>\n");
+
+            let mut child_x = x;
+            for (child_name, child) in &node.children {
+                draw(svg, layout, child_name, child, child_x, level + 1);
+                child_x += child.total as f64 * scale;
+            }
+        }
+        let layout = Layout { scale: WIDTH / total, total, height };
+        draw(&mut svg, &layout, "all", &root, 0.0, 0);
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Sample the whole process's CPU usage for `duration` (every thread that is
+/// running when the profiling timer fires). Only one capture can run at a time.
+#[cfg(unix)]
+pub async fn capture(duration: Duration) -> std::io::Result<Profile> {
+    let samples = sampler::run(duration).await?;
+    Ok(sampler::symbolize(samples))
+}
+
+#[cfg(not(unix))]
+pub async fn capture(_duration: Duration) -> std::io::Result<Profile> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "CPU profiling needs a Unix platform"))
+}
+
+#[cfg(unix)]
+mod sampler {
+    use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::{Profile, MAX_DEPTH, SAMPLE_HZ};
+
+    pub struct Sample {
+        depth: usize,
+        ips: [usize; MAX_DEPTH],
+    }
+
+    pub struct Samples {
+        slots: Vec<Sample>,
+        dropped: u64,
+    }
+
+    static RUNNING: AtomicBool = AtomicBool::new(false);
+    static BUFFER: AtomicPtr<Sample> = AtomicPtr::new(std::ptr::null_mut());
+    static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    static IN_HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+    // SIGPROF handler: only atomics and an unsynchronized stack walk into memory
+    // allocated before the timer was armed
+    extern "C" fn on_sigprof(_signal: libc::c_int) {
+        IN_HANDLER.fetch_add(1, Ordering::SeqCst);
+        let buffer = BUFFER.load(Ordering::SeqCst);
+        if !buffer.is_null() {
+            let index = NEXT.fetch_add(1, Ordering::SeqCst);
+            if index < CAPACITY.load(Ordering::SeqCst) {
+                // SAFETY: each index is handed out once, and the buffer outlives the
+                // handler (capture waits for IN_HANDLER to drain before freeing it)
+                let sample = unsafe { &mut *buffer.add(index) };
+                sample.depth = 0;
+                unsafe {
+                    backtrace::trace_unsynchronized(|frame| {
+                        sample.ips[sample.depth] = frame.ip() as usize;
+                        sample.depth += 1;
+                        sample.depth < MAX_DEPTH
+                    });
+                }
+            }
+        }
+        IN_HANDLER.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    // Not bound by the libc crate
+    extern "C" {
+        fn setitimer(which: libc::c_int, new_value: *const libc::itimerval, old_value: *mut libc::itimerval) -> libc::c_int;
+    }
+
+    fn set_timer(interval: Duration) -> std::io::Result<()> {
+        let timeval = libc::timeval {
+            tv_sec: interval.as_secs() as libc::time_t,
+            tv_usec: interval.subsec_micros() as libc::suseconds_t,
+        };
+        let timer = libc::itimerval { it_interval: timeval, it_value: timeval };
+        // SAFETY: plain syscall with a valid itimerval
+        if unsafe { setitimer(libc::ITIMER_PROF, &timer, std::ptr::null_mut()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn set_handler(handler: libc::sighandler_t) -> std::io::Result<()> {
+        // SAFETY: installs a handler that only touches atomics and preallocated memory
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handler;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(libc::SIGPROF, &action, std::ptr::null_mut()) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn run(duration: Duration) -> std::io::Result<Samples> {
+        if RUNNING.swap(true, Ordering::SeqCst) {
+            return Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "a profile is already being captured"));
+        }
+
+        // Room for every thread sampling at full rate on all cores
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let capacity = (duration.as_secs_f64() * SAMPLE_HZ as f64) as usize * cores + 64;
+        let mut slots: Vec<Sample> = (0..capacity).map(|_| Sample { depth: 0, ips: [0; MAX_DEPTH] }).collect();
+        // Resolve the unwinder's lazily-initialised state outside the signal handler
+        backtrace::trace(|_| false);
+
+        NEXT.store(0, Ordering::SeqCst);
+        CAPACITY.store(capacity, Ordering::SeqCst);
+        BUFFER.store(slots.as_mut_ptr(), Ordering::SeqCst);
+
+        let result = async {
+            set_handler(on_sigprof as *const () as libc::sighandler_t)?;
+            set_timer(Duration::from_micros(1_000_000 / SAMPLE_HZ as u64))?;
+            tokio::time::sleep(duration).await;
+            Ok::<_, std::io::Error>(())
+        }.await;
+
+        // Stop sampling (ignore rather than restore the default action, which
+        // terminates the process, in case a signal is still pending)
+        let _ = set_timer(Duration::ZERO);
+        let _ = set_handler(libc::SIG_IGN);
+        BUFFER.store(std::ptr::null_mut(), Ordering::SeqCst);
+        while IN_HANDLER.load(Ordering::SeqCst) > 0 {
+            std::thread::yield_now();
+        }
+        RUNNING.store(false, Ordering::SeqCst);
+        result?;
+
+        let taken = NEXT.load(Ordering::SeqCst);
+        slots.truncate(taken.min(capacity));
+        Ok(Samples { slots, dropped: taken.saturating_sub(capacity) as u64 })
+    }
+
+    /// Turn raw instruction pointers into demangled, root-first stacks with the
+    /// signal handler frames removed
+    pub fn symbolize(samples: Samples) -> Profile {
+        let mut names: std::collections::HashMap<usize, Vec<String>> = std::collections::HashMap::new();
+        let mut profile = Profile { dropped: samples.dropped, ..Default::default() };
+
+        for sample in &samples.slots {
+            let mut stack: Vec<String> = Vec::with_capacity(sample.depth);
+            for &ip in &sample.ips[..sample.depth] {
+                let frames = names.entry(ip).or_insert_with(|| {
+                    let mut frames = Vec::new();
+                    // Inlined functions are reported innermost first
+                    backtrace::resolve(ip as *mut std::ffi::c_void, |symbol| {
+                        if let Some(name) = symbol.name() {
+                            frames.push(format!("{:#}", name));
+                        }
+                    });
+                    if frames.is_empty() {
+                        frames.push(format!("{:#x}", ip));
+                    }
+                    frames
+                });
+                stack.extend(frames.iter().cloned());
+            }
+
+            // Innermost frames belong to the handler, followed by the signal
+            // trampoline (often unsymbolized) and then the interrupted code
+            if let Some(handler) = stack.iter().rposition(|name| name.contains("on_sigprof")) {
+                stack.drain(..(handler + 2).min(stack.len()));
+            }
+            // Unwinding ends at a null return address
+            stack.retain(|name| name != "0x0");
+            if stack.is_empty() {
+                continue;
+            }
+
+            stack.reverse();
+            *profile.stacks.entry(stack).or_insert(0) += 1;
+            profile.samples += 1;
+        }
+        profile
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> Profile {
+        let mut profile = Profile::default();
+        profile.stacks.insert(vec!["main".into(), "encode".into()], 3);
+        profile.stacks.insert(vec!["main".into(), "send<T>".into()], 1);
+        profile.samples = 4;
+        profile
+    }
+
+    #[test]
+    fn test_folded_and_svg_output() {
+        let profile = profile();
+        assert_eq!(profile.folded(), "main;encode 3\nmain;send<T> 1\n");
+
+        let svg = profile.svg("webradio");
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("<title>encode (3 samples, 75.00%)</title>"));
+        assert!(svg.contains("send&lt;T&gt;"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_capture_samples_busy_thread() {
+        let busy = std::thread::spawn(|| {
+            let start = std::time::Instant::now();
+            let mut x = 0u64;
+            while start.elapsed() < Duration::from_millis(600) {
+                x = std::hint::black_box(x.wrapping_mul(6364136223846793005).wrapping_add(1));
+            }
+            x
+        });
+        let profile = capture(Duration::from_millis(500)).await.unwrap();
+        busy.join().unwrap();
+
+        assert!(profile.samples > 0);
+        assert!(profile.folded().lines().all(|line| line.rsplit_once(' ').is_some()));
+    }
+}
@@ -0,0 +1,230 @@
+// Public IP discovery with a STUN Binding request (RFC 5389): the server echoes
+// the address our UDP packet arrived from. Only the request and the
+// (XOR-)MAPPED-ADDRESS attribute of the response are implemented.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use serde::Serialize;
+use tokio::{net::UdpSocket, sync::watch, time::timeout};
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+
+const MAGIC_COOKIE: u32 = 0x2112_A442;
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+const ATTEMPTS: usize = 2; // Per server; STUN runs over UDP
+
+/// The most recently discovered public address
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PublicAddr {
+    pub ip: IpAddr,
+    pub source: String,       // "config" or "stun:<server>"
+    pub discovered_at: u64,   // Unix seconds
+}
+
+/// Public IP of this host, from PUBLIC_IP or periodic STUN lookups. The last
+/// good result is kept when a refresh fails.
+pub struct PublicIp {
+    servers: Vec<String>,
+    refresh: Option<Duration>,
+    current: watch::Sender<Option<PublicAddr>>,
+}
+
+impl PublicIp {
+    pub fn from_config(config: &Config) -> Self {
+        let (current, _) = watch::channel(config.public_ip.map(|ip| PublicAddr {
+            ip,
+            source: "config".to_string(),
+            discovered_at: chrono::Utc::now().timestamp() as u64,
+        }));
+        Self {
+            // A fixed address needs no discovery
+            servers: if config.public_ip.is_some() { Vec::new() } else { config.public_ip_stun_servers.clone() },
+            refresh: (config.public_ip_refresh_secs > 0).then(|| Duration::from_secs(config.public_ip_refresh_secs)),
+            current,
+        }
+    }
+
+    pub fn current(&self) -> Option<PublicAddr> {
+        self.current.borrow().clone()
+    }
+
+    /// Wait up to `limit` for the first discovery to finish
+    pub async fn wait(&self, limit: Duration) -> Option<PublicAddr> {
+        let mut receiver = self.current.subscribe();
+        let _ = timeout(limit, receiver.wait_for(Option::is_some)).await;
+        self.current()
+    }
+
+    /// Discover now, then again every PUBLIC_IP_REFRESH_SECS
+    pub fn spawn_refresh(self: &std::sync::Arc<Self>) {
+        if self.servers.is_empty() {
+            return;
+        }
+        let public_ip = std::sync::Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                public_ip.refresh_once().await;
+                match public_ip.refresh {
+                    Some(every) => tokio::time::sleep(every).await,
+                    None => break,
+                }
+            }
+        });
+    }
+
+    async fn refresh_once(&self) {
+        for server in &self.servers {
+            match discover(server).await {
+                Ok(ip) => {
+                    let previous = self.current().map(|addr| addr.ip);
+                    if previous != Some(ip) {
+                        info!("Public IP is {} (via STUN {})", ip, server);
+                    }
+                    self.current.send_replace(Some(PublicAddr {
+                        ip,
+                        source: format!("stun:{}", server),
+                        discovered_at: chrono::Utc::now().timestamp() as u64,
+                    }));
+                    return;
+                }
+                Err(e) => debug!("STUN lookup via {} failed: {}", server, e),
+            }
+        }
+        warn!("Public IP discovery failed on all {} STUN servers", self.servers.len());
+    }
+}
+
+/// Ask one STUN server (`host:port`) for our public address
+pub async fn discover(server: &str) -> std::io::Result<IpAddr> {
+    let target = tokio::net::lookup_host(server).await?
+        .find(SocketAddr::is_ipv4)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no IPv4 address"))?;
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.connect(target).await?;
+
+    let transaction: [u8; 12] = *uuid::Uuid::new_v4().as_bytes().first_chunk().unwrap();
+    let request = binding_request(&transaction);
+    let mut buf = [0u8; 512];
+    for _ in 0..ATTEMPTS {
+        socket.send(&request).await?;
+        let Ok(received) = timeout(REQUEST_TIMEOUT, socket.recv(&mut buf)).await else {
+            continue;
+        };
+        if let Some(ip) = parse_binding_response(&buf[..received?], &transaction) {
+            return Ok(ip);
+        }
+    }
+    Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "no STUN response"))
+}
+
+pub fn binding_request(transaction: &[u8; 12]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(20);
+    packet.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    packet.extend_from_slice(&0u16.to_be_bytes()); // No attributes
+    packet.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    packet.extend_from_slice(transaction);
+    packet
+}
+
+/// The mapped address from a Binding success response to `transaction`
+pub fn parse_binding_response(packet: &[u8], transaction: &[u8; 12]) -> Option<IpAddr> {
+    let header = packet.get(..20)?;
+    let be16 = |b: &[u8]| u16::from_be_bytes([b[0], b[1]]);
+    if be16(&header[0..2]) != BINDING_SUCCESS
+        || header[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || &header[8..20] != transaction
+    {
+        return None;
+    }
+    let length = be16(&header[2..4]) as usize;
+    let mut attrs = packet.get(20..20 + length)?;
+
+    let mut mapped = None;
+    while attrs.len() >= 4 {
+        let (kind, len) = (be16(&attrs[0..2]), be16(&attrs[2..4]) as usize);
+        let value = attrs.get(4..4 + len)?;
+        match kind {
+            // Preferred: XOR'ed so NATs that rewrite addresses in payloads leave it alone
+            ATTR_XOR_MAPPED_ADDRESS => return parse_address(value, Some(&header[4..20])),
+            ATTR_MAPPED_ADDRESS => mapped = parse_address(value, None),
+            _ => {}
+        }
+        attrs = attrs.get((4 + len).next_multiple_of(4)..).unwrap_or_default();
+    }
+    mapped
+}
+
+// Family (1 = IPv4, 2 = IPv6), port and address; XOR'ed with cookie + transaction
+fn parse_address(value: &[u8], xor: Option<&[u8]>) -> Option<IpAddr> {
+    let unmask = |bytes: &[u8]| -> Vec<u8> {
+        match xor {
+            Some(key) => bytes.iter().zip(key).map(|(b, k)| b ^ k).collect(),
+            None => bytes.to_vec(),
+        }
+    };
+    match *value.get(1)? {
+        0x01 => {
+            let octets: [u8; 4] = unmask(value.get(4..8)?).try_into().ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        0x02 => {
+            let octets: [u8; 16] = unmask(value.get(4..20)?).try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Binding success response carrying `ip` as XOR-MAPPED-ADDRESS
+    fn response(transaction: &[u8; 12], ip: Ipv4Addr, port: u16) -> Vec<u8> {
+        let cookie = MAGIC_COOKIE.to_be_bytes();
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&BINDING_SUCCESS.to_be_bytes());
+        packet.extend_from_slice(&12u16.to_be_bytes());
+        packet.extend_from_slice(&cookie);
+        packet.extend_from_slice(transaction);
+        packet.extend_from_slice(&ATTR_XOR_MAPPED_ADDRESS.to_be_bytes());
+        packet.extend_from_slice(&8u16.to_be_bytes());
+        packet.extend_from_slice(&[0, 0x01]);
+        packet.extend_from_slice(&(port ^ (MAGIC_COOKIE >> 16) as u16).to_be_bytes());
+        packet.extend(ip.octets().iter().zip(cookie).map(|(b, k)| b ^ k));
+        packet
+    }
+
+    #[test]
+    fn test_parse_xor_mapped_address() {
+        let transaction = [7u8; 12];
+        let packet = response(&transaction, Ipv4Addr::new(203, 0, 113, 9), 54321);
+        assert_eq!(parse_binding_response(&packet, &transaction), Some("203.0.113.9".parse().unwrap()));
+        // Responses to other requests are ignored
+        assert_eq!(parse_binding_response(&packet, &[8u8; 12]), None);
+        assert_eq!(parse_binding_response(&packet[..10], &transaction), None);
+    }
+
+    #[tokio::test]
+    async fn test_discover_against_local_server() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (len, from) = server.recv_from(&mut buf).await.unwrap();
+            assert_eq!(len, 20);
+            let transaction: [u8; 12] = buf[8..20].try_into().unwrap();
+            server.send_to(&response(&transaction, Ipv4Addr::new(198, 51, 100, 7), from.port()), from).await.unwrap();
+        });
+
+        let ip = discover(&addr.to_string()).await.unwrap();
+        assert_eq!(ip, "198.51.100.7".parse::<IpAddr>().unwrap());
+    }
+}
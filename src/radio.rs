@@ -1,60 +1,210 @@
 use std::{
+    collections::VecDeque,
+    net::IpAddr,
     path::PathBuf,
     sync::{
-        atomic::{AtomicBool, AtomicU64, AtomicU32, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicU32, AtomicUsize, Ordering},
+        Arc, OnceLock,
     },
     time::{Duration, Instant},
 };
 use tokio::{
-    sync::{broadcast, RwLock},
+    sync::{broadcast, watch, Notify, RwLock},
     time::{interval, sleep},
 };
 use tokio_stream::Stream;
+use futures::StreamExt;
+use tokio_util::sync::CancellationToken;
 use axum::response::sse::Event;
 use bytes::Bytes;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use arc_swap::ArcSwap;
 use tracing::{info, warn, error, debug};
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::probe::Hint;
-use symphonia::core::formats::FormatOptions;
-use symphonia::core::meta::MetadataOptions;
+use symphonia::core::codecs::CODEC_TYPE_MP3;
 
 use crate::{
-    error::Result,
-    playlist::{Playlist, Track},
-    config::Config,
+    access::AccessRules,
+    alert::{AlertKind, Alerts},
+    archive::Archive,
+    audience::{AudienceLog, AudienceMeter, MINUTE_SECS},
+    auth::AdminAuth,
+    bans::BanList,
+    chunklog::{self, ChunkLogWriter},
+    beacon::BeaconStats,
+    clienttest::ClientTests,
+    codec::CodecOutputs,
+    error::{AppError, Result},
+    events::{EventLog, EVENT_LOG_CAPACITY},
+    geoip::{self, GeoInfo, GeoIp},
+    history::{PlayHistory, PlayRecord},
+    icecast,
+    id3,
+    integrity::ChunkIntegrity,
+    intercom::Intercom,
+    monitor::{PerformanceMonitor, Subsystem},
+    playlist::{Playlist, SharedPlaylist, TagEdit, Track},
+    prefetch::{OpenedTrack, Prefetcher},
+    preflight::{Check, PreflightReport},
+    ratecontrol::StreamRate,
+    ratelimit::{EgressShaper, IpLimiter},
+    config::{CatchUp, ClientProfile, Config, IdleMode, LagPolicy, RelayMode, StreamClock},
+    drift::DriftTracker,
+    encode::{self, TrackTranscoder},
+    mp3,
+    publicip::PublicIp,
+    relay::{self, RelaySource},
+    resume::ResumePoints,
+    rotation::Rotation,
+    signing::Signer,
+    simulcast::Simulcast,
+    sync::{SyncClock, SyncSnapshot},
+    timeshift::TimeshiftBuffer,
+    types::{
+        GeoBreakdown, ListenerDto, NextUp, NowPlaying, PlaylistDto, SearchResults, SkipVote, StatsDto, StreamHealthDto,
+        TrackDto, TrackQuarantine,
+    },
 };
 
 pub struct RadioStation {
     config: Config,  // Changed from _config to config (used now)
-    playlist: Arc<RwLock<Playlist>>,
+    playlist: Arc<SharedPlaylist>,     // Lock-free snapshots; edits swap in a new copy
     current_track: Arc<ArcSwap<Option<Track>>>,
+    track_generation: AtomicU64,                        // Bumped on every now-playing change
+    track_changes: watch::Sender<u64>,                  // The latest generation, for SSE track-change
+    recent_tracks: std::sync::Mutex<VecDeque<TrackGeneration>>, // Newest last
+    metadata_override: watch::Sender<Option<String>>,   // Admin-set title shown instead of the track's
 
     // Broadcasting
-    broadcast_tx: Arc<RwLock<broadcast::Sender<Bytes>>>,
+    broadcast_tx: Arc<RwLock<broadcast::Sender<AudioChunk>>>,
     is_broadcasting: Arc<AtomicBool>,
 
+    // Listener skip votes for the current track
+    skip_votes: DashSet<String>,
+    skip_requested: Notify,
+
     // Statistics
     listeners: Arc<DashMap<String, ListenerInfo>>,
     total_bytes_sent: Arc<AtomicU64>,
     current_position: Arc<AtomicU64>,
     start_time: Instant,
+    listener_peak: AtomicUsize, // Most MP3 listeners connected at once since startup
 
     // Stream Health Monitoring
     last_chunk_sent: Arc<AtomicU64>, // timestamp as u64
     stream_gaps_detected: Arc<AtomicU32>,
     recovery_attempts: Arc<AtomicU32>,
+    drift_trimmed_ms: Arc<AtomicU64>,
+    silence_inserted_ms: Arc<AtomicU64>,
+    on_hold: Arc<AtomicBool>,          // Broadcasting hold audio because nothing is playable
+    hold_ms: Arc<AtomicU64>,
+    paused: AtomicBool,                // IDLE_MODE=pause and nobody is listening
+    paused_ms: AtomicU64,
+    fast_forward_ms: AtomicU64,        // Playout owed from a pause, carried into the next track
+    listener_joined: Notify,
+    last_frame_header: Arc<AtomicU32>, // Raw header of the last broadcast frame (0 = none yet)
+    integrity: ChunkIntegrity,
+    chunk_log: Option<ChunkLogWriter>,
+    timeshift: Arc<TimeshiftBuffer>,    // Recent broadcast history for rewinding listeners
+    recent: Arc<TimeshiftBuffer>,       // The last few seconds, burst to new listeners at once
+    prefetcher: Prefetcher,             // The next track, opened while the current one plays
+    stream_rate: Arc<StreamRate>,       // Broadcast pace, adapted to how listeners' buffers are doing
+    resume_points: Arc<ResumePoints>,   // Where recently disconnected listeners left off
+    monitor: Arc<PerformanceMonitor>,   // CPU usage and per-subsystem busy time
+    public_ip: Arc<PublicIp>,
+    alerts: Alerts,                     // Hold alerts for SSE clients and ALERT_WEBHOOK_URL
+    events: EventLog,                   // Numbered SSE events replayed after Last-Event-ID
+    relay: Option<RelaySource>,         // Upstream stream to rebroadcast (RELAY_URL)
+
+    // Multi-room playout clock
+    sync_clock: SyncClock,
+    track_sync_position_ms: Arc<AtomicU64>, // Timeline position where the current track starts
+
+    // Play history (royalty reporting)
+    history: PlayHistory,
+    archive: Archive,
+    play_listener_ms: Arc<AtomicU64>,    // listeners × ms accumulated for the current play
+    play_peak_listeners: Arc<AtomicU64>,
+    audience: Arc<AudienceMeter>,        // The current minute of the audience time series
+    audience_log: AudienceLog,
+    bans: BanList,
+
+    // Client telemetry
+    signer: Signer,
+    beacons: BeaconStats,
+    client_tests: ClientTests,        // /debug/client-test runs
+    admin_auth: AdminAuth,
+    intercom_auth: AdminAuth,         // INTERCOM_TOKEN for the DJ side of /intercom
+    intercom: Arc<Intercom>,
+    simulcast: Simulcast,             // Re-encoded qualities on /stream-<name>
+    codecs: CodecOutputs,             // Non-MP3 formats negotiated on /stream
+    ip_limiter: IpLimiter,
+
+    // Listener access control
+    geoip: GeoIp,
+    access_rules: AccessRules,
+
+    // Startup self-check, set once by create_app
+    preflight: OnceLock<PreflightReport>,
 
     // Control
     shutdown_tx: broadcast::Sender<()>,
 }
 
+/// A unit of audio on the broadcast channel
+#[derive(Debug, Clone)]
+pub struct AudioChunk {
+    pub data: Bytes,
+    pub duration_ms: f64, // Playback duration of `data`
+    pub position_ms: f64, // Start of `data` on the sync timeline
+    pub checksum: u32,    // CRC-32 of `data`
+    pub generation: u64,  // Track generation the audio belongs to (see `NowPlaying::generation`)
+}
+
+/// Where a new listener's audio starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamStart {
+    Live,
+    Rewind(f64), // Milliseconds in the past, from the timeshift buffer
+    Resume(f64), // Sync timeline position a reconnecting listener left off at
+}
+
+/// A now-playing entry, kept for a while so listeners still hearing an earlier
+/// track (burst, rewind) can be shown its metadata
+#[derive(Debug, Clone)]
+struct TrackGeneration {
+    generation: u64,
+    track: Arc<Option<Track>>,
+    sync_position_ms: u64,
+}
+
+// Enough generations to cover a full timeshift rewind of short tracks
+const RECENT_TRACKS: usize = 64;
+
 #[derive(Debug)]
 struct ListenerInfo {
     connected_at: Instant,
     bytes_received: u64,
+    drift_ms: f64,     // Lag behind live beyond the initial burst
+    trimmed_ms: f64,   // Audio dropped to pull the listener back towards live
+    silence_ms: f64,   // Silence inserted to cover broadcast gaps
+    sync_offset_ms: Option<f64>, // Sync timeline position of the listener's first byte of audio
+    generation: watch::Sender<u64>, // Track generation of the last chunk delivered
+    location: Option<GeoInfo>,   // With STATS_GEOIP
+    resume_position_ms: Option<f64>, // Sync timeline position after the last chunk delivered
+    profile: ClientProfile,
+    burst_bytes: usize,  // Burst actually sent on connect
+    burst_ms: f64,
+    ip: Option<IpAddr>,
+    user_agent: Option<String>,
+    kick: CancellationToken, // Ends the stream when an admin disconnects the listener
+}
+
+impl ListenerInfo {
+    /// Wakes SSE and WebSocket sessions following this listener when their
+    /// audio reaches a new track
+    fn set_generation(&self, generation: u64) {
+        self.generation.send_if_modified(|current| std::mem::replace(current, generation) != generation);
+    }
 }
 
 // Removed unused MP3 frame parsing functions - can be re-added if frame-level parsing is needed
@@ -82,22 +232,135 @@ impl RadioStation {
             (config.stream_rate_multiplier - 1.0) * 100.0);
         info!("  - Broadcast capacity: {} messages", config.broadcast_channel_capacity);
 
+        let history = PlayHistory::new(&config.play_history_path);
+        let audience_log = AudienceLog::new(&config.audience_log_path);
+        let archive = Archive::new(&config.archive_dir);
+        let signer = match &config.signing_secret {
+            Some(secret) => Signer::new(secret.as_bytes()),
+            None => Signer::ephemeral(),
+        };
+
+        let admin_auth = AdminAuth::new(config.admin_token.as_deref());
+        if !admin_auth.is_configured() {
+            if config.admin_localhost {
+                info!("ADMIN_TOKEN not set; admin routes only accept connections from localhost");
+            } else {
+                warn!("ADMIN_TOKEN not set; admin routes are disabled");
+            }
+        }
+        let intercom_auth = AdminAuth::new(config.intercom_token.as_deref());
+        let simulcast = Simulcast::new(&config.simulcast_mounts);
+        let codecs = CodecOutputs::new(&config);
+
+        let ip_limiter = IpLimiter::from_config(&config);
+        let chunk_log = match &config.chunk_log_record {
+            Some(path) => {
+                info!("Recording broadcast chunks to {}", path.display());
+                Some(ChunkLogWriter::create(path)?)
+            }
+            None => None,
+        };
+        let geoip = GeoIp::from_config(&config);
+        let access_rules = AccessRules::from_config(&config);
+        let sync_clock = SyncClock::new(config.sync_delay_ms);
+        let timeshift = Arc::new(TimeshiftBuffer::new(config.timeshift_minutes as f64 * 60_000.0));
+        let recent = Arc::new(TimeshiftBuffer::new(config.burst_history_secs as f64 * 1000.0));
+        let prefetcher = Prefetcher::new(config.prefetch_secs);
+        let bans = BanList::load(&config.ban_list_path);
+        let stream_rate = Arc::new(StreamRate::new(config.stream_rate_multiplier, config.adaptive_stream_rate));
+        let resume_points = Arc::new(ResumePoints::new(Duration::from_secs(config.resume_window_secs)));
+        let public_ip = Arc::new(PublicIp::from_config(&config));
+        let alerts = Alerts::from_config(&config);
+        let relay = config.relay_url.as_deref().map(RelaySource::new);
+        if let Some(relay) = &relay {
+            info!("Relaying {} ({:?} mode)", relay.url(), config.relay_mode);
+        }
+        let metrics_history_len = match config.metrics_sample_secs {
+            0 => 0,
+            secs => (config.metrics_history_minutes * 60).div_ceil(secs) as usize,
+        };
+        let rotation = Rotation::from_config(&config);
+        if !rotation.rules().is_off() {
+            // Plays from before a restart still count; artist separation looks back up to a day
+            let since = unix_now_secs().saturating_sub(rotation.rules().track_separation_secs.max(86_400));
+            match history.load_range(since, u64::MAX).await {
+                Ok(records) => rotation.seed(&records),
+                Err(e) => warn!("Failed to read play history for rotation rules: {}", e),
+            }
+        }
+        if !access_rules.is_empty() && !geoip.is_enabled() {
+            warn!("Stream access rules are set but no GeoIP database is loaded; non-local listeners cannot be located");
+        }
+
         Ok(Self {
             config,  // Store config for use in streaming
-            playlist: Arc::new(RwLock::new(playlist)),
+            playlist: Arc::new(SharedPlaylist::new(playlist).with_rotation(rotation)),
             current_track: Arc::new(ArcSwap::from_pointee(None)),
+            track_generation: AtomicU64::new(0),
+            track_changes: watch::Sender::new(0),
+            recent_tracks: std::sync::Mutex::new(VecDeque::with_capacity(RECENT_TRACKS)),
+            metadata_override: watch::Sender::new(None),
             broadcast_tx: Arc::new(RwLock::new(broadcast_tx)),
             is_broadcasting: Arc::new(AtomicBool::new(false)),
+            skip_votes: DashSet::new(),
+            skip_requested: Notify::new(),
             listeners: Arc::new(DashMap::new()),
             total_bytes_sent: Arc::new(AtomicU64::new(0)),
             current_position: Arc::new(AtomicU64::new(0)),
             start_time: Instant::now(),
+            listener_peak: AtomicUsize::new(0),
 
             // Initialize stream health monitoring
             last_chunk_sent: Arc::new(AtomicU64::new(0)),
             stream_gaps_detected: Arc::new(AtomicU32::new(0)),
             recovery_attempts: Arc::new(AtomicU32::new(0)),
-
+            drift_trimmed_ms: Arc::new(AtomicU64::new(0)),
+            silence_inserted_ms: Arc::new(AtomicU64::new(0)),
+            on_hold: Arc::new(AtomicBool::new(false)),
+            hold_ms: Arc::new(AtomicU64::new(0)),
+            paused: AtomicBool::new(false),
+            paused_ms: AtomicU64::new(0),
+            fast_forward_ms: AtomicU64::new(0),
+            listener_joined: Notify::new(),
+            last_frame_header: Arc::new(AtomicU32::new(0)),
+            integrity: ChunkIntegrity::new(),
+            chunk_log,
+            timeshift,
+            recent,
+            prefetcher,
+            stream_rate,
+            resume_points,
+            monitor: Arc::new(PerformanceMonitor::new(metrics_history_len)),
+            public_ip,
+            alerts,
+            events: EventLog::new(EVENT_LOG_CAPACITY),
+            relay,
+
+            sync_clock,
+            track_sync_position_ms: Arc::new(AtomicU64::new(0)),
+
+            history,
+            archive,
+            play_listener_ms: Arc::new(AtomicU64::new(0)),
+            play_peak_listeners: Arc::new(AtomicU64::new(0)),
+            audience: Arc::new(AudienceMeter::new()),
+            audience_log,
+            bans,
+
+            signer,
+            beacons: BeaconStats::new(),
+            client_tests: ClientTests::new(),
+            admin_auth,
+            intercom_auth,
+            intercom: Intercom::new(),
+            simulcast,
+            codecs,
+            ip_limiter,
+
+            geoip,
+            access_rules,
+
+            preflight: OnceLock::new(),
             shutdown_tx,
         })
     }
@@ -112,7 +375,11 @@ impl RadioStation {
 
         let station = Arc::clone(&self);
         tokio::spawn(async move {
-            if let Err(e) = station.broadcast_loop().await {
+            let result = match station.config.chunk_log_replay.clone() {
+                Some(path) => station.replay_loop(&path).await,
+                None => station.broadcast_loop().await,
+            };
+            if let Err(e) = result {
                 error!("Broadcast loop error: {}", e);
             }
             // Ensure the flag is cleared if broadcast loop exits
@@ -120,6 +387,83 @@ impl RadioStation {
         });
     }
     
+    /// Sample CPU, memory and subsystem load every METRICS_SAMPLE_SECS into the
+    /// rolling history behind `/api/metrics/history`
+    pub fn start_metrics_sampler(self: &Arc<Self>) {
+        let secs = self.config.metrics_sample_secs;
+        if secs == 0 {
+            return;
+        }
+        let station = Arc::clone(self);
+        let mut shutdown = self.shutdown_tx.subscribe();
+        tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(secs));
+            ticker.tick().await; // The first tick is immediate
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown.recv() => break,
+                }
+                station.monitor.sample(unix_now_secs(), station.listener_count());
+            }
+        });
+    }
+
+    /// Number track changes, playlist edits and alerts into the SSE event log
+    pub fn start_event_log(self: &Arc<Self>) {
+        let station = Arc::clone(self);
+        let mut shutdown = self.shutdown_tx.subscribe();
+        let mut tracks = self.track_changes.subscribe();
+        let mut playlist = self.playlist.subscribe();
+        let mut alerts = self.alerts.subscribe();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Ok(()) = tracks.changed() => {
+                        station.events.publish("track-change", station.get_now_playing());
+                    }
+                    Ok(()) = playlist.changed() => {
+                        let version = *playlist.borrow_and_update();
+                        let summary = serde_json::json!({ "version": version, "tracks": station.playlist.len() });
+                        station.events.publish("playlist-updated", summary);
+                    }
+                    alert = alerts.recv() => match alert {
+                        Ok(alert) => { station.events.publish("alert", alert); }
+                        Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    _ = shutdown.recv() => break,
+                }
+            }
+        });
+    }
+
+    /// Log the audience every minute, on the minute, behind `/api/stats/timeseries`
+    pub fn start_audience_recorder(self: &Arc<Self>) {
+        let station = Arc::clone(self);
+        let mut shutdown = self.shutdown_tx.subscribe();
+        tokio::spawn(async move {
+            // The first, partial minute is merged into the next one
+            let mut minute = unix_now_secs() / MINUTE_SECS * MINUTE_SECS;
+            loop {
+                let next = minute + MINUTE_SECS;
+                let wait = next.saturating_sub(unix_now_secs());
+                tokio::select! {
+                    _ = sleep(Duration::from_secs(wait)) => {}
+                    _ = shutdown.recv() => break,
+                }
+                if unix_now_secs() < next {
+                    continue; // Woke early; wait out the rest of the minute
+                }
+                let point = station.audience.take(minute, station.total_listener_count(), station.geo_breakdown());
+                if let Err(e) = station.audience_log.append(&point).await {
+                    warn!("Failed to record audience: {}", e);
+                }
+                minute = unix_now_secs() / MINUTE_SECS * MINUTE_SECS;
+            }
+        });
+    }
+
     pub async fn stop_broadcast(&self) {
         info!("Stopping broadcast...");
         self.is_broadcasting.store(false, Ordering::Relaxed);
@@ -142,6 +486,10 @@ impl RadioStation {
         let mut shutdown = self.shutdown_tx.subscribe();
         
         info!("Broadcast loop started");
+
+        let mut consecutive_failures = 0usize;
+        let mut last_announcement = None;
+        let mut last_relay_attempt = None;
         
         loop {
             // Check if we should stop
@@ -149,92 +497,241 @@ impl RadioStation {
                 break;
             }
             
+            // In primary mode the upstream plays until it fails; one local track
+            // (or a spell of hold audio) fills in before reconnecting
+            if let (Some(relay), RelayMode::Primary) = (&self.relay, self.config.relay_mode) {
+                let retry_due = last_relay_attempt.is_none_or(|at: Instant| {
+                    at.elapsed() >= Duration::from_secs(self.config.relay_retry_secs)
+                });
+                if retry_due {
+                    last_relay_attempt = Some(Instant::now());
+                    tokio::select! {
+                        result = self.relay_session(relay, || false) => {
+                            if let Err(e) = result {
+                                warn!("Relay of {} failed: {}; falling back to the playlist", relay.url(), e);
+                            }
+                        }
+                        _ = shutdown.recv() => {
+                            info!("Received shutdown signal");
+                            break;
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            // Temporary removals that have run out return to rotation first
+            let now = unix_now_secs();
+            if self.playlist.snapshot().has_expired_removals(now) {
+                let _ = self.edit_playlist(|playlist| {
+                    playlist.restore_expired(now);
+                    Ok(())
+                }).await;
+            }
+
             // Get next track
-            let track = {
-                let mut playlist = self.playlist.write().await;
-                playlist.get_next_track()
-            };
+            let track = self.playlist.next_track();
             
+            // After a pause, tracks that would have finished playing by now are passed over
+            if let Some(track) = &track {
+                let owed_ms = self.fast_forward_ms.load(Ordering::Relaxed);
+                if let Some(duration_ms) = track.duration.map(|secs| secs * 1000).filter(|&ms| ms > 0 && ms <= owed_ms) {
+                    self.fast_forward_ms.fetch_sub(duration_ms, Ordering::Relaxed);
+                    debug!("Fast-forwarding past {} - {}", track.artist, track.title);
+                    continue;
+                }
+            }
+
             let Some(track) = track else {
-                warn!("No tracks available in playlist");
-                sleep(Duration::from_secs(5)).await;
-                continue;
+                let mut reason = "Playlist is empty".to_string();
+                // A fallback relay covers for the empty playlist until tracks show up
+                if let (Some(relay), RelayMode::Fallback) = (&self.relay, self.config.relay_mode) {
+                    let playlist_has_tracks = || !self.playlist.is_empty();
+                    info!("No tracks available in playlist; relaying {}", relay.url());
+                    let relayed = tokio::select! {
+                        result = self.relay_session(relay, playlist_has_tracks) => result,
+                        _ = shutdown.recv() => {
+                            info!("Received shutdown signal");
+                            break;
+                        }
+                    };
+                    match relayed {
+                        Ok(()) => continue,
+                        Err(e) => {
+                            warn!("Relay of {} failed: {}; broadcasting hold audio", relay.url(), e);
+                            reason = format!("Playlist is empty and relay {} failed: {}", relay.url(), e);
+                        }
+                    }
+                } else {
+                    warn!("No tracks available in playlist; broadcasting hold audio");
+                }
+                tokio::select! {
+                    _ = self.broadcast_hold(&reason, &mut last_announcement) => continue,
+                    _ = shutdown.recv() => {
+                        info!("Received shutdown signal");
+                        break;
+                    }
+                }
             };
             
             // Don't create a new channel - just continue using the same one
             // This keeps clients connected across track changes
 
             // Update current track
-            self.current_track.store(Arc::new(Some(track.clone())));
+            self.leave_hold();
+            self.set_current_track(Some(track.clone()));
             info!("Now playing: {} - {} ({})", track.artist, track.title, track.path.display());
+            self.skip_votes.clear();
+
+            // Reset per-play audience counters
+            let play_started_at = unix_now_secs();
+            let play_started = Instant::now();
+            self.play_listener_ms.store(0, Ordering::Relaxed);
+            self.play_peak_listeners.store(self.listener_count() as u64, Ordering::Relaxed);
 
             // Stream the track with automatic recovery
+            let mut hold = None;
             tokio::select! {
                 result = self.stream_track_with_recovery(&track) => {
                     match result {
-                        Ok(_) => info!("Track completed successfully"),
+                        Ok(_) => {
+                            info!("Track completed successfully");
+                            consecutive_failures = 0;
+                            self.record_play(&track, play_started_at, play_started.elapsed()).await;
+                        }
                         Err(e) => {
                             error!("Error streaming track after recovery attempts: {}", e);
-                            // Brief pause before trying next track to avoid rapid failure loops
-                            sleep(Duration::from_millis(500)).await;
+                            consecutive_failures += 1;
+                            let playlist_len = self.playlist.len();
+                            if consecutive_failures >= playlist_len.max(1) {
+                                warn!("All {} tracks failed; broadcasting hold audio", playlist_len);
+                                consecutive_failures = 0;
+                                hold = Some(format!("All {} tracks failed to play", playlist_len));
+                            } else {
+                                // Brief pause before trying next track to avoid rapid failure loops
+                                sleep(Duration::from_millis(500)).await;
+                            }
                         }
                     }
                 }
+                _ = self.skip_requested.notified() => {
+                    info!("Skipped by listener vote: {} - {}", track.artist, track.title);
+                    consecutive_failures = 0;
+                    self.record_play(&track, play_started_at, play_started.elapsed()).await;
+                }
                 _ = shutdown.recv() => {
                     info!("Received shutdown signal");
                     break;
                 }
             }
 
+            if let Some(reason) = hold {
+                tokio::select! {
+                    _ = self.broadcast_hold(&reason, &mut last_announcement) => {}
+                    _ = shutdown.recv() => {
+                        info!("Received shutdown signal");
+                        break;
+                    }
+                }
+            }
+
             // No gap between tracks - immediately start next track
         }
         
         info!("Broadcast loop ended");
         Ok(())
     }
+
+    async fn record_play(&self, track: &Track, started_at: u64, played: Duration) {
+        let record = PlayRecord {
+            started_at,
+            path: track.path.clone(),
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            album: track.album.clone(),
+            isrc: track.isrc.clone(),
+            composer: track.composer.clone(),
+            label: track.label.clone(),
+            played_seconds: played.as_secs(),
+            peak_listeners: self.play_peak_listeners.load(Ordering::Relaxed) as usize,
+            listener_seconds: self.play_listener_ms.load(Ordering::Relaxed) / 1000,
+        };
+
+        if let Err(e) = self.history.append(&record).await {
+            warn!("Failed to record play history: {}", e);
+        }
+    }
+
+    // Accumulate audience exposure for the chunk that was just broadcast
+    fn track_audience(&self, chunk_duration_ms: f64) {
+        let listeners = self.listener_count() as u64;
+        self.play_listener_ms.fetch_add((listeners as f64 * chunk_duration_ms) as u64, Ordering::Relaxed);
+        self.play_peak_listeners.fetch_max(listeners, Ordering::Relaxed);
+        self.audience.observe(self.total_listener_count());
+    }
     
-    async fn stream_track(&self, track: &Track) -> Result<()> {
-        // Track path is relative to music directory
-        let path = if track.path.is_absolute() {
+    // Track paths are relative to the music directory
+    fn track_path(&self, track: &Track) -> PathBuf {
+        if track.path.is_absolute() {
             track.path.clone()
         } else {
-            PathBuf::from("music").join(&track.path)
-        };
+            self.config.music_dir.join(&track.path)
+        }
+    }
+
+    async fn stream_track(&self, track: &Track) -> Result<()> {
+        let path = self.track_path(track);
 
         info!("Streaming track: {} at {}kbps", path.display(), track.bitrate.unwrap_or(192000) / 1000);
 
-        // Open the file with symphonia
-        let file = std::fs::File::open(&path)?;
-        let media_source = MediaSourceStream::new(Box::new(file), Default::default());
+        // Usually opened and probed already, while the previous track played
+        let mut format = match self.prefetcher.take(track.id, &path).await {
+            Some(opened) => opened,
+            None => OpenedTrack::open(&path)?,
+        };
+        let track_id = format.track_id;
+        let time_base = format.time_base;
 
-        // Create a hint to help the probe guess the format
-        let mut hint = Hint::new();
-        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-            hint.with_extension(ext);
+        // Have the next track ready by the time this one ends
+        if let Some(next) = self.playlist.upcoming(1).into_iter().next() {
+            self.prefetcher.prefetch(next.id, self.track_path(&next));
         }
 
-        // Probe the media source
-        let format_opts = FormatOptions::default();
-        let metadata_opts = MetadataOptions::default();
-
-        let probed = symphonia::default::get_probe()
-            .format(&hint, media_source, &format_opts, &metadata_opts)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("Failed to probe file: {}", e)))?;
-
-        let mut format = probed.format;
+        // Loudness normalization: MP3 frames have their global gain adjusted in 1.5 dB
+        // steps, re-encoded tracks are scaled before encoding
+        let gain_db = track.gain_db().filter(|_| self.config.normalize).unwrap_or(0.0);
+        let gain_steps = mp3::gain_steps(gain_db);
+        if gain_db != 0.0 {
+            info!("Normalizing by {:+.1} dB", gain_db);
+        }
 
-        // Get the default audio track
-        let track_info = format.default_track()
-            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "No audio track found"))?;
-        let track_id = track_info.id;
+        // Dead air from rips (long fade-out gaps) is skipped: packets before the audio starts
+        // and from where it ends, as analysis measured them
+        let (audible_from_ms, audible_to_ms) = track.audible_ms()
+            .filter(|_| self.config.trim_silence)
+            .map_or((0.0, f64::INFINITY), |(from, to)| (from as f64, to as f64));
+        if audible_from_ms > 0.0 || audible_to_ms.is_finite() {
+            info!("Playing {:.1}s to {:.1}s, without silence", audible_from_ms / 1000.0, audible_to_ms / 1000.0);
+        }
 
-        // Get timebase for duration calculations
-        let time_base = track_info.codec_params.time_base
-            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "No timebase available"))?;
+        // Tracks in other codecs (FLAC, Vorbis, MPEG Layers I and II) are re-encoded to
+        // MP3 on the fly, as are MPEG-2/2.5 MP3s: the broadcast carries MPEG-1 rates only
+        let mpeg1_rate = format.codec_params.sample_rate.is_some_and(|rate| encode::mpeg1_sample_rate(rate) == rate);
+        let mut transcoder = if format.codec_params.codec == CODEC_TYPE_MP3 && mpeg1_rate {
+            None
+        } else {
+            info!("Re-encoding to {}kbps MP3", self.config.transcode_bitrate_kbps);
+            let transcoder = TrackTranscoder::new(&format.codec_params, self.config.transcode_bitrate_kbps)
+                .map_err(|e| AppError::DecodeError { path: path.clone(), reason: e.to_string() })?;
+            Some(transcoder.with_gain_db(gain_db))
+        };
 
         // Get bitrate for logging
-        let bitrate = track.bitrate.unwrap_or(192000);
-        let stream_rate_multiplier = self.config.stream_rate_multiplier;
+        let bitrate = match transcoder {
+            Some(_) => self.config.transcode_bitrate_kbps as u64 * 1000,
+            None => track.bitrate.unwrap_or(192000),
+        };
+        let stream_rate_multiplier = self.stream_rate.multiplier();
         let base_bitrate_kbps = bitrate as f64 / 1000.0;
         let stream_rate_kbps = base_bitrate_kbps * stream_rate_multiplier;
         let chunk_interval_ms = self.config.chunk_interval_ms;
@@ -252,10 +749,12 @@ impl RadioStation {
         // Stream packets from symphonia and bundle them by duration
         let mut current_chunk_data = Vec::new();
         let mut current_chunk_duration_tb: u64 = 0; // Duration in timebase units
-        let stream_start = Instant::now();
+        let mut stream_start = Instant::now();
         let mut chunks_sent = 0;
+        let mut due = stream_start; // When the next chunk goes out
         let mut last_log = Instant::now();
         let mut total_packets = 0;
+        let mut fast_forward_ms = self.fast_forward_ms.swap(0, Ordering::Relaxed) as f64;
 
         // Pre-lock the broadcast channel to avoid timing interference
         let tx = self.broadcast_tx.read().await;
@@ -268,37 +767,44 @@ impl RadioStation {
                 break;
             }
 
-            // Read next packet
-            let packet = match format.next_packet() {
-                Ok(packet) => packet,
-                Err(symphonia::core::errors::Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    // End of file - send any remaining data
-                    if !current_chunk_data.is_empty() {
-                        let chunk = Bytes::from(current_chunk_data);
-                        let chunk_len = chunk.len();
-                        let final_duration_ms = time_base.calc_time(current_chunk_duration_tb).seconds as f64 * 1000.0;
-
-                        info!("Sending final chunk: {} bytes, {:.1}ms duration", chunk_len, final_duration_ms);
+            if let Some(away) = self.pause_while_idle().await {
+                // Resume where the schedule would be had playout continued
+                stream_start += away;
+                due += away;
+                fast_forward_ms += away.as_secs_f64() * 1000.0;
+                current_chunk_data.clear();
+                current_chunk_duration_tb = 0;
+            }
 
-                        self.total_bytes_sent.fetch_add(chunk_len as u64, Ordering::Relaxed);
+            // Read next packet; the trailing silence counts as the end of the file
+            let packet = match self.monitor.time(Subsystem::Decode, || format.next_packet()) {
+                Ok(packet) if precise_ms(time_base, packet.ts()) >= audible_to_ms => None,
+                Ok(packet) => Some(packet),
+                Err(symphonia::core::errors::Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+                Err(e) => {
+                    return Err(AppError::DecodeError { path: path.clone(), reason: format!("failed to read packet: {}", e) });
+                }
+            };
+            let Some(packet) = packet else {
+                if fast_forward_ms > 0.0 {
+                    self.fast_forward_ms.fetch_add(fast_forward_ms as u64, Ordering::Relaxed);
+                }
+                // End of file - send any remaining data
+                if let Some(transcoder) = &mut transcoder {
+                    transcoder.finish(&mut current_chunk_data)
+                        .map_err(|e| AppError::DecodeError { path: path.clone(), reason: e.to_string() })?;
+                }
+                if !current_chunk_data.is_empty() {
+                    let duration_ms = mp3::whole_frames(&current_chunk_data)
+                        .map_or(precise_ms(time_base, current_chunk_duration_tb), |(_, ms)| ms);
+                    info!("Sending final chunk: {} bytes, {:.1}ms duration", current_chunk_data.len(), duration_ms);
 
-                        if let Err(_) = tx.send(chunk) {
-                            debug!("No active listeners for final chunk");
-                        } else {
-                            let now_ms = std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_millis() as u64;
-                            self.last_chunk_sent.store(now_ms, Ordering::Relaxed);
-                        }
-                        chunks_sent += 1;
+                    if !self.publish_chunk(&tx, Bytes::from(current_chunk_data), duration_ms) {
+                        debug!("No active listeners for final chunk");
                     }
-                    break;
-                }
-                Err(e) => {
-                    warn!("Error reading packet: {}", e);
-                    break;
+                    chunks_sent += 1;
                 }
+                break;
             };
 
             // Only process packets from our audio track
@@ -306,22 +812,51 @@ impl RadioStation {
                 continue;
             }
 
+            if precise_ms(time_base, packet.ts() + packet.dur()) <= audible_from_ms {
+                continue;
+            }
+
+            if fast_forward_ms > 0.0 {
+                fast_forward_ms -= precise_ms(time_base, packet.dur());
+                continue;
+            }
+
             total_packets += 1;
 
             // Add packet data to current chunk
-            current_chunk_data.extend_from_slice(packet.buf());
+            match &mut transcoder {
+                Some(transcoder) => self.monitor.time(Subsystem::Decode, || transcoder.push(&packet, &mut current_chunk_data))
+                    .map_err(|e| AppError::DecodeError { path: path.clone(), reason: e.to_string() })?,
+                None => {
+                    let start = current_chunk_data.len();
+                    current_chunk_data.extend_from_slice(packet.buf());
+                    if gain_steps != 0 {
+                        mp3::apply_gain(&mut current_chunk_data[start..], gain_steps);
+                    }
+                }
+            }
 
             // Add packet duration to accumulated duration (in timebase units)
             current_chunk_duration_tb += packet.dur();
 
-            // Calculate current chunk duration in milliseconds
-            let chunk_duration_ms = time_base.calc_time(current_chunk_duration_tb).seconds as f64 * 1000.0;
+            // Calculate current chunk duration in milliseconds (whole seconds plus the fraction)
+            let chunk_duration_ms = precise_ms(time_base, current_chunk_duration_tb);
 
             // Check if we should send this chunk based on duration
             // Send when accumulated duration >= target_chunk_duration_ms
-            if chunk_duration_ms >= target_chunk_duration_ms {
-                // Calculate timing for smooth delivery at stream rate
-                let target_time = stream_start + Duration::from_millis((chunks_sent as f64 * target_chunk_duration_ms) as u64);
+            // (a transcoded track's first packets can leave nothing to send yet)
+            if chunk_duration_ms >= target_chunk_duration_ms && !current_chunk_data.is_empty() {
+                // Cut after the last whole frame so every chunk starts on a frame boundary
+                // (re-encoded audio can end mid-frame); the rest leads the next chunk
+                let (cut, duration_ms) = mp3::whole_frames(&current_chunk_data)
+                    .unwrap_or((current_chunk_data.len(), precise_ms(time_base, current_chunk_duration_tb)));
+                if cut == 0 {
+                    continue;
+                }
+
+                // Schedule by the audio actually sent, so rounding never accumulates over a
+                // long track; each chunk is due once the ones before it have played at stream rate
+                let target_time = due;
                 let now = Instant::now();
 
                 if target_time > now {
@@ -335,25 +870,18 @@ impl RadioStation {
                     }
                 }
 
-                // Send the chunk
-                let chunk = Bytes::from(current_chunk_data.clone());
-                let chunk_len = chunk.len();
-                self.total_bytes_sent.fetch_add(chunk_len as u64, Ordering::Relaxed);
-                self.current_position.fetch_add(chunk_len as u64, Ordering::Relaxed);
-
-                if let Err(_) = tx.send(chunk) {
+                // Send the chunk. The buffer itself becomes the payload: every listener
+                // gets a refcounted handle to it, nothing is copied per chunk or per listener
+                let mut rest = Vec::with_capacity(current_chunk_data.capacity());
+                rest.extend_from_slice(&current_chunk_data[cut..]);
+                current_chunk_data.truncate(cut);
+                let chunk_data = Bytes::from(std::mem::replace(&mut current_chunk_data, rest));
+                if !self.publish_chunk(&tx, chunk_data, duration_ms) {
                     debug!("No active listeners for chunk");
-                } else {
-                    // Record successful chunk send
-                    let now_ms = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_millis() as u64;
-                    self.last_chunk_sent.store(now_ms, Ordering::Relaxed);
                 }
 
                 chunks_sent += 1;
-                current_chunk_data.clear();
+                due += Duration::from_secs_f64(duration_ms / 1000.0 / self.stream_rate.multiplier());
                 current_chunk_duration_tb = 0; // Reset duration counter
 
                 // Log progress occasionally
@@ -377,6 +905,278 @@ impl RadioStation {
         Ok(())
     }
 
+    /// Developer mode: broadcast a recorded chunk log (`CHUNK_LOG_REPLAY`) with its original timing
+    async fn replay_loop(&self, path: &std::path::Path) -> Result<()> {
+        let chunks = chunklog::load(path).await?;
+        let mut shutdown = self.shutdown_tx.subscribe();
+        info!("Replaying {} chunks from {}", chunks.len(), path.display());
+
+        self.set_current_track(Some(Track {
+            title: format!("Replay: {}", path.display()),
+            artist: self.config.station_name.clone(),
+            ..Default::default()
+        }));
+
+        let tx = self.broadcast_tx.read().await;
+        let start = Instant::now();
+        for chunk in chunks {
+            if !self.is_broadcasting.load(Ordering::Relaxed) {
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep_until((start + chunk.at).into()) => {}
+                _ = shutdown.recv() => {
+                    info!("Received shutdown signal");
+                    return Ok(());
+                }
+            }
+            self.publish_chunk(&tx, chunk.data, chunk.duration_ms);
+        }
+
+        info!("Chunk log replay finished");
+        Ok(())
+    }
+
+    /// Broadcast hold audio for `HOLD_RETRY_SECS` so listeners keep receiving a playable
+    /// stream while there is nothing to play: the hold loop (or silence in the format of
+    /// the last broadcast frame), preceded by the announcement when one is due
+    async fn broadcast_hold(&self, reason: &str, last_announcement: &mut Option<Instant>) {
+        if !self.on_hold.swap(true, Ordering::Relaxed) {
+            self.alerts.raise(AlertKind::HoldStarted, format!("{}; broadcasting hold audio", reason));
+        }
+        self.set_current_track(Some(Track {
+            title: self.config.hold_title.clone(),
+            artist: self.config.station_name.clone(),
+            ..Default::default()
+        }));
+
+        let mut loop_frames = match &self.config.hold_audio_file {
+            Some(path) => load_frames(path).await,
+            None => Vec::new(),
+        };
+        if loop_frames.is_empty() {
+            let raw = self.last_frame_header.load(Ordering::Relaxed).to_be_bytes();
+            let header = mp3::FrameHeader::parse(&raw)
+                .or_else(|| mp3::FrameHeader::parse(&DEFAULT_FRAME_HEADER))
+                .expect("default frame header is valid");
+            loop_frames.push((Bytes::from(mp3::silent_frame(&header)), header.duration_ms()));
+        }
+
+        let interval = Duration::from_secs(self.config.hold_announcement_interval_secs);
+        let announcement = match &self.config.hold_announcement_file {
+            Some(path) if last_announcement.is_none_or(|at| at.elapsed() >= interval) => {
+                *last_announcement = Some(Instant::now());
+                load_frames(path).await
+            }
+            _ => Vec::new(),
+        };
+
+        let tx = self.broadcast_tx.read().await;
+        let hold_ms = self.config.hold_retry_secs as f64 * 1000.0;
+        let chunk_interval_ms = self.config.chunk_interval_ms as f64;
+        let mut due = Instant::now();
+        let mut sent_ms = 0.0;
+        let mut chunk = Vec::new();
+        let mut chunk_ms = 0.0;
+
+        for (frame, frame_ms) in announcement.iter().chain(loop_frames.iter().cycle()) {
+            if !self.is_broadcasting.load(Ordering::Relaxed) || sent_ms >= hold_ms {
+                break;
+            }
+            chunk.extend_from_slice(frame);
+            chunk_ms += frame_ms;
+            if chunk_ms < chunk_interval_ms {
+                continue;
+            }
+
+            // Same pacing as tracks: slightly faster than realtime to keep buffers topped up
+            let now = Instant::now();
+            if due > now {
+                sleep(due - now).await;
+            }
+
+            self.publish_chunk(&tx, Bytes::from(std::mem::take(&mut chunk)), chunk_ms);
+            due += Duration::from_secs_f64(chunk_ms / 1000.0 / self.stream_rate.multiplier());
+            sent_ms += chunk_ms;
+            chunk_ms = 0.0;
+        }
+
+        self.hold_ms.fetch_add(sent_ms as u64, Ordering::Relaxed);
+    }
+
+    /// Rebroadcast the upstream until it fails or `stop` returns true. Titles from
+    /// its ICY metadata become the now-playing track.
+    async fn relay_session(&self, relay: &RelaySource, stop: impl Fn() -> bool) -> Result<()> {
+        let mut upstream = relay.connect().await?;
+        info!("Connected to relay upstream {}", relay.url());
+
+        self.leave_hold();
+        self.set_relay_track(upstream.name.as_deref().unwrap_or(relay.url()));
+
+        let tx = self.broadcast_tx.read().await;
+        let chunk_interval_ms = self.config.chunk_interval_ms as f64;
+        let start = Instant::now();
+        let mut sent_ms = 0.0;
+        let mut chunk = Vec::new();
+        let mut chunk_ms = 0.0;
+
+        loop {
+            if !self.is_broadcasting.load(Ordering::Relaxed) || stop() {
+                return Ok(());
+            }
+            let Some(read) = upstream.read().await? else {
+                return Err(AppError::StreamClosed("upstream closed the stream".to_string()));
+            };
+            if let Some(title) = read.title {
+                info!("Relay now playing: {}", title);
+                self.set_relay_track(&title);
+            }
+
+            for (frame, frame_ms) in read.frames {
+                chunk.extend_from_slice(&frame);
+                chunk_ms += frame_ms;
+                if chunk_ms < chunk_interval_ms {
+                    continue;
+                }
+
+                // The upstream arrives in realtime; this only smooths out bursts it sends
+                let target_time = start + Duration::from_secs_f64(sent_ms / 1000.0 / self.config.stream_rate_multiplier);
+                let now = Instant::now();
+                if target_time > now {
+                    sleep(target_time - now).await;
+                }
+
+                self.publish_chunk(&tx, Bytes::from(std::mem::take(&mut chunk)), chunk_ms);
+                sent_ms += chunk_ms;
+                chunk_ms = 0.0;
+            }
+        }
+    }
+
+    fn leave_hold(&self) {
+        if self.on_hold.swap(false, Ordering::Relaxed) {
+            self.alerts.raise(AlertKind::HoldEnded, "Playback resumed");
+        }
+    }
+
+    fn set_relay_track(&self, stream_title: &str) {
+        let (artist, title) = relay::split_title(stream_title);
+        self.set_current_track(Some(Track {
+            title: title.to_string(),
+            artist: artist.unwrap_or(&self.config.station_name).to_string(),
+            ..Default::default()
+        }));
+    }
+
+    /// Switch now-playing to `track` under a new generation. Chunks published from
+    /// here on carry that generation, tying the metadata to the audio it describes.
+    fn set_current_track(&self, track: Option<Track>) {
+        let track = Arc::new(track);
+        let sync_position_ms = self.sync_clock.next_position_ms() as u64;
+        let mut recent = self.recent_tracks.lock().unwrap();
+        let generation = self.track_generation.load(Ordering::Relaxed) + 1;
+        if recent.len() == RECENT_TRACKS {
+            recent.pop_front();
+        }
+        recent.push_back(TrackGeneration { generation, track: Arc::clone(&track), sync_position_ms });
+        self.track_sync_position_ms.store(sync_position_ms, Ordering::Relaxed);
+        self.current_track.store(track);
+        self.track_generation.store(generation, Ordering::Relaxed);
+        self.track_changes.send_replace(generation);
+    }
+
+    /// Show `title` (e.g. "LIVE: Morning Show") as now-playing instead of the
+    /// track until it is cleared with `None`, for live segments. Live-track
+    /// subscribers get a track-change right away, and `metadata_changes`
+    /// subscribers are woken whatever track they are hearing.
+    pub fn set_metadata_override(&self, title: Option<String>) {
+        self.metadata_override.send_replace(title);
+        self.track_changes.send_modify(|_| {});
+    }
+
+    pub fn metadata_override(&self) -> Option<String> {
+        self.metadata_override.borrow().clone()
+    }
+
+    /// Changes whenever the metadata override is set or cleared
+    pub fn metadata_changes(&self) -> watch::Receiver<Option<String>> {
+        self.metadata_override.subscribe()
+    }
+
+    /// ICY StreamTitle for what `listener_id` is hearing: the override, else
+    /// "Artist - Title"
+    pub fn stream_title_for(&self, listener_id: Option<&str>) -> String {
+        if let Some(title) = self.metadata_override() {
+            return title;
+        }
+        let now_playing = self.track_now_playing_for(listener_id);
+        match now_playing.artist.as_str() {
+            "" => now_playing.title,
+            artist => format!("{} - {}", artist, now_playing.title),
+        }
+    }
+
+    /// With IDLE_MODE=pause, hold playout while nobody is listening. Returns how long
+    /// it was paused, or `None` if it didn't pause.
+    async fn pause_while_idle(&self) -> Option<Duration> {
+        if self.config.idle_mode != IdleMode::Pause || self.listener_count() > 0 {
+            return None;
+        }
+        info!("No listeners; pausing playout");
+        self.paused.store(true, Ordering::Relaxed);
+        let paused_at = Instant::now();
+        loop {
+            let joined = self.listener_joined.notified();
+            tokio::pin!(joined);
+            joined.as_mut().enable();
+            if self.listener_count() > 0 || !self.is_broadcasting.load(Ordering::Relaxed) {
+                break;
+            }
+            joined.await;
+        }
+        let away = paused_at.elapsed();
+        self.paused.store(false, Ordering::Relaxed);
+        self.paused_ms.fetch_add(away.as_millis() as u64, Ordering::Relaxed);
+        info!("Listener connected; resuming playout {:.1}s further on", away.as_secs_f64());
+        Some(away)
+    }
+
+    /// Stamp a chunk with its sync timeline position and checksum, update counters
+    /// and broadcast it. Returns false when nobody is subscribed.
+    fn publish_chunk(&self, tx: &broadcast::Sender<AudioChunk>, data: Bytes, duration_ms: f64) -> bool {
+        let started = Instant::now();
+        let now_ms = unix_now_ms();
+        let position_ms = self.sync_clock.advance(duration_ms, now_ms);
+        let chunk = AudioChunk {
+            generation: self.track_generation.load(Ordering::Relaxed),
+            checksum: self.integrity.inspect(&data, position_ms),
+            position_ms,
+            data,
+            duration_ms,
+        };
+        if let Some(log) = &self.chunk_log {
+            log.write(&chunk);
+        }
+        self.timeshift.push(&chunk);
+        self.recent.push(&chunk);
+
+        if let Some(header) = chunk.data.get(..4) {
+            if mp3::FrameHeader::parse(header).is_some() {
+                self.last_frame_header.store(u32::from_be_bytes(header.try_into().unwrap()), Ordering::Relaxed);
+            }
+        }
+        self.total_bytes_sent.fetch_add(chunk.data.len() as u64, Ordering::Relaxed);
+        self.current_position.fetch_add(chunk.data.len() as u64, Ordering::Relaxed);
+        self.track_audience(chunk.duration_ms);
+
+        // Published even when nobody is subscribed (send fails then): readiness and
+        // stream health are about the broadcast running, not about listeners
+        self.last_chunk_sent.store(now_ms, Ordering::Relaxed);
+        let sent = tx.send(chunk).is_ok();
+        self.monitor.record(Subsystem::Publish, started.elapsed());
+        sent
+    }
+
     async fn stream_track_with_recovery(&self, track: &Track) -> Result<()> {
         let mut attempt = 0;
         const MAX_ATTEMPTS: u32 = 3;
@@ -403,72 +1203,173 @@ impl RadioStation {
                         sleep(Duration::from_millis(delay_ms)).await;
                     } else {
                         error!("All {} stream attempts failed for track: {}", MAX_ATTEMPTS, track.title);
+                        // A file that can't be opened, read or decoded won't play next time either
+                        if matches!(e, AppError::DecodeError { .. }) {
+                            self.quarantine_track(track, &e, MAX_ATTEMPTS).await;
+                        }
                         return Err(e);
                     }
                 }
             }
         }
 
-        Err(std::io::Error::new(std::io::ErrorKind::Other, "Maximum recovery attempts exceeded").into())
+        Err(std::io::Error::other("Maximum recovery attempts exceeded").into())
     }
 
-    pub async fn create_audio_stream(&self, is_ios: bool) -> Result<impl Stream<Item = Result<Bytes>>> {
+    /// Take a track that fails to play out of rotation, keeping the error for
+    /// `/api/admin/quarantine`. Enabling the track again ends the quarantine.
+    async fn quarantine_track(&self, track: &Track, error: &AppError, attempts: u32) {
+        warn!("Quarantining {}: {}", track.path.display(), error);
+        let quarantine = TrackQuarantine { error: error.to_string(), attempts, quarantined_at: unix_now_secs() };
+        if let Err(e) = self.edit_playlist(|playlist| playlist.quarantine(track.id, quarantine.clone())).await {
+            warn!("Failed to quarantine {}: {}", track.path.display(), e);
+        }
+    }
+
+    /// Subscribe a new listener; returns its id (for `/api/sync`) and the audio stream.
+    /// A rewinding or resuming listener starts in the past, replayed from the
+    /// timeshift buffer, and drifts back to live.
+    pub async fn create_audio_stream(
+        &self,
+        profile: ClientProfile,
+        clock: StreamClock,
+        lag: LagPolicy,
+        start: StreamStart,
+    ) -> Result<(String, impl Stream<Item = Result<Bytes>>)> {
+        self.check_listener_capacity()?;
+        if matches!(start, StreamStart::Rewind(ms) if ms > 0.0) && !self.timeshift.is_enabled() {
+            return Err(AppError::BadRequest("Rewind is not available: timeshift is disabled".to_string()));
+        }
+
         let listener_id = uuid::Uuid::new_v4().to_string();
         let mut receiver = self.broadcast_tx.read().await.subscribe();
+        let kick = CancellationToken::new();
+        let stream_id = listener_id.clone();
 
         // Register listener
         self.listeners.insert(listener_id.clone(), ListenerInfo {
             connected_at: Instant::now(),
             bytes_received: 0,
+            drift_ms: 0.0,
+            trimmed_ms: 0.0,
+            silence_ms: 0.0,
+            sync_offset_ms: None,
+            generation: watch::Sender::new(0),
+            location: None,
+            resume_position_ms: None,
+            profile,
+            burst_bytes: 0,
+            burst_ms: 0.0,
+            ip: None,
+            user_agent: None,
+            kick: kick.clone(),
         });
+        self.listener_joined.notify_waiters();
 
         let listeners = self.listeners.clone();
         let current_count = self.listener_count();
+        self.listener_peak.fetch_max(current_count, Ordering::Relaxed);
+        let guard = ListenerGuard {
+            listeners: self.listeners.clone(),
+            listener_id: listener_id.clone(),
+            resume_points: self.resume_points.clone(),
+            span: tracing::Span::current(),
+            reason: "client went away",
+        };
 
-        info!("New audio listener connected: {} (total: {}, iOS: {})", &listener_id[..8], current_count, is_ios);
+        info!("New audio listener connected: {} (total: {}, profile: {}, clock: {}, lag: {})",
+            &listener_id[..8], current_count, profile.name(), clock.name(), lag.name());
 
         // Clone config values for use in the stream
-        // iOS devices need larger buffers due to aggressive power management
-        let target_buffer = if is_ios {
-            self.config.initial_buffer_kb * 1024 * 2  // Double buffer for iOS (240KB = ~10 seconds)
-        } else {
-            self.config.initial_buffer_kb * 1024
-        };
+        let burst = self.config.burst(profile).clone();
+        let target_buffer = burst.burst_kb * 1024;
+        // A fixed burst size is flushed from history alone; otherwise the rest of the
+        // target is collected from live audio, for up to the timeout
+        let burst_size = burst.size_kb.map(|kb| kb * 1024);
+        let minimum_buffer = burst.minimum_kb.min(burst.burst_kb) * 1024;
+        let buffer_timeout = Duration::from_millis(burst.timeout_ms);
 
-        let minimum_buffer = if is_ios {
-            self.config.minimum_buffer_kb * 1024 * 2  // Double minimum for iOS (160KB = ~6.6 seconds)
-        } else {
-            self.config.minimum_buffer_kb * 1024
+        let chunk_interval = Duration::from_millis(self.config.chunk_interval_ms);
+        let max_drift_ms = match clock {
+            StreamClock::Realtime => self.config.realtime_max_drift_ms as f64,
+            StreamClock::BufferBuilding => self.config.drift_max_ms as f64,
         };
-
-        let buffer_timeout = if is_ios {
-            Duration::from_millis(self.config.initial_buffer_timeout_ms * 2)  // 12 seconds for iOS
-        } else {
-            Duration::from_millis(self.config.initial_buffer_timeout_ms)
+        let min_buffered_ms = self.config.drift_min_buffer_ms as f64;
+        let drift_trimmed_ms = self.drift_trimmed_ms.clone();
+        let silence_inserted_ms = self.silence_inserted_ms.clone();
+        let monitor = self.monitor.clone();
+        let audience = self.audience.clone();
+        let stream_rate = self.stream_rate.clone();
+
+        let timeshift = self.timeshift.clone();
+        let recent = self.recent.clone();
+        let rewind_start = match start {
+            StreamStart::Live => None,
+            StreamStart::Rewind(rewind_ms) if rewind_ms > 0.0 => {
+                info!("Listener {} rewinding {:.0}s", &listener_id[..8], rewind_ms / 1000.0);
+                timeshift.start_position(rewind_ms)
+            }
+            StreamStart::Rewind(_) => None,
+            StreamStart::Resume(position_ms) => {
+                info!("Listener {} resuming at {:.0}ms", &listener_id[..8], position_ms);
+                timeshift.chunk_at(position_ms).map(|chunk| chunk.position_ms)
+            }
         };
+        let catchup_share = self.config.timeshift_catchup_percent / 100.0;
+        let stream_rate_multiplier = self.config.stream_rate_multiplier;
 
-        let chunk_interval = Duration::from_millis(self.config.chunk_interval_ms);
+        let stream = async_stream::stream! {
+            let mut guard = guard;
 
-        Ok(async_stream::stream! {
             // Phase 1: Build up initial buffer for smooth startup
-            let mut initial_buffer = Vec::new();
+            let mut initial_buffer: Vec<AudioChunk> = Vec::new();
             let mut buffered_bytes = 0;
 
-            info!("Listener {} collecting {}KB buffer (minimum: {}KB, timeout: {}ms)",
-                &listener_id[..8],
-                target_buffer / 1024,
-                minimum_buffer / 1024,
-                buffer_timeout.as_millis());
+            match burst_size {
+                Some(size) => info!("Listener {} bursting up to {}KB of recent audio", &listener_id[..8], size / 1024),
+                None => info!("Listener {} collecting {}KB buffer (minimum: {}KB, timeout: {}ms)",
+                    &listener_id[..8],
+                    target_buffer / 1024,
+                    minimum_buffer / 1024,
+                    buffer_timeout.as_millis()),
+            }
 
-            // Collect initial data with configurable timeout
-            while buffered_bytes < target_buffer {
-                match tokio::time::timeout(buffer_timeout, receiver.recv()).await {
-                    Ok(Ok(chunk)) => {
-                        buffered_bytes += chunk.len();
-                        initial_buffer.push(chunk);
+            // Rewinding: the burst comes from the timeshift buffer instead of the live broadcast
+            let mut replay_cursor = None;
+            if let Some(start) = rewind_start {
+                let mut next = timeshift.chunk_at(start);
+                while let Some(chunk) = next.take() {
+                    if buffered_bytes >= target_buffer {
+                        break;
                     }
-                    Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
-                        warn!("Initial buffering lagged by {} messages", skipped);
+                    buffered_bytes += chunk.data.len();
+                    replay_cursor = Some(chunk.position_ms);
+                    next = timeshift.chunk_after(chunk.position_ms);
+                    initial_buffer.push(chunk);
+                }
+            }
+
+            // Live: the burst comes from what was just broadcast, so playback starts at
+            // once. The receiver may already hold some of it; those are skipped later.
+            let mut resume_after = None;
+            if replay_cursor.is_none() {
+                for chunk in recent.tail(burst_size.unwrap_or(target_buffer)) {
+                    buffered_bytes += chunk.data.len();
+                    resume_after = Some(chunk.position_ms);
+                    initial_buffer.push(chunk);
+                }
+            }
+
+            // Collect (the rest of the) initial data with configurable timeout
+            while replay_cursor.is_none() && burst_size.is_none() && buffered_bytes < target_buffer {
+                match tokio::time::timeout(buffer_timeout, receiver.recv()).await {
+                    Ok(Ok(chunk)) if resume_after.is_some_and(|after| chunk.position_ms <= after) => continue,
+                    Ok(Ok(chunk)) => {
+                        buffered_bytes += chunk.data.len();
+                        initial_buffer.push(chunk);
+                    }
+                    Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
+                        warn!("Initial buffering lagged by {} messages", skipped);
                         continue;
                     }
                     Ok(Err(broadcast::error::RecvError::Closed)) => {
@@ -495,18 +1396,107 @@ impl RadioStation {
                 buffered_bytes / 1024,
                 initial_buffer.len());
 
-            // Phase 2: BURST - Send ALL initial buffer immediately (no delays!)
-            // The "burst" happens naturally by sending all buffered chunks at once
-            // The client's TCP buffer and audio decoder handle the rapid delivery
-            info!("Listener {} bursting {} chunks immediately (no delays)",
-                &listener_id[..8], initial_buffer.len());
+            // The burst is the lead this listener is expected to keep over realtime
+            let burst_ms: f64 = initial_buffer.iter().map(|chunk| chunk.duration_ms).sum();
+            if let Some(mut info) = listeners.get_mut(&listener_id) {
+                info.burst_bytes = buffered_bytes;
+                info.burst_ms = burst_ms;
+            }
+            let mut drift = DriftTracker::new(Instant::now(), burst_ms, max_drift_ms);
+            let mut last_header = initial_buffer.last().and_then(|chunk| mp3::FrameHeader::parse(&chunk.data));
+
+            // Phase 2: BURST - send the collected buffer, either all at once (the
+            // client's TCP buffer and decoder absorb it) or paced for players that
+            // choke on large bursts
+            let burst_rate = burst.pacing_kbps as f64 * 1024.0;
+            info!("Listener {} bursting {} chunks ({})",
+                &listener_id[..8], initial_buffer.len(),
+                if burst.pacing_kbps > 0 { format!("paced at {}KB/s", burst.pacing_kbps) } else { "no delays".to_string() });
 
             for chunk in initial_buffer {
                 if let Some(mut info) = listeners.get_mut(&listener_id) {
-                    info.bytes_received += chunk.len() as u64;
+                    info.bytes_received += chunk.data.len() as u64;
+                    audience.add_sent(chunk.data.len());
+                    info.resume_position_ms = Some(chunk.position_ms + chunk.duration_ms);
+                    info.sync_offset_ms = Some(chunk.position_ms - drift.delivered_ms());
+                    info.set_generation(chunk.generation);
+                }
+                drift.record_delivered(chunk.duration_ms);
+                let pause = if burst_rate > 0.0 {
+                    Some(Duration::from_secs_f64(chunk.data.len() as f64 / burst_rate))
+                } else {
+                    None
+                };
+                yield Ok(chunk.data);
+                if let Some(pause) = pause {
+                    sleep(pause).await;
+                }
+            }
+
+            // Phase 2b: TIMESHIFT - replay the buffer at realtime from this listener's
+            // cursor, skipping a share of chunks to drift back towards live
+            if let Some(mut cursor) = replay_cursor {
+                let replay_start = Instant::now();
+                let mut replayed_ms = 0.0;
+                let mut skipped_ms = 0.0;
+                let mut caught_up = false;
+
+                loop {
+                    let Some(chunk) = timeshift.chunk_after(cursor) else {
+                        if caught_up {
+                            break;
+                        }
+                        // At the live edge: rejoin the broadcast, then pick up anything
+                        // published before the new subscription took effect
+                        receiver = receiver.resubscribe();
+                        caught_up = true;
+                        continue;
+                    };
+                    cursor = chunk.position_ms;
+
+                    if !caught_up && skipped_ms < replayed_ms * catchup_share {
+                        skipped_ms += chunk.duration_ms;
+                        if let Some(mut info) = listeners.get_mut(&listener_id) {
+                            info.trimmed_ms += chunk.duration_ms;
+                        }
+                        continue;
+                    }
+
+                    // Same pacing as the broadcast itself, so skipping chunks gains on live
+                    let target_time = replay_start + Duration::from_secs_f64(replayed_ms / 1000.0 / stream_rate_multiplier);
+                    let now = Instant::now();
+                    if target_time > now {
+                        sleep(target_time - now).await;
+                    }
+                    if let Some(mut info) = listeners.get_mut(&listener_id) {
+                        info.bytes_received += chunk.data.len() as u64;
+                        audience.add_sent(chunk.data.len());
+                        info.resume_position_ms = Some(chunk.position_ms + chunk.duration_ms);
+                        info.sync_offset_ms = Some(chunk.position_ms - drift.delivered_ms());
+                        info.set_generation(chunk.generation);
+                    }
+                    drift.record_delivered(chunk.duration_ms);
+                    replayed_ms += chunk.duration_ms;
+                    yield Ok(chunk.data);
+                }
+                info!("Listener {} caught up with live after skipping {:.1}s", &listener_id[..8], skipped_ms / 1000.0);
+                resume_after = Some(cursor);
+            }
+
+            // Post-burst catch-up: optionally discard what queued up during a paced burst
+            // (timeshifted listeners have just rejoined live)
+            if replay_cursor.is_none() && burst.catch_up == CatchUp::SkipToLive {
+                let mut skipped_bytes = 0;
+                loop {
+                    match receiver.try_recv() {
+                        Ok(chunk) => skipped_bytes += chunk.data.len(),
+                        Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                        Err(_) => break,
+                    }
+                }
+                if skipped_bytes > 0 {
+                    info!("Listener {} skipped {}KB to rejoin live", &listener_id[..8], skipped_bytes / 1024);
                 }
-                yield Ok(chunk);
-                // NO DELAYS - send all buffered data immediately!
             }
 
             info!("Listener {} burst complete, entering sustain phase", &listener_id[..8]);
@@ -515,43 +1505,61 @@ impl RadioStation {
             // Use timeout of 5x chunk interval to detect gaps quickly but avoid false positives
             // 100ms chunks * 5 = 500ms timeout (much better than the old 2000ms!)
             let chunk_timeout = chunk_interval * 5;
+            let mut shaper = EgressShaper::new(burst.egress_cap);
 
             loop {
                 // Wait for chunk with timeout to detect gaps quickly
-                match tokio::time::timeout(chunk_timeout, receiver.recv()).await {
-                    Ok(Ok(chunk)) => {
-                        // Normal chunk received
-                        if let Some(mut info) = listeners.get_mut(&listener_id) {
-                            info.bytes_received += chunk.len() as u64;
-                        }
-                        yield Ok(chunk);
-                    }
+                let chunk = match tokio::time::timeout(chunk_timeout, receiver.recv()).await {
+                    Ok(Ok(chunk)) => chunk,
                     Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
-                        warn!("Listener {} lagged by {} messages, attempting recovery",
-                            &listener_id[..8], skipped);
+                        warn!("Listener {} lagged by {} messages, recovering ({})",
+                            &listener_id[..8], skipped, lag.name());
+
+                        match lag {
+                            LagPolicy::Disconnect => {
+                                guard.reason = "lagged behind the broadcast";
+                                break;
+                            }
+                            LagPolicy::DropToLive => receiver = receiver.resubscribe(),
+                            LagPolicy::Reburst => {
+                                // Rejoin live with the burst a new connection would get
+                                receiver = receiver.resubscribe();
+                                for chunk in recent.tail(target_buffer) {
+                                    if let Some(mut info) = listeners.get_mut(&listener_id) {
+                                        info.bytes_received += chunk.data.len() as u64;
+                                        audience.add_sent(chunk.data.len());
+                                        info.resume_position_ms = Some(chunk.position_ms + chunk.duration_ms);
+                                        info.sync_offset_ms = Some(chunk.position_ms - drift.delivered_ms());
+                                        info.set_generation(chunk.generation);
+                                    }
+                                    drift.record_delivered(chunk.duration_ms);
+                                    resume_after = Some(chunk.position_ms);
+                                    yield Ok(chunk.data);
+                                }
+                            }
+                        }
 
                         // Attempt immediate recovery by getting fresh data
                         match tokio::time::timeout(Duration::from_millis(500), receiver.recv()).await {
                             Ok(Ok(chunk)) => {
                                 info!("Listener {} recovered successfully", &listener_id[..8]);
-                                if let Some(mut info) = listeners.get_mut(&listener_id) {
-                                    info.bytes_received += chunk.len() as u64;
-                                }
-                                yield Ok(chunk);
-                                continue; // Continue normal streaming
+                                chunk
                             }
                             Ok(Err(_)) => {
                                 error!("Listener {} recovery failed - broadcast closed", &listener_id[..8]);
+                                guard.reason = "broadcast closed during lag recovery";
                                 break;
                             }
                             Err(_) => {
                                 error!("Listener {} recovery timeout - no data available", &listener_id[..8]);
+                                guard.reason = "no data after lagging";
                                 break;
                             }
                         }
                     }
                     Ok(Err(broadcast::error::RecvError::Closed)) => {
                         info!("Broadcast closed for listener {}", &listener_id[..8]);
+                        guard.reason = "broadcast closed";
                         break;
                     }
                     Err(_) => {
@@ -560,101 +1568,557 @@ impl RadioStation {
                             &listener_id[..8],
                             chunk_timeout.as_millis());
 
+                        // Keep the client's buffer from running dry while the broadcast is stalled
+                        let needed_ms = drift.silence_needed_ms(Instant::now(), min_buffered_ms);
+                        if let (Some(header), true) = (last_header, needed_ms > 0.0) {
+                            let (silence, duration_ms) = mp3::silence(&header, needed_ms);
+                            drift.record_delivered(duration_ms);
+                            silence_inserted_ms.fetch_add(duration_ms as u64, Ordering::Relaxed);
+                            if let Some(mut info) = listeners.get_mut(&listener_id) {
+                                info.bytes_received += silence.len() as u64;
+                                audience.add_sent(silence.len());
+                                info.silence_ms += duration_ms;
+                            }
+                            debug!("Listener {} filled gap with {:.0}ms of silence", &listener_id[..8], duration_ms);
+                            yield Ok(Bytes::from(silence));
+                        }
+
                         // Try one more time before giving up
                         match tokio::time::timeout(Duration::from_secs(1), receiver.recv()).await {
                             Ok(Ok(chunk)) => {
                                 warn!("Listener {} gap recovered", &listener_id[..8]);
-                                if let Some(mut info) = listeners.get_mut(&listener_id) {
-                                    info.bytes_received += chunk.len() as u64;
-                                }
-                                yield Ok(chunk);
-                                continue;
+                                chunk
                             }
                             _ => {
                                 error!("Listener {} giving up after prolonged gap", &listener_id[..8]);
+                                guard.reason = "prolonged gap in the broadcast";
                                 break;
                             }
                         }
                     }
+                };
+
+                // Already sent from the timeshift buffer or the burst
+                if resume_after.is_some_and(|after| chunk.position_ms <= after) {
+                    continue;
                 }
+
+                // Drift compensation: drop whole chunks while the listener is too far behind live
+                let now = Instant::now();
+                let queued_ms = receiver.len() as f64 * chunk.duration_ms;
+                if drift.should_trim(now, queued_ms) {
+                    drift_trimmed_ms.fetch_add(chunk.duration_ms as u64, Ordering::Relaxed);
+                    if let Some(mut info) = listeners.get_mut(&listener_id) {
+                        info.trimmed_ms += chunk.duration_ms;
+                        info.drift_ms = drift.drift_ms(now, queued_ms);
+                    }
+                    continue;
+                }
+
+                // Realtime clock: hold the chunk until the client has played down to its burst lead
+                if clock == StreamClock::Realtime {
+                    let ahead_ms = drift.buffered_ms(now) - burst_ms;
+                    if ahead_ms > 0.0 {
+                        sleep(Duration::from_secs_f64(ahead_ms / 1000.0)).await;
+                    }
+                }
+
+                let handling = Instant::now();
+                if let Some(header) = mp3::FrameHeader::parse(&chunk.data) {
+                    last_header = Some(header);
+                }
+                // Trims, lag skips and inserted silence move the mapping between the
+                // client's playback position and the sync timeline
+                let sync_offset_ms = chunk.position_ms - drift.delivered_ms();
+                stream_rate.record_delivery(drift.buffered_ms(now) < min_buffered_ms);
+                drift.record_delivered(chunk.duration_ms);
+                if let Some(mut info) = listeners.get_mut(&listener_id) {
+                    info.bytes_received += chunk.data.len() as u64;
+                    audience.add_sent(chunk.data.len());
+                    info.resume_position_ms = Some(chunk.position_ms + chunk.duration_ms);
+                    info.drift_ms = drift.drift_ms(now, queued_ms);
+                    info.sync_offset_ms = Some(sync_offset_ms);
+                    info.set_generation(chunk.generation);
+                }
+                monitor.record(Subsystem::Listeners, handling.elapsed());
+                let hold = shaper.delay(Instant::now(), chunk.duration_ms);
+                if !hold.is_zero() {
+                    sleep(hold).await;
+                }
+                yield Ok(chunk.data);
             }
-            
-            // Cleanup on disconnect
-            listeners.remove(&listener_id);
-            let remaining = listeners.len();
-            info!("Audio listener disconnected: {} (remaining: {})", &listener_id[..8], remaining);
-        })
+        };
+        Ok((stream_id, stream.take_until(kick.cancelled_owned())))
     }
     
-    pub fn create_event_stream(self: Arc<Self>) -> impl Stream<Item = Result<Event>> {
+    /// Server-sent events, each kind under its own event name so clients can
+    /// subscribe selectively:
+    /// - `track-change` as soon as the track changes or the metadata override is set or cleared
+    /// - `listener-count` when the number of listeners changes (checked every second)
+    /// - `stream-health` every 5s
+    /// - `playlist-updated` after every playlist edit or rescan
+    /// - `alert` when hold audio starts or ends
+    /// - `now-playing` and `sync` every 5s
+    ///
+    /// `track-change`, `playlist-updated` and `alert` carry ids from the event
+    /// log; with `last_event_id` (a reconnecting client's Last-Event-ID) the ones
+    /// it missed are replayed first. `listener` (an X-Listener-Id) makes
+    /// `now-playing` and `track-change` follow that listener's audio instead of
+    /// the live track; those track changes aren't numbered, and a reconnect gets
+    /// the track the listener is hearing.
+    pub fn create_event_stream(
+        self: Arc<Self>,
+        listener: Option<String>,
+        last_event_id: Option<u64>,
+    ) -> impl Stream<Item = Result<Event>> {
         // Don't count SSE connections as listeners
         async_stream::stream! {
-            let mut interval = interval(Duration::from_secs(5));
+            let mut refresh = interval(Duration::from_secs(5));
+            let mut poll = interval(Duration::from_secs(1));
+            // Read before subscribing, so nothing falls between the replay and live events
+            let mut sent_id = match last_event_id {
+                Some(_) => 0,
+                None => self.events.latest_id(),
+            };
+            let mut logged = self.events.subscribe();
+            let mut listeners = None;
+            let mut heard = self.track_changes_for(listener.as_deref());
+            let mut metadata = self.metadata_changes();
+            let mut retitled = false;
+            let mut generation = match (&listener, last_event_id) {
+                (Some(_), None) => self.get_now_playing_for(listener.as_deref()).generation,
+                _ => 0,
+            };
 
+            let mut replay = last_event_id.map(|last| self.events.since(last)).unwrap_or_default();
             loop {
-                interval.tick().await;
+                for event in replay.drain(..) {
+                    if event.id <= sent_id {
+                        continue;
+                    }
+                    sent_id = event.id;
+                    if !(listener.is_some() && event.name == "track-change") {
+                        yield Ok(event.to_sse());
+                    }
+                }
 
-                let event = Event::default()
-                    .event("now-playing")
-                    .json_data(self.get_now_playing())
-                    .unwrap();
+                tokio::select! {
+                    _ = refresh.tick() => {
+                        yield Ok(Event::default()
+                            .event("now-playing")
+                            .json_data(self.get_now_playing_for(listener.as_deref()))
+                            .unwrap());
 
-                yield Ok(event);
+                        let (sync, _) = self.sync_info(None);
+                        yield Ok(Event::default().event("sync").json_data(sync).unwrap());
+                        yield Ok(Event::default().event("stream-health").json_data(self.stream_health()).unwrap());
+                    }
+                    _ = poll.tick() => {
+                        let count = self.total_listener_count();
+                        if listeners != Some(count) {
+                            listeners = Some(count);
+                            yield Ok(Event::default()
+                                .event("listener-count")
+                                .json_data(serde_json::json!({ "listeners": count }))
+                                .unwrap());
+                        }
+                    }
+                    event = logged.recv() => match event {
+                        Ok(event) => replay.push(event),
+                        // Fell behind: catch up from the log
+                        Err(broadcast::error::RecvError::Lagged(_)) => replay = self.events.since(sent_id),
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    changed = heard.changed(), if listener.is_some() => {
+                        if changed.is_err() {
+                            // The listener left; follow the live track
+                            heard = self.track_changes_for(None);
+                        }
+                    }
+                    // The override applies to every listener at once
+                    Ok(()) = metadata.changed(), if listener.is_some() => retitled = true,
+                }
+
+                // A listener reaches a new track after the live change
+                if listener.is_some() {
+                    let now_playing = self.get_now_playing_for(listener.as_deref());
+                    let retitled = std::mem::take(&mut retitled);
+                    if now_playing.generation != generation || retitled {
+                        generation = now_playing.generation;
+                        yield Ok(Event::default().event("track-change").json_data(now_playing).unwrap());
+                    }
+                }
             }
         }
     }
     
-    pub fn get_now_playing(&self) -> serde_json::Value {
-        let current = self.current_track.load();
-        
-        match current.as_ref() {
-            Some(track) => serde_json::json!({
-                "title": track.title,
-                "artist": track.artist,
-                "album": track.album,
-                "duration": track.duration,
-                "bitrate": track.bitrate.unwrap_or(0) / 1000, // Show in kbps
-                "position": self.current_position.load(Ordering::Relaxed),
-                "listeners": self.listener_count(),
-            }),
-            None => serde_json::json!({
-                "title": "No track playing",
-                "listeners": self.listener_count(),
-            }),
+    /// Changes whenever the track `listener_id` is hearing does (the live track
+    /// for unknown listeners and `None`), so subscribers are woken right away
+    pub fn track_changes_for(&self, listener_id: Option<&str>) -> watch::Receiver<u64> {
+        listener_id
+            .and_then(|id| self.listeners.get(id))
+            .map(|info| info.generation.subscribe())
+            .unwrap_or_else(|| self.track_changes.subscribe())
+    }
+
+    pub fn get_now_playing(&self) -> NowPlaying {
+        self.get_now_playing_for(None)
+    }
+
+    /// Now-playing for what a listener is actually hearing: the track of the last
+    /// chunk delivered to them, which trails the live track during bursts and
+    /// rewinds. Unknown listeners (or `None`) get the live track.
+    /// A metadata override replaces the track for every listener.
+    pub fn get_now_playing_for(&self, listener_id: Option<&str>) -> NowPlaying {
+        let now_playing = self.track_now_playing_for(listener_id);
+        match self.metadata_override.borrow().as_ref() {
+            Some(title) => NowPlaying {
+                title: title.clone(),
+                artist: self.config.station_name.clone(),
+                bitrate: now_playing.bitrate,
+                listeners: now_playing.listeners,
+                server_time_ms: now_playing.server_time_ms,
+                sync_position_ms: now_playing.sync_position_ms,
+                generation: now_playing.generation,
+                ..Default::default()
+            },
+            None => now_playing,
+        }
+    }
+
+    fn track_now_playing_for(&self, listener_id: Option<&str>) -> NowPlaying {
+        let heard = listener_id
+            .and_then(|id| self.listeners.get(id))
+            .map(|info| *info.generation.borrow())
+            .filter(|&generation| generation > 0);
+        let entry = {
+            let recent = self.recent_tracks.lock().unwrap();
+            heard
+                .and_then(|generation| recent.iter().rev().find(|entry| entry.generation == generation))
+                .or(recent.back())
+                .cloned()
+        };
+        let Some(TrackGeneration { generation, track, sync_position_ms }) = entry else {
+            return NowPlaying {
+                title: "No track playing".to_string(),
+                listeners: self.listener_count(),
+                server_time_ms: unix_now_ms(),
+                ..Default::default()
+            };
+        };
+
+        match track.as_ref() {
+            Some(track) => NowPlaying {
+                title: track.title.clone(),
+                artist: track.artist.clone(),
+                album: track.album.clone(),
+                isrc: track.isrc.clone(),
+                composer: track.composer.clone(),
+                label: track.label.clone(),
+                tags: track.tags.clone(),
+                duration: track.duration,
+                bitrate: track.bitrate.unwrap_or(0) / 1000, // Show in kbps
+                position: self.current_position.load(Ordering::Relaxed),
+                listeners: self.listener_count(),
+                server_time_ms: unix_now_ms(),
+                sync_position_ms,
+                generation,
+            },
+            None => NowPlaying {
+                title: "No track playing".to_string(),
+                listeners: self.listener_count(),
+                server_time_ms: unix_now_ms(),
+                generation,
+                ..Default::default()
+            },
         }
     }
     
+    /// Bitrate on air: the last broadcast frame's, else the current track's, else 192
+    pub fn bitrate_kbps(&self) -> u64 {
+        mp3::FrameHeader::parse(&self.last_frame_header.load(Ordering::Relaxed).to_be_bytes())
+            .map(|header| header.bitrate_kbps as u64)
+            .or_else(|| self.current_track.load().as_ref().as_ref().and_then(|track| track.bitrate).map(|bps| bps / 1000))
+            .filter(|&kbps| kbps > 0)
+            .unwrap_or(192)
+    }
+
+    /// Client buffer, in seconds, a player on `profile` should aim for: the burst a
+    /// new listener is sent at the current bitrate, and never less than two chunk
+    /// intervals of pacing slack. Less than that underruns when a chunk is late;
+    /// more only adds latency.
+    pub fn buffer_hint_secs(&self, profile: ClientProfile) -> f64 {
+        let kbps = self.bitrate_kbps();
+        let burst = self.config.burst(profile);
+        let burst_secs = burst.size_kb.unwrap_or(burst.burst_kb) as f64 * 1024.0 * 8.0 / (kbps as f64 * 1000.0);
+        let pacing_secs = 2.0 * self.config.chunk_interval_ms as f64 / 1000.0;
+        (burst_secs.max(pacing_secs) * 10.0).round() / 10.0
+    }
+
     pub fn listener_count(&self) -> usize {
         self.listeners.len()
     }
+
+    /// Connected MP3 listeners in detail, for the admin API
+    pub fn listener_details(&self) -> Vec<serde_json::Value> {
+        self.listeners.iter()
+            .map(|entry| {
+                let (id, info) = entry.pair();
+                serde_json::json!({
+                    "id": id,
+                    "ip": info.ip,
+                    "user_agent": info.user_agent,
+                    "connected_seconds": info.connected_at.elapsed().as_secs(),
+                    "bytes_received": info.bytes_received,
+                    "profile": info.profile.name(),
+                })
+            })
+            .collect()
+    }
+
+    /// Record who is behind a listener, for `listener_details` and bans
+    pub fn identify_listener(&self, listener_id: &str, ip: Option<IpAddr>, user_agent: Option<&str>) {
+        if let Some(mut info) = self.listeners.get_mut(listener_id) {
+            info.ip = ip;
+            info.user_agent = user_agent.map(str::to_string);
+        }
+    }
+
+    /// Disconnect the listener with this id (or unambiguous id prefix, such as the
+    /// 8 characters `/api/stats` shows); returns its full id and address
+    pub fn kick_listener(&self, id: &str) -> Option<(String, Option<IpAddr>)> {
+        if id.is_empty() {
+            return None;
+        }
+        let mut matches = self.listeners.iter().filter(|entry| entry.key().starts_with(id));
+        let entry = matches.next()?;
+        if matches.next().is_some() {
+            return None;
+        }
+        info!("Disconnecting listener {} on admin request", &entry.key()[..8]);
+        entry.kick.cancel();
+        Some((entry.key().clone(), entry.ip))
+    }
+
+    /// Disconnect every listener connected from `ip`; returns how many there were
+    pub fn kick_ip(&self, ip: IpAddr) -> usize {
+        let ip = ip.to_canonical();
+        let mut kicked = 0;
+        for entry in self.listeners.iter().filter(|entry| entry.ip == Some(ip)) {
+            entry.kick.cancel();
+            kicked += 1;
+        }
+        kicked
+    }
+
+    pub fn bans(&self) -> &BanList {
+        &self.bans
+    }
+
+    /// Icecast's view of the station: the main mount (MP3 and the other
+    /// `/stream` codecs) and one source per simulcast mount. `base_url` is where
+    /// listeners reach the server.
+    pub fn icecast_status(&self, base_url: &str) -> icecast::Status {
+        let config = &self.config;
+        let title = self.stream_title_for(None);
+        let source = |mount: String, bitrate_kbps: u64, listeners: usize, listener_peak: usize, total_bytes_sent: u64| icecast::Source {
+            listenurl: format!("{}{}", base_url, mount),
+            mount,
+            server_name: config.station_name.clone(),
+            server_description: config.station_description.clone(),
+            server_url: config.station_url.clone().unwrap_or_else(|| base_url.to_string()),
+            genre: config.station_genre.clone(),
+            server_type: "audio/mpeg".to_string(),
+            bitrate_kbps,
+            title: title.clone(),
+            listeners,
+            listener_peak: listener_peak.max(listeners),
+            max_listeners: Some(config.max_listeners).filter(|&max| max > 0),
+            public: config.station_public,
+            total_bytes_sent,
+        };
+
+        let main_listeners = self.listener_count() + self.codecs.listener_count();
+        let mut sources = vec![source(
+            "/stream".to_string(),
+            self.bitrate_kbps(),
+            main_listeners,
+            self.listener_peak.load(Ordering::Relaxed),
+            self.total_bytes_sent.load(Ordering::Relaxed),
+        )];
+        for mount in self.simulcast.mounts() {
+            sources.push(source(mount.path(), mount.bitrate_kbps() as u64, mount.listener_count(), 0, 0));
+        }
+
+        icecast::Status {
+            host: base_url.split("://").nth(1).unwrap_or(base_url).to_string(),
+            server_id: format!("webradio {}", env!("CARGO_PKG_VERSION")),
+            started: chrono::Utc::now() - chrono::Duration::seconds(self.uptime_seconds() as i64),
+            sources,
+        }
+    }
+
+    /// MP3 listeners plus those on simulcast mounts and other codecs
+    pub fn total_listener_count(&self) -> usize {
+        self.listener_count() + self.simulcast.listener_count() + self.codecs.listener_count()
+    }
+
+    /// Reject new listeners once MAX_LISTENERS is reached (simulcast mounts and other codecs included)
+    pub fn check_listener_capacity(&self) -> Result<()> {
+        let max = self.config.max_listeners;
+        let current = self.total_listener_count();
+        if max > 0 && current >= max {
+            warn!("Listener limit reached ({}/{}), rejecting new listener", current, max);
+            return Err(AppError::ServiceUnavailable {
+                message: format!("The station is at capacity ({} of {} listeners). Please try again shortly.", current, max),
+                retry_after_secs: self.config.listener_retry_after_secs,
+            });
+        }
+        Ok(())
+    }
     
     pub fn uptime_seconds(&self) -> u64 {
         self.start_time.elapsed().as_secs()
     }
     
-    pub fn get_playlist(&self) -> Result<Playlist> {
-        // This is sync but should be fast
-        let playlist = tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                self.playlist.read().await.clone()
-            })
-        });
-        Ok(playlist)
+    /// The playlist as served by `/api/playlist`
+    pub fn get_playlist(&self) -> PlaylistDto {
+        self.playlist.to_dto()
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> SearchResults {
+        let matches = self.playlist.search(query);
+        SearchResults {
+            total: matches.len(),
+            tracks: matches.iter().take(limit).map(TrackDto::from).collect(),
+        }
+    }
+
+    pub fn next_up(&self, count: usize) -> NextUp {
+        NextUp { tracks: self.playlist.upcoming(count).iter().map(TrackDto::from).collect() }
+    }
+
+    /// The shared playlist, for rescans and edits
+    pub fn playlist(&self) -> &Arc<SharedPlaylist> {
+        &self.playlist
+    }
+
+    /// Apply an admin edit to the playlist and persist it to playlist.json
+    pub async fn edit_playlist<T>(&self, edit: impl Fn(&mut Playlist) -> Result<T>) -> Result<PlaylistDto> {
+        self.playlist.try_update(edit)?;
+        if let Err(e) = self.playlist.save_to(&self.config.music_dir).await {
+            warn!("Failed to save playlist: {}", e);
+        }
+        Ok(self.playlist.to_dto())
+    }
+
+    /// Correct a track's tags in the playlist and, with `write_file`, in its MP3's ID3
+    /// tag. Now-playing shows the correction at once when the track is on air.
+    pub async fn edit_track_tags(&self, id: uuid::Uuid, edit: &TagEdit, write_file: bool) -> Result<Track> {
+        let track = self.playlist.snapshot().get(id).cloned().ok_or_else(|| AppError::TrackNotFound(id.to_string()))?;
+        if write_file {
+            let path = self.track_path(&track);
+            if !path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mp3")) {
+                return Err(AppError::BadRequest("Tags can only be written to MP3 files".into()));
+            }
+            let data = tokio::fs::read(&path).await?;
+            let tagged = id3::set_text_frames(&data, &edit.id3_frames())
+                .map_err(|e| AppError::BadRequest(format!("Can't write tags to {}: {}", track.path.display(), e)))?;
+            // Written beside the file and renamed over it, so a failed write leaves the original
+            let temp = path.with_extension("mp3.tagging");
+            tokio::fs::write(&temp, tagged).await?;
+            tokio::fs::rename(&temp, &path).await?;
+            info!("Wrote tags to {}", path.display());
+        }
+
+        self.edit_playlist(|playlist| playlist.edit_tags(id, edit)).await?;
+        let updated = self.playlist.snapshot().get(id).cloned().ok_or_else(|| AppError::TrackNotFound(id.to_string()))?;
+
+        // Now-playing, including for listeners still hearing the track behind live
+        let shared = Arc::new(Some(updated.clone()));
+        let is_track = |track: &Option<Track>| track.as_ref().is_some_and(|track| track.id == id);
+        for entry in self.recent_tracks.lock().unwrap().iter_mut().filter(|entry| is_track(&entry.track)) {
+            entry.track = Arc::clone(&shared);
+        }
+        if is_track(&self.current_track.load()) {
+            self.current_track.store(shared);
+            self.track_changes.send_modify(|_| {});
+        }
+        Ok(updated)
     }
     
-    pub fn get_statistics(&self) -> serde_json::Value {
+    pub fn get_statistics(&self) -> StatsDto {
         let total_mb = self.total_bytes_sent.load(Ordering::Relaxed) as f64 / 1_048_576.0;
         let listeners: Vec<_> = self.listeners.iter()
             .map(|entry| {
                 let (id, info) = entry.pair();
-                serde_json::json!({
-                    "id": &id[..8],
-                    "connected_seconds": info.connected_at.elapsed().as_secs(),
-                    "mb_received": info.bytes_received as f64 / 1_048_576.0,
-                })
+                ListenerDto {
+                    id: id[..8].to_string(),
+                    connected_seconds: info.connected_at.elapsed().as_secs(),
+                    mb_received: info.bytes_received as f64 / 1_048_576.0,
+                    drift_seconds: info.drift_ms / 1000.0,
+                    trimmed_seconds: info.trimmed_ms / 1000.0,
+                    silence_seconds: info.silence_ms / 1000.0,
+                    profile: info.profile.name().to_string(),
+                    burst_kb: info.burst_bytes as f64 / 1024.0,
+                    burst_seconds: info.burst_ms / 1000.0,
+                }
+            })
+            .collect();
+
+        let burst_profiles: serde_json::Map<String, serde_json::Value> = ClientProfile::ALL
+            .iter()
+            .map(|profile| {
+                let burst = self.config.burst(*profile);
+                (profile.name().to_string(), serde_json::json!({
+                    "burst_kb": burst.burst_kb,
+                    "minimum_kb": burst.minimum_kb,
+                    "timeout_ms": burst.timeout_ms,
+                    "pacing_kbps": burst.pacing_kbps,
+                    "catch_up": if burst.catch_up == CatchUp::SkipToLive { "skip_to_live" } else { "queue" },
+                    "clock": burst.clock.name(),
+                    "egress_cap": burst.egress_cap,
+                    "lag_policy": burst.lag.name(),
+                    "burst_size_kb": burst.size_kb,
+                    "types": burst.types,
+                    "user_agents": burst.user_agents,
+                }))
             })
             .collect();
 
+        StatsDto {
+            uptime_seconds: self.uptime_seconds(),
+            total_mb_sent: total_mb,
+            current_listeners: self.listener_count(),
+            is_broadcasting: self.is_broadcasting.load(Ordering::Relaxed),
+            listeners,
+
+            // Stream health metrics
+            stream_health: self.stream_health(),
+
+            // Buffer configuration
+            buffer_config: serde_json::json!({
+                "initial_buffer_kb": self.config.initial_buffer_kb,
+                "initial_buffer_seconds": self.config.initial_buffer_kb as f64 / 24.0,
+                "minimum_buffer_kb": self.config.minimum_buffer_kb,
+                "minimum_buffer_seconds": self.config.minimum_buffer_kb as f64 / 24.0,
+                "chunk_interval_ms": self.config.chunk_interval_ms,
+                "stream_rate_multiplier": self.stream_rate.multiplier(),
+                "stream_rate_percent": self.stream_rate.multiplier() * 100.0,
+                "buffer_growth_percent_per_sec": (self.stream_rate.multiplier() - 1.0) * 100.0,
+                "adaptive_stream_rate": self.config.adaptive_stream_rate,
+                "broadcast_channel_capacity": self.config.broadcast_channel_capacity,
+                "burst_profiles": burst_profiles,
+                "timeshift_minutes": self.config.timeshift_minutes,
+                "timeshift_buffered_seconds": self.timeshift.buffered_ms() / 1000.0,
+            }),
+
+            client_telemetry: self.beacons.snapshot(),
+            geo: self.geo_breakdown(),
+        }
+    }
+    
+    pub fn stream_health(&self) -> StreamHealthDto {
         // Calculate time since last chunk sent
         let last_chunk_ms = self.last_chunk_sent.load(Ordering::Relaxed);
         let now_ms = std::time::SystemTime::now()
@@ -667,36 +2131,232 @@ impl RadioStation {
             0
         };
 
-        serde_json::json!({
-            "uptime_seconds": self.uptime_seconds(),
-            "total_mb_sent": total_mb,
-            "current_listeners": self.listener_count(),
-            "is_broadcasting": self.is_broadcasting.load(Ordering::Relaxed),
-            "listeners": listeners,
+        StreamHealthDto {
+            gaps_detected: self.stream_gaps_detected.load(Ordering::Relaxed),
+            recovery_attempts: self.recovery_attempts.load(Ordering::Relaxed),
+            ms_since_last_chunk,
+            is_streaming: ms_since_last_chunk < 500, // Healthy if chunk sent in last 500ms
+            drift_trimmed_seconds: self.drift_trimmed_ms.load(Ordering::Relaxed) as f64 / 1000.0,
+            silence_inserted_seconds: self.silence_inserted_ms.load(Ordering::Relaxed) as f64 / 1000.0,
+            on_hold: self.on_hold.load(Ordering::Relaxed),
+            hold_seconds: self.hold_ms.load(Ordering::Relaxed) as f64 / 1000.0,
+            idle_mode: self.config.idle_mode.name().to_string(),
+            paused: self.paused.load(Ordering::Relaxed),
+            paused_seconds: self.paused_ms.load(Ordering::Relaxed) as f64 / 1000.0,
+            chunk_integrity: self.integrity.snapshot(),
+        }
+    }
 
-            // Stream health metrics
-            "stream_health": {
-                "gaps_detected": self.stream_gaps_detected.load(Ordering::Relaxed),
-                "recovery_attempts": self.recovery_attempts.load(Ordering::Relaxed),
-                "ms_since_last_chunk": ms_since_last_chunk,
-                "is_streaming": ms_since_last_chunk < 500, // Healthy if chunk sent in last 500ms
-            },
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
 
-            // Buffer configuration
-            "buffer_config": {
-                "initial_buffer_kb": self.config.initial_buffer_kb,
-                "initial_buffer_seconds": self.config.initial_buffer_kb as f64 / 24.0,
-                "minimum_buffer_kb": self.config.minimum_buffer_kb,
-                "minimum_buffer_seconds": self.config.minimum_buffer_kb as f64 / 24.0,
-                "chunk_interval_ms": self.config.chunk_interval_ms,
-                "stream_rate_multiplier": self.config.stream_rate_multiplier,
-                "stream_rate_percent": self.config.stream_rate_multiplier * 100.0,
-                "buffer_growth_percent_per_sec": (self.config.stream_rate_multiplier - 1.0) * 100.0,
-                "broadcast_channel_capacity": self.config.broadcast_channel_capacity,
-            },
-        })
+    pub fn play_history(&self) -> &PlayHistory {
+        &self.history
     }
-    
+
+    pub fn audience_log(&self) -> &AudienceLog {
+        &self.audience_log
+    }
+
+    /// Receive the broadcast chunk stream without registering as a listener
+    pub async fn subscribe(&self) -> broadcast::Receiver<AudioChunk> {
+        self.broadcast_tx.read().await.subscribe()
+    }
+
+    pub fn current_track(&self) -> Option<Track> {
+        self.current_track.load().as_ref().clone()
+    }
+
+    pub fn archive(&self) -> &Archive {
+        &self.archive
+    }
+
+    pub fn monitor(&self) -> &PerformanceMonitor {
+        &self.monitor
+    }
+
+    pub fn public_ip(&self) -> &Arc<PublicIp> {
+        &self.public_ip
+    }
+
+    pub fn chunk_integrity(&self) -> &ChunkIntegrity {
+        &self.integrity
+    }
+
+    pub fn ip_limiter(&self) -> &IpLimiter {
+        &self.ip_limiter
+    }
+
+    pub fn admin_auth(&self) -> &AdminAuth {
+        &self.admin_auth
+    }
+
+    pub fn signer(&self) -> &Signer {
+        &self.signer
+    }
+
+    pub fn beacon_stats(&self) -> &BeaconStats {
+        &self.beacons
+    }
+
+    pub fn client_tests(&self) -> &ClientTests {
+        &self.client_tests
+    }
+
+    pub fn intercom_auth(&self) -> &AdminAuth {
+        &self.intercom_auth
+    }
+
+    pub fn intercom(&self) -> &Arc<Intercom> {
+        &self.intercom
+    }
+
+    pub fn simulcast(&self) -> &Simulcast {
+        &self.simulcast
+    }
+
+    pub fn codecs(&self) -> &CodecOutputs {
+        &self.codecs
+    }
+
+    /// The startup preflight report, once the checks have run
+    pub fn preflight(&self) -> Option<&PreflightReport> {
+        self.preflight.get()
+    }
+
+    pub fn set_preflight(&self, report: PreflightReport) {
+        let _ = self.preflight.set(report);
+    }
+
+    /// Whether the broadcast is actually running, for /readyz: something to play,
+    /// the broadcast loop up, and audio published within READY_MAX_CHUNK_AGE_MS
+    /// (unless playout is paused for want of listeners)
+    pub fn runtime_checks(&self) -> Vec<Check> {
+        let tracks = self.playlist.len();
+        let playlist = if tracks > 0 {
+            Check::pass("playlist", format!("{} tracks", tracks))
+        } else if self.config.relay_url.is_some() || self.config.chunk_log_replay.is_some() {
+            Check::pass("playlist", "empty; broadcasting a relay or chunk log")
+        } else {
+            Check::fail("playlist", "no tracks loaded")
+        };
+        let broadcast = if self.is_broadcasting() {
+            Check::pass("broadcast", "broadcast loop running")
+        } else {
+            Check::fail("broadcast", "broadcast loop not running")
+        };
+
+        let last_chunk_ms = self.last_chunk_sent.load(Ordering::Relaxed);
+        let age_ms = unix_now_ms().saturating_sub(last_chunk_ms);
+        let max_age_ms = self.config.ready_max_chunk_age_ms;
+        let audio = if self.paused.load(Ordering::Relaxed) {
+            Check::pass("audio", "paused while nobody is listening")
+        } else if last_chunk_ms == 0 {
+            Check::fail("audio", "no audio published yet")
+        } else if age_ms > max_age_ms {
+            Check::fail("audio", format!("last chunk {}ms ago (limit {}ms)", age_ms, max_age_ms))
+        } else {
+            Check::pass("audio", format!("last chunk {}ms ago", age_ms))
+        };
+        vec![playlist, broadcast, audio]
+    }
+
+    /// Record a listener's vote to skip the current track. The track ends early once
+    /// SKIP_VOTE_FRACTION of the connected listeners have voted for it.
+    pub fn vote_skip(&self, listener_id: &str) -> Result<SkipVote> {
+        if self.config.skip_vote_fraction <= 0.0 {
+            return Err(AppError::BadRequest("Skip voting is disabled".to_string()));
+        }
+        if !self.listeners.contains_key(listener_id) {
+            return Err(AppError::NotFound);
+        }
+        // Hold audio and relayed streams have no playlist track to skip
+        let skippable = self.current_track.load().as_ref().as_ref()
+            .is_some_and(|track| !track.path.as_os_str().is_empty());
+        if !skippable || self.on_hold.load(Ordering::Relaxed) {
+            return Err(AppError::BadRequest("Nothing to skip".to_string()));
+        }
+
+        self.skip_votes.insert(listener_id.to_string());
+        // Votes of listeners who have since disconnected don't count
+        self.skip_votes.retain(|id| self.listeners.contains_key(id));
+        let votes = self.skip_votes.len();
+        let listeners = self.listener_count();
+        let required = ((listeners as f64 * self.config.skip_vote_fraction).ceil() as usize).max(1);
+
+        let skipped = votes >= required;
+        if skipped {
+            self.skip_votes.clear();
+            // Only wakes a track that is playing; a vote between tracks is dropped
+            self.skip_requested.notify_waiters();
+        }
+        Ok(SkipVote { votes, required, skipped })
+    }
+
+    /// Playout clock snapshot, plus the listener's timeline mapping when an id is given
+    pub fn sync_info(&self, listener_id: Option<&str>) -> (SyncSnapshot, Option<f64>) {
+        let snapshot = self.sync_clock.snapshot(unix_now_ms());
+        let offset = listener_id
+            .and_then(|id| self.listeners.get(id))
+            .and_then(|info| info.sync_offset_ms);
+        (snapshot, offset)
+    }
+
+    pub fn geoip(&self) -> &GeoIp {
+        &self.geoip
+    }
+
+    /// Record where a listener connects from for the STATS_GEOIP breakdown. Only
+    /// the location is kept; with STATS_GEOIP_ANONYMIZE the lookup itself uses
+    /// the truncated address.
+    pub fn locate_listener(&self, listener_id: &str, ip: IpAddr) {
+        if !self.config.stats_geoip || !self.geoip.is_enabled() {
+            return;
+        }
+        let ip = if self.config.stats_geoip_anonymize { geoip::anonymize(ip) } else { ip };
+        let mut location = self.geoip.lookup(ip);
+        location.asn = None;
+        location.as_org = None;
+        if let Some(mut info) = self.listeners.get_mut(listener_id) {
+            info.location = Some(location);
+        }
+    }
+
+    /// Token that lets a listener resume where it left off after a dropped
+    /// connection; None unless RESUME_WINDOW_SECS and the timeshift buffer are on
+    pub fn resume_token(&self, listener_id: &str) -> Option<String> {
+        (self.resume_points.is_enabled() && self.timeshift.is_enabled())
+            .then(|| self.signer.resume_token(listener_id))
+    }
+
+    /// Where the listener a resume token belongs to left off, if it disconnected
+    /// within the resume window. Each token resumes once.
+    pub fn resume_position(&self, token: &str) -> Option<f64> {
+        let listener_id = self.signer.verify_resume_token(token)?;
+        self.resume_points.take(listener_id, Instant::now())
+    }
+
+    /// Connected listeners by country and region; None unless STATS_GEOIP is on
+    /// and a GeoIP database is loaded
+    pub fn geo_breakdown(&self) -> Option<GeoBreakdown> {
+        if !self.config.stats_geoip || !self.geoip.is_enabled() {
+            return None;
+        }
+        let locations: Vec<GeoInfo> = self.listeners.iter()
+            .map(|entry| entry.location.clone().unwrap_or_default())
+            .collect();
+        Some(geoip::breakdown(&locations, self.config.stats_geoip_anonymize))
+    }
+
+    /// Apply the configured country/network rules to a listener address
+    pub fn check_stream_access(&self, ip: IpAddr) -> std::result::Result<(), String> {
+        if self.access_rules.is_empty() {
+            return Ok(());
+        }
+        self.access_rules.check(ip, &self.geoip.lookup(ip))
+    }
+
     pub fn is_broadcasting(&self) -> bool {
         self.is_broadcasting.load(Ordering::Relaxed)
     }
@@ -706,6 +2366,73 @@ impl RadioStation {
     }
 }
 
+// Duration in milliseconds including the fractional second
+fn precise_ms(time_base: symphonia::core::units::TimeBase, ts: u64) -> f64 {
+    let time = time_base.calc_time(ts);
+    (time.seconds as f64 + time.frac) * 1000.0
+}
+
+/// Unregisters a listener when its audio stream is dropped, whether the stream
+/// ended or the client went away mid-stream
+struct ListenerGuard {
+    listeners: Arc<DashMap<String, ListenerInfo>>,
+    listener_id: String,
+    resume_points: Arc<ResumePoints>,
+    span: tracing::Span, // The connecting request's, so the disconnect is logged with its request id
+    reason: &'static str, // Set by the stream when it ends itself
+}
+
+impl Drop for ListenerGuard {
+    fn drop(&mut self) {
+        if let Some((_, info)) = self.listeners.remove(&self.listener_id) {
+            if let Some(position_ms) = info.resume_position_ms {
+                self.resume_points.record(&self.listener_id, position_ms, Instant::now());
+            }
+            if info.kick.is_cancelled() {
+                self.reason = "disconnected by an admin";
+            }
+        }
+        let _entered = self.span.enter();
+        info!("Audio listener disconnected: {} ({}; remaining: {})", &self.listener_id[..8], self.reason, self.listeners.len());
+    }
+}
+
+// Format for generated silence before anything has been broadcast: 128kbps, 44.1kHz, joint stereo
+const DEFAULT_FRAME_HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x64];
+
+/// Frames of an MP3 file with their durations; empty (with a warning) if unreadable
+async fn load_frames(path: &std::path::Path) -> Vec<(Bytes, f64)> {
+    match tokio::fs::read(path).await {
+        Ok(data) => {
+            let frames: Vec<(Bytes, f64)> = mp3::split_frames(&data).into_iter()
+                .map(|(header, frame)| (Bytes::copy_from_slice(frame), header.duration_ms()))
+                .collect();
+            if frames.is_empty() {
+                warn!("No MPEG-1 Layer III frames found in {}", path.display());
+            }
+            frames
+        }
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+fn unix_now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn unix_now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 impl Drop for RadioStation {
     fn drop(&mut self) {
         info!("RadioStation dropping, stopping broadcast");
@@ -739,12 +2466,75 @@ mod tests {
         let info = ListenerInfo {
             connected_at: Instant::now(),
             bytes_received: 1024,
+            drift_ms: 0.0,
+            trimmed_ms: 0.0,
+            silence_ms: 0.0,
+            sync_offset_ms: None,
+            generation: watch::Sender::new(0),
+            location: None,
+            resume_position_ms: None,
+            profile: ClientProfile::Default,
+            burst_bytes: 0,
+            burst_ms: 0.0,
+            ip: None,
+            user_agent: None,
+            kick: CancellationToken::new(),
         };
 
         assert_eq!(info.bytes_received, 1024);
         assert!(info.connected_at.elapsed().as_secs() < 1);
     }
 
+    #[tokio::test]
+    async fn test_now_playing_follows_listener_generation() {
+        let music_dir = std::env::temp_dir().join(format!("webradio_generation_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&music_dir).unwrap();
+        let mut config = Config::from_env();
+        config.music_dir = music_dir.clone();
+        config.play_history_path = music_dir.join("history.jsonl");
+        let station = RadioStation::new(config).await.unwrap();
+        let track = |title: &str| Some(Track { title: title.to_string(), ..Default::default() });
+
+        station.set_current_track(track("First"));
+        let first = station.get_now_playing().generation;
+        station.listeners.insert("behind".to_string(), ListenerInfo {
+            connected_at: Instant::now(),
+            bytes_received: 0,
+            drift_ms: 0.0,
+            trimmed_ms: 0.0,
+            silence_ms: 0.0,
+            sync_offset_ms: None,
+            generation: watch::Sender::new(first),
+            location: None,
+            resume_position_ms: None,
+            profile: ClientProfile::Default,
+            burst_bytes: 0,
+            burst_ms: 0.0,
+            ip: None,
+            user_agent: None,
+            kick: CancellationToken::new(),
+        });
+        station.set_current_track(track("Second"));
+
+        let live = station.get_now_playing();
+        assert_eq!(live.title, "Second");
+        assert_eq!(live.generation, first + 1);
+        let heard = station.get_now_playing_for(Some("behind"));
+        assert_eq!(heard.title, "First");
+        assert_eq!(heard.generation, first);
+        assert_eq!(station.get_now_playing_for(Some("unknown")).title, "Second");
+
+        // Followers wake when the listener's audio reaches the new track, not before
+        let mut changes = station.track_changes_for(Some("behind"));
+        station.listeners.get("behind").unwrap().set_generation(first);
+        assert!(!changes.has_changed().unwrap());
+        station.listeners.get("behind").unwrap().set_generation(first + 1);
+        assert_eq!(*changes.borrow_and_update(), first + 1);
+        assert_eq!(station.get_now_playing_for(Some("behind")).title, "Second");
+
+        std::fs::remove_dir_all(&music_dir).ok();
+    }
+
     #[test]
     fn test_stream_rate_calculation() {
         // At 192kbps with 1.10 multiplier
@@ -869,7 +2659,7 @@ mod tests {
         // Duration-based bundling ensures consistent timing regardless of bitrate variation
 
         // Example: VBR file with varying frame sizes
-        let frame_sizes = vec![417, 626, 835, 417]; // Different byte sizes
+        let frame_sizes = [417, 626, 835, 417]; // Different byte sizes
         let total_bytes: usize = frame_sizes.iter().sum();
 
         // Byte-based: Would send when reaching ~2400 bytes
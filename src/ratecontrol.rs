@@ -0,0 +1,113 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+const WINDOW: Duration = Duration::from_secs(10);
+const STEP: f64 = 0.01;
+// Share of deliveries made to clients below their minimum buffer that speeds the broadcast up
+const DRAINING_SHARE: f64 = 0.1;
+
+/// How much faster than realtime the broadcast is paced. Fixed at
+/// STREAM_RATE_MULTIPLIER, or with ADAPTIVE_STREAM_RATE adjusted every 10s from
+/// what listener streams report: a step faster while a good share of deliveries
+/// go to clients whose buffers are draining, a step back towards realtime while
+/// they aren't.
+pub struct StreamRate {
+    max: f64,
+    adaptive: bool,
+    deliveries: AtomicU64,
+    draining: AtomicU64,
+    state: Mutex<RateState>,
+}
+
+struct RateState {
+    multiplier: f64,
+    window_start: Instant,
+}
+
+impl StreamRate {
+    pub fn new(configured: f64, adaptive: bool) -> Self {
+        Self {
+            max: configured.max(1.0),
+            adaptive,
+            deliveries: AtomicU64::new(0),
+            draining: AtomicU64::new(0),
+            state: Mutex::new(RateState {
+                multiplier: if adaptive { 1.0 } else { configured },
+                window_start: Instant::now(),
+            }),
+        }
+    }
+
+    /// A chunk went out to a listener whose estimated buffer is `draining` (below
+    /// its minimum) or not
+    pub fn record_delivery(&self, draining: bool) {
+        if !self.adaptive {
+            return;
+        }
+        self.deliveries.fetch_add(1, Ordering::Relaxed);
+        if draining {
+            self.draining.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn multiplier(&self) -> f64 {
+        self.multiplier_at(Instant::now())
+    }
+
+    fn multiplier_at(&self, now: Instant) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        if self.adaptive && now.saturating_duration_since(state.window_start) >= WINDOW {
+            state.window_start = now;
+            let deliveries = self.deliveries.swap(0, Ordering::Relaxed);
+            let draining = self.draining.swap(0, Ordering::Relaxed);
+            state.multiplier = if deliveries > 0 && draining as f64 >= deliveries as f64 * DRAINING_SHARE {
+                (state.multiplier + STEP).min(self.max)
+            } else {
+                (state.multiplier - STEP).max(1.0)
+            };
+        }
+        state.multiplier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_rate() {
+        let rate = StreamRate::new(1.1, false);
+        rate.record_delivery(true);
+        assert_eq!(rate.multiplier_at(Instant::now() + WINDOW), 1.1);
+    }
+
+    #[test]
+    fn test_speeds_up_for_draining_listeners_and_settles() {
+        let rate = StreamRate::new(1.02, true);
+        let mut now = Instant::now();
+        assert_eq!(rate.multiplier_at(now), 1.0);
+
+        for round in 1..=3 {
+            for i in 0..10 {
+                rate.record_delivery(i < 2);
+            }
+            now += WINDOW;
+            let expected = (1.0 + round as f64 * STEP).min(1.02);
+            assert!((rate.multiplier_at(now) - expected).abs() < 1e-9);
+        }
+
+        // Stable listeners: back to realtime, one step per window
+        rate.record_delivery(false);
+        now += WINDOW;
+        assert!((rate.multiplier_at(now) - 1.01).abs() < 1e-9);
+        now += WINDOW;
+        assert_eq!(rate.multiplier_at(now), 1.0);
+        now += WINDOW;
+        assert_eq!(rate.multiplier_at(now), 1.0);
+    }
+}
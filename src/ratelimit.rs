@@ -0,0 +1,387 @@
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+use tracing::{info, warn};
+
+use crate::config::Config;
+
+// Idle API buckets are dropped once the table grows past this
+const MAX_TRACKED_IPS: usize = 4096;
+// Window over which short-lived streams count as churn
+const CHURN_WINDOW: Duration = Duration::from_secs(60);
+
+/// Per-client-IP limits: simultaneous audio streams and API request rate
+pub struct IpLimiter {
+    max_streams: usize,      // 0 = unlimited
+    api_rate: f64,           // Requests per second; 0 = unlimited
+    streams: Arc<DashMap<IpAddr, usize>>,
+    buckets: DashMap<IpAddr, TokenBucket>,
+    churn: Arc<ChurnTracker>,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Connect/disconnect churn limits: streams that close within `short` count
+/// against their IP. Past `max_per_min` of those in a minute new streams are
+/// tarpitted; past twice that the IP is banned for `ban`.
+#[derive(Debug, Clone)]
+pub struct ChurnPolicy {
+    pub max_per_min: usize, // 0 = no churn detection
+    pub short: Duration,
+    pub tarpit: Duration,
+    pub ban: Duration,
+}
+
+impl ChurnPolicy {
+    pub fn disabled() -> Self {
+        Self {
+            max_per_min: 0,
+            short: Duration::ZERO,
+            tarpit: Duration::ZERO,
+            ban: Duration::ZERO,
+        }
+    }
+}
+
+/// What to do with a new stream from an IP, given its recent churn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChurnVerdict {
+    Allow,
+    Tarpit(Duration),
+    Banned(Duration), // Time left on the ban
+}
+
+/// An IP with recent short-lived streams, for the admin view
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChurnStatus {
+    pub ip: IpAddr,
+    pub short_streams: usize,        // Within the last minute
+    pub banned_secs: Option<u64>,    // Time left on a ban
+}
+
+struct ChurnTracker {
+    policy: ChurnPolicy,
+    records: DashMap<IpAddr, ChurnRecord>,
+}
+
+#[derive(Default)]
+struct ChurnRecord {
+    short_closes: VecDeque<Instant>,
+    banned_until: Option<Instant>,
+}
+
+impl ChurnRecord {
+    fn expire(&mut self, now: Instant) {
+        while self.short_closes.front().is_some_and(|&at| now.saturating_duration_since(at) >= CHURN_WINDOW) {
+            self.short_closes.pop_front();
+        }
+        if self.banned_until.is_some_and(|until| until <= now) {
+            self.banned_until = None;
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.short_closes.is_empty() && self.banned_until.is_none()
+    }
+}
+
+impl ChurnTracker {
+    fn record_close(&self, ip: IpAddr, opened: Instant, now: Instant) {
+        let policy = &self.policy;
+        if policy.max_per_min == 0 || now.saturating_duration_since(opened) >= policy.short {
+            return;
+        }
+        if self.records.len() > MAX_TRACKED_IPS {
+            self.records.retain(|_, record| {
+                record.expire(now);
+                !record.is_idle()
+            });
+        }
+
+        let mut record = self.records.entry(ip).or_default();
+        record.expire(now);
+        record.short_closes.push_back(now);
+        let count = record.short_closes.len();
+        if count > policy.max_per_min * 2 && record.banned_until.is_none() {
+            warn!("Banning {} for {}s after {} short-lived streams in a minute", ip, policy.ban.as_secs(), count);
+            record.banned_until = Some(now + policy.ban);
+        } else if count == policy.max_per_min + 1 {
+            info!("Tarpitting streams from {} after {} short-lived streams in a minute", ip, count);
+        }
+    }
+
+    fn check(&self, ip: IpAddr, now: Instant) -> ChurnVerdict {
+        if self.policy.max_per_min == 0 {
+            return ChurnVerdict::Allow;
+        }
+        let Some(mut record) = self.records.get_mut(&ip) else {
+            return ChurnVerdict::Allow;
+        };
+        record.expire(now);
+        if let Some(until) = record.banned_until {
+            ChurnVerdict::Banned(until - now)
+        } else if record.short_closes.len() > self.policy.max_per_min {
+            ChurnVerdict::Tarpit(self.policy.tarpit)
+        } else {
+            ChurnVerdict::Allow
+        }
+    }
+}
+
+/// One open stream for an IP; the slot is released when the permit is dropped
+pub struct StreamPermit {
+    streams: Arc<DashMap<IpAddr, usize>>,
+    churn: Arc<ChurnTracker>,
+    ip: IpAddr,
+    opened: Instant,
+}
+
+impl StreamPermit {
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+}
+
+impl Drop for StreamPermit {
+    fn drop(&mut self) {
+        self.churn.record_close(self.ip, self.opened, Instant::now());
+        if let dashmap::mapref::entry::Entry::Occupied(mut entry) = self.streams.entry(self.ip) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+}
+
+impl IpLimiter {
+    pub fn new(max_streams: usize, api_rate: f64) -> Self {
+        Self {
+            max_streams,
+            api_rate,
+            streams: Arc::new(DashMap::new()),
+            buckets: DashMap::new(),
+            churn: Arc::new(ChurnTracker {
+                policy: ChurnPolicy::disabled(),
+                records: DashMap::new(),
+            }),
+        }
+    }
+
+    pub fn with_churn(self, policy: ChurnPolicy) -> Self {
+        Self {
+            churn: Arc::new(ChurnTracker { policy, records: DashMap::new() }),
+            ..self
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.max_streams_per_ip, config.api_requests_per_sec).with_churn(ChurnPolicy {
+            max_per_min: config.churn_max_per_min,
+            short: Duration::from_secs(config.churn_short_secs),
+            tarpit: Duration::from_millis(config.churn_tarpit_ms),
+            ban: Duration::from_secs(config.churn_ban_secs),
+        })
+    }
+
+    /// Whether a new stream from `ip` should go ahead, be held back or be refused
+    pub fn check_churn(&self, ip: IpAddr, now: Instant) -> ChurnVerdict {
+        self.churn.check(ip, now)
+    }
+
+    /// IPs with short-lived streams in the last minute, worst first
+    pub fn churn_status(&self, now: Instant) -> Vec<ChurnStatus> {
+        let mut status: Vec<ChurnStatus> = self.churn.records.iter_mut()
+            .filter_map(|mut entry| {
+                entry.expire(now);
+                (!entry.is_idle()).then(|| ChurnStatus {
+                    ip: *entry.key(),
+                    short_streams: entry.short_closes.len(),
+                    banned_secs: entry.banned_until.map(|until| (until - now).as_secs_f64().ceil() as u64),
+                })
+            })
+            .collect();
+        status.sort_by(|a, b| b.short_streams.cmp(&a.short_streams).then(a.ip.cmp(&b.ip)));
+        status
+    }
+
+    /// Reserve a stream slot for `ip`, or `None` if it already has the maximum open
+    pub fn try_acquire_stream(&self, ip: IpAddr) -> Option<StreamPermit> {
+        let mut count = self.streams.entry(ip).or_insert(0);
+        if self.max_streams > 0 && *count >= self.max_streams {
+            return None;
+        }
+        *count += 1;
+        Some(StreamPermit {
+            streams: Arc::clone(&self.streams),
+            churn: Arc::clone(&self.churn),
+            ip,
+            opened: Instant::now(),
+        })
+    }
+
+    pub fn streams_for(&self, ip: IpAddr) -> usize {
+        self.streams.get(&ip).map_or(0, |count| *count)
+    }
+
+    /// Take one API request from `ip`'s token bucket (capacity: one second of requests).
+    /// On refusal returns how long until the next request would be allowed.
+    pub fn check_api(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        if self.api_rate <= 0.0 {
+            return Ok(());
+        }
+        if self.buckets.len() > MAX_TRACKED_IPS {
+            self.buckets.retain(|_, bucket| now.saturating_duration_since(bucket.updated) < Duration::from_secs(60));
+        }
+
+        let mut bucket = self.buckets.entry(ip).or_insert(TokenBucket {
+            tokens: self.api_rate,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.api_rate).min(self.api_rate);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.api_rate))
+        }
+    }
+}
+
+/// Caps one connection's delivery at `cap`x realtime once its burst is out: audio
+/// lasting `d` takes at least `d / cap` to send. Time a slow reader spends not
+/// reading is not banked, so it can't be spent on a burst later.
+pub struct EgressShaper {
+    cap: f64, // 0 = unshaped
+    next_send: Option<Instant>,
+}
+
+impl EgressShaper {
+    pub fn new(cap: f64) -> Self {
+        Self { cap, next_send: None }
+    }
+
+    /// How long to wait at `now` before sending `duration_ms` of audio
+    pub fn delay(&mut self, now: Instant, duration_ms: f64) -> Duration {
+        if self.cap <= 0.0 {
+            return Duration::ZERO;
+        }
+        let start = self.next_send.map_or(now, |next| next.max(now));
+        self.next_send = Some(start + Duration::from_secs_f64(duration_ms / 1000.0 / self.cap));
+        start - now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_stream_permits() {
+        let limiter = IpLimiter::new(2, 0.0);
+        let a = limiter.try_acquire_stream(ip("203.0.113.1")).unwrap();
+        let _b = limiter.try_acquire_stream(ip("203.0.113.1")).unwrap();
+        assert!(limiter.try_acquire_stream(ip("203.0.113.1")).is_none());
+        assert!(limiter.try_acquire_stream(ip("203.0.113.2")).is_some());
+
+        drop(a);
+        assert_eq!(limiter.streams_for(ip("203.0.113.1")), 1);
+        assert!(limiter.try_acquire_stream(ip("203.0.113.1")).is_some());
+    }
+
+    #[test]
+    fn test_api_token_bucket() {
+        let limiter = IpLimiter::new(0, 2.0);
+        let client = ip("203.0.113.1");
+        let start = Instant::now();
+
+        assert!(limiter.check_api(client, start).is_ok());
+        assert!(limiter.check_api(client, start).is_ok());
+        let wait = limiter.check_api(client, start).unwrap_err();
+        assert!(wait <= Duration::from_millis(500));
+
+        // Half a second refills one request
+        assert!(limiter.check_api(client, start + Duration::from_millis(500)).is_ok());
+        assert!(limiter.check_api(ip("203.0.113.2"), start).is_ok());
+    }
+
+    #[test]
+    fn test_egress_shaper_caps_rate() {
+        let start = Instant::now();
+        let mut shaper = EgressShaper::new(2.0);
+        // A backlog of 100ms chunks goes out at 50ms intervals
+        assert_eq!(shaper.delay(start, 100.0), Duration::ZERO);
+        assert_eq!(shaper.delay(start, 100.0), Duration::from_millis(50));
+        assert_eq!(shaper.delay(start + Duration::from_millis(50), 100.0), Duration::from_millis(50));
+
+        // Idle time isn't banked
+        let later = start + Duration::from_secs(10);
+        assert_eq!(shaper.delay(later, 100.0), Duration::ZERO);
+        assert_eq!(shaper.delay(later, 100.0), Duration::from_millis(50));
+
+        let mut unshaped = EgressShaper::new(0.0);
+        assert!((0..10).all(|_| unshaped.delay(start, 100.0).is_zero()));
+    }
+
+    #[test]
+    fn test_churn_tarpit_then_ban() {
+        let limiter = IpLimiter::new(0, 0.0).with_churn(ChurnPolicy {
+            max_per_min: 2,
+            short: Duration::from_secs(10),
+            tarpit: Duration::from_secs(3),
+            ban: Duration::from_secs(300),
+        });
+        let client = ip("203.0.113.1");
+        let churn = |n: usize| (0..n).for_each(|_| drop(limiter.try_acquire_stream(client).unwrap()));
+
+        churn(2);
+        assert_eq!(limiter.check_churn(client, Instant::now()), ChurnVerdict::Allow);
+        churn(1);
+        assert_eq!(limiter.check_churn(client, Instant::now()), ChurnVerdict::Tarpit(Duration::from_secs(3)));
+        churn(2);
+        assert!(matches!(limiter.check_churn(client, Instant::now()), ChurnVerdict::Banned(left) if left > Duration::from_secs(290)));
+        assert_eq!(limiter.check_churn(ip("203.0.113.2"), Instant::now()), ChurnVerdict::Allow);
+
+        let status = limiter.churn_status(Instant::now());
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].short_streams, 5);
+
+        // Both the churn and the ban wear off
+        let later = Instant::now() + Duration::from_secs(301);
+        assert_eq!(limiter.check_churn(client, later), ChurnVerdict::Allow);
+        assert!(limiter.churn_status(later).is_empty());
+    }
+
+    #[test]
+    fn test_long_streams_are_not_churn() {
+        let limiter = IpLimiter::new(0, 0.0).with_churn(ChurnPolicy {
+            max_per_min: 1,
+            short: Duration::ZERO,
+            tarpit: Duration::from_secs(3),
+            ban: Duration::from_secs(300),
+        });
+        let client = ip("203.0.113.1");
+        (0..5).for_each(|_| drop(limiter.try_acquire_stream(client).unwrap()));
+        assert_eq!(limiter.check_churn(client, Instant::now()), ChurnVerdict::Allow);
+    }
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let limiter = IpLimiter::new(0, 0.0);
+        let client = ip("203.0.113.1");
+        let _permits: Vec<_> = (0..100).map(|_| limiter.try_acquire_stream(client).unwrap()).collect();
+        assert!((0..100).all(|_| limiter.check_api(client, Instant::now()).is_ok()));
+    }
+}
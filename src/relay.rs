@@ -0,0 +1,362 @@
+// Relay of an upstream Icecast/SHOUTcast or plain HTTP MP3 stream. ICY metadata
+// is requested so the upstream's StreamTitle can be passed through; the audio is
+// split back into whole MPEG frames and rebroadcast as-is. `IcyMuxer` does the
+// reverse for /stream listeners that ask for metadata.
+
+use std::{io, time::Duration};
+use bytes::{Buf, Bytes, BytesMut};
+use tokio::time::timeout;
+
+use crate::mp3::FrameHeader;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(10); // An upstream silent this long is treated as down
+const MAX_BUFFERED: usize = 64 * 1024;                  // Unsynced bytes kept while looking for a frame
+
+/// An upstream stream to relay
+#[derive(Debug, Clone)]
+pub struct RelaySource {
+    url: String,
+    http: reqwest::Client,
+}
+
+/// What one read from the upstream produced
+#[derive(Debug, Default)]
+pub struct RelayRead {
+    pub frames: Vec<(Bytes, f64)>, // Whole frames and their durations (ms)
+    pub title: Option<String>,     // StreamTitle, when the upstream sent new metadata
+}
+
+/// An open connection to the upstream
+pub struct RelayConnection {
+    response: reqwest::Response,
+    demuxer: IcyDemuxer,
+    frames: FrameAssembler,
+    pub name: Option<String>, // icy-name
+}
+
+impl RelaySource {
+    pub fn new(url: impl Into<String>) -> Self {
+        let http = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self { url: url.into(), http }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn connect(&self) -> io::Result<RelayConnection> {
+        let response = self.http.get(&self.url)
+            .header("Icy-MetaData", "1")
+            .send()
+            .await
+            .map_err(io::Error::other)?;
+        if !response.status().is_success() {
+            return Err(io::Error::other(format!("upstream returned {}", response.status())));
+        }
+
+        let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let metaint = header("icy-metaint").and_then(|v| v.trim().parse().ok()).filter(|&n: &usize| n > 0);
+        let name = header("icy-name").filter(|v| !v.trim().is_empty());
+        Ok(RelayConnection {
+            response,
+            demuxer: IcyDemuxer::new(metaint),
+            frames: FrameAssembler::default(),
+            name,
+        })
+    }
+}
+
+impl RelayConnection {
+    /// The next piece of the upstream; `Ok(None)` once it closes the stream
+    pub async fn read(&mut self) -> io::Result<Option<RelayRead>> {
+        let chunk = timeout(READ_TIMEOUT, self.response.chunk())
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "upstream stopped sending"))?
+            .map_err(io::Error::other)?;
+        let Some(chunk) = chunk else {
+            return Ok(None);
+        };
+
+        let mut audio = Vec::with_capacity(chunk.len());
+        let title = self.demuxer.push(&chunk, &mut audio);
+        self.frames.push(&audio);
+        let mut frames = Vec::new();
+        while let Some((header, frame)) = self.frames.next_frame() {
+            frames.push((frame, header.duration_ms()));
+        }
+        Ok(Some(RelayRead { frames, title }))
+    }
+}
+
+/// Separates ICY metadata blocks from the audio: after every `metaint` audio bytes
+/// comes a length byte (×16) and that many bytes of `StreamTitle='...';` text
+#[derive(Debug)]
+pub struct IcyDemuxer {
+    metaint: Option<usize>,
+    audio_left: usize,       // Audio bytes before the next metadata block
+    meta_left: Option<usize>, // Inside a metadata block: bytes still to read
+    meta: Vec<u8>,
+}
+
+impl IcyDemuxer {
+    /// `metaint` from the icy-metaint response header; `None` passes everything through
+    pub fn new(metaint: Option<usize>) -> Self {
+        Self { metaint, audio_left: metaint.unwrap_or(0), meta_left: None, meta: Vec::new() }
+    }
+
+    /// Append the audio in `data` to `audio`; returns the last title announced in it
+    pub fn push(&mut self, mut data: &[u8], audio: &mut Vec<u8>) -> Option<String> {
+        let Some(metaint) = self.metaint else {
+            audio.extend_from_slice(data);
+            return None;
+        };
+
+        let mut title = None;
+        while !data.is_empty() {
+            match self.meta_left {
+                None if self.audio_left > 0 => {
+                    let n = self.audio_left.min(data.len());
+                    audio.extend_from_slice(&data[..n]);
+                    self.audio_left -= n;
+                    data = &data[n..];
+                }
+                None => {
+                    self.meta_left = Some(data[0] as usize * 16);
+                    self.meta.clear();
+                    data = &data[1..];
+                }
+                Some(left) => {
+                    let n = left.min(data.len());
+                    self.meta.extend_from_slice(&data[..n]);
+                    data = &data[n..];
+                    self.meta_left = Some(left - n);
+                }
+            }
+            if self.meta_left == Some(0) {
+                // Empty blocks mean "unchanged"
+                if !self.meta.is_empty() {
+                    title = stream_title(&self.meta).or(title);
+                }
+                self.meta_left = None;
+                self.audio_left = metaint;
+            }
+        }
+        title
+    }
+}
+
+/// The reverse of `IcyDemuxer`, for listeners that send `Icy-MetaData: 1`: a
+/// metadata block after every `metaint` audio bytes, carrying the title when it
+/// changed since the last block and empty otherwise
+#[derive(Debug)]
+pub struct IcyMuxer {
+    metaint: usize,
+    audio_left: usize,          // Audio bytes before the next metadata block
+    sent_title: Option<String>, // Title in the last non-empty block
+}
+
+impl IcyMuxer {
+    pub fn new(metaint: usize) -> Self {
+        Self { metaint, audio_left: metaint, sent_title: None }
+    }
+
+    /// `data` with a metadata block at each `metaint` boundary it crosses; `title`
+    /// is what the audio is playing
+    pub fn push(&mut self, mut data: &[u8], title: &str) -> Bytes {
+        let mut out = BytesMut::with_capacity(data.len() + (data.len() / self.metaint + 1) * 64);
+        while !data.is_empty() {
+            let n = self.audio_left.min(data.len());
+            out.extend_from_slice(&data[..n]);
+            self.audio_left -= n;
+            data = &data[n..];
+            if self.audio_left == 0 {
+                if self.sent_title.as_deref() == Some(title) {
+                    out.extend_from_slice(&[0]);
+                } else {
+                    out.extend_from_slice(&metadata_block(title));
+                    self.sent_title = Some(title.to_string());
+                }
+                self.audio_left = self.metaint;
+            }
+        }
+        out.freeze()
+    }
+}
+
+fn metadata_block(title: &str) -> Vec<u8> {
+    let text = format!("StreamTitle='{}';", title.replace('\'', "’"));
+    let len = text.len().div_ceil(16).min(255);
+    let mut block = vec![len as u8];
+    block.extend(text.bytes().take(len * 16));
+    block.resize(1 + len * 16, 0);
+    block
+}
+
+/// The StreamTitle of an ICY metadata block
+pub fn stream_title(block: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(block);
+    let start = text.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &text[start..];
+    // Titles may contain quotes, so the value ends at the first `';`
+    let end = rest.find("';").unwrap_or_else(|| rest.trim_end_matches('\0').trim_end_matches('\'').len());
+    let title = rest[..end].trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// Split a relayed title into (artist, title) on the conventional " - "
+pub fn split_title(stream_title: &str) -> (Option<&str>, &str) {
+    match stream_title.split_once(" - ") {
+        Some((artist, title)) if !artist.trim().is_empty() && !title.trim().is_empty() => {
+            (Some(artist.trim()), title.trim())
+        }
+        _ => (None, stream_title),
+    }
+}
+
+/// Reassembles whole MPEG-1 Layer III frames from arbitrarily split bytes,
+/// skipping anything between frames (ID3 tags, junk after a reconnect)
+#[derive(Debug, Default)]
+pub struct FrameAssembler {
+    buf: BytesMut,
+}
+
+impl FrameAssembler {
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    pub fn next_frame(&mut self) -> Option<(FrameHeader, Bytes)> {
+        let mut offset = 0;
+        let found = loop {
+            if offset + 4 > self.buf.len() {
+                break None;
+            }
+            match FrameHeader::parse(&self.buf[offset..]) {
+                Some(header) => {
+                    let end = offset + header.frame_size();
+                    if end > self.buf.len() {
+                        break None; // Wait for the rest of the frame
+                    }
+                    // A sync word inside audio data is rarely followed by another header
+                    match self.buf.get(end..end + 4) {
+                        Some(next) if FrameHeader::parse(next).is_none() => offset += 1,
+                        _ => break Some((header, offset, end)),
+                    }
+                }
+                None => offset += 1,
+            }
+        };
+
+        match found {
+            Some((header, start, end)) => {
+                // Split off rather than copy: the frame keeps sharing the buffer's memory
+                self.buf.advance(start);
+                Some((header, self.buf.split_to(end - start).freeze()))
+            }
+            None => {
+                // Drop bytes that can no longer start a frame
+                let keep_from = offset.min(self.buf.len()).max(self.buf.len().saturating_sub(MAX_BUFFERED));
+                self.buf.advance(keep_from);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 128kbps, 44.1kHz, joint stereo, no CRC: 417-byte frames
+    const HEADER_128K: [u8; 4] = [0xFF, 0xFB, 0x90, 0x64];
+
+    fn frame() -> Vec<u8> {
+        let mut frame = vec![0u8; 417];
+        frame[..4].copy_from_slice(&HEADER_128K);
+        frame
+    }
+
+    #[test]
+    fn test_demuxer_strips_metadata() {
+        let mut demuxer = IcyDemuxer::new(Some(4));
+        let meta = b"StreamTitle='Artist - Song';";
+        let mut block = vec![meta.len().div_ceil(16) as u8];
+        block.extend_from_slice(meta);
+        block.resize(1 + block[0] as usize * 16, 0);
+
+        let mut stream = b"abcd".to_vec();
+        stream.extend_from_slice(&block);
+        stream.extend_from_slice(b"efgh\0ij");
+
+        // Fed one byte at a time to cross every boundary
+        let mut audio = Vec::new();
+        let mut titles = Vec::new();
+        for byte in stream.chunks(1) {
+            titles.extend(demuxer.push(byte, &mut audio));
+        }
+        assert_eq!(audio, b"abcdefghij");
+        assert_eq!(titles, vec!["Artist - Song".to_string()]);
+    }
+
+    #[test]
+    fn test_muxer_round_trips_title_changes() {
+        let audio: Vec<u8> = (0..20_000u32).map(|i| i as u8).collect();
+        let mut muxer = IcyMuxer::new(4096);
+        let mut muxed = muxer.push(&audio[..10_000], "Artist - First").to_vec();
+        let first_block = 4096 + 1 + 32;
+        assert_eq!(muxed[first_block + 4096], 0); // Unchanged title: empty block
+        muxed.extend_from_slice(&muxer.push(&audio[10_000..], "Artist - Second"));
+
+        let mut demuxer = IcyDemuxer::new(Some(4096));
+        let mut out = Vec::new();
+        assert_eq!(demuxer.push(&muxed[..first_block], &mut out).as_deref(), Some("Artist - First"));
+        assert_eq!(demuxer.push(&muxed[first_block..], &mut out).as_deref(), Some("Artist - Second"));
+        assert_eq!(out, audio);
+    }
+
+    #[test]
+    fn test_stream_title() {
+        assert_eq!(stream_title(b"StreamTitle='It's Here';StreamUrl='';").as_deref(), Some("It's Here"));
+        assert_eq!(stream_title(b"StreamTitle='';\0\0"), None);
+        assert_eq!(split_title("Artist - Song"), (Some("Artist"), "Song"));
+        assert_eq!(split_title("Live show"), (None, "Live show"));
+    }
+
+    #[test]
+    fn test_assembler_resyncs() {
+        let mut assembler = FrameAssembler::default();
+        let mut data = b"junk".to_vec();
+        data.extend(frame());
+        data.extend(frame());
+        data.extend(&frame()[..100]);
+
+        for piece in data.chunks(50) {
+            assembler.push(piece);
+        }
+        let mut frames = Vec::new();
+        while let Some((header, frame)) = assembler.next_frame() {
+            assert_eq!(header.bitrate_kbps, 128);
+            frames.push(frame);
+        }
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|f| f.len() == 417 && f[..4] == HEADER_128K));
+
+        // The partial third frame completes with the next read
+        assembler.push(&frame()[100..]);
+        assert!(assembler.next_frame().is_some());
+    }
+
+    #[test]
+    fn test_assembler_frames_share_the_buffer() {
+        let mut assembler = FrameAssembler::default();
+        assembler.push(&[frame(), frame()].concat());
+        let (_, first) = assembler.next_frame().unwrap();
+        let (_, second) = assembler.next_frame().unwrap();
+        // Split from one allocation, not copied out of it
+        assert_eq!(second.as_ptr(), first.as_ptr().wrapping_add(first.len()));
+    }
+}
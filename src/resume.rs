@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+
+/// Where recently disconnected listeners stopped, so a client that reconnects
+/// with its resume token (`/stream?resume=`) picks up from its last delivered
+/// chunk instead of rebuffering from live. Entries expire after the window.
+pub struct ResumePoints {
+    window: Duration,
+    points: DashMap<String, ResumePoint>, // Keyed by listener id
+}
+
+struct ResumePoint {
+    position_ms: f64, // Sync timeline position of the first undelivered audio
+    left_at: Instant,
+}
+
+impl ResumePoints {
+    pub fn new(window: Duration) -> Self {
+        Self { window, points: DashMap::new() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.window.is_zero()
+    }
+
+    pub fn record(&self, listener_id: &str, position_ms: f64, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.points.retain(|_, point| now.saturating_duration_since(point.left_at) <= self.window);
+        self.points.insert(listener_id.to_string(), ResumePoint { position_ms, left_at: now });
+    }
+
+    /// The position a listener left at, once: a token resumes a single reconnect
+    pub fn take(&self, listener_id: &str, now: Instant) -> Option<f64> {
+        let (_, point) = self.points.remove(listener_id)?;
+        (now.saturating_duration_since(point.left_at) <= self.window).then_some(point.position_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_within_window_once() {
+        let points = ResumePoints::new(Duration::from_secs(30));
+        let now = Instant::now();
+        points.record("a", 1500.0, now);
+
+        assert_eq!(points.take("a", now + Duration::from_secs(10)), Some(1500.0));
+        assert_eq!(points.take("a", now + Duration::from_secs(10)), None);
+        assert_eq!(points.take("unknown", now), None);
+    }
+
+    #[test]
+    fn test_expired_points_are_refused_and_pruned() {
+        let points = ResumePoints::new(Duration::from_secs(30));
+        let now = Instant::now();
+        points.record("old", 100.0, now);
+        points.record("late", 200.0, now);
+        assert_eq!(points.take("late", now + Duration::from_secs(31)), None);
+
+        points.record("new", 300.0, now + Duration::from_secs(60));
+        assert_eq!(points.points.len(), 1);
+    }
+
+    #[test]
+    fn test_disabled_records_nothing() {
+        let points = ResumePoints::new(Duration::ZERO);
+        points.record("a", 1.0, Instant::now());
+        assert_eq!(points.take("a", Instant::now()), None);
+    }
+}
@@ -0,0 +1,270 @@
+// Rotation rules: keep an artist from coming back within a few tracks and a
+// track within a few hours, and leave out tracks whose tags match an exclude
+// rule. The selector walks the playlist in order and pulls the first track that
+// satisfies the rules forward; the tracks it passed stay due.
+
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{config::Config, history::PlayRecord, playlist::Track};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RotationRules {
+    pub artist_separation: usize, // Other tracks between two by the same artist; 0 = off
+    pub track_separation_secs: u64, // Before a track may repeat; 0 = off
+    pub exclude: Vec<FieldRule>,  // Tracks matching any of these never play
+}
+
+impl RotationRules {
+    pub fn is_off(&self) -> bool {
+        self.artist_separation == 0 && self.track_separation_secs == 0 && self.exclude.is_empty()
+    }
+}
+
+/// `field=value` over `Track::field`: title, artist, album, isrc, composer, label
+/// or any tag frame (e.g. `TCON=Christmas`), compared case-insensitively
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldRule {
+    pub field: String,
+    pub value: String,
+}
+
+impl FieldRule {
+    pub fn parse(rule: &str) -> Option<Self> {
+        let (field, value) = rule.split_once('=')?;
+        let (field, value) = (field.trim(), value.trim());
+        (!field.is_empty() && !value.is_empty()).then(|| Self { field: field.to_string(), value: value.to_string() })
+    }
+
+    pub fn matches(&self, track: &Track) -> bool {
+        track.field(&self.field).is_some_and(|value| value.trim().eq_ignore_ascii_case(&self.value))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Aired {
+    path: PathBuf,
+    artist: Option<String>, // Normalized; None when untagged
+    at: u64,                // Unix seconds
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    recent: VecDeque<Aired>, // Oldest first
+    pulled: HashSet<Uuid>,   // Played ahead of their turn; passed over when the rotation reaches them
+}
+
+/// The play-history-aware next-track selector
+#[derive(Debug, Default)]
+pub struct Rotation {
+    rules: RotationRules,
+    state: Mutex<State>,
+}
+
+// "Unknown" is what the scanner writes for a missing artist tag; it is no artist in particular
+fn artist_key(artist: &str) -> Option<String> {
+    let artist = artist.trim().to_lowercase();
+    (!artist.is_empty() && artist != "unknown").then_some(artist)
+}
+
+impl Rotation {
+    pub fn new(rules: RotationRules) -> Self {
+        Self { rules, state: Mutex::default() }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(RotationRules {
+            artist_separation: config.artist_separation,
+            track_separation_secs: (config.track_separation_hours * 3600.0) as u64,
+            exclude: config.rotation_exclude.clone(),
+        })
+    }
+
+    pub fn rules(&self) -> &RotationRules {
+        &self.rules
+    }
+
+    /// Remember plays from before a restart, so the rules hold across it
+    pub fn seed(&self, records: &[PlayRecord]) {
+        let mut records: Vec<&PlayRecord> = records.iter().collect();
+        records.sort_by_key(|record| record.started_at);
+        let mut state = self.state.lock().unwrap();
+        for record in records {
+            self.push(&mut state, Aired { path: record.path.clone(), artist: artist_key(&record.artist), at: record.started_at });
+        }
+    }
+
+    /// Choose the next of `tracks` from rotation position `from` and record it as played
+    /// at `now`. Returns the track's index and the rotation position after it.
+    pub fn next(&self, tracks: &[Track], from: usize, now: u64) -> Option<(usize, usize)> {
+        let mut state = self.state.lock().unwrap();
+        self.select(&mut state, tracks, from, now)
+    }
+
+    /// The next `count` tracks as `next` would choose them, without recording anything
+    pub fn plan(&self, tracks: &[Track], from: usize, count: usize, now: u64) -> Vec<usize> {
+        let mut state = self.state.lock().unwrap().clone();
+        let mut from = from;
+        let mut planned = Vec::new();
+        while planned.len() < count {
+            match self.select(&mut state, tracks, from, now) {
+                Some((index, next)) if !planned.contains(&index) => {
+                    planned.push(index);
+                    from = next;
+                }
+                _ => break,
+            }
+        }
+        planned
+    }
+
+    fn select(&self, state: &mut State, tracks: &[Track], from: usize, now: u64) -> Option<(usize, usize)> {
+        let len = tracks.len();
+        if len == 0 {
+            return None;
+        }
+        let in_order = |state: &State| -> Vec<usize> {
+            (0..len)
+                .map(|offset| (from % len + offset) % len)
+                .filter(|&index| tracks[index].enabled && !state.pulled.contains(&tracks[index].id))
+                .filter(|&index| !self.rules.exclude.iter().any(|rule| rule.matches(&tracks[index])))
+                .collect()
+        };
+        let mut candidates = in_order(state);
+        if candidates.is_empty() {
+            // Everything left was pulled forward already; start the round over
+            state.pulled.clear();
+            candidates = in_order(state);
+        }
+        let due = *candidates.first()?;
+
+        // Both rules, else only the track rule, else the track that is due
+        let chosen = candidates.iter()
+            .find(|&&index| self.track_allowed(state, &tracks[index], now) && self.artist_allowed(state, &tracks[index]))
+            .or_else(|| candidates.iter().find(|&&index| self.track_allowed(state, &tracks[index], now)))
+            .copied()
+            .unwrap_or(due);
+
+        // Pulled tracks the rotation has now reached have had their turn
+        let mut index = from % len;
+        while index != due {
+            state.pulled.remove(&tracks[index].id);
+            index = (index + 1) % len;
+        }
+        let next = if chosen == due {
+            (due + 1) % len
+        } else {
+            state.pulled.insert(tracks[chosen].id);
+            due
+        };
+
+        let track = &tracks[chosen];
+        self.push(state, Aired { path: track.path.clone(), artist: artist_key(&track.artist), at: now });
+        Some((chosen, next))
+    }
+
+    fn track_allowed(&self, state: &State, track: &Track, now: u64) -> bool {
+        self.rules.track_separation_secs == 0 || !state.recent.iter().any(|aired| {
+            aired.path == track.path && aired.at + self.rules.track_separation_secs > now
+        })
+    }
+
+    fn artist_allowed(&self, state: &State, track: &Track) -> bool {
+        let Some(artist) = artist_key(&track.artist) else {
+            return true;
+        };
+        !state.recent.iter().rev().take(self.rules.artist_separation).any(|aired| aired.artist.as_ref() == Some(&artist))
+    }
+
+    // Keep the last `artist_separation` plays and everything within the track separation
+    fn push(&self, state: &mut State, aired: Aired) {
+        state.recent.push_back(aired);
+        let now = state.recent.back().map_or(0, |aired| aired.at);
+        while state.recent.len() > self.rules.artist_separation
+            && state.recent.front().is_some_and(|oldest| oldest.at + self.rules.track_separation_secs <= now)
+        {
+            state.recent.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str, artist: &str) -> Track {
+        Track {
+            path: PathBuf::from(format!("{}.mp3", title)),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn play_order(rotation: &Rotation, tracks: &[Track], plays: usize) -> Vec<String> {
+        let mut from = 0;
+        (0..plays as u64).map(|n| {
+            let (index, next) = rotation.next(tracks, from, n * 200).unwrap();
+            from = next;
+            tracks[index].title.clone()
+        }).collect()
+    }
+
+    #[test]
+    fn test_artist_separation_pulls_tracks_forward() {
+        let tracks = [track("A1", "Alpha"), track("A2", "alpha "), track("B1", "Beta"), track("C1", "Gamma")];
+        let rotation = Rotation::new(RotationRules { artist_separation: 1, ..Default::default() });
+        // A2 waits a track while B1 is pulled forward; B1 still plays once a round
+        assert_eq!(play_order(&rotation, &tracks, 8), ["A1", "B1", "A2", "C1", "A1", "B1", "A2", "C1"]);
+
+        let off = Rotation::default();
+        assert_eq!(play_order(&off, &tracks, 4), ["A1", "A2", "B1", "C1"]);
+    }
+
+    #[test]
+    fn test_rules_give_way_when_nothing_fits() {
+        let tracks = [track("A1", "Alpha"), track("A2", "Alpha"), track("U1", "Unknown"), track("U2", "Unknown")];
+        let rotation = Rotation::new(RotationRules { artist_separation: 3, ..Default::default() });
+        // Untagged tracks don't count as one artist; with only Alpha left, Alpha plays
+        assert_eq!(play_order(&rotation, &tracks, 4), ["A1", "U1", "U2", "A2"]);
+    }
+
+    #[test]
+    fn test_exclude_rules_match_tags_and_isrc() {
+        let mut tracks = [track("A", "Alpha"), track("B", "Beta"), track("C", "Gamma")];
+        tracks[0].tags.insert("TCON".to_string(), "Christmas".to_string());
+        tracks[2].isrc = Some("USRC17607839".to_string());
+        let rules = RotationRules {
+            exclude: ["tcon=christmas", "ISRC=USRC17607839", "nonsense"].into_iter().filter_map(FieldRule::parse).collect(),
+            ..Default::default()
+        };
+        assert_eq!(rules.exclude.len(), 2);
+        assert!(!rules.is_off());
+        let rotation = Rotation::new(rules);
+        assert_eq!(play_order(&rotation, &tracks, 3), ["B", "B", "B"]);
+        assert_eq!(rotation.plan(&tracks, 0, 3, 0), [1]);
+    }
+
+    #[test]
+    fn test_track_separation_survives_restart() {
+        let tracks = [track("A", "Alpha"), track("B", "Beta"), track("C", "Gamma")];
+        let rotation = Rotation::new(RotationRules { track_separation_secs: 3600, ..Default::default() });
+        rotation.seed(&[PlayRecord {
+            started_at: 0,
+            path: PathBuf::from("A.mp3"),
+            title: "A".to_string(),
+            artist: "Alpha".to_string(),
+            album: String::new(),
+            isrc: None,
+            composer: None,
+            label: None,
+            played_seconds: 200,
+            peak_listeners: 0,
+            listener_seconds: 0,
+        }]);
+        assert_eq!(rotation.plan(&tracks, 0, 3, 100), [1, 2, 0]);
+        assert_eq!(play_order(&rotation, &tracks, 3), ["B", "C", "A"]);
+    }
+}
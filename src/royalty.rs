@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use chrono::{Datelike, NaiveDate};
+
+use crate::history::PlayRecord;
+
+/// CSV layouts accepted by common royalty collection bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// SoundExchange Report of Use (census reporting, actual total performances)
+    SoundExchange,
+    /// PRS for Music online usage return
+    Prs,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "soundexchange" | "sx" => Ok(Self::SoundExchange),
+            "prs" => Ok(Self::Prs),
+            other => Err(format!("Unknown report format '{}' (expected soundexchange or prs)", other)),
+        }
+    }
+}
+
+impl ReportFormat {
+    pub fn file_suffix(&self) -> &'static str {
+        match self {
+            Self::SoundExchange => "soundexchange",
+            Self::Prs => "prs",
+        }
+    }
+}
+
+/// Aggregated usage of a single recording over the reporting period
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoyaltyLine {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub isrc: String,
+    pub composer: String,
+    pub label: String,
+    pub duration_seconds: u64,
+    pub play_count: u64,
+    pub total_performances: u64, // Sum of listeners reached by each play
+    pub listener_hours: f64,
+}
+
+/// Parse a `YYYY-MM` month into a `[start, end)` range of Unix timestamps (UTC)
+pub fn month_range(month: &str) -> Option<(u64, u64)> {
+    let (year, month) = month.split_once('-')?;
+    let start = NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?;
+    let end = if start.month() == 12 {
+        NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)?
+    };
+
+    let to_unix = |date: NaiveDate| date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc().timestamp());
+    let start = u64::try_from(to_unix(start)?).ok()?;
+    let end = u64::try_from(to_unix(end)?).ok()?;
+    Some((start, end))
+}
+
+/// Collapse individual plays into one line per recording, sorted by artist and title
+pub fn aggregate(records: &[PlayRecord]) -> Vec<RoyaltyLine> {
+    let mut lines: BTreeMap<(String, String, String, String), RoyaltyLine> = BTreeMap::new();
+
+    for record in records {
+        // Recordings with an ISRC are distinct even if their tags look identical
+        let isrc = record.isrc.clone().unwrap_or_default();
+        let key = (record.artist.clone(), record.title.clone(), record.album.clone(), isrc.clone());
+        let line = lines.entry(key).or_insert_with(|| RoyaltyLine {
+            title: record.title.clone(),
+            artist: record.artist.clone(),
+            album: record.album.clone(),
+            isrc,
+            composer: record.composer.clone().unwrap_or_default(),
+            label: record.label.clone().unwrap_or_default(),
+            duration_seconds: record.played_seconds,
+            play_count: 0,
+            total_performances: 0,
+            listener_hours: 0.0,
+        });
+
+        line.play_count += 1;
+        line.total_performances += record.peak_listeners as u64;
+        line.listener_hours += record.listener_seconds as f64 / 3600.0;
+        line.duration_seconds = line.duration_seconds.max(record.played_seconds);
+    }
+
+    lines.into_values().collect()
+}
+
+/// Render aggregated lines in the CSV layout expected by the given royalty body
+pub fn render_csv(format: ReportFormat, service_name: &str, lines: &[RoyaltyLine]) -> String {
+    let mut out = String::new();
+
+    match format {
+        ReportFormat::SoundExchange => {
+            out.push_str("NAME_OF_SERVICE,FEATURED_ARTIST,SOUND_RECORDING_TITLE,ISRC,ALBUM_TITLE,MARKETING_LABEL,ACTUAL_TOTAL_PERFORMANCES\r\n");
+            for line in lines {
+                let row = [
+                    csv_field(service_name),
+                    csv_field(&line.artist),
+                    csv_field(&line.title),
+                    csv_field(&line.isrc),
+                    csv_field(&line.album),
+                    csv_field(&line.label),
+                    line.total_performances.to_string(),
+                ];
+                out.push_str(&row.join(","));
+                out.push_str("\r\n");
+            }
+        }
+        ReportFormat::Prs => {
+            out.push_str("Title,Artist,Composer,Album,Label,ISRC,Duration,Usage Count,Listener Hours\r\n");
+            for line in lines {
+                let row = [
+                    csv_field(&line.title),
+                    csv_field(&line.artist),
+                    csv_field(&line.composer),
+                    csv_field(&line.album),
+                    csv_field(&line.label),
+                    csv_field(&line.isrc),
+                    format_duration(line.duration_seconds),
+                    line.play_count.to_string(),
+                    format!("{:.2}", line.listener_hours),
+                ];
+                out.push_str(&row.join(","));
+                out.push_str("\r\n");
+            }
+        }
+    }
+
+    out
+}
+
+// Quote fields containing separators, quotes or line breaks (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn play(title: &str, listeners: usize, listener_seconds: u64) -> PlayRecord {
+        PlayRecord {
+            started_at: 0,
+            path: PathBuf::from(format!("{}.mp3", title)),
+            title: title.to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            isrc: None,
+            composer: None,
+            label: None,
+            played_seconds: 200,
+            peak_listeners: listeners,
+            listener_seconds,
+        }
+    }
+
+    #[test]
+    fn test_month_range() {
+        let (start, end) = month_range("2025-09").unwrap();
+        assert_eq!(start, 1_756_684_800);
+        assert_eq!(end - start, 30 * 86_400);
+
+        let (start, end) = month_range("2025-12").unwrap();
+        assert_eq!(end - start, 31 * 86_400);
+
+        assert!(month_range("2025-13").is_none());
+        assert!(month_range("September").is_none());
+    }
+
+    #[test]
+    fn test_aggregate_sums_plays() {
+        let records = vec![play("Song", 3, 3600), play("Song", 1, 1800), play("Other", 0, 0)];
+        let lines = aggregate(&records);
+
+        assert_eq!(lines.len(), 2);
+        let song = lines.iter().find(|l| l.title == "Song").unwrap();
+        assert_eq!(song.play_count, 2);
+        assert_eq!(song.total_performances, 4);
+        assert!((song.listener_hours - 1.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_render_soundexchange_csv() {
+        let lines = aggregate(&[play("Hello, World", 5, 600)]);
+        let csv = render_csv(ReportFormat::SoundExchange, "WebRadio", &lines);
+
+        let mut rows = csv.lines();
+        assert!(rows.next().unwrap().starts_with("NAME_OF_SERVICE,"));
+        assert_eq!(rows.next().unwrap(), "WebRadio,Artist,\"Hello, World\",,Album,,5");
+    }
+
+    #[test]
+    fn test_render_prs_csv() {
+        let lines = aggregate(&[play("Song", 2, 7200)]);
+        let csv = render_csv(ReportFormat::Prs, "WebRadio", &lines);
+
+        let row = csv.lines().nth(1).unwrap();
+        assert_eq!(row, "Song,Artist,,Album,,,00:03:20,1,2.00");
+    }
+
+    #[test]
+    fn test_render_includes_isrc_and_label() {
+        let mut record = play("Tagged", 1, 60);
+        record.isrc = Some("GBAYE0601498".to_string());
+        record.composer = Some("Composer".to_string());
+        record.label = Some("Label".to_string());
+
+        let lines = aggregate(&[record]);
+        let sx = render_csv(ReportFormat::SoundExchange, "WebRadio", &lines);
+        assert_eq!(sx.lines().nth(1).unwrap(), "WebRadio,Artist,Tagged,GBAYE0601498,Album,Label,1");
+
+        let prs = render_csv(ReportFormat::Prs, "WebRadio", &lines);
+        assert_eq!(prs.lines().nth(1).unwrap(), "Tagged,Artist,Composer,Album,Label,GBAYE0601498,00:03:20,1,0.02");
+    }
+
+    #[test]
+    fn test_report_format_parsing() {
+        assert_eq!("SoundExchange".parse::<ReportFormat>().unwrap(), ReportFormat::SoundExchange);
+        assert_eq!("prs".parse::<ReportFormat>().unwrap(), ReportFormat::Prs);
+        assert!("ascap".parse::<ReportFormat>().is_err());
+    }
+}
@@ -0,0 +1,90 @@
+// In-memory library search behind /api/search: titles, artists and albums
+// folded to lowercase without diacritics, so "beyonce" finds "Beyoncé"
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::playlist::Track;
+
+/// Lowercase with diacritics stripped (decomposed, combining marks dropped)
+pub fn fold(text: &str) -> String {
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Folded fields of every track, in playlist order
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    entries: Vec<[String; 3]>, // Title, artist, album
+}
+
+impl SearchIndex {
+    pub fn build(tracks: &[Track]) -> Self {
+        Self {
+            entries: tracks.iter()
+                .map(|track| [fold(&track.title), fold(&track.artist), fold(&track.album)])
+                .collect(),
+        }
+    }
+
+    /// Positions of the tracks matching every word of `query`, best first: a
+    /// word starting a title word counts most, then artist, then album; ties
+    /// keep playlist order
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let words: Vec<String> = fold(query).split_whitespace().map(str::to_string).collect();
+        if words.is_empty() {
+            return Vec::new();
+        }
+        let mut hits: Vec<(u32, usize)> = self.entries.iter()
+            .enumerate()
+            .filter_map(|(position, fields)| {
+                let mut score = 0;
+                for word in &words {
+                    let word_score = fields.iter()
+                        .zip([6, 4, 2])
+                        .filter_map(|(field, weight)| match field.find(word.as_str())? {
+                            at if at == 0 || !field[..at].ends_with(char::is_alphanumeric) => Some(weight),
+                            _ => Some(weight / 2),
+                        })
+                        .max()?;
+                    score += word_score;
+                }
+                Some((score, position))
+            })
+            .collect();
+        hits.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        hits.into_iter().map(|(_, position)| position).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str, artist: &str, album: &str) -> Track {
+        Track { title: title.into(), artist: artist.into(), album: album.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_fold_strips_case_and_diacritics() {
+        assert_eq!(fold("Beyoncé"), "beyonce");
+        assert_eq!(fold("MÖTLEY CRÜE"), "motley crue");
+        assert_eq!(fold("Ｓigur Rós"), "sigur ros");
+    }
+
+    #[test]
+    fn test_search_needs_every_word_and_ranks_titles_first() {
+        let index = SearchIndex::build(&[
+            track("Halo", "Beyoncé", "I Am... Sasha Fierce"),
+            track("Crazy in Love", "Beyoncé", "Dangerously in Love"),
+            track("Love Song", "The Cure", "Disintegration"),
+            track("Glove", "Nobody", "Gloves"),
+        ]);
+        assert_eq!(index.search("beyonce"), vec![0, 1]);
+        assert_eq!(index.search("LOVE"), vec![1, 2, 3]);
+        assert_eq!(index.search("love beyoncé"), vec![1]);
+        assert!(index.search("  ").is_empty());
+        assert!(index.search("metallica").is_empty());
+    }
+}
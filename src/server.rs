@@ -0,0 +1,1955 @@
+use axum::{
+    Router,
+    middleware,
+    extract::{ConnectInfo, State, ws::{Message, WebSocket, WebSocketUpgrade}},
+    response::{Html, IntoResponse, Response, sse::{Event, KeepAlive, Sse}},
+    routing::{delete, get, get_service, patch, post, put},
+    http::{StatusCode, header},
+    Json,
+};
+use tower::ServiceExt;
+use tower_http::{
+    services::{ServeDir, ServeFile},
+    compression::{predicate::{NotForContentType, Predicate}, CompressionLayer, DefaultPredicate},
+    cors::{CorsLayer, Any},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use std::{net::{IpAddr, SocketAddr}, path::PathBuf, sync::Arc, time::Duration};
+use tracing::{debug, info, warn, Instrument};
+use tokio::signal;
+use futures::stream::{Stream, StreamExt};
+use uuid::Uuid;
+
+use crate::{
+    archive,
+    archiver,
+    audience,
+    bans::Ban,
+    beacon,
+    clienttest,
+    codec,
+    config::{ClientProfile, Codec, Config, LagPolicy, StreamClock},
+    error::AppError,
+    etag,
+    fingerprint,
+    intercom::{IntercomMember, IntercomMessage, Role},
+    monitor::Subsystem,
+    listen::ListenLink,
+    mqtt,
+    playlist,
+    preflight,
+    netif,
+    notify,
+    openapi,
+    profile,
+    radio::{RadioStation, StreamStart},
+    ratelimit::{ChurnStatus, ChurnVerdict, StreamPermit},
+    relay::IcyMuxer,
+    royalty,
+    tone,
+    watcher,
+    types::{Health, Listeners, NextUp, NowPlaying, PlaylistDto, SearchResults, SkipVote, StatsDto, TrackDto},
+};
+
+pub type AppState = Arc<RadioStation>;
+
+/// Audio bytes between ICY metadata blocks on /stream (Icecast's default)
+const ICY_METAINT: usize = 16000;
+
+/// Build a ready-to-serve application: loads the playlist, starts the broadcast
+/// and background publishers, and returns the router together with the station
+/// so callers can embed it in a larger app or stop it on shutdown
+pub async fn create_app(config: Config) -> crate::Result<(Router, Arc<RadioStation>)> {
+    // Check the environment before anything starts; with PREFLIGHT_STRICT a failed
+    // check stops startup, otherwise the station runs degraded and /readyz says why
+    let report = preflight::run(&config).await;
+    if !report.is_ready() {
+        if config.preflight_strict {
+            return Err(AppError::Preflight(report.failures()));
+        }
+        warn!("Starting in degraded mode: {}", report.failures());
+    }
+
+    // Create radio station
+    let station = Arc::new(RadioStation::new(config.clone()).await?);
+    station.set_preflight(report);
+
+    // Start the radio broadcast
+    Arc::clone(&station).start_broadcast();
+    station.start_metrics_sampler();
+    station.start_audience_recorder();
+    station.start_event_log();
+    station.public_ip().spawn_refresh();
+    if config.watch_music_dir {
+        if let Err(e) = watcher::spawn(station.clone()) {
+            warn!("Not watching {}: {}", config.music_dir.display(), e);
+        }
+    }
+
+    // Optional MQTT now-playing/health publisher
+    if let Some(publisher) = mqtt::MqttPublisher::from_config(&config) {
+        publisher.spawn(station.clone());
+    }
+
+    // Optional now-playing posts to Discord, Slack and Telegram
+    if let Some(notifier) = notify::Notifier::from_config(&config) {
+        notifier.spawn(station.clone());
+    }
+
+    // Optional re-encoded qualities on their own mounts
+    if station.simulcast().is_enabled() {
+        if let Err(e) = station.simulcast().spawn(station.subscribe().await) {
+            warn!("Failed to start simulcast encoder: {}", e);
+        }
+    }
+
+    // Optional non-MP3 formats for /stream
+    if station.codecs().is_enabled() {
+        station.codecs().spawn(station.subscribe().await);
+    }
+
+    // Optional continuous recording into the archive
+    if let Some(archiver) = archiver::Archiver::from_config(&config) {
+        archiver.spawn(station.clone());
+    }
+
+    let app = create_router(station.clone(), &config);
+    Ok((app, station))
+}
+
+pub fn create_router(state: AppState, _config: &Config) -> Router {
+    // Admin routes: reports, debugging and (later) anything that changes station state
+    let admin = Router::new()
+        .route("/debug", get(debug_info))
+        .route("/reports/royalty", get(royalty_report))
+        .route("/stream-token", post(mint_stream_token))
+        .route("/admin/profile", get(cpu_profile))
+        .route("/playlist/tracks", post(add_playlist_track).delete(remove_playlist_track))
+        .route("/playlist/order", put(reorder_playlist))
+        .route("/playlist/play-next", post(play_next))
+        .route("/playlist/enabled", put(set_track_enabled))
+        .route("/intercom", get(intercom_status))
+        .route("/admin/churn", get(churn_status))
+        .route("/admin/listeners", get(admin_listeners))
+        .route("/admin/listeners/:id", delete(kick_listener))
+        .route("/admin/bans", get(list_bans).post(add_ban))
+        .route("/admin/bans/:ip", delete(remove_ban))
+        .route("/admin/metadata", post(set_metadata_override).delete(clear_metadata_override))
+        .route("/admin/duplicates", get(duplicates_report))
+        .route("/admin/disabled", get(disabled_tracks))
+        .route("/admin/quarantine", get(quarantined_tracks))
+        .route("/admin/tracks/:id", patch(edit_track_tags))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
+
+    // JSON API, relative to its version prefix
+    let api = Router::new()
+        .route("/now-playing", get(now_playing))
+        .route("/listeners", get(listener_count))
+        .route("/playlist", get(get_playlist))
+        .route("/next-up", get(next_up))
+        .route("/search", get(search))
+        .route("/stats", get(get_stats))
+        .route("/stats/timeseries", get(stats_timeseries))
+        .route("/metrics", get(get_metrics))
+        .route("/metrics/history", get(metrics_history))
+        .route("/health", get(health_check))
+        .route("/server-info", get(server_info))
+        .route("/archive", get(list_archive))
+        .route("/archive/:id/chapters", get(archive_chapters))
+        .route("/tracks/:id/audio", get(track_audio))
+        .route("/tracks/:id/artwork", get(track_artwork))
+        .route("/sync", get(sync_time))
+        .route("/vote-skip", post(vote_skip))
+        .route("/beacon/session", get(beacon_session))
+        .route("/beacon", post(receive_beacon))
+        .route("/telemetry", post(receive_telemetry))
+        .route("/openapi.json", get(openapi_spec))
+        .route("/docs", get(api_docs))
+        .merge(admin)
+        // gzip/br when the client accepts it; track audio goes out as stored so
+        // Range requests keep working. /stream, /ws and /events are outside the API
+        .layer(CompressionLayer::new()
+            .compress_when(DefaultPredicate::new().and(NotForContentType::const_new("audio/"))));
+
+    // One route per simulcast mount: /stream-low, /stream-high, ...
+    let mut simulcast = Router::new();
+    for mount in state.simulcast().mounts() {
+        let name = mount.name().to_string();
+        simulcast = simulcast.route(&mount.path(), get(
+            move |State(station): State<AppState>,
+                  connect_info: Option<ConnectInfo<SocketAddr>>,
+                  headers: axum::http::HeaderMap,
+                  query: axum::extract::Query<std::collections::HashMap<String, String>>| {
+                simulcast_stream(station, name.clone(), connect_info, headers, query)
+            },
+        ));
+    }
+
+    Router::new()
+        // Main routes
+        .route("/", get(index))
+        .route("/stream", get(audio_stream))
+        .route("/stream.ogg", get(ogg_stream))
+        .route("/ws", get(ws_stream))
+        .route("/listen.m3u", get(listen_m3u))
+        .route("/listen.pls", get(listen_pls))
+        .route("/test-audio", get(test_audio))
+        .route("/debug/client-test", get(client_test_run))
+        .route("/debug/client-test/:run", get(client_test_results))
+        .route("/debug/client-test/:run/:case", get(client_test_stream).post(client_test_report))
+        .route("/archive/:id/stream", get(archive_stream))
+        .route("/intercom/:role", get(intercom_connect))
+        .route("/events", get(sse_events))
+        .route("/readyz", get(readyz))
+        .route("/healthz", get(healthz))
+        .route("/status-json.xsl", get(icecast_status_json))
+        .route("/admin/stats", get(icecast_admin_stats)
+            .route_layer(middleware::from_fn_with_state(state.clone(), require_admin)))
+        .merge(simulcast)
+        
+        // API routes: /api/v1 is the current version; the unversioned /api paths
+        // are a compatibility alias that stays on v1 when a later version changes
+        // response shapes
+        .nest(openapi::API_PREFIX, api.clone())
+        .nest("/api", api.layer(middleware::from_fn(legacy_api)))
+        .route_layer(middleware::from_fn_with_state(state.clone(), time_api))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit_api))
+        
+        // Static files
+        .nest_service(
+            "/static",
+            get_service(ServeDir::new("static"))
+                .handle_error(|_| async { StatusCode::NOT_FOUND }),
+        )
+        
+        // Add middleware
+        .layer(CorsLayer::new()
+            .allow_origin(Any)
+            .allow_headers([header::IF_NONE_MATCH])
+            // Let browser players read the stream's own headers
+            .expose_headers([
+                axum::http::HeaderName::from_static("x-listener-id"),
+                axum::http::HeaderName::from_static("x-buffer-hint"),
+                axum::http::HeaderName::from_static("x-resume-token"),
+                axum::http::HeaderName::from_static("x-resumed"),
+                header::ETAG,
+                axum::http::HeaderName::from_static("x-request-id"),
+            ]))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .with_state(state)
+}
+
+// Span for everything a request logs, listener lifecycle included, keyed by its
+// X-Request-Id: generated unless a proxy already set one, and sent back on the
+// response so a listener's report can be matched to the server logs. The query
+// is left out as it can carry stream and resume tokens.
+fn request_span(request: &axum::http::Request<axum::body::Body>) -> tracing::Span {
+    let request_id = request.headers().get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!("request", id = %request_id, method = %request.method(), path = %request.uri().path())
+}
+
+pub async fn shutdown_signal(station: AppState) {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {
+            info!("Received CTRL+C signal, initiating graceful shutdown");
+        },
+        _ = terminate => {
+            info!("Received terminate signal, initiating graceful shutdown");
+        },
+    }
+
+    // Stop the broadcast explicitly
+    station.stop_broadcast().await;
+
+    // Force exit after a short grace period
+    tokio::spawn(async {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        info!("Forcing exit...");
+        std::process::exit(0);
+    });
+}
+
+// Route handlers
+
+async fn index() -> Html<&'static str> {
+    Html(include_str!("../templates/index.html"))
+}
+
+/// Listener address: the socket peer, or the X-Forwarded-For hop the trusted
+/// proxies recorded when the server is configured to sit behind them. IPv4
+/// clients of the dual-stack listener arrive as v4-mapped IPv6 and are reported
+/// as IPv4.
+fn client_ip(station: &RadioStation, headers: &axum::http::HeaderMap, peer: Option<SocketAddr>) -> Option<IpAddr> {
+    let config = station.config();
+    if config.trust_forwarded_for {
+        let forwarded = headers.get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| forwarded_client(v, config.trusted_proxy_hops));
+        if let Some(ip) = forwarded {
+            return Some(IpAddr::to_canonical(&ip));
+        }
+    }
+    peer.map(|addr| addr.ip().to_canonical())
+}
+
+// Each proxy appends the address it received the request from, so entries left
+// of the last `hops` were written by the client and prove nothing. A shorter
+// list than expected means a proxy was skipped; its leftmost entry is the best
+// we have.
+fn forwarded_client(header: &str, hops: usize) -> Option<IpAddr> {
+    let entries: Vec<&str> = header.split(',').map(str::trim).collect();
+    let index = entries.len().saturating_sub(hops.max(1));
+    entries[index].parse().ok()
+}
+
+// Admin routes need the configured token (`Authorization: Bearer` or `X-API-Key`);
+// with no token configured they are refused, unless ADMIN_LOCALHOST allows local clients
+async fn require_admin(
+    State(station): State<AppState>,
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> Result<Response, AppError> {
+    if !is_admin(&station, &request) {
+        info!("Rejected unauthorized request to {}", request.uri().path());
+        return Err(AppError::Unauthorized);
+    }
+    Ok(next.run(request).await)
+}
+
+fn is_admin(station: &RadioStation, request: &axum::extract::Request) -> bool {
+    let auth = station.admin_auth();
+    if auth.is_configured() {
+        auth.check(request.headers())
+    } else {
+        let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| *addr);
+        local_admin(station, peer)
+    }
+}
+
+// Tokenless admin access with ADMIN_LOCALHOST: the socket peer must be loopback.
+// X-Forwarded-For never counts, and behind a reverse proxy every peer is local,
+// so the option is for servers reached directly.
+fn local_admin(station: &RadioStation, peer: Option<SocketAddr>) -> bool {
+    station.config().admin_localhost && peer.is_some_and(|addr| addr.ip().to_canonical().is_loopback())
+}
+
+// Per-IP request rate limit for the API (API_RATE_LIMIT requests/sec); other
+// routes pass straight through
+async fn rate_limit_api(
+    State(station): State<AppState>,
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> Result<Response, AppError> {
+    if request.uri().path().starts_with("/api/") {
+        let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| *addr);
+        if let Some(ip) = client_ip(&station, request.headers(), peer) {
+            if let Err(wait) = station.ip_limiter().check_api(ip, std::time::Instant::now()) {
+                info!("Rate limiting API requests from {}", ip);
+                return Err(AppError::RateLimited {
+                    message: "API request rate limit exceeded".to_string(),
+                    retry_after_secs: wait.as_secs_f64().ceil().max(1.0) as u64,
+                });
+            }
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+// Unversioned /api routes answer as v1 and point at their versioned successor
+async fn legacy_api(request: axum::extract::Request, next: middleware::Next) -> Response {
+    // Inside the /api nest the path arrives without its prefix
+    let successor = format!("<{}{}>; rel=\"successor-version\"", openapi::API_PREFIX, request.uri().path());
+    let mut response = next.run(request).await;
+    if let Ok(value) = header::HeaderValue::from_str(&successor) {
+        response.headers_mut().insert(header::LINK, value);
+    }
+    response
+}
+
+// Count API handler time (wall time, including awaits) against the `api` subsystem
+async fn time_api(
+    State(station): State<AppState>,
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> Response {
+    if !request.uri().path().starts_with("/api/") {
+        return next.run(request).await;
+    }
+    let started = std::time::Instant::now();
+    let response = next.run(request).await;
+    station.monitor().record(Subsystem::Api, started.elapsed());
+    response
+}
+
+async fn audio_stream(
+    State(station): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let permit = check_stream_access(&station, &headers, connect_info, &query).await?;
+    let (profile, clock, lag) = select_profile(&station, &headers, &query)?;
+    let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok());
+    let codec = codec::negotiate(accept, query.get("codec").map(String::as_str), &station.codecs().available())?;
+    if codec != Codec::Mp3 {
+        return codec_stream(&station, codec, permit, &headers);
+    }
+
+    // Log request details to debug multiple connections
+    let user_agent = headers.get("user-agent")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown");
+    let range = headers.get("range")
+        .and_then(|v| v.to_str().ok());
+    let client_type = query.get("type").map(|s| s.as_str()).unwrap_or("unknown");
+    let is_ios = profile == ClientProfile::Ios;
+
+    // Check if this is Safari doing its probe
+    let is_safari = user_agent.contains("Safari") && !user_agent.contains("Chrome");
+
+    info!("New audio stream request from: {} (type: {}, range: {:?}, safari: {}, ios: {})",
+        user_agent, client_type, range, is_safari, is_ios);
+
+    // For range requests from Safari, we need to handle them specially
+    // Safari won't play the stream unless we respond to its range probe
+    if let Some(range_header) = range {
+        if range_header == "bytes=0-1" {
+            // Safari's initial probe - send a small response
+            info!("Handling Safari probe request");
+            return Ok(Response::builder()
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_TYPE, "audio/mpeg")
+                .header("Content-Range", "bytes 0-1/999999999")
+                .header("Accept-Ranges", "bytes")
+                .header(header::CONTENT_LENGTH, "2")
+                .body(axum::body::Body::from(vec![0xFF, 0xFB]))?);  // MP3 sync bytes
+        }
+        // For other range requests, just stream normally
+        info!("Converting range request to normal stream");
+    }
+
+    let start = stream_start(&station, &query)?;
+    let buffer_hint = station.buffer_hint_secs(profile);
+    let (listener_id, stream) = station.create_audio_stream(profile, clock, lag, start).await?;
+    let resume_token = station.resume_token(&listener_id);
+    station.identify_listener(&listener_id, permit.as_ref().map(StreamPermit::ip), headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok()));
+    if let Some(permit) = &permit {
+        station.locate_listener(&listener_id, permit.ip());
+    }
+    // StreamTitle follows the track this listener is hearing, or the override
+    let icy_metaint = wants_icy_metadata(&headers).then_some(ICY_METAINT);
+    let mut icy = icy_metaint.map(IcyMuxer::new);
+    let (titles, heard_by) = (Arc::clone(&station), listener_id.clone());
+    // The per-IP slot is held for as long as the body stream lives
+    let stream = stream.map(move |chunk| {
+        let _permit = &permit;
+        match (&mut icy, chunk) {
+            (Some(muxer), Ok(data)) => Ok(muxer.push(&data, &titles.stream_title_for(Some(&heard_by)))),
+            (_, chunk) => chunk,
+        }
+    });
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "audio/mpeg")
+        .header(header::CACHE_CONTROL, "no-cache, no-store, must-revalidate")
+        .header(header::CONNECTION, "close")
+        .header("X-Content-Type-Options", "nosniff")
+        .header("Accept-Ranges", "none")
+        .header("Transfer-Encoding", "chunked")
+        .header(header::VARY, "Accept")
+        .header("X-Listener-Id", listener_id)
+        .header("X-Buffer-Hint", format!("{:.1}", buffer_hint))
+        .header("X-Resumed", matches!(start, StreamStart::Resume(_)).to_string());
+    if let Some(token) = resume_token {
+        response = response.header("X-Resume-Token", token);
+    }
+    if let Some(metaint) = icy_metaint {
+        response = response.header("icy-metaint", metaint.to_string());
+    }
+    for (name, value) in icy_headers(&station, &headers) {
+        response = response.header(name, value);
+    }
+    Ok(response.body(axum::body::Body::from_stream(stream))?)
+}
+
+// Ogg/Opus without negotiation, for <audio> elements and players that can't send
+// Accept; 404 unless opus is in STREAM_CODECS
+async fn ogg_stream(
+    State(station): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    if station.codecs().output(Codec::Opus).is_none() {
+        return Err(AppError::NotFound);
+    }
+    let permit = check_stream_access(&station, &headers, connect_info, &query).await?;
+    codec_stream(&station, Codec::Opus, permit, &headers)
+}
+
+// /stream negotiated to a format other than MP3: the shared encoder output for that
+// codec, with no burst profiles or timeshift (as for simulcast mounts)
+fn codec_stream(
+    station: &RadioStation,
+    codec: Codec,
+    permit: Option<StreamPermit>,
+    headers: &axum::http::HeaderMap,
+) -> Result<Response, AppError> {
+    let output = station.codecs().output(codec).cloned().ok_or(AppError::NotFound)?;
+    station.check_listener_capacity()?;
+
+    info!("New {} listener ({}kbps, {} on this codec)", codec.name(), output.bitrate_kbps(), output.listener_count() + 1);
+    let stream = output.listen().map(move |data| {
+        let _permit = &permit;
+        Ok::<_, AppError>(data)
+    });
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, codec.content_type())
+        .header(header::CACHE_CONTROL, "no-cache, no-store, must-revalidate")
+        .header(header::VARY, "Accept")
+        .header("X-Content-Type-Options", "nosniff")
+        .header("Accept-Ranges", "none");
+    for (name, value) in icy_headers(station, headers) {
+        let value = if name == "icy-br" { output.bitrate_kbps().to_string() } else { value };
+        response = response.header(name, value);
+    }
+    Ok(response.body(axum::body::Body::from_stream(stream))?)
+}
+
+// A simulcast mount: the broadcast re-encoded at the mount's bitrate. Same access
+// rules and listener cap as /stream, without /stream's burst profiles and timeshift.
+async fn simulcast_stream(
+    station: AppState,
+    name: String,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let mount = station.simulcast().mount(&name).cloned().ok_or(AppError::NotFound)?;
+    let permit = check_stream_access(&station, &headers, connect_info, &query).await?;
+    station.check_listener_capacity()?;
+
+    info!("New simulcast listener on {} ({}kbps, {} on this mount)", mount.path(), mount.bitrate_kbps(), mount.listener_count() + 1);
+    let stream = mount.listen().map(move |data| {
+        let _permit = &permit;
+        Ok::<_, AppError>(data)
+    });
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "audio/mpeg")
+        .header(header::CACHE_CONTROL, "no-cache, no-store, must-revalidate")
+        .header("X-Content-Type-Options", "nosniff")
+        .header("Accept-Ranges", "none");
+    for (name, value) in icy_headers(&station, &headers) {
+        let value = if name == "icy-br" { mount.bitrate_kbps().to_string() } else { value };
+        response = response.header(name, value);
+    }
+    Ok(response.body(axum::body::Body::from_stream(stream))?)
+}
+
+async fn check_stream_access(
+    station: &RadioStation,
+    headers: &axum::http::HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    query: &std::collections::HashMap<String, String>,
+) -> Result<Option<StreamPermit>, AppError> {
+    check_stream_token(station, query)?;
+
+    let Some(ip) = client_ip(station, headers, connect_info.map(|ConnectInfo(addr)| addr)) else {
+        return Ok(None);
+    };
+    if station.bans().is_banned(ip) {
+        info!("Refusing stream to {}: banned", ip);
+        return Err(AppError::Forbidden);
+    }
+    if let Err(reason) = station.check_stream_access(ip) {
+        info!("Refusing stream to {}: {}", ip, reason);
+        return Err(AppError::UnavailableForLegalReasons(reason));
+    }
+    match station.ip_limiter().check_churn(ip, std::time::Instant::now()) {
+        ChurnVerdict::Allow => {}
+        ChurnVerdict::Tarpit(delay) => {
+            debug!("Tarpitting stream request from {} for {}ms", ip, delay.as_millis());
+            tokio::time::sleep(delay).await;
+        }
+        ChurnVerdict::Banned(left) => {
+            info!("Refusing stream to {}: banned for connection churn", ip);
+            return Err(AppError::RateLimited {
+                message: "Too many short-lived connections from this address".to_string(),
+                retry_after_secs: left.as_secs_f64().ceil().max(1.0) as u64,
+            });
+        }
+    }
+    match station.ip_limiter().try_acquire_stream(ip) {
+        Some(permit) => Ok(Some(permit)),
+        None => {
+            info!("Refusing stream to {}: too many simultaneous streams", ip);
+            Err(AppError::RateLimited {
+                message: "Too many simultaneous streams from this address".to_string(),
+                retry_after_secs: station.config().listener_retry_after_secs,
+            })
+        }
+    }
+}
+
+/// With REQUIRE_SIGNED_STREAMS, audio URLs need `expires` and `token` (and `user`
+/// when the token was minted for one) so private streams can't be hotlinked
+fn check_stream_token(station: &RadioStation, query: &std::collections::HashMap<String, String>) -> Result<(), AppError> {
+    if !station.config().require_signed_streams {
+        return Ok(());
+    }
+    if !valid_stream_token(station, query) {
+        info!("Refusing stream with missing, invalid or expired token");
+        return Err(AppError::Forbidden);
+    }
+    Ok(())
+}
+
+// `expires`, `token` (and `user`) from /api/stream-token, unexpired
+fn valid_stream_token(station: &RadioStation, query: &std::collections::HashMap<String, String>) -> bool {
+    let expires = query.get("expires").and_then(|v| v.parse::<u64>().ok());
+    match (query.get("token"), expires) {
+        (Some(token), Some(expires)) => {
+            let now = chrono::Utc::now().timestamp() as u64;
+            station.signer().verify_stream_token(token, expires, query.get("user").map(String::as_str), now)
+        }
+        _ => false,
+    }
+}
+
+/// Burst profile from `?type=` or the user agent (see `Config::profile_for`); clock and lag policy from
+/// `?clock=` and `?lag=`, or the profile
+fn select_profile(
+    station: &RadioStation,
+    headers: &axum::http::HeaderMap,
+    query: &std::collections::HashMap<String, String>,
+) -> Result<(ClientProfile, StreamClock, LagPolicy), AppError> {
+    let user_agent = headers.get("user-agent")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown");
+    let profile = station.config().profile_for(query.get("type").map(String::as_str), user_agent);
+    let clock = match query.get("clock") {
+        Some(clock) => clock.parse::<StreamClock>().map_err(AppError::BadRequest)?,
+        None => station.config().burst(profile).clock,
+    };
+    let lag = match query.get("lag") {
+        Some(lag) => lag.parse::<LagPolicy>().map_err(AppError::BadRequest)?,
+        None => station.config().burst(profile).lag,
+    };
+    Ok((profile, clock, lag))
+}
+
+/// `?resume=<token>` picks up where a dropped connection left off; an expired or
+/// already used token falls through to `?rewind=<seconds>` (timeshift) or live
+fn stream_start(station: &RadioStation, query: &std::collections::HashMap<String, String>) -> Result<StreamStart, AppError> {
+    if let Some(token) = query.get("resume") {
+        match station.resume_position(token) {
+            Some(position_ms) => return Ok(StreamStart::Resume(position_ms)),
+            None => debug!("Resume token not accepted, starting a new session"),
+        }
+    }
+    match query.get("rewind") {
+        None => Ok(StreamStart::Live),
+        Some(value) => match value.parse::<f64>() {
+            Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(StreamStart::Rewind(seconds * 1000.0)),
+            _ => Err(AppError::BadRequest(format!("Invalid rewind: {}", value))),
+        },
+    }
+}
+
+// WebSocket variant of /stream for clients that can't consume chunked HTTP:
+// binary frames carry MP3 data, text frames carry now-playing JSON
+async fn ws_stream(
+    ws: WebSocketUpgrade,
+    State(station): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let permit = check_stream_access(&station, &headers, connect_info, &query).await?;
+    let selection = select_profile(&station, &headers, &query)?;
+    let start = stream_start(&station, &query)?;
+    station.check_listener_capacity()?;
+    let user_agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok()).map(str::to_string);
+
+    let (profile, clock, lag) = selection;
+    info!("New WebSocket stream request (profile: {}, clock: {}, lag: {})", profile.name(), clock.name(), lag.name());
+    // The session outlives the request; keep logging it under the request's id
+    let span = tracing::Span::current();
+    Ok(ws.on_upgrade(move |socket| ws_session(socket, station, selection, start, permit, user_agent).instrument(span)))
+}
+
+async fn ws_session(
+    mut socket: WebSocket,
+    station: AppState,
+    (profile, clock, lag): (ClientProfile, StreamClock, LagPolicy),
+    start: StreamStart,
+    permit: Option<StreamPermit>,
+    user_agent: Option<String>,
+) {
+    let (listener_id, stream) = match station.create_audio_stream(profile, clock, lag, start).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!("Failed to start WebSocket stream: {}", e);
+            return;
+        }
+    };
+    station.identify_listener(&listener_id, permit.as_ref().map(StreamPermit::ip), user_agent.as_deref());
+    if let Some(permit) = &permit {
+        station.locate_listener(&listener_id, permit.ip());
+    }
+    let mut stream = Box::pin(stream);
+    let resume_token = station.resume_token(&listener_id);
+
+    let now_playing_frame = |now_playing: &NowPlaying| {
+        let mut json = serde_json::to_value(now_playing).unwrap_or_default();
+        json["type"] = "now-playing".into();
+        json["listener_id"] = listener_id.clone().into();
+        json["resume_token"] = resume_token.clone().into();
+        json["resumed"] = matches!(start, StreamStart::Resume(_)).into();
+        Message::Text(json.to_string())
+    };
+    // Follow the generation of the audio this socket has been sent, so title changes
+    // arrive with the new track's audio rather than ahead of the burst
+    let mut now_playing = station.get_now_playing_for(Some(&listener_id));
+    let mut current_track = now_playing.generation;
+    let mut connected = socket.send(now_playing_frame(&now_playing)).await.is_ok();
+
+    // Sent as soon as this socket's audio reaches a new track, with a full refresh every 5s like /events
+    let mut track_changes = station.track_changes_for(Some(&listener_id));
+    // and as soon as the metadata override is set or cleared
+    let mut metadata = station.metadata_changes();
+    let refresh_every = Duration::from_secs(5);
+    let mut refresh = tokio::time::interval_at(tokio::time::Instant::now() + refresh_every, refresh_every);
+    let ping_every = Duration::from_secs(station.config().ws_ping_interval_secs.max(1));
+    let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + ping_every, ping_every);
+    let mut awaiting_pong = false;
+
+    while connected {
+        tokio::select! {
+            chunk = stream.next() => match chunk {
+                // axum 0.7's Message::Binary owns a Vec, so this is the one per-listener copy left
+                Some(Ok(data)) => connected = socket.send(Message::Binary(data.to_vec())).await.is_ok(),
+                Some(Err(e)) => {
+                    warn!("WebSocket stream error for {}: {}", &listener_id[..8], e);
+                    connected = false;
+                }
+                None => connected = false,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Pong(_))) => awaiting_pong = false,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => connected = false,
+                Some(Ok(_)) => {} // Pings are answered automatically; other client messages are ignored
+            },
+            Ok(()) = track_changes.changed() => {
+                now_playing = station.get_now_playing_for(Some(&listener_id));
+                if now_playing.generation != current_track {
+                    current_track = now_playing.generation;
+                    connected = socket.send(now_playing_frame(&now_playing)).await.is_ok();
+                }
+            },
+            Ok(()) = metadata.changed() => {
+                now_playing = station.get_now_playing_for(Some(&listener_id));
+                current_track = now_playing.generation;
+                connected = socket.send(now_playing_frame(&now_playing)).await.is_ok();
+            },
+            _ = refresh.tick() => {
+                now_playing = station.get_now_playing_for(Some(&listener_id));
+                current_track = now_playing.generation;
+                connected = socket.send(now_playing_frame(&now_playing)).await.is_ok();
+            },
+            _ = ping.tick() => {
+                if awaiting_pong {
+                    info!("WebSocket listener {} missed a pong, disconnecting", &listener_id[..8]);
+                    connected = false;
+                } else {
+                    awaiting_pong = true;
+                    connected = socket.send(Message::Ping(Vec::new())).await.is_ok();
+                }
+            },
+        }
+    }
+}
+
+// Base URL listeners reach the server at: PUBLIC_URL, else the Host the request
+// came in on (behind a trusted proxy, with its X-Forwarded-Proto), else the
+// discovered public address
+fn public_base_url(station: &RadioStation, headers: &axum::http::HeaderMap) -> String {
+    let config = station.config();
+    if let Some(url) = &config.public_url {
+        return url.trim_end_matches('/').to_string();
+    }
+    let mut scheme = if config.tls_cert_path.is_some() { "https" } else { "http" };
+    if config.trust_forwarded_for {
+        match headers.get("x-forwarded-proto").and_then(|v| v.to_str().ok()) {
+            Some("https") => scheme = "https",
+            Some("http") => scheme = "http",
+            _ => {}
+        }
+    }
+    let host = headers.get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .filter(|host| !host.is_empty() && host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']')));
+    match (host, station.public_ip().current()) {
+        (Some(host), _) => format!("{}://{}", scheme, host),
+        (None, Some(addr)) => netif::url(scheme, addr.ip, config.port),
+        (None, None) => format!("{}://localhost:{}", scheme, config.port),
+    }
+}
+
+fn listen_link(station: &RadioStation, headers: &axum::http::HeaderMap) -> ListenLink {
+    ListenLink {
+        stream_url: format!("{}/stream", public_base_url(station, headers)),
+        station_name: station.config().station_name.clone(),
+        bitrate_kbps: station.bitrate_kbps(),
+    }
+}
+
+// Players that can show titles ask for interleaved metadata with `Icy-MetaData: 1`
+fn wants_icy_metadata(headers: &axum::http::HeaderMap) -> bool {
+    headers.get("icy-metadata").and_then(|v| v.to_str().ok()).map(str::trim) == Some("1")
+}
+
+/// ICY (SHOUTcast/Icecast) station headers, so players and directory services
+/// can name and classify the stream
+fn icy_headers(station: &RadioStation, headers: &axum::http::HeaderMap) -> Vec<(&'static str, String)> {
+    let config = station.config();
+    // Sent as raw UTF-8 like Icecast does; only control characters are invalid
+    let clean = |value: &str| value.chars().filter(|c| !c.is_control()).collect::<String>();
+    let mut icy = vec![
+        ("icy-name", clean(&config.station_name)),
+        ("icy-genre", clean(&config.station_genre)),
+        ("icy-br", station.bitrate_kbps().to_string()),
+        ("icy-url", clean(&config.station_url.clone().unwrap_or_else(|| public_base_url(station, headers)))),
+        ("icy-pub", if config.station_public { "1" } else { "0" }.to_string()),
+    ];
+    if let Some(description) = &config.station_description {
+        icy.push(("icy-description", clean(description)));
+    }
+    icy
+}
+
+// Playlist file for "open network stream"; the ICY headers name the station for
+// players that show them before connecting
+fn listen_playlist(
+    station: &RadioStation,
+    headers: &axum::http::HeaderMap,
+    content_type: &str,
+    filename: &str,
+    body: String,
+) -> Result<Response, AppError> {
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .header(header::CACHE_CONTROL, "no-cache");
+    for (name, value) in icy_headers(station, headers) {
+        response = response.header(name, value);
+    }
+    Ok(response.body(axum::body::Body::from(body))?)
+}
+
+// Icecast's public status document, for dashboards and directory probes
+async fn icecast_status_json(
+    State(station): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Json<serde_json::Value> {
+    Json(station.icecast_status(&public_base_url(&station, &headers)).json())
+}
+
+// Icecast's admin statistics (XML); admin credentials as for /api/admin/*
+async fn icecast_admin_stats(
+    State(station): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Response {
+    let xml = station.icecast_status(&public_base_url(&station, &headers)).xml();
+    ([(header::CONTENT_TYPE, "text/xml; charset=utf-8")], xml).into_response()
+}
+
+async fn listen_m3u(
+    State(station): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, AppError> {
+    let link = listen_link(&station, &headers);
+    listen_playlist(&station, &headers, "audio/x-mpegurl", "listen.m3u", link.m3u())
+}
+
+async fn listen_pls(
+    State(station): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, AppError> {
+    let link = listen_link(&station, &headers);
+    listen_playlist(&station, &headers, "audio/x-scpls", "listen.pls", link.pls())
+}
+
+// A sine tone (`freq` Hz, default 440; `seconds`, default 5, at most 30) as a
+// complete MP3, for checking that a client can decode and play audio at all
+async fn test_audio(
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let frequency_hz = match query.get("freq") {
+        Some(v) => v.parse().ok().filter(|f| (20..=20_000).contains(f))
+            .ok_or_else(|| AppError::BadRequest("freq must be 20-20000 Hz".into()))?,
+        None => tone::DEFAULT_FREQUENCY_HZ,
+    };
+    let seconds = match query.get("seconds") {
+        Some(v) => v.parse().ok().filter(|s| (1..=tone::MAX_SECONDS).contains(s))
+            .ok_or_else(|| AppError::BadRequest(format!("seconds must be 1-{}", tone::MAX_SECONDS)))?,
+        None => tone::DEFAULT_SECONDS,
+    };
+    info!("Test audio request: {}Hz for {}s", frequency_hz, seconds);
+
+    let audio_data = tokio::task::spawn_blocking(move || tone::sine_mp3(frequency_hz, seconds, tone::DEFAULT_BITRATE_KBPS))
+        .await
+        .map_err(|_| AppError::Internal)??;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "audio/mpeg")
+        .header(header::CONTENT_LENGTH, audio_data.len().to_string())
+        .header(header::CACHE_CONTROL, "public, max-age=86400")
+        .body(axum::body::Body::from(audio_data))?)
+}
+
+// Start a client test run: the cases to play, each with its stream URL
+async fn client_test_run(
+    State(station): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Json<serde_json::Value> {
+    let user_agent = headers.get("user-agent").and_then(|v| v.to_str().ok()).unwrap_or("unknown");
+    let run = station.client_tests().create_run(user_agent);
+    info!("Client test run {} started for {}", run.run, user_agent);
+
+    let cases: Vec<_> = run.cases.iter().map(|result| {
+        let mut case = serde_json::to_value(result.case).unwrap_or_default();
+        case["url"] = format!("/debug/client-test/{}/{}", run.run, result.case.id).into();
+        case
+    }).collect();
+    Json(serde_json::json!({
+        "run": run.run,
+        "results_url": format!("/debug/client-test/{}", run.run),
+        "cases": cases,
+    }))
+}
+
+async fn client_test_results(
+    State(station): State<AppState>,
+    axum::extract::Path(run): axum::extract::Path<String>,
+) -> Result<Json<clienttest::ClientTestRun>, AppError> {
+    station.client_tests().run(&run).map(Json).ok_or(AppError::NotFound)
+}
+
+async fn client_test_stream(
+    State(station): State<AppState>,
+    axum::extract::Path((run, case)): axum::extract::Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, AppError> {
+    let case = clienttest::case(&case).ok_or(AppError::NotFound)?;
+    if !station.client_tests().contains(&run) {
+        return Err(AppError::NotFound);
+    }
+    let icy_requested = wants_icy_metadata(&headers);
+
+    let body = tokio::task::spawn_blocking(move || clienttest::case_body(case, icy_requested))
+        .await
+        .map_err(|_| AppError::Internal)??;
+    let stream = station.client_tests().stream(&run, case, body, icy_requested);
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "audio/mpeg")
+        .header(header::CACHE_CONTROL, "no-cache, no-store")
+        .header("X-Content-Type-Options", "nosniff");
+    if let Some(metaint) = clienttest::icy_metaint(case, icy_requested) {
+        response = response.header("icy-metaint", metaint.to_string());
+    }
+    Ok(response.body(axum::body::Body::from_stream(stream))?)
+}
+
+// The player's verdict: `?ok=true`, or `?ok=false&error=<message>`
+async fn client_test_report(
+    State(station): State<AppState>,
+    axum::extract::Path((run, case)): axum::extract::Path<(String, String)>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<StatusCode, AppError> {
+    let ok = match query.get("ok").map(String::as_str) {
+        Some("true" | "1") => true,
+        Some("false" | "0") => false,
+        _ => return Err(AppError::BadRequest("ok must be true or false".into())),
+    };
+    if !station.client_tests().report(&run, &case, ok, query.get("error").cloned()) {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// DJ/studio talkback, never broadcast. The DJ side needs INTERCOM_TOKEN, the studio
+// side the admin credentials (local only without ADMIN_TOKEN). Tokens may also come
+// as `?token=` since browsers can't set WebSocket headers.
+async fn intercom_connect(
+    ws: WebSocketUpgrade,
+    State(station): State<AppState>,
+    axum::extract::Path(role): axum::extract::Path<String>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    if !station.intercom_auth().is_configured() {
+        return Err(AppError::NotFound);
+    }
+    let role = match role.as_str() {
+        "dj" => Role::Dj,
+        "studio" => Role::Studio,
+        _ => return Err(AppError::NotFound),
+    };
+    let auth = match role {
+        Role::Dj => station.intercom_auth(),
+        Role::Studio => station.admin_auth(),
+    };
+    let allowed = if auth.is_configured() {
+        auth.check(&headers) || query.get("token").is_some_and(|token| auth.check_token(token))
+    } else {
+        local_admin(&station, connect_info.map(|ConnectInfo(addr)| addr))
+    };
+    if !allowed {
+        info!("Rejected unauthorized intercom connection ({})", role.name());
+        return Err(AppError::Unauthorized);
+    }
+
+    info!("Intercom {} connected", role.name());
+    let member = station.intercom().join(role);
+    Ok(ws.max_message_size(crate::intercom::MAX_FRAME_BYTES)
+        .on_upgrade(move |socket| intercom_session(socket, member)))
+}
+
+async fn intercom_session(mut socket: WebSocket, mut member: IntercomMember) {
+    let mut connected = true;
+    while connected {
+        tokio::select! {
+            message = member.recv() => match message {
+                Some((message, skipped)) => {
+                    if skipped > 0 {
+                        info!("Intercom {} fell behind, dropped {} messages", member.role().name(), skipped);
+                    }
+                    let message = match message {
+                        IntercomMessage::Audio(frame) => Message::Binary(frame.to_vec()),
+                        IntercomMessage::Text(text) => Message::Text(text),
+                    };
+                    connected = socket.send(message).await.is_ok();
+                }
+                None => connected = false,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Binary(frame))) => {
+                    member.send(IntercomMessage::Audio(frame.into()));
+                }
+                Some(Ok(Message::Text(text))) => {
+                    if !member.send(IntercomMessage::Text(text)) {
+                        let error = serde_json::json!({ "type": "error", "error": "Text messages must be JSON objects of at most 4KB" });
+                        connected = socket.send(Message::Text(error.to_string())).await.is_ok();
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => connected = false,
+                Some(Ok(_)) => {} // Pings are answered automatically
+            },
+        }
+    }
+    info!("Intercom {} disconnected", member.role().name());
+}
+
+async fn intercom_status(State(station): State<AppState>) -> Result<Json<serde_json::Value>, AppError> {
+    if !station.intercom_auth().is_configured() {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(serde_json::json!({ "connected": station.intercom().presence() })))
+}
+
+// IPs flagged for rapid connect/disconnect cycles on the audio endpoints
+async fn churn_status(State(station): State<AppState>) -> Json<Vec<ChurnStatus>> {
+    Json(station.ip_limiter().churn_status(std::time::Instant::now()))
+}
+
+async fn admin_listeners(State(station): State<AppState>) -> Json<Vec<serde_json::Value>> {
+    Json(station.listener_details())
+}
+
+// Disconnect a listener (admin); `?ban=true` also bans its address, with an
+// optional `?reason=`, and disconnects everyone else connected from it
+async fn kick_listener(
+    State(station): State<AppState>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<StatusCode, AppError> {
+    let (_, ip) = station.kick_listener(&id).ok_or(AppError::NotFound)?;
+    if query.get("ban").is_some_and(|ban| ban == "true" || ban == "1") {
+        let ip = ip.ok_or_else(|| AppError::BadRequest("The listener's address is unknown".to_string()))?;
+        station.bans().add(ip, query.get("reason").cloned()).await?;
+        station.kick_ip(ip);
+        info!("Banned {}", ip);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BanRequest {
+    ip: IpAddr,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+async fn list_bans(State(station): State<AppState>) -> Json<Vec<Ban>> {
+    Json(station.bans().list())
+}
+
+// Ban an address (admin); body `{"ip": "...", "reason": "..."}`. Its current
+// listeners are disconnected.
+async fn add_ban(
+    State(station): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<Json<Ban>, AppError> {
+    let request: BanRequest = parse_body(&body)?;
+    let ban = station.bans().add(request.ip, request.reason).await?;
+    let kicked = station.kick_ip(request.ip);
+    info!("Banned {} ({} listeners disconnected)", ban.ip, kicked);
+    Ok(Json(ban))
+}
+
+async fn remove_ban(
+    State(station): State<AppState>,
+    axum::extract::Path(ip): axum::extract::Path<String>,
+) -> Result<StatusCode, AppError> {
+    let ip: IpAddr = ip.parse().map_err(|_| AppError::BadRequest(format!("Invalid address '{}'", ip)))?;
+    if !station.bans().remove(ip).await? {
+        return Err(AppError::NotFound);
+    }
+    info!("Lifted the ban on {}", ip);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Recordings in the library more than once, by fingerprint (admin). Only tracks
+// `webradio analyze` has fingerprinted are compared.
+async fn duplicates_report(State(station): State<AppState>) -> Result<Json<serde_json::Value>, AppError> {
+    let playlist = station.playlist().snapshot();
+    let fingerprinted = playlist.tracks.iter()
+        .filter(|track| track.analysis.as_ref().is_some_and(|a| !a.fingerprint.is_empty()))
+        .count();
+    let report = tokio::task::spawn_blocking(move || {
+        let groups: Vec<serde_json::Value> = fingerprint::find_duplicates(&playlist.tracks).into_iter()
+            .map(|group| serde_json::json!({
+                "keep": group.keep,
+                "similarity": group.similarity,
+                "tracks": group.tracks.iter()
+                    .filter_map(|&id| playlist.get(id))
+                    .map(|track| serde_json::json!({
+                        "id": track.id,
+                        "title": track.title,
+                        "artist": track.artist,
+                        "bitrate": track.bitrate,
+                        "duration": track.duration,
+                        "enabled": track.enabled,
+                    }))
+                    .collect::<Vec<_>>(),
+            }))
+            .collect();
+        serde_json::json!({
+            "fingerprinted": fingerprinted,
+            "unfingerprinted": playlist.tracks.len() - fingerprinted,
+            "groups": groups,
+        })
+    }).await.map_err(|_| AppError::Internal)?;
+    Ok(Json(report))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct MetadataOverrideRequest {
+    title: String,
+}
+
+// Replace the track-derived now-playing with a custom title (admin), e.g. during
+// live segments; body `{"title": "LIVE: Morning Show"}`. Stays until cleared.
+async fn set_metadata_override(
+    State(station): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<Json<NowPlaying>, AppError> {
+    let request: MetadataOverrideRequest = parse_body(&body)?;
+    let title = request.title.trim();
+    if title.is_empty() {
+        return Err(AppError::BadRequest("title must not be empty".to_string()));
+    }
+    info!("Now-playing overridden with '{}'", title);
+    station.set_metadata_override(Some(title.to_string()));
+    Ok(Json(station.get_now_playing()))
+}
+
+async fn clear_metadata_override(State(station): State<AppState>) -> StatusCode {
+    if station.metadata_override().is_some() {
+        info!("Now-playing override cleared");
+        station.set_metadata_override(None);
+    }
+    StatusCode::NO_CONTENT
+}
+
+// EventSource sends Last-Event-ID when it reconnects
+async fn sse_events(
+    State(station): State<AppState>,
+    headers: axum::http::HeaderMap,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Sse<impl Stream<Item = Result<Event, AppError>>> {
+    let last_event_id = headers.get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok());
+    let stream = station.create_event_stream(query.get("listener").cloned(), last_event_id);
+    
+    Sse::new(stream)
+        .keep_alive(KeepAlive::new().interval(Duration::from_secs(30)))
+}
+
+// With `listener` (the X-Listener-Id of a stream), the track that listener is
+// hearing rather than the live one. The ETag leaves out the clock fields, so a
+// client's cached copy stays current until the track or listener count changes:
+// its `position` is still right as of its `server_time_ms`.
+async fn now_playing(
+    State(station): State<AppState>,
+    headers: axum::http::HeaderMap,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let info = station.get_now_playing_for(query.get("listener").map(String::as_str));
+    let key = NowPlaying { position: 0, server_time_ms: 0, ..info.clone() };
+    etag::weak_json(&headers, &key, &info)
+}
+
+async fn listener_count(
+    State(station): State<AppState>,
+) -> Json<Listeners> {
+    Json(Listeners {
+        listeners: station.listener_count(),
+        max_listeners: match station.config().max_listeners {
+            0 => None,
+            max => Some(max),
+        },
+        uptime: station.uptime_seconds(),
+    })
+}
+
+async fn get_playlist(
+    State(station): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, AppError> {
+    etag::json(&headers, &station.get_playlist())
+}
+
+/// Most tracks `/api/next-up?count=` returns
+const MAX_NEXT_UP: usize = 50;
+
+async fn next_up(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<NextUp>, AppError> {
+    let count = match query.get("count") {
+        Some(value) => value.parse::<usize>()
+            .ok()
+            .filter(|count| (1..=MAX_NEXT_UP).contains(count))
+            .ok_or_else(|| AppError::BadRequest(format!("'count' must be between 1 and {}", MAX_NEXT_UP)))?,
+        None => 5,
+    };
+    Ok(Json(station.next_up(count)))
+}
+
+/// Most tracks `/api/search?limit=` returns
+const MAX_SEARCH_RESULTS: usize = 100;
+
+async fn search(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<SearchResults>, AppError> {
+    let text = query.get("q").map(|q| q.trim()).unwrap_or_default();
+    if text.is_empty() {
+        return Err(AppError::BadRequest("'q' is required".to_string()));
+    }
+    let limit = match query.get("limit") {
+        Some(value) => value.parse::<usize>()
+            .ok()
+            .filter(|limit| (1..=MAX_SEARCH_RESULTS).contains(limit))
+            .ok_or_else(|| AppError::BadRequest(format!("'limit' must be between 1 and {}", MAX_SEARCH_RESULTS)))?,
+        None => 20,
+    };
+    Ok(Json(station.search(text, limit)))
+}
+
+// Either a file in the music directory or the id of a track taken out of rotation
+#[derive(Debug, serde::Deserialize)]
+struct TrackAddRequest {
+    path: Option<PathBuf>,
+    id: Option<Uuid>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TrackIdRequest {
+    id: Uuid,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TrackEnabledRequest {
+    id: Uuid,
+    enabled: bool,
+    #[serde(default)]
+    reason: Option<String>, // Why it is benched
+    #[serde(default)]
+    until: Option<u64>,     // Unix seconds; benched only until then
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TrackTagsRequest {
+    #[serde(flatten)]
+    tags: playlist::TagEdit,
+    #[serde(default)]
+    write_file: bool, // Also write the tags into the MP3's ID3 tag
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PlaylistOrderRequest {
+    ids: Vec<Uuid>,
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, AppError> {
+    serde_json::from_slice(body).map_err(|e| AppError::BadRequest(format!("Invalid request body: {}", e)))
+}
+
+fn parse_track_id(id: &str) -> Result<Uuid, AppError> {
+    Uuid::parse_str(id).map_err(|_| AppError::BadRequest(format!("Invalid track id '{}'", id)))
+}
+
+// Put a track (back) into rotation (admin): body `{"id": "..."}` for one listed in
+// `excluded` by /api/playlist, or `{"path": "..."}` relative to the music directory
+async fn add_playlist_track(
+    State(station): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<Json<PlaylistDto>, AppError> {
+    let path = match parse_body(&body)? {
+        TrackAddRequest { id: Some(id), path: None } => station.playlist().snapshot()
+            .excluded_track(id)
+            .map(|excluded| excluded.path.clone())
+            .ok_or_else(|| AppError::TrackNotFound(id.to_string()))?,
+        TrackAddRequest { id: None, path: Some(path) } => path,
+        _ => return Err(AppError::BadRequest("give either id or path".into())),
+    };
+    let track = playlist::read_track(&station.config().music_dir, &path).await?;
+    info!("Adding {} to the playlist", track.path.display());
+    Ok(Json(station.edit_playlist(|playlist| playlist.add(track.clone())).await?))
+}
+
+// Take a track out of rotation (admin): `?id=`
+async fn remove_playlist_track(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<PlaylistDto>, AppError> {
+    let id = parse_track_id(query.get("id").ok_or_else(|| AppError::BadRequest("id is required".into()))?)?;
+    info!("Removing {} from the playlist", id);
+    Ok(Json(station.edit_playlist(|playlist| playlist.remove(id)).await?))
+}
+
+// Replace the playlist order (admin); body `{"ids": [...]}` listing every track once
+async fn reorder_playlist(
+    State(station): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<Json<PlaylistDto>, AppError> {
+    let request: PlaylistOrderRequest = parse_body(&body)?;
+    Ok(Json(station.edit_playlist(|playlist| playlist.reorder(&request.ids)).await?))
+}
+
+// Queue a track to play after the current one (admin); body `{"id": "..."}`
+async fn play_next(
+    State(station): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<Json<PlaylistDto>, AppError> {
+    let request: TrackIdRequest = parse_body(&body)?;
+    info!("Playing {} next", request.id);
+    Ok(Json(station.edit_playlist(|playlist| playlist.play_next(request.id)).await?))
+}
+
+// Bench a track or return it to rotation (admin); body `{"id": "...", "enabled": false}`,
+// optionally with a `reason` and an `until` time for a temporary removal.
+// Benched tracks keep their place in the playlist.
+async fn set_track_enabled(
+    State(station): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<Json<PlaylistDto>, AppError> {
+    let request: TrackEnabledRequest = parse_body(&body)?;
+    if request.enabled {
+        info!("Enabling {}", request.id);
+        return Ok(Json(station.edit_playlist(|playlist| playlist.set_enabled(request.id, true)).await?));
+    }
+    if request.until.is_some_and(|until| until <= chrono::Utc::now().timestamp() as u64) {
+        return Err(AppError::BadRequest("until must be in the future".into()));
+    }
+    let reason = request.reason.filter(|reason| !reason.trim().is_empty());
+    info!("Benching {} ({})", request.id, reason.as_deref().unwrap_or("no reason given"));
+    Ok(Json(station.edit_playlist(|playlist| playlist.disable(request.id, reason.clone(), request.until)).await?))
+}
+
+// Correct a track's title, artist, album or genre (admin); body e.g. `{"title": "...",
+// "write_file": true}`. Fields left out stay as they are.
+async fn edit_track_tags(
+    State(station): State<AppState>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    body: axum::body::Bytes,
+) -> Result<Json<TrackDto>, AppError> {
+    let id = parse_track_id(&id)?;
+    let request: TrackTagsRequest = parse_body(&body)?;
+    if request.tags.is_empty() {
+        return Err(AppError::BadRequest("Nothing to change: give a title, artist, album or genre".into()));
+    }
+    if request.tags.title.as_deref().is_some_and(|title| title.trim().is_empty()) {
+        return Err(AppError::BadRequest("title can't be empty".into()));
+    }
+    info!("Editing tags of {}{}", id, if request.write_file { " and its file" } else { "" });
+    let track = station.edit_track_tags(id, &request.tags, request.write_file).await?;
+    Ok(Json(TrackDto::from(&track)))
+}
+
+// Benched tracks with why and until when (admin)
+async fn disabled_tracks(State(station): State<AppState>) -> Json<serde_json::Value> {
+    let playlist = station.playlist().snapshot();
+    let tracks: Vec<TrackDto> = playlist.tracks.iter().filter(|track| !track.enabled).map(TrackDto::from).collect();
+    Json(serde_json::json!({ "tracks": tracks }))
+}
+
+// Tracks the broadcast benched for failing to open or decode, with the error (admin)
+async fn quarantined_tracks(State(station): State<AppState>) -> Json<serde_json::Value> {
+    let playlist = station.playlist().snapshot();
+    let tracks: Vec<TrackDto> = playlist.tracks.iter().filter(|track| track.quarantine.is_some()).map(TrackDto::from).collect();
+    Json(serde_json::json!({ "tracks": tracks }))
+}
+
+async fn get_stats(
+    State(station): State<AppState>,
+) -> Json<StatsDto> {
+    Json(station.get_statistics())
+}
+
+// Per-minute audience from the audience log: `?from=&to=` in Unix seconds (default
+// the last 24 hours), `?step=` merges minutes into coarser buckets for long ranges
+async fn stats_timeseries(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let param = |name: &str| -> Result<Option<u64>, AppError> {
+        query.get(name)
+            .map(|value| value.parse::<u64>()
+                .map_err(|_| AppError::BadRequest(format!("Invalid '{}' value '{}'", name, value))))
+            .transpose()
+    };
+    let to = param("to")?.unwrap_or_else(|| chrono::Utc::now().timestamp() as u64);
+    let from = param("from")?.unwrap_or(to.saturating_sub(24 * 3600));
+    if from >= to {
+        return Err(AppError::BadRequest("'from' must be before 'to'".to_string()));
+    }
+    let step = param("step")?.unwrap_or(audience::MINUTE_SECS);
+    if step == 0 || step % audience::MINUTE_SECS != 0 {
+        return Err(AppError::BadRequest("'step' must be a whole number of minutes in seconds".to_string()));
+    }
+
+    let minutes = station.audience_log().load_range(from, to).await?;
+    let points = audience::bucket(&minutes, step);
+    Ok(Json(serde_json::json!({
+        "from": from,
+        "to": to,
+        "step": step,
+        "peak_listeners": points.iter().map(|point| point.peak_listeners).max().unwrap_or(0),
+        "bytes_sent": points.iter().map(|point| point.bytes_sent).sum::<u64>(),
+        "points": points,
+    })))
+}
+
+async fn get_metrics(
+    State(station): State<AppState>,
+) -> Json<serde_json::Value> {
+    Json(station.monitor().snapshot())
+}
+
+// Rolling performance history, oldest sample first; `?minutes=` limits how far back
+async fn metrics_history(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let since = match query.get("minutes") {
+        Some(value) => {
+            let minutes: u64 = value.parse()
+                .map_err(|_| AppError::BadRequest(format!("Invalid 'minutes' value '{}'", value)))?;
+            // Any window longer than the history is the whole history
+            (chrono::Utc::now().timestamp() as u64).saturating_sub(minutes.saturating_mul(60))
+        }
+        None => 0,
+    };
+    let samples = station.monitor().history(since);
+    Ok(Json(serde_json::json!({
+        "interval_seconds": station.config().metrics_sample_secs,
+        "count": samples.len(),
+        "samples": samples,
+    })))
+}
+
+// Public addresses for building shareable stream links: PUBLIC_URL when set,
+// otherwise the discovered public IP
+async fn server_info(
+    State(station): State<AppState>,
+) -> Json<serde_json::Value> {
+    let config = station.config();
+    let scheme = if config.tls_cert_path.is_some() { "https" } else { "http" };
+    let public_ip = station.public_ip().current();
+    let public_url = config.public_url.clone().or_else(|| {
+        public_ip.as_ref().map(|addr| netif::url(scheme, addr.ip, config.port))
+    });
+    let local_urls: Vec<String> = netif::shareable_addrs().iter()
+        .map(|iface| iface.url(scheme, config.port))
+        .collect();
+
+    Json(serde_json::json!({
+        "station_name": config.station_name,
+        "version": env!("CARGO_PKG_VERSION"),
+        "public_ip": public_ip,
+        "public_url": public_url,
+        "stream_url": public_url.as_ref().map(|url| format!("{}/stream", url)),
+        "local_urls": local_urls,
+        "simulcast": station.simulcast().mounts().iter().map(|mount| mount.stats()).collect::<Vec<_>>(),
+        "codecs": station.codecs().available().iter().map(|codec| codec.name()).collect::<Vec<_>>(),
+    }))
+}
+
+// Machine-readable contract for the /api routes
+async fn openapi_spec(State(station): State<AppState>) -> Json<serde_json::Value> {
+    Json(openapi::document(&station.config().station_name))
+}
+
+// Swagger UI over /api/openapi.json
+async fn api_docs() -> Html<&'static str> {
+    Html(include_str!("../templates/api-docs.html"))
+}
+
+// Readiness: the startup preflight plus the running broadcast's checks; 200 when
+// every check passes, 503 otherwise
+async fn readyz(State(station): State<AppState>) -> Response {
+    match station.preflight().map(|preflight| preflight.with_checks(station.runtime_checks())) {
+        Some(report) => {
+            let status = if report.is_ready() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+            (status, Json(report)).into_response()
+        }
+        None => (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "status": "starting", "checks": [] }))).into_response(),
+    }
+}
+
+// Liveness: the process is up and serving requests, whatever state the station is in
+async fn healthz(State(station): State<AppState>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "alive", "uptime": station.uptime_seconds() }))
+}
+
+async fn health_check(
+    State(station): State<AppState>,
+) -> Json<Health> {
+    Json(Health {
+        status: "healthy".to_string(),
+        is_broadcasting: station.is_broadcasting(),
+        listeners: station.listener_count(),
+        uptime: station.uptime_seconds(),
+    })
+}
+
+/// Capture a CPU profile of the running server: `?seconds=10` (max 60),
+/// `?format=svg` (flamegraph, default) or `folded` (collapsed stacks)
+async fn cpu_profile(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let seconds = match query.get("seconds") {
+        Some(value) => value.parse::<u64>().ok()
+            .filter(|s| (1..=profile::MAX_SECONDS).contains(s))
+            .ok_or_else(|| AppError::BadRequest(format!("seconds must be 1-{}", profile::MAX_SECONDS)))?,
+        None => 10,
+    };
+    let format = query.get("format").map(String::as_str).unwrap_or("svg");
+    if format != "svg" && format != "folded" {
+        return Err(AppError::BadRequest(format!("Unknown profile format: {}", format)));
+    }
+
+    info!("Capturing {}s CPU profile", seconds);
+    let captured = match profile::capture(Duration::from_secs(seconds)).await {
+        Ok(captured) => captured,
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+            return Err(AppError::ServiceUnavailable {
+                message: "A CPU profile is already being captured".to_string(),
+                retry_after_secs: seconds,
+            });
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if captured.dropped > 0 {
+        warn!("CPU profile dropped {} samples", captured.dropped);
+    }
+
+    let (content_type, body) = if format == "folded" {
+        ("text/plain; charset=utf-8", captured.folded())
+    } else {
+        let title = format!("{} CPU profile, {}s at {}Hz", station.config().station_name, seconds, profile::SAMPLE_HZ);
+        ("image/svg+xml", captured.svg(&title))
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], body).into_response())
+}
+
+async fn debug_info(
+    State(station): State<AppState>,
+) -> Json<serde_json::Value> {
+    let now_playing = station.get_now_playing();
+    let stats = station.get_statistics();
+    
+    Json(serde_json::json!({
+        "debug": {
+            "is_broadcasting": station.is_broadcasting(),
+            "broadcast_receiver_count": station.get_broadcast_receiver_count().await,
+            "listener_count": station.listener_count(),
+            "now_playing": now_playing,
+            "stats": stats,
+            "recent_chunks": station.chunk_integrity().recent(),
+        }
+    }))
+}
+
+async fn royalty_report(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let month = query.get("month")
+        .ok_or_else(|| AppError::BadRequest("Missing 'month' parameter (YYYY-MM)".to_string()))?;
+    let (from, to) = royalty::month_range(month)
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid month '{}' (expected YYYY-MM)", month)))?;
+    let format: royalty::ReportFormat = query.get("format")
+        .map(|f| f.parse())
+        .transpose()
+        .map_err(AppError::BadRequest)?
+        .unwrap_or(royalty::ReportFormat::SoundExchange);
+
+    let records = station.play_history().load_range(from, to).await?;
+    let lines = royalty::aggregate(&records);
+    let csv = royalty::render_csv(format, &station.config().station_name, &lines);
+
+    info!("Generated {} royalty report for {}: {} recordings from {} plays",
+        format.file_suffix(), month, lines.len(), records.len());
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
+        .header(header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"royalty-{}-{}.csv\"", month, format.file_suffix()))
+        .body(axum::body::Body::from(csv))?)
+}
+
+async fn list_archive(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let date = |name: &str| -> Result<Option<chrono::NaiveDate>, AppError> {
+        query.get(name)
+            .map(|value| chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| AppError::BadRequest(format!("Invalid '{}' date '{}' (expected YYYY-MM-DD)", name, value))))
+            .transpose()
+    };
+    let filter = archive::ArchiveQuery {
+        show: query.get("show").cloned(),
+        date: date("date")?,
+        from: date("from")?,
+        to: date("to")?,
+        q: query.get("q").cloned(),
+    };
+
+    let entries = station.archive().search(&filter).await?;
+    let shows: Vec<serde_json::Value> = entries.iter()
+        .map(|entry| {
+            let mut json = serde_json::to_value(entry).unwrap_or_default();
+            json["stream_url"] = format!("/archive/{}/stream", entry.id).into();
+            json
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "count": shows.len(),
+        "shows": shows,
+    })))
+}
+
+// Track chapters of an archived show, as JSON or a CUE sheet (`?format=cue`).
+// Chapters come from the sidecar; otherwise they are derived from the play history
+// and saved to the sidecar once the recording is complete
+async fn archive_chapters(
+    State(station): State<AppState>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let entry = station.archive().find(&id).await?.ok_or(AppError::NotFound)?;
+
+    let chapters = match &entry.chapters {
+        Some(chapters) => chapters.clone(),
+        None => {
+            let now = chrono::Utc::now().timestamp() as u64;
+            let end = entry.duration.map_or(now, |duration| entry.started_at + duration);
+            // Plays are logged by start time, so look back far enough to find the
+            // track that was already on air when the recording started
+            let records = station.play_history()
+                .load_range(entry.started_at.saturating_sub(6 * 3600), end)
+                .await?;
+            let chapters = archive::chapters_from_history(&entry, &records);
+            if !entry.recording && entry.duration.is_some() && end < now && !chapters.is_empty() {
+                station.archive().write_chapters(&entry, &chapters).await?;
+                info!("Wrote {} chapters for archived show {}", chapters.len(), entry.id);
+            }
+            chapters
+        }
+    };
+
+    match query.get("format").map(String::as_str) {
+        Some("cue") => Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/x-cue; charset=utf-8")
+            .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.cue\"", entry.id))
+            .body(axum::body::Body::from(archive::render_cue(&entry, &chapters)))?),
+        Some("json") | None => Ok(Json(serde_json::json!({
+            "id": entry.id,
+            "show": entry.show,
+            "duration": entry.duration,
+            "chapters": chapters,
+        })).into_response()),
+        Some(other) => Err(AppError::BadRequest(format!("Unknown chapter format '{}' (expected json or cue)", other))),
+    }
+}
+
+// On-demand playback of an archived show; ServeFile handles Range/If-Range so
+// players can seek and resume
+async fn archive_stream(
+    State(station): State<AppState>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+    request: axum::extract::Request,
+) -> Result<Response, AppError> {
+    check_stream_token(&station, &query)?;
+    let entry = station.archive().find(&id).await?.ok_or(AppError::NotFound)?;
+    info!("Serving archived show '{}' ({})", entry.show, entry.id);
+
+    let response = ServeFile::new(&entry.path)
+        .oneshot(request)
+        .await
+        .map_err(|_| AppError::Internal)?;
+    Ok(response.map(axum::body::Body::new))
+}
+
+// A playlist track's file as stored, for previews in the web UI and auditioning;
+// `id` is the track's id from /api/playlist. Needs admin credentials, or a
+// signed `expires`/`token` pair from /api/stream-token since <audio> elements
+// can't send headers. ServeFile handles Range/If-Range.
+async fn track_audio(
+    State(station): State<AppState>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+    request: axum::extract::Request,
+) -> Result<Response, AppError> {
+    if !valid_stream_token(&station, &query) && !is_admin(&station, &request) {
+        info!("Rejected unauthorized request for track {}", id);
+        return Err(AppError::Unauthorized);
+    }
+
+    let playlist = station.playlist().snapshot();
+    if playlist.tracks.is_empty() {
+        return Err(AppError::PlaylistEmpty);
+    }
+    let track = playlist.get(parse_track_id(&id)?).ok_or(AppError::TrackNotFound(id))?;
+    let path = if track.path.is_absolute() {
+        track.path.clone()
+    } else {
+        station.config().music_dir.join(&track.path)
+    };
+    debug!("Serving track file {}", path.display());
+
+    let response = ServeFile::new(&path)
+        .oneshot(request)
+        .await
+        .map_err(|_| AppError::Internal)?;
+    Ok(response.map(axum::body::Body::new))
+}
+
+// A track's embedded cover art. Public, unlike the audio, so chat services and
+// players can fetch it for now-playing posts
+async fn track_artwork(
+    State(station): State<AppState>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<Response, AppError> {
+    let playlist = station.playlist().snapshot();
+    let track = playlist.get(parse_track_id(&id)?).ok_or(AppError::TrackNotFound(id))?;
+    let path = if track.path.is_absolute() {
+        track.path.clone()
+    } else {
+        station.config().music_dir.join(&track.path)
+    };
+    let (media_type, data) = tokio::task::spawn_blocking(move || playlist::read_artwork(&path))
+        .await
+        .map_err(|_| AppError::Internal)?
+        .ok_or(AppError::NotFound)?;
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, media_type)
+        .header(header::CACHE_CONTROL, "public, max-age=86400")
+        .body(axum::body::Body::from(data))?)
+}
+
+// Time endpoint for multi-room sync. Clients send their clock as `t0` and estimate
+// their offset NTP-style; with `listener` (the X-Listener-Id of their stream) the
+// response also maps their playback position onto the sync timeline
+async fn sync_time(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Json<serde_json::Value> {
+    let (snapshot, sync_offset_ms) = station.sync_info(query.get("listener").map(String::as_str));
+    let t0 = query.get("t0").and_then(|t| t.parse::<f64>().ok());
+
+    Json(serde_json::json!({
+        "t0": t0,
+        "server_time_ms": snapshot.server_time_ms,
+        "epoch_ms": snapshot.epoch_ms,
+        "delay_ms": snapshot.delay_ms,
+        "live_position_ms": snapshot.live_position_ms,
+        "target_position_ms": snapshot.target_position_ms,
+        "epoch_shifts": snapshot.epoch_shifts,
+        "listener_offset_ms": sync_offset_ms,
+    }))
+}
+
+// A listener votes to skip the current track; `listener` is the X-Listener-Id of
+// their stream (or `listener_id` from a WebSocket frame), which ties one vote to
+// one connection
+async fn vote_skip(
+    State(station): State<AppState>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<SkipVote>, AppError> {
+    let listener = query.get("listener")
+        .ok_or_else(|| AppError::BadRequest("Missing 'listener' parameter".to_string()))?;
+    let vote = station.vote_skip(listener)?;
+    if vote.skipped {
+        info!("Skip vote passed ({}/{})", vote.votes, vote.required);
+    }
+    Ok(Json(vote))
+}
+
+// Maximum lifetime of a minted stream token
+const MAX_STREAM_TOKEN_TTL_SECS: u64 = 7 * 24 * 3600;
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct StreamTokenRequest {
+    user: Option<String>,
+    ttl_secs: Option<u64>,
+}
+
+// Mint a signed, expiring stream URL (admin); the body is optional JSON
+// `{"user": "...", "ttl_secs": 3600}`
+async fn mint_stream_token(
+    State(station): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let request: StreamTokenRequest = if body.iter().all(u8::is_ascii_whitespace) {
+        StreamTokenRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| AppError::BadRequest(format!("Invalid token request: {}", e)))?
+    };
+
+    if let Some(user) = &request.user {
+        let valid = !user.is_empty()
+            && user.len() <= 128
+            && user.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@'));
+        if !valid {
+            return Err(AppError::BadRequest("'user' must be 1-128 characters of A-Z, 0-9, '-', '_', '.' or '@'".to_string()));
+        }
+    }
+
+    let ttl = request.ttl_secs
+        .unwrap_or(station.config().stream_token_ttl_secs)
+        .clamp(1, MAX_STREAM_TOKEN_TTL_SECS);
+    let expires = chrono::Utc::now().timestamp() as u64 + ttl;
+    let token = station.signer().stream_token(expires, request.user.as_deref());
+
+    let mut query = format!("expires={}&token={}", expires, token);
+    if let Some(user) = &request.user {
+        query.push_str(&format!("&user={}", user));
+    }
+
+    Ok(Json(serde_json::json!({
+        "token": token,
+        "expires": expires,
+        "user": request.user,
+        "query": query,
+        "url": format!("/stream?{}", query),
+    })))
+}
+
+async fn beacon_session(
+    State(station): State<AppState>,
+) -> Json<serde_json::Value> {
+    let session = uuid::Uuid::new_v4().to_string();
+    let sig = station.signer().sign(&session);
+    Json(serde_json::json!({
+        "session": session,
+        "sig": sig,
+        "max_events": beacon::MAX_EVENTS_PER_BEACON,
+    }))
+}
+
+// Body is parsed by hand: navigator.sendBeacon may post JSON as text/plain
+async fn receive_beacon(
+    State(station): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<StatusCode, AppError> {
+    let payload: beacon::BeaconPayload = serde_json::from_slice(&body)?;
+
+    if !station.signer().verify(&payload.session, &payload.sig) {
+        station.beacon_stats().record_rejected();
+        return Err(AppError::Forbidden);
+    }
+
+    station.beacon_stats().record(&payload.session, payload.platform.as_deref(), &payload.events);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Signed with the same session as /beacon, and parsed by hand for the same reason
+async fn receive_telemetry(
+    State(station): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<StatusCode, AppError> {
+    let report: beacon::TelemetryReport = serde_json::from_slice(&body)?;
+
+    if !station.signer().verify(&report.session, &report.sig) {
+        station.beacon_stats().record_rejected();
+        return Err(AppError::Forbidden);
+    }
+
+    station.beacon_stats().record_telemetry(&report);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,129 @@
+use ring::hmac;
+
+/// HMAC-SHA256 signer for tokens handed out to clients (beacon sessions, stream URLs)
+pub struct Signer {
+    key: hmac::Key,
+}
+
+impl Signer {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        }
+    }
+
+    /// Signer with a random per-process secret; tokens become invalid on restart
+    pub fn ephemeral() -> Self {
+        let secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        Self::new(secret.as_bytes())
+    }
+
+    /// Hex-encoded signature of `message`
+    pub fn sign(&self, message: &str) -> String {
+        let tag = hmac::sign(&self.key, message.as_bytes());
+        tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Constant-time check of a hex-encoded signature
+    pub fn verify(&self, message: &str, signature: &str) -> bool {
+        match decode_hex(signature) {
+            Some(bytes) => hmac::verify(&self.key, message.as_bytes(), &bytes).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Token for a stream URL valid until `expires` (unix seconds), optionally bound to a user
+    pub fn stream_token(&self, expires: u64, user: Option<&str>) -> String {
+        self.sign(&stream_message(expires, user))
+    }
+
+    /// Check a stream URL token and its expiry against `now` (unix seconds)
+    pub fn verify_stream_token(&self, token: &str, expires: u64, user: Option<&str>, now: u64) -> bool {
+        expires >= now && self.verify(&stream_message(expires, user), token)
+    }
+
+    /// Resume token for a listener: its id plus a signature, so ids can't be
+    /// guessed or borrowed from another listener's stats entry
+    pub fn resume_token(&self, listener_id: &str) -> String {
+        format!("{}.{}", listener_id, self.sign(&format!("resume:{}", listener_id)))
+    }
+
+    /// The listener id a resume token was minted for
+    pub fn verify_resume_token<'a>(&self, token: &'a str) -> Option<&'a str> {
+        let (listener_id, signature) = token.split_once('.')?;
+        self.verify(&format!("resume:{}", listener_id), signature).then_some(listener_id)
+    }
+}
+
+// Prefixed so stream tokens can never be replayed as other signed values (beacon sessions)
+fn stream_message(expires: u64, user: Option<&str>) -> String {
+    format!("stream:{}:{}", expires, user.unwrap_or(""))
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = Signer::new(b"secret");
+        let signature = signer.sign("session-1");
+
+        assert_eq!(signature.len(), 64);
+        assert!(signer.verify("session-1", &signature));
+        assert!(!signer.verify("session-2", &signature));
+        assert!(!signer.verify("session-1", "not-hex"));
+        assert!(!signer.verify("session-1", &signature[..62]));
+    }
+
+    #[test]
+    fn test_known_vector() {
+        // RFC 4231 test case 2
+        let signer = Signer::new(b"Jefe");
+        assert_eq!(
+            signer.sign("what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_stream_tokens() {
+        let signer = Signer::new(b"secret");
+        let token = signer.stream_token(1_000, Some("alice"));
+
+        assert!(signer.verify_stream_token(&token, 1_000, Some("alice"), 999));
+        assert!(!signer.verify_stream_token(&token, 1_000, Some("alice"), 1_001)); // Expired
+        assert!(!signer.verify_stream_token(&token, 2_000, Some("alice"), 999));   // Extended expiry
+        assert!(!signer.verify_stream_token(&token, 1_000, Some("bob"), 999));
+        assert!(!signer.verify_stream_token(&token, 1_000, None, 999));
+    }
+
+    #[test]
+    fn test_resume_tokens() {
+        let signer = Signer::new(b"secret");
+        let token = signer.resume_token("listener-1");
+
+        assert_eq!(signer.verify_resume_token(&token), Some("listener-1"));
+        assert_eq!(signer.verify_resume_token(&token.replace("listener-1", "listener-2")), None);
+        assert_eq!(signer.verify_resume_token("listener-1"), None);
+        // Other signed values don't pass as resume tokens
+        assert_eq!(signer.verify_resume_token(&format!("listener-1.{}", signer.sign("listener-1"))), None);
+    }
+
+    #[test]
+    fn test_different_secrets_do_not_verify() {
+        let a = Signer::ephemeral();
+        let b = Signer::ephemeral();
+        assert!(!b.verify("message", &a.sign("message")));
+    }
+}
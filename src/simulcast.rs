@@ -0,0 +1,324 @@
+// Simulcast mounts: the broadcast is decoded once and re-encoded at extra MP3
+// bitrates, each served on its own mount (/stream-low, /stream-high, ...), so
+// listeners on poor connections aren't stuck with the full-bitrate feed
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use bytes::Bytes;
+use futures::stream::Stream;
+use mp3lame_encoder::Encoder;
+use symphonia::core::{
+    codecs::{CodecParameters, Decoder, DecoderOptions, CODEC_TYPE_MP3},
+    errors::Error as DecodeError,
+    formats::Packet,
+};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::{config::SimulcastMount, encode, mp3, radio::AudioChunk};
+
+const CHANNEL_CAPACITY: usize = 64;
+const BURST_MS: f64 = 2000.0; // Recent audio sent to a new listener so playback starts at once
+
+/// One re-encoded quality and its listeners
+pub struct Mount {
+    name: String,
+    bitrate_kbps: u32,
+    tx: broadcast::Sender<Bytes>,
+    recent: Mutex<VecDeque<(Bytes, f64)>>, // Latest encoded blocks with their duration, for the burst
+    listeners: Arc<AtomicUsize>,
+    bytes_encoded: AtomicU64,
+}
+
+impl Mount {
+    fn new(name: &str, bitrate_kbps: u32) -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            name: name.to_string(),
+            bitrate_kbps,
+            tx,
+            recent: Mutex::new(VecDeque::new()),
+            listeners: Arc::new(AtomicUsize::new(0)),
+            bytes_encoded: AtomicU64::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Route the mount is served on
+    pub fn path(&self) -> String {
+        format!("/stream-{}", self.name)
+    }
+
+    pub fn bitrate_kbps(&self) -> u32 {
+        self.bitrate_kbps
+    }
+
+    pub fn listener_count(&self) -> usize {
+        self.listeners.load(Ordering::Relaxed)
+    }
+
+    fn publish(&self, data: Bytes, duration_ms: f64) {
+        self.bytes_encoded.fetch_add(data.len() as u64, Ordering::Relaxed);
+        // Held while sending so a new listener's burst and live data don't overlap
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_back((data.clone(), duration_ms));
+        let mut buffered_ms: f64 = recent.iter().map(|(_, ms)| ms).sum();
+        while buffered_ms > BURST_MS && recent.len() > 1 {
+            buffered_ms -= recent.pop_front().map(|(_, ms)| ms).unwrap_or(0.0);
+        }
+        let _ = self.tx.send(data);
+    }
+
+    fn subscribe(&self) -> (Vec<Bytes>, broadcast::Receiver<Bytes>) {
+        let recent = self.recent.lock().unwrap();
+        let burst = recent.iter().map(|(data, _)| data.clone()).collect();
+        (burst, self.tx.subscribe())
+    }
+
+    /// A listener's stream: the recent burst, then live audio. A listener that falls
+    /// behind skips ahead rather than delaying the mount.
+    pub fn listen(&self) -> impl Stream<Item = Bytes> + Send + 'static {
+        let (burst, mut receiver) = self.subscribe();
+        let guard = ListenerGuard::new(&self.listeners);
+        let name = self.name.clone();
+        async_stream::stream! {
+            let _guard = guard;
+            for data in burst {
+                yield data;
+            }
+            loop {
+                match receiver.recv().await {
+                    Ok(data) => yield data,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Simulcast listener on {} lagged by {} blocks", name, skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "path": self.path(),
+            "bitrate_kbps": self.bitrate_kbps,
+            "listeners": self.listener_count(),
+            "bytes_encoded": self.bytes_encoded.load(Ordering::Relaxed),
+        })
+    }
+}
+
+pub(crate) struct ListenerGuard(Arc<AtomicUsize>);
+
+impl ListenerGuard {
+    pub(crate) fn new(count: &Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count.clone())
+    }
+}
+
+impl Drop for ListenerGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The configured simulcast mounts
+pub struct Simulcast {
+    mounts: Vec<Arc<Mount>>,
+}
+
+impl Simulcast {
+    /// Mounts from SIMULCAST_MOUNTS; duplicates and bitrates LAME can't encode are skipped
+    pub fn new(config: &[SimulcastMount]) -> Self {
+        let mut mounts: Vec<Arc<Mount>> = Vec::new();
+        for mount in config {
+            if encode::lame_bitrate(mount.bitrate_kbps).is_none() {
+                warn!("Skipping simulcast mount {}: unsupported bitrate {}kbps", mount.name, mount.bitrate_kbps);
+            } else if mounts.iter().any(|m| m.name == mount.name) {
+                warn!("Skipping duplicate simulcast mount {}", mount.name);
+            } else {
+                mounts.push(Arc::new(Mount::new(&mount.name, mount.bitrate_kbps)));
+            }
+        }
+        Self { mounts }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.mounts.is_empty()
+    }
+
+    pub fn mounts(&self) -> &[Arc<Mount>] {
+        &self.mounts
+    }
+
+    pub fn mount(&self, name: &str) -> Option<&Arc<Mount>> {
+        self.mounts.iter().find(|mount| mount.name == name)
+    }
+
+    pub fn listener_count(&self) -> usize {
+        self.mounts.iter().map(|mount| mount.listener_count()).sum()
+    }
+
+    /// Decode `input` and encode it for every mount on a dedicated thread, since
+    /// encoding is CPU-bound. The thread ends when the broadcast channel closes.
+    pub fn spawn(&self, input: broadcast::Receiver<AudioChunk>) -> std::io::Result<std::thread::JoinHandle<()>> {
+        let mounts = self.mounts.clone();
+        std::thread::Builder::new()
+            .name("simulcast".to_string())
+            .spawn(move || run(mounts, input))
+    }
+}
+
+fn run(mounts: Vec<Arc<Mount>>, mut input: broadcast::Receiver<AudioChunk>) {
+    let params = CodecParameters::new().for_codec(CODEC_TYPE_MP3).clone();
+    let mut decoder = match symphonia::default::get_codecs().make(&params, &DecoderOptions::default()) {
+        Ok(decoder) => decoder,
+        Err(e) => {
+            warn!("Simulcast disabled: no MP3 decoder: {}", e);
+            return;
+        }
+    };
+    let names: Vec<&str> = mounts.iter().map(|mount| mount.name.as_str()).collect();
+    info!("Simulcasting to {}", names.join(", "));
+
+    let mut encoders = Encoders { sample_rate: 0, encoders: Vec::new() };
+    let mut outputs: Vec<Vec<u8>> = vec![Vec::new(); mounts.len()];
+    loop {
+        let chunk = match input.blocking_recv() {
+            Ok(chunk) => chunk,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("Simulcast encoder lagged by {} chunks", skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        // Chunks hold whole frames; anything else (e.g. a relay splitting a frame)
+        // loses that frame, and the decoder picks up again at the next one
+        for (_, frame) in mp3::split_frames(&chunk.data) {
+            let Some((sample_rate, pcm)) = decode_frame(decoder.as_mut(), frame) else {
+                continue;
+            };
+            if sample_rate != encoders.sample_rate {
+                encoders.flush(&mut outputs);
+                encoders = Encoders::new(&mounts, sample_rate);
+            }
+            encoders.encode(&pcm, &mut outputs);
+        }
+
+        for (mount, output) in mounts.iter().zip(outputs.iter_mut()) {
+            if !output.is_empty() {
+                mount.publish(Bytes::from(std::mem::take(output)), chunk.duration_ms);
+            }
+        }
+    }
+    info!("Simulcast stopped");
+}
+
+/// Interleaved stereo samples of one frame and their sample rate
+fn decode_frame(decoder: &mut dyn Decoder, frame: &[u8]) -> Option<(u32, Vec<i16>)> {
+    let decoded = match decoder.decode(&Packet::new_from_slice(0, 0, 0, frame)) {
+        Ok(decoded) => decoded,
+        Err(DecodeError::DecodeError(_)) => return None, // Damaged frame (or a missing bit reservoir after a gap)
+        Err(e) => {
+            warn!("Simulcast decode error: {}", e);
+            decoder.reset();
+            return None;
+        }
+    };
+    Some(encode::stereo_pcm(decoded))
+}
+
+/// One LAME encoder per mount at the current sample rate; `None` where it couldn't be created
+struct Encoders {
+    sample_rate: u32,
+    encoders: Vec<Option<Encoder>>,
+}
+
+impl Encoders {
+    fn new(mounts: &[Arc<Mount>], sample_rate: u32) -> Self {
+        let encoders = mounts.iter()
+            .map(|mount| match encode::cbr_encoder(mount.bitrate_kbps, sample_rate) {
+                Ok(encoder) => Some(encoder),
+                Err(e) => {
+                    warn!("Simulcast mount {} can't encode {}Hz audio: {}", mount.name, sample_rate, e);
+                    None
+                }
+            })
+            .collect();
+        Self { sample_rate, encoders }
+    }
+
+    fn encode(&mut self, pcm: &[i16], outputs: &mut [Vec<u8>]) {
+        for (encoder, output) in self.encoders.iter_mut().zip(outputs.iter_mut()) {
+            let Some(encoder) = encoder else { continue };
+            if let Err(e) = encode::encode(encoder, pcm, output) {
+                warn!("Simulcast encode error: {}", e);
+            }
+        }
+    }
+
+    // Emit what the encoders still hold before they are replaced
+    fn flush(&mut self, outputs: &mut [Vec<u8>]) {
+        for (encoder, output) in self.encoders.iter_mut().zip(outputs.iter_mut()) {
+            let Some(encoder) = encoder else { continue };
+            let _ = encode::flush(encoder, output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp3::FrameHeader;
+
+    fn mount(name: &str, bitrate_kbps: u32) -> SimulcastMount {
+        SimulcastMount { name: name.to_string(), bitrate_kbps }
+    }
+
+    #[test]
+    fn test_skips_unsupported_and_duplicate_mounts() {
+        let simulcast = Simulcast::new(&[mount("low", 64), mount("odd", 65), mount("low", 96)]);
+        let names: Vec<&str> = simulcast.mounts().iter().map(|m| m.name()).collect();
+        assert_eq!(names, vec!["low"]);
+        assert_eq!(simulcast.mount("low").unwrap().path(), "/stream-low");
+    }
+
+    #[test]
+    fn test_reencodes_broadcast_at_each_bitrate() {
+        let simulcast = Simulcast::new(&[mount("low", 64), mount("high", 192)]);
+        let (tx, rx) = broadcast::channel(64);
+        let thread = simulcast.spawn(rx).unwrap();
+
+        // One second of 128kbps tone, broadcast in ~100ms chunks like the playout loop
+        let tone = crate::tone::sine_mp3(440, 1, 128).unwrap();
+        for frames in mp3::split_frames(&tone).chunks(4) {
+            let data: Vec<u8> = frames.iter().flat_map(|(_, frame)| frame.iter().copied()).collect();
+            let duration_ms = frames.iter().map(|(header, _)| header.duration_ms()).sum();
+            tx.send(AudioChunk { data: Bytes::from(data), duration_ms, position_ms: 0.0, checksum: 0, generation: 0 }).unwrap();
+        }
+        drop(tx);
+        thread.join().unwrap();
+
+        for (name, kbps) in [("low", 64), ("high", 192)] {
+            let (burst, _) = simulcast.mount(name).unwrap().subscribe();
+            let encoded: Vec<u8> = burst.concat();
+            let frames = mp3::split_frames(&encoded);
+            assert!(frames.iter().all(|(header, _)| header.bitrate_kbps == kbps), "{}", name);
+            // LAME holds back its last frames until flushed
+            let duration_ms: f64 = frames.iter().map(|(header, _)| header.duration_ms()).sum();
+            assert!((700.0..1100.0).contains(&duration_ms), "{}: {}ms", name, duration_ms);
+            assert!(FrameHeader::parse(&encoded).is_some());
+        }
+    }
+}
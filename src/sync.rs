@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::Serialize;
+
+/// Playout clock shared by every player that wants to stay in sync.
+///
+/// Each broadcast chunk gets a position on a continuous audio timeline (ms of audio
+/// since the broadcast started). Audio at position `P` is due to be heard at wall
+/// time `epoch + P + delay`. The broadcast runs at or above realtime, so chunks are
+/// normally sent before they are due; if the broadcast stalls and a chunk goes out
+/// late, the epoch moves forward so the schedule stays achievable.
+#[derive(Debug)]
+pub struct SyncClock {
+    epoch_ms: AtomicU64,      // Unix ms; 0 until the first chunk
+    position_ns: AtomicU64,   // Timeline position of the next chunk; ns so rounding can't add up over days
+    delay_ms: u64,
+    epoch_shifts: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyncSnapshot {
+    pub server_time_ms: u64,
+    pub epoch_ms: u64,
+    pub delay_ms: u64,
+    pub live_position_ms: f64,    // Position of the newest broadcast audio
+    pub target_position_ms: f64,  // Position every synced player should be hearing now
+    pub epoch_shifts: u64,
+}
+
+impl SyncClock {
+    pub fn new(delay_ms: u64) -> Self {
+        Self {
+            epoch_ms: AtomicU64::new(0),
+            position_ns: AtomicU64::new(0),
+            delay_ms,
+            epoch_shifts: AtomicU64::new(0),
+        }
+    }
+
+    /// Register a chunk being broadcast at `now_ms`; returns its timeline position
+    pub fn advance(&self, duration_ms: f64, now_ms: u64) -> f64 {
+        let position_ns = self.position_ns.fetch_add((duration_ms * 1_000_000.0).round() as u64, Ordering::Relaxed);
+        let position_ms = position_ns / 1_000_000;
+
+        let epoch = self.epoch_ms.load(Ordering::Relaxed);
+        let due = epoch + position_ms;
+        if epoch == 0 || now_ms > due {
+            // First chunk, or the broadcast fell behind its own schedule
+            self.epoch_ms.store(now_ms.saturating_sub(position_ms).max(1), Ordering::Relaxed);
+            if epoch != 0 {
+                self.epoch_shifts.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        position_ns as f64 / 1_000_000.0
+    }
+
+    /// Timeline position the next chunk will get
+    pub fn next_position_ms(&self) -> f64 {
+        self.position_ns.load(Ordering::Relaxed) as f64 / 1_000_000.0
+    }
+
+    pub fn snapshot(&self, now_ms: u64) -> SyncSnapshot {
+        let epoch_ms = self.epoch_ms.load(Ordering::Relaxed);
+        let target_position_ms = if epoch_ms == 0 {
+            0.0
+        } else {
+            (now_ms as f64 - epoch_ms as f64 - self.delay_ms as f64).max(0.0)
+        };
+
+        SyncSnapshot {
+            server_time_ms: now_ms,
+            epoch_ms,
+            delay_ms: self.delay_ms,
+            live_position_ms: self.position_ns.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+            target_position_ms,
+            epoch_shifts: self.epoch_shifts.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions_accumulate() {
+        let clock = SyncClock::new(3000);
+        assert_eq!(clock.advance(100.0, 10_000), 0.0);
+        assert_eq!(clock.advance(100.0, 10_050), 100.0);
+        assert_eq!(clock.snapshot(10_050).live_position_ms, 200.0);
+    }
+
+    #[test]
+    fn test_frame_positions_stay_exact() {
+        let clock = SyncClock::new(0);
+        let frame_ms = 1152.0 * 1000.0 / 44100.0;
+        // A day of 4-frame chunks
+        for _ in 0..827_000 {
+            clock.advance(4.0 * frame_ms, 10_000);
+        }
+        let expected = 827_000.0 * 4.0 * frame_ms;
+        assert!((clock.next_position_ms() - expected).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_epoch_fixed_while_ahead_of_schedule() {
+        let clock = SyncClock::new(3000);
+        clock.advance(1000.0, 10_000);
+        // Broadcast faster than realtime: chunk at position 1000 sent after only 500ms
+        clock.advance(1000.0, 10_500);
+
+        let snapshot = clock.snapshot(14_000);
+        assert_eq!(snapshot.epoch_ms, 10_000);
+        assert_eq!(snapshot.target_position_ms, 1000.0);
+        assert_eq!(snapshot.epoch_shifts, 0);
+    }
+
+    #[test]
+    fn test_epoch_shifts_after_stall() {
+        let clock = SyncClock::new(0);
+        clock.advance(1000.0, 10_000);
+        // Position 1000 was due at 11_000 but went out at 15_000
+        assert_eq!(clock.advance(1000.0, 15_000), 1000.0);
+
+        let snapshot = clock.snapshot(15_000);
+        assert_eq!(snapshot.epoch_ms, 14_000);
+        assert_eq!(snapshot.target_position_ms, 1000.0);
+        assert_eq!(snapshot.epoch_shifts, 1);
+    }
+
+    #[test]
+    fn test_snapshot_before_first_chunk() {
+        let clock = SyncClock::new(3000);
+        let snapshot = clock.snapshot(5000);
+        assert_eq!(snapshot.epoch_ms, 0);
+        assert_eq!(snapshot.target_position_ms, 0.0);
+    }
+}
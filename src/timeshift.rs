@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::radio::AudioChunk;
+
+/// The last few minutes of broadcast chunks, so listeners can join in the past
+/// (`/stream?rewind=120`). Chunks are ordered by their sync timeline position,
+/// which listeners use as a replay cursor.
+pub struct TimeshiftBuffer {
+    max_ms: f64,
+    chunks: Mutex<VecDeque<AudioChunk>>,
+}
+
+impl TimeshiftBuffer {
+    pub fn new(max_ms: f64) -> Self {
+        Self {
+            max_ms,
+            chunks: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_ms > 0.0
+    }
+
+    pub fn push(&self, chunk: &AudioChunk) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut chunks = self.chunks.lock().unwrap();
+        chunks.push_back(chunk.clone());
+        while chunks.front().is_some_and(|oldest| chunk.position_ms - oldest.position_ms > self.max_ms) {
+            chunks.pop_front();
+        }
+    }
+
+    /// Position of the chunk a listener rewinding `rewind_ms` should start with
+    /// (clamped to the oldest buffered chunk), or `None` if nothing is buffered
+    pub fn start_position(&self, rewind_ms: f64) -> Option<f64> {
+        let chunks = self.chunks.lock().unwrap();
+        let newest = chunks.back()?;
+        let target = newest.position_ms + newest.duration_ms - rewind_ms;
+        chunks.iter()
+            .find(|chunk| chunk.position_ms + chunk.duration_ms > target)
+            .map(|chunk| chunk.position_ms)
+    }
+
+    /// The first chunk at or after `position_ms`. A cursor that has fallen out of
+    /// the buffer resumes at the oldest chunk still held.
+    pub fn chunk_at(&self, position_ms: f64) -> Option<AudioChunk> {
+        let chunks = self.chunks.lock().unwrap();
+        let index = chunks.partition_point(|chunk| chunk.position_ms < position_ms);
+        chunks.get(index).cloned()
+    }
+
+    /// The chunk following the one at `position_ms`, or `None` at the live edge
+    pub fn chunk_after(&self, position_ms: f64) -> Option<AudioChunk> {
+        let chunks = self.chunks.lock().unwrap();
+        let index = chunks.partition_point(|chunk| chunk.position_ms <= position_ms);
+        chunks.get(index).cloned()
+    }
+
+    /// The newest chunks adding up to at most `max_bytes`, oldest first
+    pub fn tail(&self, max_bytes: usize) -> Vec<AudioChunk> {
+        let chunks = self.chunks.lock().unwrap();
+        let mut bytes = 0;
+        let count = chunks.iter().rev()
+            .take_while(|chunk| {
+                bytes += chunk.data.len();
+                bytes <= max_bytes
+            })
+            .count();
+        chunks.range(chunks.len() - count..).cloned().collect()
+    }
+
+    /// Audio held, in milliseconds
+    pub fn buffered_ms(&self) -> f64 {
+        let chunks = self.chunks.lock().unwrap();
+        match (chunks.front(), chunks.back()) {
+            (Some(oldest), Some(newest)) => newest.position_ms + newest.duration_ms - oldest.position_ms,
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn chunk(position_ms: f64) -> AudioChunk {
+        AudioChunk { data: Bytes::from(vec![0u8; 10]), duration_ms: 100.0, position_ms, checksum: 0, generation: 0 }
+    }
+
+    #[test]
+    fn test_keeps_only_the_window() {
+        let buffer = TimeshiftBuffer::new(1000.0);
+        for i in 0..30 {
+            buffer.push(&chunk(i as f64 * 100.0));
+        }
+        assert_eq!(buffer.buffered_ms(), 1100.0);
+        // A cursor older than the window resumes at the oldest chunk
+        assert_eq!(buffer.chunk_at(0.0).unwrap().position_ms, 1900.0);
+    }
+
+    #[test]
+    fn test_rewind_cursor() {
+        let buffer = TimeshiftBuffer::new(60_000.0);
+        assert_eq!(buffer.start_position(500.0), None);
+        for i in 0..10 {
+            buffer.push(&chunk(i as f64 * 100.0));
+        }
+        // Live edge is 1000ms; rewinding 500ms starts at the chunk covering 500ms
+        assert_eq!(buffer.start_position(500.0), Some(500.0));
+        assert_eq!(buffer.start_position(10_000.0), Some(0.0));
+        assert_eq!(buffer.chunk_at(550.0).unwrap().position_ms, 600.0);
+        assert!(buffer.chunk_at(1000.0).is_none());
+        assert_eq!(buffer.chunk_after(500.0).unwrap().position_ms, 600.0);
+        assert!(buffer.chunk_after(900.0).is_none());
+    }
+
+    #[test]
+    fn test_tail() {
+        let buffer = TimeshiftBuffer::new(60_000.0);
+        assert!(buffer.tail(100).is_empty());
+        for i in 0..10 {
+            buffer.push(&chunk(i as f64 * 100.0));
+        }
+        let tail = buffer.tail(35);
+        assert_eq!(tail.iter().map(|chunk| chunk.position_ms).collect::<Vec<_>>(), vec![700.0, 800.0, 900.0]);
+        assert_eq!(buffer.tail(1000).len(), 10);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let buffer = TimeshiftBuffer::new(0.0);
+        buffer.push(&chunk(0.0));
+        assert!(!buffer.is_enabled());
+        assert_eq!(buffer.start_position(0.0), None);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use arc_swap::ArcSwap;
+use axum::{extract::ConnectInfo, Router};
+use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+use tokio::net::TcpListener;
+use tokio_rustls::{rustls, TlsAcceptor};
+use tower::ServiceExt;
+use tracing::{debug, info, warn};
+
+use crate::error::Result;
+
+// How often certificate files are checked for renewal
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Build a rustls server config from PEM certificate chain and private key files
+/// (PKCS#8, PKCS#1 RSA or SEC1 EC keys)
+pub fn load_server_config(cert_path: &Path, key_path: &Path) -> Result<Arc<rustls::ServerConfig>> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+
+    let mut reader = std::io::BufReader::new(std::fs::File::open(cert_path)?);
+    let certs: Vec<rustls::Certificate> = rustls_pemfile::certs(&mut reader)?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    if certs.is_empty() {
+        return Err(invalid(format!("no certificates in {}", cert_path.display())).into());
+    }
+
+    let mut reader = std::io::BufReader::new(std::fs::File::open(key_path)?);
+    let key = loop {
+        match rustls_pemfile::read_one(&mut reader)? {
+            Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => break rustls::PrivateKey(key),
+            Some(_) => continue,
+            None => return Err(invalid(format!("no private key in {}", key_path.display())).into()),
+        }
+    };
+
+    let mut config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid(e.to_string()))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// TLS settings that pick up renewed certificate files (certbot, lego, ...)
+/// without a restart; new connections use the latest certificate
+pub struct TlsConfig {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: ArcSwap<rustls::ServerConfig>,
+}
+
+impl TlsConfig {
+    pub fn load(cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Result<Arc<Self>> {
+        let cert_path = cert_path.into();
+        let key_path = key_path.into();
+        let config = load_server_config(&cert_path, &key_path)?;
+        Ok(Arc::new(Self {
+            cert_path,
+            key_path,
+            current: ArcSwap::new(config),
+        }))
+    }
+
+    pub fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.current.load_full())
+    }
+
+    /// Reload whenever either file's modification time changes. A renewal that
+    /// fails to load keeps the previous certificate in service.
+    pub fn spawn_reload(self: &Arc<Self>) {
+        let tls = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut last = tls.modified();
+            loop {
+                tokio::time::sleep(RELOAD_CHECK_INTERVAL).await;
+                let modified = tls.modified();
+                if modified == last {
+                    continue;
+                }
+                last = modified;
+                match load_server_config(&tls.cert_path, &tls.key_path) {
+                    Ok(config) => {
+                        tls.current.store(config);
+                        info!("Reloaded TLS certificate from {}", tls.cert_path.display());
+                    }
+                    Err(e) => warn!("Failed to reload TLS certificate, keeping the current one: {}", e),
+                }
+            }
+        });
+    }
+
+    fn modified(&self) -> (Option<SystemTime>, Option<SystemTime>) {
+        let mtime = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        (mtime(&self.cert_path), mtime(&self.key_path))
+    }
+}
+
+/// Serve `app` over HTTPS until `shutdown` completes. Handlers see the client
+/// address as `ConnectInfo<SocketAddr>`, as with plain `axum::serve`.
+pub async fn serve(listener: TcpListener, app: Router, tls: Arc<TlsConfig>, shutdown: impl Future<Output = ()>) {
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let acceptor = tls.acceptor();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    debug!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+                Err(_) => {
+                    debug!("TLS handshake with {} timed out", peer);
+                    return;
+                }
+            };
+
+            let service = app.map_request(move |mut request: axum::extract::Request<hyper::body::Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(peer));
+                request
+            });
+            let connection = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), TowerToHyperService::new(service))
+                .with_upgrades(); // WebSocket streams
+            if let Err(e) = connection.await {
+                debug!("Connection from {} closed with error: {}", peer, e);
+            }
+        });
+    }
+    info!("HTTPS listener stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_server_config() {
+        let config = load_server_config(Path::new("tests/fixtures/localhost.crt"), Path::new("tests/fixtures/localhost.key"));
+        assert!(config.is_ok());
+
+        // The certificate file holds no private key
+        let err = load_server_config(Path::new("tests/fixtures/localhost.crt"), Path::new("tests/fixtures/localhost.crt"));
+        assert!(err.unwrap_err().to_string().contains("no private key"));
+    }
+}
@@ -0,0 +1,81 @@
+// Generated test tone for /test-audio: a sine wave encoded to MP3 with LAME, so
+// clients can check end-to-end playback without depending on the music library
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Mutex, OnceLock},
+};
+use bytes::Bytes;
+
+use crate::encode;
+
+pub const SAMPLE_RATE: u32 = 44_100;
+pub const DEFAULT_FREQUENCY_HZ: u32 = 440;
+pub const DEFAULT_SECONDS: u32 = 5;
+pub const DEFAULT_BITRATE_KBPS: u32 = 128;
+pub const MAX_SECONDS: u32 = 30;
+const AMPLITUDE: f64 = 0.5 * i16::MAX as f64; // -6 dBFS
+const FADE_SECS: f64 = 0.01;                  // Avoids clicks at the start and end
+
+// Encoded tones by (frequency, seconds, bitrate); only a handful are ever requested
+type ToneKey = (u32, u32, u32);
+static CACHE: OnceLock<Mutex<HashMap<ToneKey, Bytes>>> = OnceLock::new();
+
+/// The MP3 for a tone, encoding it on first use
+pub fn sine_mp3(frequency_hz: u32, seconds: u32, bitrate_kbps: u32) -> io::Result<Bytes> {
+    let key = (frequency_hz, seconds, bitrate_kbps);
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some(mp3) = cache.lock().unwrap().get(&key) {
+        return Ok(mp3.clone());
+    }
+    let mp3 = Bytes::from(encode_sine(frequency_hz, seconds, bitrate_kbps)?);
+    cache.lock().unwrap().insert(key, mp3.clone());
+    Ok(mp3)
+}
+
+// Encoded in one pass and flushed, so the file ends with complete frames
+fn encode_sine(frequency_hz: u32, seconds: u32, bitrate_kbps: u32) -> io::Result<Vec<u8>> {
+    let mut encoder = encode::cbr_encoder(bitrate_kbps, SAMPLE_RATE)?;
+    let mut mp3 = Vec::new();
+    encode::encode(&mut encoder, &sine_pcm(frequency_hz, seconds), &mut mp3)?;
+    encode::flush(&mut encoder, &mut mp3)?;
+    Ok(mp3)
+}
+
+// Interleaved stereo samples with short fades in and out
+fn sine_pcm(frequency_hz: u32, seconds: u32) -> Vec<i16> {
+    let total = (SAMPLE_RATE * seconds) as usize;
+    let fade = (SAMPLE_RATE as f64 * FADE_SECS) as usize;
+    let mut pcm = Vec::with_capacity(total * 2);
+    for n in 0..total {
+        let t = n as f64 / SAMPLE_RATE as f64;
+        let gain = (n.min(total - 1 - n) as f64 / fade as f64).min(1.0);
+        let sample = (AMPLITUDE * gain * (2.0 * std::f64::consts::PI * frequency_hz as f64 * t).sin()) as i16;
+        pcm.push(sample);
+        pcm.push(sample);
+    }
+    pcm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp3::{split_frames, FrameHeader};
+
+    #[test]
+    fn test_tone_is_whole_mp3_frames() {
+        let mp3 = sine_mp3(DEFAULT_FREQUENCY_HZ, 1, DEFAULT_BITRATE_KBPS).unwrap();
+        let frames = split_frames(&mp3);
+        let framed: usize = frames.iter().map(|(_, frame)| frame.len()).sum();
+        assert_eq!(framed, mp3.len());
+
+        let header = FrameHeader::parse(&mp3).unwrap();
+        assert_eq!(header.bitrate_kbps, 128);
+        let duration_ms: f64 = frames.iter().map(|(header, _)| header.duration_ms()).sum();
+        assert!((950.0..1200.0).contains(&duration_ms), "{}", duration_ms);
+
+        // Cached
+        assert_eq!(sine_mp3(DEFAULT_FREQUENCY_HZ, 1, DEFAULT_BITRATE_KBPS).unwrap().as_ptr(), mp3.as_ptr());
+    }
+}
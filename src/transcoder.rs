@@ -0,0 +1,272 @@
+use std::{
+    path::PathBuf,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::{broadcast, Mutex},
+};
+use tracing::{debug, info, warn};
+
+use crate::radio::AudioChunk;
+
+/// Command line of an encoder process reading MP3 on stdin and writing the
+/// target format to stdout (normally ffmpeg)
+#[derive(Debug, Clone)]
+pub struct EncoderSpec {
+    pub name: String, // Output name for logs and stats, e.g. "ogg"
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl EncoderSpec {
+    /// ffmpeg reading MP3 from stdin and writing `format` with `codec_args` to stdout
+    pub fn ffmpeg(name: &str, ffmpeg: impl Into<PathBuf>, format: &str, codec_args: &[&str]) -> Self {
+        let mut args: Vec<String> = ["-hide_banner", "-loglevel", "error", "-f", "mp3", "-i", "pipe:0"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        args.extend(codec_args.iter().map(|s| s.to_string()));
+        args.extend(["-f", format, "pipe:1"].iter().map(|s| s.to_string()));
+        Self {
+            name: name.to_string(),
+            program: ffmpeg.into(),
+            args,
+        }
+    }
+}
+
+/// A running encoder process
+pub struct Encoder {
+    child: Child,
+    pub stdin: ChildStdin,
+    pub stdout: ChildStdout,
+}
+
+impl Encoder {
+    fn spawn(spec: &EncoderSpec) -> std::io::Result<Self> {
+        let mut child = Command::new(&spec.program)
+            .args(&spec.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| std::io::Error::other("encoder stdin unavailable"))?;
+        let stdout = child.stdout.take().ok_or_else(|| std::io::Error::other("encoder stdout unavailable"))?;
+        Ok(Self { child, stdin, stdout })
+    }
+
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+/// Pre-spawned encoder processes for one output, so switching to the output or
+/// replacing a crashed encoder doesn't wait for process startup (ffmpeg takes
+/// hundreds of milliseconds to seconds before it produces its first frame)
+pub struct WarmPool {
+    spec: EncoderSpec,
+    standby: usize,
+    idle: Mutex<Vec<Encoder>>,
+    spawned: AtomicU64,
+    taken_warm: AtomicU64,
+    taken_cold: AtomicU64,
+}
+
+impl WarmPool {
+    pub fn new(spec: EncoderSpec, standby: usize) -> Arc<Self> {
+        Arc::new(Self {
+            spec,
+            standby,
+            idle: Mutex::new(Vec::new()),
+            spawned: AtomicU64::new(0),
+            taken_warm: AtomicU64::new(0),
+            taken_cold: AtomicU64::new(0),
+        })
+    }
+
+    /// Spawn encoders until `standby` live ones are idle, dropping any that exited
+    pub async fn fill(&self) -> std::io::Result<()> {
+        let mut idle = self.idle.lock().await;
+        idle.retain_mut(|encoder| encoder.is_alive());
+        while idle.len() < self.standby {
+            idle.push(Encoder::spawn(&self.spec)?);
+            self.spawned.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// A live encoder: a warm one if available, otherwise a freshly spawned one.
+    /// The pool is refilled in the background.
+    pub async fn take(self: &Arc<Self>) -> std::io::Result<Encoder> {
+        let warm = {
+            let mut idle = self.idle.lock().await;
+            let mut found = None;
+            while let Some(mut encoder) = idle.pop() {
+                if encoder.is_alive() {
+                    found = Some(encoder);
+                    break;
+                }
+                debug!("Discarding exited standby encoder for {}", self.spec.name);
+            }
+            found
+        };
+
+        let encoder = match warm {
+            Some(encoder) => {
+                self.taken_warm.fetch_add(1, Ordering::Relaxed);
+                encoder
+            }
+            None => {
+                self.taken_cold.fetch_add(1, Ordering::Relaxed);
+                self.spawned.fetch_add(1, Ordering::Relaxed);
+                Encoder::spawn(&self.spec)?
+            }
+        };
+
+        let pool = Arc::clone(self);
+        tokio::spawn(async move {
+            if let Err(e) = pool.fill().await {
+                warn!("Failed to refill {} encoder pool: {}", pool.spec.name, e);
+            }
+        });
+        Ok(encoder)
+    }
+
+    pub fn stats(&self) -> serde_json::Value {
+        serde_json::json!({
+            "output": self.spec.name,
+            "standby": self.standby,
+            "spawned": self.spawned.load(Ordering::Relaxed),
+            "taken_warm": self.taken_warm.load(Ordering::Relaxed),
+            "taken_cold": self.taken_cold.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Feed broadcast audio through an encoder from `pool` and publish its output.
+/// When the encoder dies it is replaced from the pool immediately; the chunk that
+/// failed to write is re-sent to the replacement. Returns when `input` closes.
+pub async fn run_output(pool: Arc<WarmPool>, mut input: broadcast::Receiver<AudioChunk>, output: broadcast::Sender<Bytes>) {
+    let name = pool.spec.name.clone();
+    let mut pending: Option<Bytes> = None;
+
+    loop {
+        let encoder = match pool.take().await {
+            Ok(encoder) => encoder,
+            Err(e) => {
+                warn!("Failed to start {} encoder: {}", name, e);
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let Encoder { child, mut stdin, mut stdout } = encoder;
+
+        // Forward encoder output until it closes
+        let tx = output.clone();
+        let reader = tokio::spawn(async move {
+            let _child = child; // Killed when the reader finishes
+            let mut buf = vec![0u8; 16 * 1024];
+            while let Ok(n) = stdout.read(&mut buf).await {
+                if n == 0 {
+                    break;
+                }
+                let _ = tx.send(Bytes::copy_from_slice(&buf[..n]));
+            }
+        });
+
+        let mut input_closed = false;
+        loop {
+            let data = match pending.take() {
+                Some(data) => data,
+                None => match input.recv().await {
+                    Ok(chunk) => chunk.data,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("{} encoder input lagged by {} chunks", name, skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        input_closed = true;
+                        break;
+                    }
+                },
+            };
+            if stdin.write_all(&data).await.is_err() || reader.is_finished() {
+                pending = Some(data);
+                break;
+            }
+        }
+
+        drop(stdin);
+        if input_closed {
+            let _ = reader.await;
+            info!("{} output stopped", name);
+            return;
+        }
+        reader.abort();
+        warn!("{} encoder exited, switching to a standby encoder", name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passthrough() -> EncoderSpec {
+        EncoderSpec {
+            name: "test".to_string(),
+            program: PathBuf::from("cat"),
+            args: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_uses_warm_encoder_and_refills() {
+        let pool = WarmPool::new(passthrough(), 1);
+        pool.fill().await.unwrap();
+
+        let mut encoder = pool.take().await.unwrap();
+        encoder.stdin.write_all(b"frame").await.unwrap();
+        let mut buf = [0u8; 5];
+        encoder.stdout.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"frame");
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let stats = pool.stats();
+        assert_eq!(stats["taken_warm"], 1);
+        assert_eq!(stats["spawned"], 2); // Initial standby plus its replacement
+    }
+
+    #[tokio::test]
+    async fn test_exited_standby_is_replaced() {
+        // `head -c 3` stands in for an encoder that crashes after some input
+        let spec = EncoderSpec { program: PathBuf::from("head"), args: vec!["-c".into(), "3".into()], ..passthrough() };
+        let pool = WarmPool::new(spec, 1);
+
+        let (input_tx, input_rx) = broadcast::channel(16);
+        let (output_tx, mut output_rx) = broadcast::channel(16);
+        let task = tokio::spawn(run_output(Arc::clone(&pool), input_rx, output_tx));
+
+        let chunk = |data: &'static [u8]| AudioChunk { data: Bytes::from_static(data), duration_ms: 0.0, position_ms: 0.0, checksum: 0, generation: 0 };
+        let mut received = Vec::new();
+        for data in [b"abc" as &[u8], b"def", b"ghi"] {
+            input_tx.send(chunk(data)).unwrap();
+            let out = tokio::time::timeout(std::time::Duration::from_secs(5), output_rx.recv()).await.unwrap().unwrap();
+            received.extend_from_slice(&out);
+            // Let the crashed encoder finish exiting so the next write fails over
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert_eq!(received, b"abcdefghi");
+        assert!(pool.stats()["spawned"].as_u64().unwrap() >= 3);
+
+        drop(input_tx);
+        tokio::time::timeout(std::time::Duration::from_secs(5), task).await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+// Watches MUSIC_DIR and rescans the playlist when audio files are added, removed or renamed
+
+use std::{path::Path, sync::Arc, time::Duration};
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::{playlist, radio::RadioStation};
+
+// Wait this long after the last change before rescanning, so copies in progress finish
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// Start watching the station's music directory. The watcher lives as long as the
+/// returned task.
+pub fn spawn(station: Arc<RadioStation>) -> notify::Result<tokio::task::JoinHandle<()>> {
+    let music_dir = station.config().music_dir.clone();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) if is_relevant(&event) => {
+                let _ = tx.send(());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Music directory watch error: {}", e),
+        }
+    })?;
+    watcher.watch(&music_dir, RecursiveMode::Recursive)?;
+    info!("Watching {} for added and removed tracks", music_dir.display());
+
+    Ok(tokio::spawn(async move {
+        let _watcher = watcher;
+        while rx.recv().await.is_some() {
+            // Let a burst of changes settle into one rescan
+            while let Ok(Some(())) = tokio::time::timeout(SETTLE, rx.recv()).await {}
+            rescan(&station, &music_dir).await;
+        }
+    }))
+}
+
+/// Rescan `music_dir` and swap in the result if any tracks were added or removed
+pub async fn rescan(station: &RadioStation, music_dir: &Path) {
+    let current = station.playlist().snapshot();
+    match current.rescan(music_dir).await {
+        Ok(Some(updated)) => {
+            let tracks = updated.tracks.clone();
+            station.playlist().update(|playlist| playlist.replace_tracks(tracks.clone()));
+            if let Err(e) = station.playlist().save_to(music_dir).await {
+                warn!("Failed to save playlist: {}", e);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to rescan {}: {}", music_dir.display(), e),
+    }
+}
+
+// File creation, removal and renames of audio files (or directories that may hold them);
+// finished writes count too, since a copy creates the file before its data lands
+fn is_relevant(event: &notify::Event) -> bool {
+    let kind_matters = matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(notify::event::ModifyKind::Name(_))
+            | EventKind::Access(notify::event::AccessKind::Close(notify::event::AccessMode::Write))
+    );
+    kind_matters && event.paths.iter().any(|path| path.extension().is_none() || playlist::is_audio_file(path)) // No extension: directories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, ModifyKind};
+
+    fn event(kind: EventKind, path: &str) -> notify::Event {
+        notify::Event::new(kind).add_path(path.into())
+    }
+
+    #[test]
+    fn test_relevant_events() {
+        assert!(is_relevant(&event(EventKind::Create(CreateKind::File), "music/new.mp3")));
+        assert!(is_relevant(&event(EventKind::Create(CreateKind::File), "music/new.FLAC")));
+        assert!(is_relevant(&event(EventKind::Create(CreateKind::File), "music/new.ogg")));
+        assert!(is_relevant(&event(EventKind::Remove(notify::event::RemoveKind::Any), "music/album")));
+        assert!(!is_relevant(&event(EventKind::Create(CreateKind::File), "music/playlist.json")));
+        assert!(!is_relevant(&event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), "music/a.mp3")));
+    }
+}
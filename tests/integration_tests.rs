@@ -267,7 +267,7 @@ async fn test_health_endpoint_integration() {
     use webradio::Config;
 
     // Create config
-    let config = Config::from_env();
+    let _config = Config::from_env();
 
     // Bind to a random port on localhost (127.0.0.1:0)
     let listener = match tokio::net::TcpListener::bind("127.0.0.1:0").await {
@@ -407,3 +407,56 @@ async fn test_stats_endpoint_integration() {
 
     server.abort();
 }
+
+#[tokio::test]
+async fn test_analyze_library_saves_and_skips_unchanged() {
+    let music_dir = std::env::temp_dir().join(format!("webradio_analyze_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&music_dir).unwrap();
+    let tone = webradio::tone::sine_mp3(440, 2, 128).unwrap();
+    std::fs::write(music_dir.join("tone.mp3"), &tone).unwrap();
+
+    let summary = webradio::analyze::analyze_library(&music_dir, 2, false, false, -50.0).await.unwrap();
+    assert_eq!((summary.analyzed, summary.unchanged, summary.failed), (1, 0, 0));
+
+    let playlist: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(music_dir.join("playlist.json")).unwrap()).unwrap();
+    let analysis = &playlist["tracks"][0]["analysis"];
+    assert_eq!(analysis["sha256"].as_str().unwrap().len(), 64);
+    assert!(analysis["loudness_lufs"].as_f64().unwrap() < 0.0);
+    assert!(analysis["duration_ms"].as_u64().unwrap() > 0);
+    assert!(!analysis["fingerprint"].as_str().unwrap().is_empty());
+
+    let again = webradio::analyze::analyze_library(&music_dir, 2, false, false, -50.0).await.unwrap();
+    assert_eq!((again.analyzed, again.unchanged), (0, 1));
+    // A new silence threshold means measuring again
+    let retuned = webradio::analyze::analyze_library(&music_dir, 2, false, false, -40.0).await.unwrap();
+    assert_eq!(retuned.analyzed, 1);
+    let forced = webradio::analyze::analyze_library(&music_dir, 2, true, false, -50.0).await.unwrap();
+    assert_eq!(forced.analyzed, 1);
+
+    std::fs::remove_dir_all(&music_dir).ok();
+}
+
+#[tokio::test]
+async fn test_analyze_benches_duplicates() {
+    let music_dir = std::env::temp_dir().join(format!("webradio_duplicates_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&music_dir).unwrap();
+    // The first half minute or so is plenty to fingerprint
+    let mut birds = std::fs::read("music/Singing Birds.mp3").unwrap();
+    birds.truncate(600_000);
+    std::fs::write(music_dir.join("Birds.mp3"), &birds).unwrap();
+    std::fs::write(music_dir.join("Birds (copy).mp3"), &birds).unwrap();
+
+    let summary = webradio::analyze::analyze_library(&music_dir, 2, false, true, -50.0).await.unwrap();
+    assert_eq!(summary.benched_duplicates, 1);
+
+    let playlist = webradio::playlist::Playlist::load_or_scan(&music_dir).await.unwrap();
+    let groups = webradio::fingerprint::find_duplicates(&playlist.tracks);
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].tracks.len(), 2);
+    let benched: Vec<_> = playlist.tracks.iter().filter(|track| !track.enabled).collect();
+    assert_eq!(benched.len(), 1);
+    assert_eq!(benched[0].id, groups[0].tracks[1]);
+
+    std::fs::remove_dir_all(&music_dir).ok();
+}
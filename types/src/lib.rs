@@ -0,0 +1,264 @@
+// API response types for WebRadio
+// The server serializes these and `webradio::client` deserializes them, so Rust
+// frontends (Yew, Leptos, ...) can depend on this crate alone for the exact shapes.
+// It must keep building for wasm32-unknown-unknown: serde only, no std::fs/net.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// `/api/now-playing`, the `now-playing` SSE event and WebSocket text frames.
+/// While nothing is playing the title is "No track playing" and the track
+/// fields are empty.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NowPlaying {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub isrc: Option<String>,
+    pub composer: Option<String>,
+    pub label: Option<String>,
+    pub tags: BTreeMap<String, String>,
+    pub duration: Option<u64>, // Seconds
+    pub bitrate: u64,          // kbps
+    pub position: u64,         // Seconds into the track
+    pub listeners: usize,
+    pub server_time_ms: u64,
+    pub sync_position_ms: u64, // Timeline position where the current track starts
+    pub generation: u64,       // Changes with every track; audio chunks carry the same id
+}
+
+/// A playlist entry as served by `/api/playlist`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackDto {
+    pub id: String, // Stable across rescans and renames; admin endpoints take it
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration: Option<u64>,
+    pub bitrate: Option<u64>, // bits/s
+    #[serde(default)]
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub composer: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub replaygain_gain_db: Option<f64>, // REPLAYGAIN_TRACK_GAIN tag
+    #[serde(default)]
+    pub replaygain_peak: Option<f64>,    // REPLAYGAIN_TRACK_PEAK tag, 1.0 = full scale
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<TrackAnalysis>,
+    #[serde(default = "enabled")]
+    pub enabled: bool, // false: benched, skipped by the rotation
+    #[serde(default)]
+    pub disabled_reason: Option<String>, // Why it is benched, e.g. "corrupt" or "explicit"
+    #[serde(default)]
+    pub disabled_until: Option<u64>,     // Unix seconds when a temporary removal ends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantine: Option<TrackQuarantine>, // Set when the track was benched for failing to play
+}
+
+impl Default for TrackDto {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            title: String::new(),
+            artist: String::new(),
+            album: String::new(),
+            duration: None,
+            bitrate: None,
+            isrc: None,
+            composer: None,
+            label: None,
+            tags: BTreeMap::new(),
+            replaygain_gain_db: None,
+            replaygain_peak: None,
+            analysis: None,
+            enabled: true,
+            disabled_reason: None,
+            disabled_until: None,
+            quarantine: None,
+        }
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Why a track was taken out of rotation as "quarantined": every attempt to open or
+/// decode it failed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackQuarantine {
+    pub error: String,       // The last attempt's error
+    pub attempts: u32,
+    pub quarantined_at: u64, // Unix seconds
+}
+
+/// Offline analysis of a track's audio (`webradio analyze`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackAnalysis {
+    pub loudness_lufs: Option<f64>, // Integrated loudness (ITU-R BS.1770); None for silence
+    pub gain_db: Option<f64>,       // Gain to the -18 LUFS ReplayGain 2.0 reference
+    pub peak: f64,                  // Sample peak, 1.0 = full scale
+    pub peak_dbfs: f64,
+    pub duration_ms: u64,           // Decoded length
+    pub sha256: String,             // Of the file, to tell when it needs analyzing again
+    pub analyzed_at: u64,           // Unix seconds
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub fingerprint: String,        // Chromaprint-style, for duplicate detection; left out of /api/playlist
+    #[serde(default)]
+    pub silence_threshold_db: Option<f64>, // Level (dBFS) silence was measured against; None before silence detection
+    #[serde(default)]
+    pub leading_silence_ms: u64,    // Before the audio first rises above the threshold
+    #[serde(default)]
+    pub trailing_silence_ms: u64,   // After it last does
+}
+
+/// `/api/playlist`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistDto {
+    pub tracks: Vec<TrackDto>,
+    #[serde(default)]
+    pub current_index: usize,
+    #[serde(default)]
+    pub excluded: Vec<ExcludedTrackDto>, // Taken out of rotation
+}
+
+/// A track taken out of rotation; posting its id to `/api/playlist/tracks` puts it back
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExcludedTrackDto {
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+}
+
+/// `/api/next-up`: the tracks that will play after the current one, in order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NextUp {
+    pub tracks: Vec<TrackDto>,
+}
+
+/// `/api/search`: the best matches first, `total` counting those past the limit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub total: usize,
+    pub tracks: Vec<TrackDto>,
+}
+
+/// `/api/stats`. Sections that mirror server internals (buffer settings,
+/// integrity counters, client telemetry) are left as JSON values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatsDto {
+    pub uptime_seconds: u64,
+    pub total_mb_sent: f64,
+    pub current_listeners: usize,
+    pub is_broadcasting: bool,
+    pub listeners: Vec<ListenerDto>,
+    pub stream_health: StreamHealthDto,
+    #[serde(default)]
+    pub buffer_config: serde_json::Value,
+    #[serde(default)]
+    pub client_telemetry: serde_json::Value,
+    #[serde(default)]
+    pub geo: Option<GeoBreakdown>, // With STATS_GEOIP and a GeoIP database
+}
+
+/// Listeners by location. Keys are ISO 3166-1 country codes and ISO 3166-2
+/// region codes ("GB", "GB-ENG"), plus "unknown" for addresses the database
+/// doesn't cover and, when anonymized, "other" for groups too small to report.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GeoBreakdown {
+    pub countries: BTreeMap<String, usize>,
+    #[serde(default)]
+    pub regions: BTreeMap<String, usize>, // Empty when anonymized
+}
+
+/// One connected listener in `/api/stats`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ListenerDto {
+    pub id: String, // First 8 characters of the listener id
+    pub connected_seconds: u64,
+    pub mb_received: f64,
+    pub drift_seconds: f64,
+    pub trimmed_seconds: f64,
+    pub silence_seconds: f64,
+    #[serde(default)]
+    pub profile: String,   // Burst profile the listener was matched to
+    #[serde(default)]
+    pub burst_kb: f64,     // Burst flushed on connect, before live pacing
+    #[serde(default)]
+    pub burst_seconds: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StreamHealthDto {
+    pub gaps_detected: u32,
+    pub recovery_attempts: u32,
+    pub ms_since_last_chunk: u64,
+    pub is_streaming: bool, // A chunk went out in the last 500ms
+    pub drift_trimmed_seconds: f64,
+    pub silence_inserted_seconds: f64,
+    pub on_hold: bool,
+    pub hold_seconds: f64,
+    #[serde(default)]
+    pub idle_mode: String,      // "broadcast" or "pause" (IDLE_MODE)
+    #[serde(default)]
+    pub paused: bool,           // Playout is paused because nobody is listening
+    #[serde(default)]
+    pub paused_seconds: f64,    // Total time spent paused
+    #[serde(default)]
+    pub chunk_integrity: serde_json::Value,
+}
+
+/// `POST /api/vote-skip`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkipVote {
+    pub votes: usize,    // Votes from connected listeners for the current track
+    pub required: usize, // Votes needed to skip it
+    pub skipped: bool,   // This vote reached the threshold
+}
+
+/// `/api/listeners`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listeners {
+    pub listeners: usize,
+    pub max_listeners: Option<usize>,
+    pub uptime: u64,
+}
+
+/// `/api/health`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Health {
+    pub status: String,
+    pub is_broadcasting: bool,
+    pub listeners: usize,
+    pub uptime: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_now_playing_without_track() {
+        let now: NowPlaying = serde_json::from_str(r#"{"title":"No track playing","listeners":3}"#).unwrap();
+        assert_eq!(now.listeners, 3);
+        assert!(now.duration.is_none());
+    }
+
+    #[test]
+    fn test_playlist_round_trip() {
+        let playlist = PlaylistDto {
+            tracks: vec![TrackDto { id: "4f1c2d7e-0000-4000-8000-000000000001".into(), title: "A".into(), ..Default::default() }],
+            current_index: 0,
+            excluded: vec![ExcludedTrackDto { id: "4f1c2d7e-0000-4000-8000-000000000002".into(), title: "B".into(), artist: "C".into() }],
+        };
+        let json = serde_json::to_string(&playlist).unwrap();
+        assert!(!json.contains("path"));
+        assert_eq!(serde_json::from_str::<PlaylistDto>(&json).unwrap(), playlist);
+    }
+}